@@ -31,6 +31,10 @@ pub mod protocol;
 pub mod pipeline;
 pub mod dsp;
 pub mod audio;
+pub mod decoders;
+pub mod record;
+pub mod remote;
+pub mod client;
 pub mod ui;
 use crate::app::common::globals;
 use crate::app::common::common_defs;
@@ -76,17 +80,73 @@ pub struct Appdata{
 
     // Local audio
     pub i_local_audio : audio::audio_out::AudioData,
+    // Ring buffers pipeline or remote engine -> local audio, one per receiver
+    pub rb_local_audio : Vec<Arc<common::ringb::SyncByteRingBuf>>,
     pub stream : Option<cpal::Stream>,
+    pub aux_stream : Option<cpal::Stream>,
 
     //=================================================
     // Pipeline
     // Channel
     pub pipeline_sender : crossbeam_channel::Sender<common::messages::PipelineMsg>,
     pub pipeline_receiver : crossbeam_channel::Receiver<common::messages::PipelineMsg>,
+    // Decoder output channel
+    pub dec_sender : crossbeam_channel::Sender<common::messages::DecoderMsg>,
+    pub dec_receiver : crossbeam_channel::Receiver<common::messages::DecoderMsg>,
     // DSP thread join handle
     pub opt_pipeline_join_handle: option::Option<thread::JoinHandle<()>>,
-    // Ring buffer Reader thread <-> pipeline thread
-    pub rb_iq : Arc<common::ringb::SyncByteRingBuf>,
+    // Ring buffers Reader thread <-> pipeline thread, one per receiver
+    pub rb_iq : Vec<Arc<common::ringb::SyncByteRingBuf>>,
+
+    //=================================================
+    // Recorder
+    // Channel
+    pub rec_sender : crossbeam_channel::Sender<common::messages::RecorderMsg>,
+    pub rec_receiver : crossbeam_channel::Receiver<common::messages::RecorderMsg>,
+    // Recorder thread join handle
+    pub opt_recorder_join_handle: option::Option<thread::JoinHandle<()>>,
+
+    //=================================================
+    // Player
+    // Channel
+    pub play_sender : crossbeam_channel::Sender<common::messages::PlayerMsg>,
+    pub play_receiver : crossbeam_channel::Receiver<common::messages::PlayerMsg>,
+    // Player thread join handle
+    pub opt_player_join_handle: option::Option<thread::JoinHandle<()>>,
+
+    //=================================================
+    // Remote control
+    // Channels, the servers send CAT changes to the UI
+    pub rig_sender : crossbeam_channel::Sender<common::messages::RigctlMsg>,
+    pub rig_receiver : crossbeam_channel::Receiver<common::messages::RigctlMsg>,
+    pub cat_sender : crossbeam_channel::Sender<common::messages::CatMsg>,
+    pub cat_receiver : crossbeam_channel::Receiver<common::messages::CatMsg>,
+    pub cat_pty_sender : crossbeam_channel::Sender<common::messages::CatPtyMsg>,
+    pub cat_pty_receiver : crossbeam_channel::Receiver<common::messages::CatPtyMsg>,
+    pub web_sender : crossbeam_channel::Sender<common::messages::WebMsg>,
+    pub web_receiver : crossbeam_channel::Receiver<common::messages::WebMsg>,
+    // Rigctl server thread join handle
+    pub opt_rigctld_join_handle: option::Option<thread::JoinHandle<()>>,
+    // CAT pty thread join handle
+    pub opt_cat_pty_join_handle: option::Option<thread::JoinHandle<()>>,
+    // Web server thread join handle
+    pub opt_web_join_handle: option::Option<thread::JoinHandle<()>>,
+    // Protocol 1 server, the reader sends it the shared receiver
+    pub hpsdr_sender : crossbeam_channel::Sender<common::messages::HpsdrMsg>,
+    pub hpsdr_receiver : crossbeam_channel::Receiver<common::messages::HpsdrMsg>,
+    pub opt_hpsdr_join_handle: option::Option<thread::JoinHandle<()>>,
+    // rtl_tcp server, the pipeline sends it the shared receiver
+    pub rtl_sender : crossbeam_channel::Sender<common::messages::RtlTcpMsg>,
+    pub rtl_receiver : crossbeam_channel::Receiver<common::messages::RtlTcpMsg>,
+    pub opt_rtl_join_handle: option::Option<thread::JoinHandle<()>>,
+
+    //=================================================
+    // Audio bridge
+    // Channel
+    pub bridge_sender : crossbeam_channel::Sender<common::messages::BridgeMsg>,
+    pub bridge_receiver : crossbeam_channel::Receiver<common::messages::BridgeMsg>,
+    // Audio bridge thread join handle
+    pub opt_bridge_join_handle: option::Option<thread::JoinHandle<()>>,
 
     // Command and Control out
     pub i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
@@ -94,52 +154,59 @@ pub struct Appdata{
     //=================================================
     // State
     pub run : bool,
+    // Hardware and DSP are ours, otherwise the UI is for a remote engine
+    pub local : bool,
 
 }
 
 //=========================================================================================
 // Implementation
 impl Appdata {
-    // Instantiate the application modules
-    pub fn new(_prefs: Rc<RefCell<prefs::Prefs>>) -> Appdata {
+    // Instantiate the application modules.
+    // When the UI is for a remote engine there is no hardware, DSP or servers here,
+    // the local system just plays its audio.
+    pub fn new(prefs: Rc<RefCell<prefs::Prefs>>, local: bool) -> Appdata {
         // Local runnable
         let mut l_run = false;
 
-        // First check/create the DSP Wisdom file
-        dsp::dsp_interface::wdsp_wisdom();
-
-        // Open a DSP receiver channel
-        dsp::dsp_interface::wdsp_open_ch(
-            common::common_defs::CH_RX as i32, 0, common::common_defs::DSP_BLK_SZ as i32, 
-            common::common_defs::DSP_BLK_SZ as i32, globals::get_smpl_rate() as i32, 
-            common::common_defs::SMPLS_48K as i32, 0.0, 0.0, 0.0, 0.0);
-        // and start the channel
-        dsp::dsp_interface::wdsp_set_ch_state(0, 1, 0);
-    
-        // Open a display channel
-        if dsp::dsp_interface::wdsp_open_disp(
-            0, common_defs::FFT_SZ, common_defs::WindowTypes::Rectangular as i32, 
-            common_defs::SUB_SPANS, common_defs::IN_SZ, common_defs::DISPLAY_WIDTH, 
-            common_defs::AvMode::PanTimeAvLin as i32, common_defs::OVER_FRAMES, 
-            globals::get_smpl_rate() as i32, common_defs::FRAME_RATE) {
-                println!("Opened display channel");
+        if local {
+            Appdata::open_dsp();
         }
+        Appdata::restore_rx(prefs.clone(), local);
 
         // Create the message q's for reader, hardware and Pipeline
         let (r_s, r_r) = unbounded();
         let (w_s, w_r) = unbounded();
         let (hw_s, hw_r) = unbounded();
         let (pipeline_s, pipeline_r) = unbounded();
+        let (dec_s, dec_r) = unbounded();
+        let (rec_s, rec_r) = unbounded();
+        let (play_s, play_r) = unbounded();
+        let (rig_s, rig_r) = unbounded();
+        let (cat_s, cat_r) = unbounded();
+        let (cat_pty_s, cat_pty_r) = unbounded();
+        let (web_s, web_r) = unbounded();
+        let (hpsdr_s, hpsdr_r) = unbounded();
+        let (rtl_s, rtl_r) = unbounded();
+        let (bridge_s, bridge_r) = unbounded();
 
         // Create ring buffers 
         // Buffer for read IQ data to DSP
         let num_rx = globals::get_num_rx();
         let rb_capacity: usize = (num_rx * common::common_defs::PROT_SZ * 2 * common::common_defs::BYTES_PER_SAMPLE * common::common_defs::FRAMES_IN_RING ) as usize;
-        let rb_iq = Arc::new(common::ringb::SyncByteRingBuf::with_capacity(rb_capacity));
+        let mut rb_iq = Vec::new();
+        // Buffers to write audio data from DSP for local audio, one per receiver
+        let mut rb_local_audio = Vec::new();
+        for _rx in 0..common_defs::MAX_RADIOS {
+            rb_iq.push(Arc::new(common::ringb::SyncByteRingBuf::with_capacity(
+                common::common_defs::iq_ring_capacity(1, globals::get_smpl_rate()))));
+            rb_local_audio.push(Arc::new(common::ringb::SyncByteRingBuf::with_capacity(rb_capacity)));
+        }
+        // Buffer for read Mic data to the TX DSP
+        let rb_mic = Arc::new(common::ringb::SyncByteRingBuf::with_capacity(
+            (common::common_defs::MIC_BLK_SZ * common::common_defs::MIC_BYTES_PER_SAMPLE * common::common_defs::FRAMES_IN_RING) as usize));
         // Buffer to write audio data from DSP
         let rb_audio = Arc::new(common::ringb::SyncByteRingBuf::with_capacity(rb_capacity));
-        // Buffer to write audio data from DSP for local audio
-        let rb_local_audio = Arc::new(common::ringb::SyncByteRingBuf::with_capacity(rb_capacity));
 
         // Create condition variables
         // Between UDP Reader and Pipeline for data transfer
@@ -154,7 +221,9 @@ impl Appdata {
         let arc1 = p_sock.clone();
         let mut i_hw_control = udp::hw_control::HWData::new(arc1);
         // Do discovery and get address of the hardware unit
-        if i_hw_control.do_discover() {
+        if !local {
+            globals::set_discover_state(false);
+        } else if i_hw_control.do_discover() {
             globals::set_discover_state(true);
         } else {
             println!("Discovery failed, reader and writer will not be operational!");
@@ -187,7 +256,7 @@ impl Appdata {
                 // Start the UDP reader thread
                 opt_reader_join_handle = Some(
                     udp::udp_reader::reader_start(r_r.clone(), 
-                    arc4, rb_iq.clone(), iq_cond.clone()));
+                    arc4, rb_iq.clone(), rb_mic.clone(), iq_cond.clone(), hpsdr_s.clone()));
 
                 // OK to run
                 l_run = true;
@@ -197,14 +266,49 @@ impl Appdata {
             }
         }        
 
-        // Start the pipeline thread
-        #[allow(unused_assignments)]
+        // The pipeline and everything fed by it only run for our own hardware
         let mut opt_pipeline_join_handle: option::Option<thread::JoinHandle<()>> = None;
-        opt_pipeline_join_handle = Some(pipeline::pipeline::pipeline_start(
-                pipeline_r.clone(), rb_iq.clone(), iq_cond.clone(), rb_audio.clone(), rb_local_audio.clone()));
+        let mut opt_recorder_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_player_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_bridge_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_rigctld_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_cat_pty_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_web_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_hpsdr_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        let mut opt_rtl_join_handle: option::Option<thread::JoinHandle<()>> = None;
+        if local {
+            // Start the pipeline thread
+            opt_pipeline_join_handle = Some(pipeline::pipeline::pipeline_start(
+                    pipeline_r.clone(), rb_iq.clone(), iq_cond.clone(), rb_audio.clone(), rb_local_audio.clone(), rb_mic.clone(), dec_s.clone(), rec_s.clone(), bridge_s.clone(), web_s.clone(), rtl_s.clone()));
+
+            // Start the recorder thread
+            opt_recorder_join_handle = Some(record::recorder::recorder_start(rec_r.clone()));
+
+            // Start the player thread, this can stand in for the reader when playing IQ files
+            opt_player_join_handle = Some(record::player::player_start(play_r.clone(), rb_iq.clone(), iq_cond.clone()));
+
+            // Start the audio bridge thread, it sends and listens when enabled from the UI
+            opt_bridge_join_handle = Some(audio::audio_bridge::bridge_start(bridge_r.clone(), rb_mic.clone()));
+
+            // Start the rigctl server thread, it listens when enabled from the UI
+            opt_rigctld_join_handle = Some(remote::rigctld::rigctld_start(rig_r.clone(), cat_s.clone()));
+
+            // Start the TS-2000 CAT thread, the pty is opened when enabled from the UI
+            opt_cat_pty_join_handle = Some(remote::ts2000::cat_pty_start(cat_pty_r.clone(), cat_s.clone()));
+
+            // Start the web server thread, it listens when enabled from the UI
+            opt_web_join_handle = Some(remote::web_server::web_start(web_r.clone(), cat_s.clone(), rb_mic.clone()));
+
+            // Start the Protocol 1 server thread, it listens when enabled from the UI
+            opt_hpsdr_join_handle = Some(remote::hpsdr_server::hpsdr_start(hpsdr_r.clone(), cat_s.clone()));
+
+            // Start the rtl_tcp server thread, it listens when enabled from the UI
+            opt_rtl_join_handle = Some(remote::rtl_tcp_server::rtl_tcp_start(rtl_r.clone(), cat_s.clone(), i_cc.clone()));
+        }
 
         // Create the local audio
-        let i_local_audio = audio::audio_out::AudioData::new(rb_local_audio.clone());
+        let aux_device = prefs.borrow().audio.aux_device.clone();
+        let i_local_audio = audio::audio_out::AudioData::new(rb_local_audio.clone(), aux_device);
 
         // Initialise the application data
         Appdata { 
@@ -221,19 +325,194 @@ impl Appdata {
             hw_receiver : hw_r,
             pipeline_sender : pipeline_s,
             pipeline_receiver : pipeline_r,
+            dec_sender : dec_s,
+            dec_receiver : dec_r,
             opt_pipeline_join_handle : opt_pipeline_join_handle,
             rb_iq : rb_iq,
+            rec_sender : rec_s,
+            rec_receiver : rec_r,
+            opt_recorder_join_handle : opt_recorder_join_handle,
+            play_sender : play_s,
+            play_receiver : play_r,
+            opt_player_join_handle : opt_player_join_handle,
+            rig_sender : rig_s,
+            rig_receiver : rig_r,
+            cat_sender : cat_s,
+            cat_receiver : cat_r,
+            cat_pty_sender : cat_pty_s,
+            cat_pty_receiver : cat_pty_r,
+            web_sender : web_s,
+            web_receiver : web_r,
+            opt_rigctld_join_handle : opt_rigctld_join_handle,
+            opt_cat_pty_join_handle : opt_cat_pty_join_handle,
+            opt_web_join_handle : opt_web_join_handle,
+            hpsdr_sender : hpsdr_s,
+            hpsdr_receiver : hpsdr_r,
+            opt_hpsdr_join_handle : opt_hpsdr_join_handle,
+            rtl_sender : rtl_s,
+            rtl_receiver : rtl_r,
+            opt_rtl_join_handle : opt_rtl_join_handle,
+            bridge_sender : bridge_s,
+            bridge_receiver : bridge_r,
+            opt_bridge_join_handle : opt_bridge_join_handle,
             i_local_audio : i_local_audio,
+            rb_local_audio : rb_local_audio,
             stream : None,
+            aux_stream : None,
             run : l_run,
+            local : local,
             i_cc : i_cc,
         }
     }
     
+    //=========================================================================================
+    // Open the DSP channels and the display
+    fn open_dsp() {
+        // First check/create the DSP Wisdom file
+        dsp::dsp_interface::wdsp_wisdom();
+
+        // Open a DSP receiver channel for each receiver, channel = RX - 1
+        for ch in 0..common_defs::MAX_RADIOS as i32 {
+            dsp::dsp_interface::wdsp_open_ch(
+                common::common_defs::CH_RX as i32, ch, common::common_defs::DSP_BLK_SZ as i32, 
+                common::common_defs::DSP_BLK_SZ as i32, globals::get_smpl_rate() as i32, 
+                common::common_defs::SMPLS_48K as i32, 0.0, 0.0, 0.0, 0.0);
+            // and start the channel
+            dsp::dsp_interface::wdsp_set_ch_state(ch, 1, 0);
+        }
+        // Open a channel for each sub-receiver.
+        // These take the full rate input and decimate to 48K after the frequency shift.
+        // They are started when enabled.
+        for id in 0..common_defs::MAX_SUB_RX {
+            let ch = (common_defs::SUB_RX_CH + id) as i32;
+            dsp::dsp_interface::wdsp_open_ch(
+                common::common_defs::CH_RX as i32, ch, common::common_defs::DSP_BLK_SZ as i32, 
+                common::common_defs::DSP_BLK_SZ as i32, globals::get_smpl_rate() as i32, 
+                common::common_defs::SMPLS_48K as i32, 0.0, 0.0, 0.0, 0.0);
+            dsp::dsp_interface::wdsp_set_dsp_rate(ch, common::common_defs::SMPLS_48K as i32);
+        }
+        // Open the TX channel, Mic in and IQ out are both at 48K
+        dsp::dsp_interface::wdsp_open_ch(
+            common::common_defs::CH_TX as i32, common_defs::TX_CH as i32, common::common_defs::MIC_BLK_SZ as i32, 
+            common::common_defs::MIC_BLK_SZ as i32, common::common_defs::SMPLS_48K as i32, 
+            common::common_defs::SMPLS_48K as i32, 0.0, 0.0, 0.0, 0.0);
+        dsp::dsp_interface::wdsp_set_ch_state(common_defs::TX_CH as i32, 1, 0);
+
+        // Open a display channel
+        if dsp::dsp_interface::wdsp_open_disp(
+            0, common_defs::FFT_SZ, common_defs::WindowTypes::Rectangular as i32, 
+            common_defs::SUB_SPANS, common_defs::IN_SZ, common_defs::DISPLAY_WIDTH, 
+            common_defs::AvMode::PanTimeAvLin as i32, common_defs::OVER_FRAMES, 
+            globals::get_smpl_rate() as i32, common_defs::FRAME_RATE) {
+                println!("Opened display channel");
+        }
+    }
+
+    //=========================================================================================
+    // Restore mode, filter and mixer settings for all receivers, the decoder and diversity settings
+    // The UI only sets the selected receiver so restore mode and filter for all
+    fn restore_rx(prefs: Rc<RefCell<prefs::Prefs>>, local: bool) {
+        let p = prefs.borrow();
+        let rx_prefs = [(&p.radio.rx1, &p.audio.rx1), (&p.radio.rx2, &p.audio.rx2), (&p.radio.rx3, &p.audio.rx3)];
+        for (i, (radio, mixer)) in rx_prefs.iter().enumerate() {
+            let rx = i as i32 + 1;
+            globals::set_mode(rx, radio.mode as u32);
+            globals::set_filter(rx, radio.filter as u32);
+            if local {
+                dsp::dsp_interface::set_mode_filter(rx - 1, rx);
+            }
+            globals::set_rx_vol(rx, mixer.vol);
+            globals::set_rx_pan(rx, mixer.pan);
+            globals::set_rx_mute(rx, mixer.mute);
+            globals::set_rx_route(rx, mixer.route);
+        }
+        globals::set_dec_type(p.decoder.dec_type);
+        globals::set_dec_offset(p.decoder.offset);
+        globals::set_diversity(p.diversity.enabled);
+        globals::set_div_gain(p.diversity.gain);
+        globals::set_div_phase(p.diversity.phase);
+        // TX processing from the current profile
+        if local {
+            Appdata::restore_tx(&p);
+        }
+        globals::set_vox(p.vox.enabled);
+        globals::set_vox_threshold(p.vox.threshold);
+        globals::set_vox_attack(p.vox.attack);
+        globals::set_vox_hang(p.vox.hang);
+        globals::set_anti_vox(p.vox.anti_vox);
+        globals::set_anti_vox_gain(p.vox.anti_vox_gain);
+        // The software keyer is not used when the hardware keyer is selected
+        globals::set_cw_keyer(p.cw.keyer && !p.cw.hw_keyer);
+        globals::set_keyer_mode_b(p.cw.mode_b);
+        globals::set_keyer_wpm(p.cw.wpm);
+        globals::set_keyer_weight(p.cw.weight);
+        globals::set_sidetone_freq(p.cw.sidetone_freq);
+        globals::set_sidetone_vol(p.cw.sidetone_vol);
+        globals::set_cw_hang(p.cw.hang);
+        globals::set_test_mode(0);
+        globals::set_test_max_level(p.test.max_level);
+        globals::set_test_tone1(p.test.tone1);
+        globals::set_test_tone2(p.test.tone2);
+        globals::set_test_timeout(p.test.timeout);
+        globals::set_rpt_shift_dir(p.repeater.shift_dir);
+        globals::set_rpt_shift(p.repeater.shift);
+        globals::set_rpt_reverse(p.repeater.reverse);
+        globals::set_tone_sql(p.repeater.tone_sql);
+        globals::set_sql_ctcss(p.repeater.sql_ctcss);
+        globals::set_sql_dcs(p.repeater.sql_dcs);
+        // Sub-receivers are started by the UI once it knows they are in the passband
+        for (i, sub) in p.sub_rx.iter().enumerate() {
+            let id = i as u32;
+            if local {
+                let ch = (common_defs::SUB_RX_CH + id) as i32;
+                dsp::dsp_interface::wdsp_set_mode_filter(ch, sub.mode as u32, sub.filter as u32);
+            }
+            globals::set_sub_enabled(id, false);
+            globals::set_sub_vol(id, sub.vol);
+            globals::set_sub_pan(id, sub.pan);
+            globals::set_sub_mute(id, sub.mute);
+        }
+    }
+
+    // Set the TX chain from the current profile
+    pub fn restore_tx(p: &prefs::Prefs) {
+        let ch = common_defs::TX_CH as i32;
+        let profile = p.tx.current();
+        let mode;
+        match p.radio.sel_rx {
+            2 => mode = p.radio.rx2.mode,
+            3 => mode = p.radio.rx3.mode,
+            _ => mode = p.radio.rx1.mode,
+        }
+        dsp::dsp_interface::wdsp_set_tx_mode_filter(ch, mode as u32, profile.low, profile.high);
+        dsp::dsp_interface::wdsp_set_mic_gain(ch, profile.mic_gain);
+        dsp::dsp_interface::wdsp_set_tx_eq(ch, profile.eq_enabled, profile.eq_preamp, &profile.eq);
+        dsp::dsp_interface::wdsp_set_leveler(ch, profile.leveler_enabled, profile.leveler_gain);
+        dsp::dsp_interface::wdsp_set_compressor(ch, profile.comp_enabled, profile.comp_gain, profile.cfc_enabled);
+        dsp::dsp_interface::wdsp_set_cessb(ch, profile.cessb_enabled);
+        dsp::dsp_interface::wdsp_set_alc(ch, profile.alc_gain);
+        dsp::dsp_interface::wdsp_set_ctcss(ch, p.repeater.ctcss_tx, p.repeater.ctcss_tx_freq);
+    }
+
     //=========================================================================================
     // Initialise system to a running state
     pub fn app_init(&mut self ) {
 
+        if self.local {
+            self.start_local();
+        }
+
+        // Start the local audio stream
+        // This runs without hardware so that IQ files can be played
+        self.stream = Some(self.i_local_audio.run_audio());
+        // and the auxiliary stream if configured
+        self.aux_stream = self.i_local_audio.run_aux_audio();
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // Start the hardware, pipeline and reader
+    fn start_local(&mut self) {
+
         // Prime the hardware.
         self.w_sender.send(common::messages::WriterMsg::PrimeHardware).unwrap();
         thread::sleep(Duration::from_millis(100));
@@ -246,37 +525,99 @@ impl Appdata {
         // then signals the pipeline
         self.r_sender.send(common::messages::ReaderMsg::StartListening).unwrap();
         thread::sleep(Duration::from_millis(100));
-
-        if self.run {
-            // Start the local audio stream
-            self.stream = Some(self.i_local_audio.run_audio());
-            thread::sleep(Duration::from_millis(100));
-        }
     }
 
     //=========================================================================================
     // Run the UI event loop. Only returns when the UI is closed.
-    //pub fn ui_run(&mut self, prefs: Rc<RefCell<prefs::Prefs>>) {
-    pub fn ui_run(&mut self, prefs: Rc<RefCell<prefs::Prefs>>) {
+    // With an engine address and token the UI is for that engine, otherwise for this one.
+    pub fn ui_run(&mut self, prefs: Rc<RefCell<prefs::Prefs>>, engine: Option<(String, String)>) {
         
-        let i_cc = self.i_cc.clone();
-        ui::egui_main::ui_run(i_cc, prefs, self.i_hw_control.clone());
+        let radio_client: Rc<RefCell<dyn client::radio_client::RadioClient>> = match engine {
+            Some((addr, token)) => Rc::new(RefCell::new(client::engine_client::EngineClient::new(
+                addr, token, prefs.clone(), self.rb_local_audio.clone()))),
+            None => Rc::new(RefCell::new(client::local_client::LocalClient::new(
+                self.i_cc.clone(), self.i_hw_control.clone(), self.pipeline_sender.clone(), self.w_sender.clone()))),
+        };
+        ui::egui_main::ui_run(
+            radio_client, prefs, self.dec_receiver.clone(), self.rec_sender.clone(),
+            self.play_sender.clone(), self.rig_sender.clone(), self.cat_pty_sender.clone(), self.bridge_sender.clone(), self.web_sender.clone(), self.hpsdr_sender.clone(), self.rtl_sender.clone(), self.cat_receiver.clone());
+    }
+
+    //=========================================================================================
+    // Run without a UI, remote clients control the radio. Only returns when told to quit.
+    pub fn engine_run(&mut self, prefs: Rc<RefCell<prefs::Prefs>>) {
+        let radio_client = client::local_client::LocalClient::new(
+            self.i_cc.clone(), self.i_hw_control.clone(), self.pipeline_sender.clone(), self.w_sender.clone());
+        let mut i_engine = remote::engine::Engine::new(
+            prefs, radio_client, self.cat_receiver.clone(), self.dec_receiver.clone(), self.web_sender.clone(),
+            self.hpsdr_sender.clone(), self.rtl_sender.clone());
+        i_engine.engine_run();
     }
 
     //=========================================================================================
     // Tidy close everything
     pub fn app_close(&mut self) { 
         
-        println!("Closing DSP channels");
-        dsp::dsp_interface::wdsp_close_ch(0);
-        dsp::dsp_interface::destroy_analyzer(0);
+        if self.local {
+            println!("Closing DSP channels");
+            for ch in 0..(common_defs::MAX_RADIOS + common_defs::MAX_SUB_RX) as i32 {
+                dsp::dsp_interface::wdsp_close_ch(ch);
+            }
+            dsp::dsp_interface::wdsp_close_ch(common_defs::TX_CH as i32);
+            dsp::dsp_interface::destroy_analyzer(0);
+        }
+
+        // Close local audio
+        if let Some(stream) = self.stream.as_ref() {
+            self.i_local_audio.close_audio(stream);
+        }
+        if let Some(stream) = self.aux_stream.as_ref() {
+            self.i_local_audio.close_aux_audio(stream);
+        }
+
+        // Stop remote control so nothing is tuned or keyed while closing
+        self.rig_sender.send(common::messages::RigctlMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_rigctld_join_handle.take(){
+            println!("Waiting for rigctl server to terminate...");
+            h.join().expect("Join Rigctl server failed!");
+            println!("Rigctl server terminated")
+        }
+        self.cat_pty_sender.send(common::messages::CatPtyMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_cat_pty_join_handle.take(){
+            println!("Waiting for CAT pty server to terminate...");
+            h.join().expect("Join CAT pty server failed!");
+            println!("CAT pty server terminated")
+        }
+        self.web_sender.send(common::messages::WebMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_web_join_handle.take(){
+            println!("Waiting for web server to terminate...");
+            h.join().expect("Join web server failed!");
+            println!("Web server terminated")
+        }
+        self.hpsdr_sender.send(common::messages::HpsdrMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_hpsdr_join_handle.take(){
+            println!("Waiting for HPSDR server to terminate...");
+            h.join().expect("Join HPSDR server failed!");
+            println!("HPSDR server terminated")
+        }
+        self.rtl_sender.send(common::messages::RtlTcpMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_rtl_join_handle.take(){
+            println!("Waiting for rtl_tcp server to terminate...");
+            h.join().expect("Join rtl_tcp server failed!");
+            println!("rtl_tcp server terminated")
+        }
+
+        // Stop playing before the pipeline goes
+        self.play_sender.send(common::messages::PlayerMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_player_join_handle.take(){
+            println!("Waiting for player to terminate...");
+            h.join().expect("Join Player failed!");
+            println!("Player terminated")
+        }
 
         if self.run {
             // Stop the hardware
             self.i_hw_control.borrow_mut().do_stop();
-
-            // Close local audio
-            self.i_local_audio.close_audio(&(self.stream.as_ref().unwrap()));
         
             // Tell threads to stop
             self.r_sender.send(common::messages::ReaderMsg::StopListening).unwrap();
@@ -306,6 +647,22 @@ impl Appdata {
             h.join().expect("Join Pipeline failed!");
             println!("Pipeline terminated")
         }
+
+        // Terminate the audio bridge now nothing is sending to it
+        self.bridge_sender.send(common::messages::BridgeMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_bridge_join_handle.take(){
+            println!("Waiting for audio bridge to terminate...");
+            h.join().expect("Join Audio bridge failed!");
+            println!("Audio bridge terminated")
+        }
+
+        // Terminate recorder, any open recording is completed
+        self.rec_sender.send(common::messages::RecorderMsg::Terminate).unwrap();
+        if let Some(h) = self.opt_recorder_join_handle.take(){
+            println!("Waiting for recorder to terminate...");
+            h.join().expect("Join Recorder failed!");
+            println!("Recorder terminated")
+        }
        
     }
 }
@@ -26,3 +26,8 @@ bob@bobcowdery.plus.com
 */
 
 pub mod audio_out;
+pub mod resampler;
+pub mod rtp;
+pub mod audio_bridge;
+pub mod opus;
+pub mod jitter;
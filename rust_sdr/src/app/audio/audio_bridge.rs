@@ -0,0 +1,233 @@
+/*
+audio_bridge.rs
+
+Module - audio_bridge
+RX and TX audio for other applications as RTP/L16 over UDP
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::io::{self, Write};
+use std::net::{IpAddr, UdpSocket};
+use std::sync::Arc;
+
+use crate::app::common::common_defs;
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::common::ringb;
+use crate::app::audio::rtp;
+
+// Socket poll interval
+const POLL_MS: u64 = 5;
+
+// 10ms packets at 48K
+const RTP_FRAME: usize = 480;
+
+// TX audio has stopped when nothing arrives for this long
+const TX_IDLE_MS: u64 = 200;
+
+// Where to send the receivers and listen for TX audio.
+// Each receiver is a 48K mono stream on its own port, RX1 on rx_port, RX2 on rx_port + 2 and so on.
+// TX audio is the same format and replaces the Mic while it is arriving.
+pub struct BridgeSpec {
+    pub host: String,
+    pub rx_port: u32,
+    pub tx_port: u32,
+}
+
+//==================================================================================
+// Runtime object for thread
+pub struct BridgeData {
+    receiver : crossbeam_channel::Receiver<messages::BridgeMsg>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    sock : Option<UdpSocket>,
+    spec : Option<BridgeSpec>,
+    streams : Vec<rtp::RtpStream>,
+    tx_last : Option<Instant>,
+}
+
+// Implementation methods on BridgeData
+impl BridgeData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::BridgeMsg>,
+        rb_mic : Arc<ringb::SyncByteRingBuf>) -> BridgeData {
+
+        BridgeData {
+            receiver: receiver,
+            rb_mic: rb_mic,
+            sock: None,
+            spec: None,
+            streams: Vec::new(),
+            tx_last: None,
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn bridge_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::BridgeMsg::Terminate => break,
+                        messages::BridgeMsg::Start(spec) => self.start(spec),
+                        messages::BridgeMsg::Stop => self.stop(),
+                        messages::BridgeMsg::RxAudio(rx, audio) => self.send(rx, &audio),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.receive();
+        }
+        self.stop();
+    }
+
+    fn start(&mut self, spec: BridgeSpec) {
+        self.stop();
+        // Only take TX audio from other hosts when sending to them
+        let mut bind = "0.0.0.0";
+        match spec.host.parse::<IpAddr>() {
+            Ok(addr) if addr.is_loopback() => bind = "127.0.0.1",
+            _ => (),
+        }
+        let r = UdpSocket::bind((bind, spec.tx_port as u16)).and_then(|s| {
+            s.set_nonblocking(true)?;
+            Ok(s)
+        });
+        match r {
+            Ok(sock) => {
+                println!("Audio bridge to {} port {}, TX audio on {}:{}", spec.host, spec.rx_port, bind, spec.tx_port);
+                // Each start is a new source to the receiving application
+                let ssrc = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+                self.streams = (0..common_defs::MAX_RADIOS)
+                    .map(|rx| rtp::RtpStream::new(rtp::PT_L16_48K, ssrc ^ rx, RTP_FRAME))
+                    .collect();
+                self.sock = Some(sock);
+                self.spec = Some(spec);
+                globals::set_audio_bridge(true);
+            },
+            Err(e) => println!("Failed to open audio bridge on port {}: {:?}", spec.tx_port, e),
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.sock.take().is_some() {
+            println!("Audio bridge stopped");
+        }
+        self.spec = None;
+        self.tx_last = None;
+        globals::set_audio_bridge(false);
+        globals::set_bridge_tx(false);
+    }
+
+    // Send receiver audio, rx is 1 based
+    fn send(&mut self, rx: u32, audio: &[f32]) {
+        let (sock, spec) = match (self.sock.as_ref(), self.spec.as_ref()) {
+            (Some(sock), Some(spec)) => (sock, spec),
+            _ => return,
+        };
+        if rx == 0 {
+            return;
+        }
+        let stream = match self.streams.get_mut(rx as usize - 1) {
+            Some(s) => s,
+            None => return,
+        };
+        let port = spec.rx_port + 2 * (rx - 1);
+        for packet in stream.push(audio) {
+            match sock.send_to(&packet, (spec.host.as_str(), port as u16)) {
+                Ok(_) => (),
+                // Nothing listening is not an error
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => (),
+                Err(e) => {
+                    println!("Audio bridge send error {:?}", e);
+                    break;
+                },
+            }
+        }
+    }
+
+    // Take any TX audio into the Mic ring buffer
+    fn receive(&mut self) {
+        let sock = match self.sock.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        let mut data = [0; 2048];
+        loop {
+            match sock.recv_from(&mut data) {
+                Ok((n, _addr)) => {
+                    match rtp::payload(&data[..n]) {
+                        Some((_pt, audio)) => {
+                            // Already big endian 16 bit mono at 48K as the Mic
+                            let sz = audio.len() - audio.len() % common_defs::MIC_BYTES_PER_SAMPLE as usize;
+                            match self.rb_mic.write().write(&audio[..sz]) {
+                                Ok(_) => (),
+                                // Pipeline not keeping up, TX audio will have a gap
+                                Err(_e) => (),
+                            }
+                            self.tx_last = Some(Instant::now());
+                            globals::set_bridge_tx(true);
+                        },
+                        None => (),
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_e) => break,
+            }
+        }
+        if let Some(last) = self.tx_last {
+            if last.elapsed() > Duration::from_millis(TX_IDLE_MS) {
+                // Back to the Mic
+                self.tx_last = None;
+                globals::set_bridge_tx(false);
+            }
+        }
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn bridge_start(
+    receiver : crossbeam_channel::Receiver<messages::BridgeMsg>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        bridge_run(receiver, rb_mic);
+    });
+    return join_handle;
+}
+
+fn bridge_run(
+    receiver : crossbeam_channel::Receiver<messages::BridgeMsg>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>) {
+    println!("Audio bridge running");
+
+    // Instantiate the runtime object
+    let mut i_bridge = BridgeData::new(receiver, rb_mic);
+
+    // Exits when the bridge loop exits
+    i_bridge.bridge_run();
+
+    println!("Audio bridge exiting");
+}
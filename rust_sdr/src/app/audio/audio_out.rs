@@ -27,32 +27,97 @@ bob@bobcowdery.plus.com
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Sample;
-use std::vec;
 use std::io::Read;
 use std::sync::Arc;
 
 use crate::app::common::globals;
+use crate::app::common::common_defs;
 use crate::app::common::converters;
 use crate::app::common::ringb;
+use crate::app::audio::resampler;
+
+// The pipeline always delivers 48KHz stereo as LE i16 pairs
+const SRC_CHANNELS: usize = 2;
+const SRC_BYTES_PER_FRAME: usize = 4;
+// Ring buffer fill we try to maintain, two DSP blocks at 48KHz
+const TARGET_FILL: f64 = (common_defs::DSP_BLK_SZ as usize * SRC_BYTES_PER_FRAME * 2) as f64;
+// Maximum drift correction, +/- 0.5%
+const MAX_ADJUST: f64 = 0.005;
+// Smoothing for the drift correction loop
+const ADJUST_ALPHA: f64 = 0.01;
 
 //==================================================================================
 // Audio output
+// Each receiver is a source to the mixer with its own ring buffer.
+// A receiver may be routed to the main device or to an optional auxiliary device.
 pub struct AudioData {
+    rb_audio: Vec<Arc<ringb::SyncByteRingBuf>>,
+    aux_device: String,
+}
+
+// A mixer source
+struct MixerSource {
+    rx_id: i32,
     rb_audio: Arc<ringb::SyncByteRingBuf>,
 }
 
+// State owned by the audio callback
+struct AudioCallbackState {
+    route: u32,
+    sources: Vec<MixerSource>,
+    resampler: resampler::Resampler,
+    out_channels: usize,
+    rb_data: Vec<u8>,
+    src_data: Vec<f32>,
+    in_data: Vec<f32>,
+    out_data: Vec<f32>,
+}
+
 impl AudioData {
     // Create a new instance and initialise the default data
-    pub fn new(rb_audio: Arc<ringb::SyncByteRingBuf>) -> AudioData {
+    // There is one ring buffer per receiver, the aux device name may be empty
+    pub fn new(rb_audio: Vec<Arc<ringb::SyncByteRingBuf>>, aux_device: String) -> AudioData {
         AudioData {
             rb_audio: rb_audio,
+            aux_device: aux_device,
         }
     }
  
     // Run stream
     pub fn run_audio(&mut self) -> cpal::Stream {
         println!("Initialising local audio...");
-       return self.init_audio();
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .expect("no output device available");
+        return self.init_audio(&device, common_defs::AUDIO_MAIN);
+    }
+
+    // Run the auxiliary stream if a device is configured and present
+    pub fn run_aux_audio(&mut self) -> Option<cpal::Stream> {
+        if self.aux_device.len() == 0 {
+            return None;
+        }
+        println!("Initialising auxiliary audio on {}...", self.aux_device);
+        let host = cpal::default_host();
+        let devices = match host.output_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                println!("Unable to enumerate audio devices {:?}", e);
+                return None;
+            }
+        };
+        for device in devices {
+            if let Ok(name) = device.name() {
+                if name == self.aux_device {
+                    let stream = self.init_audio(&device, common_defs::AUDIO_AUX);
+                    globals::set_aux_audio_state(true);
+                    return Some(stream);
+                }
+            }
+        }
+        println!("Auxiliary audio device {} not found, all receivers will use the main device", self.aux_device);
+        return None;
     }
 
     // Pause stream
@@ -61,30 +126,44 @@ impl AudioData {
         println!("Closing audio stream");
     }
 
-    // Create an audio output stream
-    fn init_audio(&mut self) -> cpal::Stream {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .expect("no output device available");
+    // Pause auxiliary stream
+    pub fn close_aux_audio(&mut self, stream: &cpal::Stream) {
+        globals::set_aux_audio_state(false);
+        stream.pause().unwrap();
+        println!("Closing auxiliary audio stream");
+    }
 
-        let mut supported_configs_range = device
-            .supported_output_configs()
-            .expect("error while querying configs");
-        let supported_config = supported_configs_range
-            .next()
-            .expect("no supported config?!")
-            .with_max_sample_rate();
+    // Create an audio output stream on the device for the given route
+    fn init_audio(&mut self, device: &cpal::Device, route: u32) -> cpal::Stream {
+        let supported_config = negotiate_config(device);
+        let sample_format = supported_config.sample_format();
+        let config: cpal::StreamConfig = supported_config.into();
+        println!("Audio device config: {} channels, {} Hz, {:?}", config.channels, config.sample_rate.0, sample_format);
 
-        let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
-        let config = supported_config.into();
-        let rb_audio = self.rb_audio.clone();
+        let err_fn = |err: cpal::StreamError| eprintln!("an error occurred on the output audio stream: {}", err);
+        let mut sources = Vec::new();
+        for (i, rb) in self.rb_audio.iter().enumerate() {
+            sources.push(MixerSource {
+                rx_id: i as i32 + 1,
+                rb_audio: rb.clone(),
+            });
+        }
+        let state = AudioCallbackState {
+            route: route,
+            sources: sources,
+            resampler: resampler::Resampler::new(common_defs::SMPLS_48K, config.sample_rate.0, SRC_CHANNELS),
+            out_channels: config.channels as usize,
+            rb_data: Vec::new(),
+            src_data: Vec::new(),
+            in_data: Vec::new(),
+            out_data: Vec::new(),
+        };
 
-        let stream = device.build_output_stream(
-                &config,
-                move |data, info| write_audio::<f32>(data, info, &rb_audio),
-                err_fn,
-            ).unwrap();
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(device, &config, state, err_fn),
+            cpal::SampleFormat::I16 => build_stream::<i16>(device, &config, state, err_fn),
+            cpal::SampleFormat::U16 => build_stream::<u16>(device, &config, state, err_fn),
+        };
 
         println!("Starting audio stream");
         stream.play().unwrap();
@@ -92,36 +171,186 @@ impl AudioData {
     } 
 }
 
-// Callback when the audio output needs more data
-fn write_audio<T: Sample>(data: &mut [f32], _: &cpal::OutputCallbackInfo, rb_audio: &ringb::SyncByteRingBuf) {
-    // Byte data from ring buffer
-    let mut rb_data: Vec<u8> = vec![0; data.len()*2];
-    // Converted data
-    let mut out_data: Vec<f32> = vec![0.0; data.len()];
-    // Iterator
-    let mut i = 0;
-
-    // Read data from ring buffer
-    let audio_data = rb_audio.read().read(&mut rb_data);
-    match audio_data {
-        Ok(_sz) => {
-            // The U8 data in the ring buffer is ordered as LE i32 values
-            // Convert from 8 i8 bytes to 2 f32 samples
-            converters::i8le_to_f32le(&rb_data, &mut out_data, ((data.len())*2) as u32);
-            // Copy data to audio buffer
-            for sample in data.iter_mut() {
-                *sample = out_data[i] * globals::get_af_gain() as f32;
-                i += 1;
+// Names of all output devices, used to select the auxiliary device
+pub fn output_device_names() -> Vec<String> {
+    let mut names = Vec::new();
+    let host = cpal::default_host();
+    if let Ok(devices) = host.output_devices() {
+        for device in devices {
+            if let Ok(name) = device.name() {
+                names.push(name);
             }
         }
-        Err(_e) => {
-            // Output silence
-            for sample in data.iter_mut() {
-                *sample = 0.0;
-                i += 1;
-            }
-        }, //println!("Read error on rb_audio {:?}. Skipping cycle.", _e),
-    } 
+    }
+    return names;
+}
+
+// The device a receiver is currently routed to
+// If the auxiliary device is not running everything goes to the main device
+fn rx_route(rx_id: i32) -> u32 {
+    let route = globals::get_rx_route(rx_id);
+    if route == common_defs::AUDIO_AUX && !globals::get_aux_audio_state() {
+        return common_defs::AUDIO_MAIN;
+    }
+    return route;
 }
 
+// Choose the best output config for 48KHz stereo.
+// Prefer a config that supports 48KHz directly, then f32, then stereo.
+// If 48KHz is not supported take the closest rate and the resampler will convert.
+fn negotiate_config(device: &cpal::Device) -> cpal::SupportedStreamConfig {
+    let target = cpal::SampleRate(common_defs::SMPLS_48K);
+    let configs: Vec<cpal::SupportedStreamConfigRange> = device
+        .supported_output_configs()
+        .expect("error while querying configs")
+        .collect();
+    if configs.len() == 0 {
+        panic!("no supported config?!");
+    }
 
+    // Score each config, lower is better
+    let score = |c: &cpal::SupportedStreamConfigRange| -> u32 {
+        let mut s = 0;
+        if !(c.min_sample_rate() <= target && target <= c.max_sample_rate()) {
+            s += 100;
+        }
+        match c.sample_format() {
+            cpal::SampleFormat::F32 => (),
+            cpal::SampleFormat::I16 => s += 1,
+            cpal::SampleFormat::U16 => s += 2,
+        }
+        if c.channels() != 2 {
+            s += 10;
+        }
+        return s;
+    };
+    let mut best = configs[0].clone();
+    for c in configs.iter() {
+        if score(c) < score(&best) {
+            best = c.clone();
+        }
+    }
+
+    // Pick the rate
+    let rate;
+    if best.min_sample_rate() <= target && target <= best.max_sample_rate() {
+        rate = target;
+    } else if best.max_sample_rate() < target {
+        rate = best.max_sample_rate();
+    } else {
+        rate = best.min_sample_rate();
+    }
+    return best.with_sample_rate(rate);
+}
+
+// Build the output stream for the device sample type
+fn build_stream<T: Sample>(
+        device: &cpal::Device, config: &cpal::StreamConfig, 
+        mut state: AudioCallbackState, err_fn: fn(cpal::StreamError)) -> cpal::Stream {
+    return device.build_output_stream(
+            config,
+            move |data: &mut [T], info: &cpal::OutputCallbackInfo| write_audio::<T>(data, info, &mut state),
+            err_fn,
+        ).unwrap();
+}
+
+// Callback when the audio output needs more data
+fn write_audio<T: Sample>(data: &mut [T], _: &cpal::OutputCallbackInfo, state: &mut AudioCallbackState) {
+    let out_frames = data.len() / state.out_channels;
+
+    // Find the receivers routed to this device
+    let num_rx = globals::get_num_rx() as i32;
+    let mut active: Vec<usize> = Vec::new();
+    for (i, source) in state.sources.iter().enumerate() {
+        if source.rx_id <= num_rx && rx_route(source.rx_id) == state.route {
+            active.push(i);
+        }
+    }
+
+    // The receivers are written in step so track the one with the least data
+    let mut available = 0;
+    for (n, i) in active.iter().enumerate() {
+        let a = state.sources[*i].rb_audio.read().available();
+        if n == 0 || a < available {
+            available = a;
+        }
+    }
+
+    // Trim the conversion ratio to hold the ring buffer near the target fill.
+    // This compensates for the difference between the radio and sound card clocks.
+    if active.len() > 0 {
+        let error = (available as f64 - TARGET_FILL) / TARGET_FILL;
+        let wanted = 1.0 + f64::max(-MAX_ADJUST, f64::min(MAX_ADJUST, error * MAX_ADJUST));
+        let adjust = state.resampler.get_adjust();
+        state.resampler.set_adjust(adjust + (wanted - adjust) * ADJUST_ALPHA);
+    }
+
+    // Take what we need from each source and mix
+    let needed = state.resampler.frames_needed(out_frames);
+    let frames = usize::min(needed, available / SRC_BYTES_PER_FRAME);
+    if frames > 0 {
+        state.rb_data.resize(frames * SRC_BYTES_PER_FRAME, 0);
+        state.src_data.resize(frames * SRC_CHANNELS, 0.0);
+        state.in_data.clear();
+        state.in_data.resize(frames * SRC_CHANNELS, 0.0);
+        let af_gain = globals::get_af_gain();
+        for i in active.iter() {
+            let source = &state.sources[*i];
+            let audio_data = source.rb_audio.read().read(&mut state.rb_data);
+            match audio_data {
+                Ok(_sz) => {
+                    if globals::get_rx_mute(source.rx_id) {
+                        continue;
+                    }
+                    // The U8 data in the ring buffer is ordered as LE i16 values
+                    // Convert from 4 i8 bytes to 2 f32 samples
+                    converters::i8le_to_f32le(&state.rb_data, &mut state.src_data, (frames * SRC_BYTES_PER_FRAME) as u32);
+                    // Balance law, centre leaves both channels at full level
+                    let gain = af_gain * globals::get_rx_vol(source.rx_id);
+                    let pan = globals::get_rx_pan(source.rx_id);
+                    let gain_l = gain * f32::min(1.0, 1.0 - pan);
+                    let gain_r = gain * f32::min(1.0, 1.0 + pan);
+                    for frame in 0..frames {
+                        state.in_data[frame * SRC_CHANNELS] += state.src_data[frame * SRC_CHANNELS] * gain_l;
+                        state.in_data[frame * SRC_CHANNELS + 1] += state.src_data[frame * SRC_CHANNELS + 1] * gain_r;
+                    }
+                }
+                Err(_e) => (),
+            }
+        }
+        for sample in state.in_data.iter_mut() {
+            *sample = f32::max(-1.0, f32::min(1.0, *sample));
+        }
+        state.resampler.push(&state.in_data);
+    }
+    if frames < needed {
+        // Underrun, fill with silence to preserve timing
+        state.resampler.push_silence(needed - frames);
+    }
+
+    // Convert to the device rate
+    state.out_data.resize(out_frames * SRC_CHANNELS, 0.0);
+    let produced = state.resampler.pull(&mut state.out_data);
+
+    // Map the stereo source onto the device channels and sample format
+    for frame in 0..out_frames {
+        let (l, r) = if frame < produced {
+            (state.out_data[frame * SRC_CHANNELS], state.out_data[frame * SRC_CHANNELS + 1])
+        } else {
+            (0.0, 0.0)
+        };
+        for ch in 0..state.out_channels {
+            let v: f32;
+            if state.out_channels == 1 {
+                v = (l + r) * 0.5;
+            } else if ch == 0 {
+                v = l;
+            } else if ch == 1 {
+                v = r;
+            } else {
+                v = 0.0;
+            }
+            data[frame * state.out_channels + ch] = <T as Sample>::from(&v);
+        }
+    }
+}
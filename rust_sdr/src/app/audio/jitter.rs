@@ -0,0 +1,191 @@
+/*
+jitter.rs
+
+Module - jitter
+Jitter buffer for sequenced audio frames
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::collections::BTreeMap;
+
+// What to play next
+#[derive(PartialEq, Debug)]
+pub enum Slot {
+    Frame(Vec<u8>),
+    // Never arrived, the decoder should conceal it
+    Lost,
+}
+
+//==================================================================================
+// Frames are held until depth have arrived then taken one per frame time.
+// An underrun waits for depth frames again, an overrun skips to the newest.
+pub struct JitterBuffer {
+    depth: usize,
+    max: usize,
+    // Keyed by sequence number
+    frames: BTreeMap<u16, Vec<u8>>,
+    next: Option<u16>,
+    primed: bool,
+    pub underruns: u32,
+}
+
+impl JitterBuffer {
+    pub fn new(depth: usize, max: usize) -> JitterBuffer {
+        JitterBuffer {
+            depth: depth,
+            max: usize::max(max, depth),
+            frames: BTreeMap::new(),
+            next: None,
+            primed: false,
+            underruns: 0,
+        }
+    }
+
+    pub fn push(&mut self, seq: u16, frame: Vec<u8>) {
+        let next = *self.next.get_or_insert(seq);
+        let offset = seq.wrapping_sub(next);
+        if offset > u16::MAX / 2 {
+            // Late, already played or concealed
+            return;
+        }
+        self.frames.insert(seq, frame);
+        // Too far behind, drop the oldest and move on
+        while self.span() > self.max {
+            self.advance();
+        }
+    }
+
+    // The next frame to play, None while filling
+    pub fn pop(&mut self) -> Option<Slot> {
+        let next = self.next?;
+        if !self.primed {
+            if self.span() < self.depth {
+                return None;
+            }
+            self.primed = true;
+        }
+        if self.frames.len() == 0 {
+            self.primed = false;
+            self.underruns += 1;
+            return None;
+        }
+        let slot = match self.frames.remove(&next) {
+            Some(f) => Slot::Frame(f),
+            None => Slot::Lost,
+        };
+        self.advance();
+        return Some(slot);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.frames.len();
+    }
+
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.next = None;
+        self.primed = false;
+    }
+
+    // Frames from the next to play to the newest
+    fn span(&self) -> usize {
+        let next = match self.next {
+            Some(n) => n,
+            None => return 0,
+        };
+        return match self.frames.keys().map(|k| k.wrapping_sub(next)).max() {
+            Some(d) => d as usize + 1,
+            None => 0,
+        };
+    }
+
+    fn advance(&mut self) {
+        if let Some(next) = self.next {
+            self.frames.remove(&next);
+            self.next = Some(next.wrapping_add(1));
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(n: u8) -> Vec<u8> {
+        return vec![n];
+    }
+
+    #[test]
+    fn fills_to_depth() {
+        let mut jb = JitterBuffer::new(3, 10);
+        jb.push(100, frame(0));
+        jb.push(101, frame(1));
+        assert_eq!(jb.pop(), None);
+        jb.push(102, frame(2));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(0))));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(1))));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(2))));
+        // Underrun then refill
+        assert_eq!(jb.pop(), None);
+        assert_eq!(jb.underruns, 1);
+        jb.push(103, frame(3));
+        assert_eq!(jb.pop(), None);
+    }
+
+    #[test]
+    fn reorders_and_conceals() {
+        let mut jb = JitterBuffer::new(2, 10);
+        jb.push(1, frame(1));
+        jb.push(3, frame(3));
+        jb.push(2, frame(2));
+        jb.push(5, frame(5));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(1))));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(2))));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(3))));
+        assert_eq!(jb.pop(), Some(Slot::Lost));
+        // Too late now
+        jb.push(4, frame(4));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(5))));
+        assert_eq!(jb.len(), 0);
+    }
+
+    #[test]
+    fn wraps_sequence() {
+        let mut jb = JitterBuffer::new(2, 10);
+        jb.push(65535, frame(0));
+        jb.push(0, frame(1));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(0))));
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(1))));
+    }
+
+    #[test]
+    fn overrun_skips_oldest() {
+        let mut jb = JitterBuffer::new(2, 4);
+        for i in 0..6 {
+            jb.push(i, frame(i as u8));
+        }
+        assert_eq!(jb.len(), 4);
+        assert_eq!(jb.pop(), Some(Slot::Frame(frame(2))));
+    }
+}
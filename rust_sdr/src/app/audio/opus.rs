@@ -0,0 +1,178 @@
+/*
+opus.rs
+
+Module - opus
+Opus encoder and decoder for compressed remote audio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+// Mono at 48K in 20ms frames
+pub const OPUS_RATE: i32 = 48000;
+pub const FRAME_SZ: usize = 960;
+pub const MAX_PACKET: usize = 1275;
+
+// Bitrate range in bits/s
+pub const MIN_BITRATE: u32 = 8000;
+pub const MAX_BITRATE: u32 = 64000;
+
+//==================================================================================
+// Built with the opus feature which is on by default, see the README for libopus.
+#[cfg(feature = "opus")]
+mod codec {
+    use std::os::raw::{c_int, c_void};
+    use std::ptr;
+
+    use super::{OPUS_RATE, FRAME_SZ, MAX_PACKET};
+
+    const OPUS_OK: c_int = 0;
+    const OPUS_APPLICATION_AUDIO: c_int = 2049;
+    const OPUS_SET_BITRATE_REQUEST: c_int = 4002;
+    const OPUS_SET_INBAND_FEC_REQUEST: c_int = 4012;
+    const OPUS_SET_PACKET_LOSS_PERC_REQUEST: c_int = 4014;
+
+    #[link(name = "opus")]
+    extern "C" {
+        fn opus_encoder_create(fs: i32, channels: c_int, application: c_int, error: *mut c_int) -> *mut c_void;
+        fn opus_encoder_ctl(st: *mut c_void, request: c_int, ...) -> c_int;
+        fn opus_encode_float(st: *mut c_void, pcm: *const f32, frame_size: c_int, data: *mut u8, max_data_bytes: i32) -> i32;
+        fn opus_encoder_destroy(st: *mut c_void);
+        fn opus_decoder_create(fs: i32, channels: c_int, error: *mut c_int) -> *mut c_void;
+        fn opus_decode_float(st: *mut c_void, data: *const u8, len: i32, pcm: *mut f32, frame_size: c_int, decode_fec: c_int) -> c_int;
+        fn opus_decoder_destroy(st: *mut c_void);
+    }
+
+    pub struct Encoder {
+        st: *mut c_void,
+    }
+
+    // Only ever used by one thread at a time
+    unsafe impl Send for Encoder {}
+
+    impl Encoder {
+        pub fn new(bitrate: u32) -> Result<Encoder, String> {
+            let mut error: c_int = 0;
+            let st = unsafe { opus_encoder_create(OPUS_RATE, 1, OPUS_APPLICATION_AUDIO, &mut error) };
+            if st.is_null() || error != OPUS_OK {
+                return Err(format!("Opus encoder create failed {}", error));
+            }
+            let mut enc = Encoder { st: st };
+            enc.set_bitrate(bitrate);
+            unsafe {
+                // Some protection against the odd dropped frame
+                opus_encoder_ctl(enc.st, OPUS_SET_INBAND_FEC_REQUEST, 1 as c_int);
+                opus_encoder_ctl(enc.st, OPUS_SET_PACKET_LOSS_PERC_REQUEST, 5 as c_int);
+            }
+            return Ok(enc);
+        }
+
+        pub fn set_bitrate(&mut self, bitrate: u32) {
+            unsafe { opus_encoder_ctl(self.st, OPUS_SET_BITRATE_REQUEST, bitrate as i32); }
+        }
+
+        // One frame of FRAME_SZ samples
+        pub fn encode(&mut self, pcm: &[f32]) -> Option<Vec<u8>> {
+            let mut data = vec![0u8; MAX_PACKET];
+            let n = unsafe {
+                opus_encode_float(self.st, pcm.as_ptr(), FRAME_SZ as c_int, data.as_mut_ptr(), MAX_PACKET as i32)
+            };
+            if n < 0 {
+                return None;
+            }
+            data.truncate(n as usize);
+            return Some(data);
+        }
+    }
+
+    impl Drop for Encoder {
+        fn drop(&mut self) {
+            unsafe { opus_encoder_destroy(self.st); }
+        }
+    }
+
+    pub struct Decoder {
+        st: *mut c_void,
+    }
+
+    unsafe impl Send for Decoder {}
+
+    impl Decoder {
+        pub fn new() -> Result<Decoder, String> {
+            let mut error: c_int = 0;
+            let st = unsafe { opus_decoder_create(OPUS_RATE, 1, &mut error) };
+            if st.is_null() || error != OPUS_OK {
+                return Err(format!("Opus decoder create failed {}", error));
+            }
+            return Ok(Decoder { st: st });
+        }
+
+        // One frame, None conceals a lost frame
+        pub fn decode(&mut self, data: Option<&[u8]>) -> Vec<f32> {
+            let mut pcm = vec![0.0; FRAME_SZ];
+            let (p, len) = match data {
+                Some(d) => (d.as_ptr(), d.len() as i32),
+                None => (ptr::null(), 0),
+            };
+            let n = unsafe { opus_decode_float(self.st, p, len, pcm.as_mut_ptr(), FRAME_SZ as c_int, 0) };
+            pcm.truncate(n.max(0) as usize);
+            return pcm;
+        }
+    }
+
+    impl Drop for Decoder {
+        fn drop(&mut self) {
+            unsafe { opus_decoder_destroy(self.st); }
+        }
+    }
+}
+
+#[cfg(not(feature = "opus"))]
+mod codec {
+    const NOT_BUILT: &str = "Opus audio is not available, build with the opus feature";
+
+    pub struct Encoder {}
+
+    impl Encoder {
+        pub fn new(_bitrate: u32) -> Result<Encoder, String> {
+            return Err(String::from(NOT_BUILT));
+        }
+
+        pub fn set_bitrate(&mut self, _bitrate: u32) {}
+
+        pub fn encode(&mut self, _pcm: &[f32]) -> Option<Vec<u8>> {
+            return None;
+        }
+    }
+
+    pub struct Decoder {}
+
+    impl Decoder {
+        pub fn new() -> Result<Decoder, String> {
+            return Err(String::from(NOT_BUILT));
+        }
+
+        pub fn decode(&mut self, _data: Option<&[u8]>) -> Vec<f32> {
+            return Vec::new();
+        }
+    }
+}
+
+pub use codec::{Encoder, Decoder};
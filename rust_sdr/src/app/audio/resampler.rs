@@ -0,0 +1,350 @@
+/*
+resampler.rs
+
+Module - resampler
+Polyphase sample rate converter for local audio
+
+Copyright (C) 2022 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f64::consts::PI;
+
+// Number of phases in the filter bank.
+// Intermediate phases are linearly interpolated so this can be modest.
+const PHASES: usize = 128;
+// Taps per phase at unity ratio
+const BASE_TAPS: usize = 16;
+// Fraction of the Nyquist frequency to pass
+const PASSBAND: f64 = 0.9;
+
+//==================================================================================
+// Resampler state
+// Input and output are interleaved f32 with a fixed number of channels,
+// two for IQ or stereo audio.
+// The ratio may be trimmed at run time to track clock drift between the
+// radio and the sound card.
+pub struct Resampler {
+    in_rate: u32,
+    out_rate: u32,
+    channels: usize,
+    // Nominal input rate / output rate
+    ratio: f64,
+    // Drift correction applied to the ratio, 1.0 is no correction
+    adjust: f64,
+    taps: usize,
+    // (PHASES + 1) rows of taps
+    bank: Vec<f32>,
+    // Interleaved input history
+    buf: Vec<f32>,
+    // Fractional read position in frames from the start of buf
+    pos: f64,
+}
+
+impl Resampler {
+    // Create a new instance for the given rates and channel count
+    pub fn new(in_rate: u32, out_rate: u32, channels: usize) -> Resampler {
+        let ratio = in_rate as f64 / out_rate as f64;
+        // When decimating the kernel must be stretched to lower the cut-off
+        let stretch = f64::max(1.0, ratio);
+        let mut taps = (BASE_TAPS as f64 * stretch).ceil() as usize;
+        if taps % 2 == 1 {
+            taps += 1;
+        }
+        // Cut-off as a fraction of the input sample rate
+        let fc = 0.5 * PASSBAND / stretch;
+
+        // Build the filter bank. Row p is the kernel for a fractional offset of p/PHASES.
+        let mut bank: Vec<f32> = vec![0.0; (PHASES + 1) * taps];
+        let half = (taps / 2) as f64;
+        for p in 0..=PHASES {
+            let frac = p as f64 / PHASES as f64;
+            let mut sum = 0.0;
+            for k in 0..taps {
+                // Distance of this tap from the interpolation point
+                let t = (k as f64 - half + 1.0) - frac;
+                let h = sinc(2.0 * fc * t) * 2.0 * fc * blackman(t + half, taps as f64);
+                bank[p * taps + k] = h as f32;
+                sum += h;
+            }
+            // Normalise for unity gain at DC
+            for k in 0..taps {
+                bank[p * taps + k] = (bank[p * taps + k] as f64 / sum) as f32;
+            }
+        }
+
+        Resampler {
+            in_rate: in_rate,
+            out_rate: out_rate,
+            channels: channels,
+            ratio: ratio,
+            adjust: 1.0,
+            taps: taps,
+            bank: bank,
+            buf: Vec::with_capacity(8192 * channels),
+            // Start so the first output has a full kernel of history
+            pos: half - 1.0,
+        }
+    }
+
+    // The filter is made again for new rates and anything buffered is discarded
+    pub fn set_rates(&mut self, in_rate: u32, out_rate: u32) {
+        if in_rate != self.in_rate || out_rate != self.out_rate {
+            *self = Resampler::new(in_rate, out_rate, self.channels);
+        }
+    }
+
+    // Discard anything buffered, e.g. after a seek
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.pos = (self.taps / 2) as f64 - 1.0;
+    }
+
+    // Trim the conversion ratio, values > 1.0 consume input faster
+    pub fn set_adjust(&mut self, adjust: f64) {
+        self.adjust = adjust;
+    }
+
+    pub fn get_adjust(&self) -> f64 {
+        return self.adjust;
+    }
+
+    // Number of input frames that must be pushed to produce out_frames
+    pub fn frames_needed(&self, out_frames: usize) -> usize {
+        let step = self.ratio * self.adjust;
+        let last = self.pos + (out_frames as f64) * step;
+        let required = last.floor() as usize + self.taps / 2 + 1;
+        let buffered = self.buf.len() / self.channels;
+        if required > buffered {
+            return required - buffered;
+        }
+        return 0;
+    }
+
+    // Append interleaved input samples
+    pub fn push(&mut self, input: &[f32]) {
+        self.buf.extend_from_slice(input);
+    }
+
+    // Append silence, used on underrun so timing is preserved
+    pub fn push_silence(&mut self, frames: usize) {
+        self.buf.resize(self.buf.len() + frames * self.channels, 0.0);
+    }
+
+    // Produce as many output frames as will fit in output and as the input allows.
+    // Returns the number of frames written.
+    pub fn pull(&mut self, output: &mut [f32]) -> usize {
+        let step = self.ratio * self.adjust;
+        let half = self.taps / 2;
+        let buffered = self.buf.len() / self.channels;
+        let out_frames = output.len() / self.channels;
+        let mut done = 0;
+
+        while done < out_frames {
+            let n = self.pos.floor() as usize;
+            if n + half + 1 > buffered {
+                // Not enough history for this output sample
+                break;
+            }
+            // Select and interpolate between the two nearest phases
+            let frac = self.pos - n as f64;
+            let phase_f = frac * PHASES as f64;
+            let phase = phase_f.floor() as usize;
+            let mix = (phase_f - phase as f64) as f32;
+            let row_a = &self.bank[phase * self.taps..(phase + 1) * self.taps];
+            let row_b = &self.bank[(phase + 1) * self.taps..(phase + 2) * self.taps];
+            let first = n + 1 - half;
+            for ch in 0..self.channels {
+                let mut acc_a: f32 = 0.0;
+                let mut acc_b: f32 = 0.0;
+                for k in 0..self.taps {
+                    let s = self.buf[(first + k) * self.channels + ch];
+                    acc_a += s * row_a[k];
+                    acc_b += s * row_b[k];
+                }
+                output[done * self.channels + ch] = acc_a + (acc_b - acc_a) * mix;
+            }
+            done += 1;
+            self.pos += step;
+        }
+
+        // Discard history no longer needed by the kernel
+        let n = self.pos.floor() as usize;
+        if n + 1 > half {
+            let discard = usize::min(n + 1 - half, buffered);
+            self.buf.drain(0..discard * self.channels);
+            self.pos -= discard as f64;
+        }
+        return done;
+    }
+
+    // Push the input and append all the output it allows, for a stream
+    // where the output is taken at whatever rate the input arrives
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.push(input);
+        let step = self.ratio * self.adjust;
+        let end = (self.buf.len() / self.channels) as f64 - (self.taps / 2) as f64;
+        if end <= self.pos {
+            return;
+        }
+        let start = out.len();
+        let frames = ((end - self.pos) / step).ceil() as usize;
+        out.resize(start + frames * self.channels, 0.0);
+        let done = self.pull(&mut out[start..]);
+        out.truncate(start + done * self.channels);
+    }
+}
+
+//==================================================================================
+// Helpers
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        return 1.0;
+    }
+    return (PI * x).sin() / (PI * x);
+}
+
+// Blackman window over 0..len
+fn blackman(n: f64, len: f64) -> f64 {
+    if n < 0.0 || n > len {
+        return 0.0;
+    }
+    let a = 2.0 * PI * n / len;
+    return 0.42 - 0.5 * a.cos() + 0.08 * (2.0 * a).cos();
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Complex tone as interleaved IQ
+    fn tone(rate: u32, freq: f64, frames: usize) -> Vec<f32> {
+        let mut iq = Vec::with_capacity(frames * 2);
+        for i in 0..frames {
+            let a = 2.0 * PI * freq * i as f64 / rate as f64;
+            iq.push(a.cos() as f32);
+            iq.push(a.sin() as f32);
+        }
+        return iq;
+    }
+
+    // Amplitude and frequency of a complex tone from the second half of a block
+    fn measure(iq: &[f32], rate: u32) -> (f64, f64) {
+        let n = iq.len() / 2;
+        let mut amp = 0.0;
+        let mut turn = 0.0;
+        for i in n / 2..n - 1 {
+            let (a, b) = (iq[2 * i] as f64, iq[2 * i + 1] as f64);
+            let (c, d) = (iq[2 * i + 2] as f64, iq[2 * i + 3] as f64);
+            amp = f64::max(amp, (a * a + b * b).sqrt());
+            turn += (a * d - b * c).atan2(a * c + b * d);
+        }
+        return (amp, turn / (n - 1 - n / 2) as f64 * rate as f64 / (2.0 * PI));
+    }
+
+    // Pull blocks of output as the sound card does, returns the output and input frames used
+    fn run(r: &mut Resampler, input: &[f32], block: usize, blocks: usize) -> (Vec<f32>, usize) {
+        let mut out = Vec::new();
+        let mut taken = 0;
+        let mut buf = vec![0.0; block * 2];
+        for _ in 0..blocks {
+            let need = r.frames_needed(block);
+            r.push(&input[taken * 2..(taken + need) * 2]);
+            taken += need;
+            assert_eq!(r.pull(&mut buf), block);
+            out.extend_from_slice(&buf);
+        }
+        return (out, taken);
+    }
+
+    #[test]
+    fn ratio() {
+        // 48K to 44.1K and back, the input used follows the ratio plus the kernel history
+        let input = tone(48000, 1000.0, 100000);
+        let mut r = Resampler::new(48000, 44100, 2);
+        let (_, taken) = run(&mut r, &input, 441, 100);
+        assert!(taken >= 48000 && taken <= 48000 + r.taps);
+        let mut r = Resampler::new(44100, 48000, 2);
+        let (_, taken) = run(&mut r, &input, 480, 100);
+        assert!(taken >= 44100 && taken <= 44100 + r.taps);
+    }
+
+    #[test]
+    fn passband_level() {
+        // Flat over most of the output band and well down past its Nyquist frequency
+        for (freq, low, high) in [(100.0, 0.99, 1.01), (12000.0, 0.99, 1.01), (15000.0, 0.95, 1.01), (30000.0, 0.0, 0.01)] {
+            let mut r = Resampler::new(96000, 44100, 2);
+            let (out, _) = run(&mut r, &tone(96000, freq, 50000), 441, 40);
+            let (amp, _) = measure(&out, 44100);
+            assert!(amp >= low && amp <= high, "{} Hz at {}", freq, amp);
+        }
+    }
+
+    #[test]
+    fn drift_adjust() {
+        // Trimming the ratio up uses more input for the same output at the same frequency
+        let input = tone(48000, 1000.0, 200000);
+        let mut r = Resampler::new(48000, 48000, 2);
+        let (_, nominal) = run(&mut r, &input, 480, 200);
+        let mut r = Resampler::new(48000, 48000, 2);
+        r.set_adjust(1.01);
+        assert_eq!(r.get_adjust(), 1.01);
+        let (out, taken) = run(&mut r, &input, 480, 200);
+        assert!((taken as f64 / nominal as f64 - 1.01).abs() < 0.001);
+        let (amp, freq) = measure(&out, 48000);
+        assert!((amp - 1.0).abs() < 0.01);
+        assert!((freq - 1010.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn stream_up() {
+        let mut r = Resampler::new(48000, 240000, 2);
+        let mut out = Vec::new();
+        for block in tone(48000, 1000.0, 4800).chunks(1024) {
+            r.process(block, &mut out);
+        }
+        // Short of the input by the kernel length
+        let frames = out.len() / 2;
+        assert!(frames <= 24000 && frames > 24000 - BASE_TAPS * 5);
+        let (amp, freq) = measure(&out, 240000);
+        assert!((amp - 1.0).abs() < 0.01);
+        assert!((freq - 1000.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn stream_down() {
+        let mut r = Resampler::new(192000, 50000, 2);
+        let mut out = Vec::new();
+        r.process(&tone(192000, -5000.0, 19200), &mut out);
+        let frames = out.len() / 2;
+        assert!(frames <= 5000 && frames > 5000 - BASE_TAPS);
+        let (amp, freq) = measure(&out, 50000);
+        assert!((amp - 1.0).abs() < 0.05);
+        assert!((freq + 5000.0).abs() < 5.0);
+        // Outside the new span so it must not fold back in
+        let mut r = Resampler::new(192000, 48000, 2);
+        let mut out = Vec::new();
+        r.process(&tone(192000, 60000.0, 19200), &mut out);
+        let (amp, _) = measure(&out, 48000);
+        assert!(amp < 0.05);
+    }
+}
@@ -0,0 +1,165 @@
+/*
+rtp.rs
+
+Module - rtp
+RTP packets with linear 16 bit PCM audio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+// L16 (RFC 3551) is big endian signed 16 bit, the same as the Mic from the hardware.
+// The static L16 payload types are 44.1kHz so 48kHz mono uses a dynamic type.
+pub const RTP_HDR_SZ: usize = 12;
+const RTP_VERSION: u8 = 2;
+pub const PT_L16_48K: u8 = 96;
+
+//==================================================================================
+// One outgoing mono stream, samples are sent in fixed size packets
+pub struct RtpStream {
+    pt: u8,
+    ssrc: u32,
+    seq: u16,
+    timestamp: u32,
+    frame: usize,
+    pending: Vec<i16>,
+}
+
+impl RtpStream {
+    // frame is samples per packet
+    pub fn new(pt: u8, ssrc: u32, frame: usize) -> RtpStream {
+        RtpStream {
+            pt: pt,
+            ssrc: ssrc,
+            seq: 0,
+            timestamp: 0,
+            frame: frame,
+            pending: Vec::with_capacity(frame * 2),
+        }
+    }
+
+    // Queue samples in the range +-1.0, returns any complete packets
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<u8>> {
+        for s in samples.iter() {
+            let v = f32::min(f32::max(*s, -1.0), 1.0) * i16::MAX as f32;
+            self.pending.push(v.round() as i16);
+        }
+        let mut packets = Vec::new();
+        while self.pending.len() >= self.frame {
+            let frame: Vec<i16> = self.pending.drain(..self.frame).collect();
+            packets.push(self.packet(&frame));
+        }
+        return packets;
+    }
+
+    fn packet(&mut self, samples: &[i16]) -> Vec<u8> {
+        let mut p = Vec::with_capacity(RTP_HDR_SZ + samples.len() * 2);
+        p.push(RTP_VERSION << 6);
+        p.push(self.pt & 0x7f);
+        p.extend_from_slice(&self.seq.to_be_bytes());
+        p.extend_from_slice(&self.timestamp.to_be_bytes());
+        p.extend_from_slice(&self.ssrc.to_be_bytes());
+        for s in samples.iter() {
+            p.extend_from_slice(&s.to_be_bytes());
+        }
+        self.seq = self.seq.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(samples.len() as u32);
+        return p;
+    }
+}
+
+//==================================================================================
+// The payload type and payload of a packet skipping any CSRC list, extension and padding.
+// None if it is not an RTP packet.
+pub fn payload(packet: &[u8]) -> Option<(u8, &[u8])> {
+    if packet.len() < RTP_HDR_SZ || packet[0] >> 6 != RTP_VERSION {
+        return None;
+    }
+    let mut start = RTP_HDR_SZ + (packet[0] & 0x0f) as usize * 4;
+    if packet[0] & 0x10 != 0 {
+        // Extension header then its length in 32 bit words
+        if packet.len() < start + 4 {
+            return None;
+        }
+        let words = u16::from_be_bytes([packet[start + 2], packet[start + 3]]) as usize;
+        start += 4 + words * 4;
+    }
+    let mut end = packet.len();
+    if packet[0] & 0x20 != 0 {
+        end = end.saturating_sub(packet[packet.len() - 1] as usize);
+    }
+    if start > end {
+        return None;
+    }
+    return Some((packet[1] & 0x7f, &packet[start..end]));
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_are_framed() {
+        let mut s = RtpStream::new(PT_L16_48K, 0x12345678, 4);
+        assert_eq!(s.push(&[0.0, 0.5, -0.5]).len(), 0);
+        let packets = s.push(&[1.0, 2.0, -2.0, 0.25]);
+        assert_eq!(packets.len(), 1);
+        let p = &packets[0];
+        assert_eq!(p.len(), RTP_HDR_SZ + 8);
+        assert_eq!(&p[..2], &[0x80, 96]);
+        assert_eq!(&p[8..12], &[0x12, 0x34, 0x56, 0x78]);
+        let (pt, data) = payload(p).unwrap();
+        assert_eq!(pt, PT_L16_48K);
+        // Big endian and clipped
+        assert_eq!(data, &[0x00, 0x00, 0x40, 0x00, 0xc0, 0x00, 0x7f, 0xff]);
+    }
+
+    #[test]
+    fn sequence_and_timestamp() {
+        let mut s = RtpStream::new(PT_L16_48K, 1, 480);
+        let packets = s.push(&vec![0.0; 480 * 3]);
+        assert_eq!(packets.len(), 3);
+        for (i, p) in packets.iter().enumerate() {
+            assert_eq!(u16::from_be_bytes([p[2], p[3]]), i as u16);
+            assert_eq!(u32::from_be_bytes([p[4], p[5], p[6], p[7]]), i as u32 * 480);
+        }
+    }
+
+    #[test]
+    fn payload_skips_csrc_extension_and_padding() {
+        // One CSRC, a one word extension and 2 bytes of padding
+        let mut p = vec![0x80 | 0x20 | 0x10 | 0x01, 11, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        p.extend_from_slice(&[9, 9, 9, 9]);
+        p.extend_from_slice(&[0xbe, 0xde, 0, 1, 7, 7, 7, 7]);
+        p.extend_from_slice(&[1, 2, 3, 4]);
+        p.extend_from_slice(&[0, 2]);
+        assert_eq!(payload(&p), Some((11, &[1u8, 2, 3, 4][..])));
+    }
+
+    #[test]
+    fn rejects_bad_packets() {
+        assert_eq!(payload(&[0x80, 96, 0, 0]), None);
+        assert_eq!(payload(&[0x40, 96, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), None);
+        // CSRC count beyond the end
+        assert_eq!(payload(&[0x8f, 96, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), None);
+    }
+}
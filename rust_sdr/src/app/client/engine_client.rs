@@ -0,0 +1,756 @@
+/*
+engine_client.rs
+
+Module - engine_client
+A headless engine on another machine, reached through its web server
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::{Duration, Instant};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::{cell::RefCell, rc::Rc};
+
+use serde_json::{json, Value};
+
+use crate::app::common::common_defs;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::common::prefs;
+use crate::app::common::ringb;
+use crate::app::audio::opus;
+use crate::app::client::radio_client::RadioClient;
+use crate::app::remote::radio;
+use crate::app::remote::websocket;
+use crate::app::remote::web_server::{BIN_SPECTRUM, BIN_AUDIO};
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+use crate::app::pipeline::test_signal::TestMode;
+
+// Socket poll interval
+const POLL_MS: u64 = 10;
+// Connect and handshake must complete in this time, then retry after a pause
+const CONNECT_MS: u64 = 3000;
+const RETRY_MS: u64 = 2000;
+const MAX_RESPONSE: usize = 8192;
+const MAX_FRAME: usize = 1024 * 1024;
+
+// The engine state lags a change so ignore it for a while after sending one
+const HOLD_MS: u64 = 300;
+// Errors from the engine are shown this long
+const ERROR_MS: u64 = 5000;
+// Meter reading until the engine sends one
+const NO_SIGNAL: f32 = -140.0;
+
+// One receiver as the engine has it
+struct RxState {
+    freq: u32,
+    mode: ModeId,
+    filter: FilterId,
+}
+
+// Last known engine state
+struct EngineState {
+    hw: bool,
+    run: bool,
+    rx: u32,
+    rate: u32,
+    gain: f32,
+    ptt: bool,
+    receivers: Vec<RxState>,
+}
+
+//==================================================================================
+// The UI side.
+// Engine state is copied to the prefs in poll() so the panels show it, what the
+// panels then set is only sent on when it differs from the engine.
+pub struct EngineClient {
+    addr : String,
+    prefs : Rc<RefCell<prefs::Prefs>>,
+    sender : crossbeam_channel::Sender<messages::EngineMsg>,
+    receiver : crossbeam_channel::Receiver<messages::EngineEvent>,
+    join_handle : Option<thread::JoinHandle<()>>,
+    connected : bool,
+    status : String,
+    error : Option<(String, Instant)>,
+    state : Option<EngineState>,
+    sent_time : Instant,
+    row : Vec<f32>,
+    new_row : bool,
+    // Selected receiver meters by MeterType, without calibration
+    meters : Vec<f32>,
+    // The link can play engine audio, and the receiver the engine is sending
+    audio : bool,
+    audio_rx : Option<u32>,
+}
+
+impl EngineClient {
+    pub fn new(
+            addr : String, token : String, prefs : Rc<RefCell<prefs::Prefs>>,
+            rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>) -> EngineClient {
+
+        let (s, r) = crossbeam_channel::unbounded();
+        let (event_s, event_r) = crossbeam_channel::unbounded();
+        let join_handle = engine_link_start(addr.clone(), token, r, event_s, rb_local_audio);
+        EngineClient {
+            status: format!("Connecting to engine {}", addr),
+            addr: addr,
+            prefs: prefs,
+            sender: s,
+            receiver: event_r,
+            join_handle: Some(join_handle),
+            connected: false,
+            error: None,
+            state: None,
+            sent_time: Instant::now(),
+            row: Vec::new(),
+            new_row: false,
+            meters: Vec::new(),
+            audio: false,
+            audio_rx: None,
+        }
+    }
+
+    fn event(&mut self, event: messages::EngineEvent) {
+        match event {
+            messages::EngineEvent::Connected(audio) => {
+                println!("Connected to engine {}", self.addr);
+                self.connected = true;
+                // Audio is asked for by the mixer
+                self.audio = audio;
+                self.audio_rx = None;
+                self.status = format!("Engine {}", self.addr);
+            },
+            messages::EngineEvent::Disconnected(reason) => {
+                if self.connected {
+                    println!("Engine {} disconnected: {}", self.addr, reason);
+                }
+                self.connected = false;
+                self.state = None;
+                self.meters.clear();
+                self.status = format!("Engine {} {}, retrying", self.addr, reason);
+            },
+            messages::EngineEvent::Text(text) => self.text(&text),
+            messages::EngineEvent::Spectrum(row) => {
+                self.row = row;
+                self.new_row = true;
+            },
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let v: Value = match serde_json::from_str(text) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        match v["type"].as_str().unwrap_or("") {
+            "state" => {
+                if let Some(state) = parse_state(&v) {
+                    if self.state.is_none() || self.sent_time.elapsed() >= Duration::from_millis(HOLD_MS) {
+                        self.apply(&state);
+                        self.state = Some(state);
+                    }
+                }
+            },
+            "meter" => {
+                if let Some(meters) = v["meters"].as_array() {
+                    self.meters = meters.iter().map(|m| m.as_f64().unwrap_or(NO_SIGNAL as f64) as f32).collect();
+                }
+            },
+            "error" => {
+                let message = String::from(v["message"].as_str().unwrap_or(""));
+                println!("Engine error: {}", message);
+                self.error = Some((message, Instant::now()));
+            },
+            _ => (),
+        }
+    }
+
+    // The panels read their settings from the prefs
+    fn apply(&mut self, state: &EngineState) {
+        let mut p = self.prefs.borrow_mut();
+        p.radio.sel_rx = state.rx;
+        globals::set_sel_rx(state.rx);
+        p.radio.af_gain = state.gain;
+        globals::set_af_gain(state.gain);
+        globals::set_num_rx(state.receivers.len() as u32);
+        for (i, r) in state.receivers.iter().enumerate() {
+            let rx = match i {
+                0 => &mut p.radio.rx1,
+                1 => &mut p.radio.rx2,
+                2 => &mut p.radio.rx3,
+                _ => break,
+            };
+            rx.frequency = r.freq;
+            rx.mode = r.mode;
+            rx.filter = r.filter;
+        }
+    }
+
+    fn send(&mut self, cmd: Value) {
+        self.sender.send(messages::EngineMsg::Command(cmd.to_string())).unwrap();
+        self.sent_time = Instant::now();
+    }
+
+    // Receivers the engine has, None until connected
+    fn rx_state(&mut self, rx: u32) -> Option<&mut RxState> {
+        let state = self.state.as_mut()?;
+        return state.receivers.get_mut(rx as usize - 1);
+    }
+}
+
+impl Drop for EngineClient {
+    fn drop(&mut self) {
+        self.sender.send(messages::EngineMsg::Terminate).unwrap();
+        if let Some(h) = self.join_handle.take() {
+            h.join().expect("Join engine link failed!");
+        }
+    }
+}
+
+impl RadioClient for EngineClient {
+    fn poll(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => self.event(event),
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn is_remote(&self) -> bool {
+        return true;
+    }
+
+    fn status(&self) -> String {
+        if let Some((message, time)) = self.error.as_ref() {
+            if time.elapsed() < Duration::from_millis(ERROR_MS) {
+                return format!("{}: {}", self.status, message);
+            }
+        }
+        return self.status.clone();
+    }
+
+    fn discovered(&self) -> bool {
+        return self.state.as_ref().map_or(false, |s| s.hw);
+    }
+
+    fn running(&self) -> bool {
+        return self.state.as_ref().map_or(false, |s| s.run);
+    }
+
+    fn set_run(&mut self, run: bool) {
+        let changed = match self.state.as_mut() {
+            Some(s) if s.run != run => {
+                s.run = run;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            let cmd = if run { "start" } else { "stop" };
+            self.send(json!({"cmd": cmd}));
+        }
+    }
+
+    fn sel_rx(&self) -> u32 {
+        return globals::get_sel_rx();
+    }
+
+    fn set_sel_rx(&mut self, rx: u32) {
+        globals::set_sel_rx(rx);
+        let changed = match self.state.as_mut() {
+            Some(s) if s.rx != rx => {
+                s.rx = rx;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            self.send(json!({"cmd": "rx", "rx": rx}));
+        }
+    }
+
+    fn set_freq(&mut self, rx: u32, freq: u32) {
+        let changed = match self.rx_state(rx) {
+            Some(r) if r.freq != freq => {
+                r.freq = freq;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            self.send(json!({"cmd": "tune", "rx": rx, "freq": freq}));
+        }
+    }
+
+    fn set_mode(&mut self, rx: u32, mode: ModeId) {
+        let changed = match self.rx_state(rx) {
+            Some(r) if r.mode != mode => {
+                r.mode = mode;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            self.send(json!({"cmd": "mode", "rx": rx, "mode": radio::mode_name(mode)}));
+        }
+    }
+
+    fn set_filter(&mut self, rx: u32, filter: FilterId) {
+        let changed = match self.rx_state(rx) {
+            Some(r) if r.filter != filter => {
+                r.filter = filter;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            self.send(json!({"cmd": "filter", "rx": rx, "width": radio::filter_width(filter)}));
+        }
+    }
+
+    // Engine audio arrives before the gain so it is applied here as well
+    fn set_af_gain(&mut self, gain: f32) {
+        globals::set_af_gain(gain);
+        let changed = match self.state.as_mut() {
+            Some(s) if s.gain != gain => {
+                s.gain = gain;
+                true
+            },
+            _ => false,
+        };
+        if changed {
+            self.send(json!({"cmd": "gain", "value": gain}));
+        }
+    }
+
+    fn smpl_rate(&self) -> u32 {
+        return self.state.as_ref().map_or(globals::get_smpl_rate(), |s| s.rate);
+    }
+
+    // The engine row is stretched to the width of our display
+    fn display(&mut self, out: &mut [f32; common_defs::DSP_BLK_SZ as usize]) -> bool {
+        if !self.new_row || self.row.len() == 0 {
+            return false;
+        }
+        self.new_row = false;
+        let width = usize::min(globals::get_disp_width() as usize, out.len());
+        let n = self.row.len();
+        for i in 0..width {
+            out[i] = self.row[i * n / width];
+        }
+        return true;
+    }
+
+    fn num_rx(&self) -> u32 {
+        return self.state.as_ref().map_or(globals::get_num_rx(), |s| s.receivers.len() as u32);
+    }
+
+    // The engine has these set where it runs, the panel shows them disabled
+    fn set_num_rx(&mut self, _num_rx: u32) {}
+    fn set_smpl_rate(&mut self, _rate: u32) {}
+
+    fn set_disp_width(&mut self, width: u32) {
+        globals::set_disp_width(width);
+    }
+
+    fn meter(&self, mt: common_defs::MeterType) -> f32 {
+        match self.meters.get(mt as usize) {
+            Some(m) => return *m,
+            None => return NO_SIGNAL,
+        }
+    }
+
+    // The engine only sends the selected receiver
+    fn rx_meter(&self, rx: u32, mt: common_defs::MeterType) -> f32 {
+        if rx != globals::get_sel_rx() {
+            return NO_SIGNAL;
+        }
+        return self.meter(mt);
+    }
+
+    // Engine audio goes through the local mixer, the engine only sends it while
+    // the selected receiver is not muted
+    fn set_rx_audio(&mut self, rx: u32, channel: &prefs::MixerChannel) {
+        globals::set_rx_vol(rx as i32, channel.vol);
+        globals::set_rx_pan(rx as i32, channel.pan);
+        globals::set_rx_mute(rx as i32, channel.mute);
+        globals::set_rx_route(rx as i32, channel.route);
+        if !self.connected || rx != globals::get_sel_rx() {
+            return;
+        }
+        let mut audio_rx = None;
+        if self.audio && !channel.mute {
+            audio_rx = Some(rx);
+        }
+        if audio_rx != self.audio_rx {
+            let cmd = match audio_rx {
+                Some(rx) => json!({"cmd": "audio", "enable": true, "rx": rx}),
+                None => json!({"cmd": "audio", "enable": false}),
+            };
+            // Not a state change so the engine state is not held off
+            self.sender.send(messages::EngineMsg::Command(cmd.to_string())).unwrap();
+            self.audio_rx = audio_rx;
+        }
+    }
+
+    // Local only features, the panels are not shown for an engine and the
+    // main window says these are set where the engine runs
+    fn set_rf_gain(&mut self, _attn: u32, _preamp: bool) {}
+    fn set_notches(&mut self, _rx: u32, _notches: &[(u32, u32)]) {}
+    fn set_notch_tune(&mut self, _rx: u32, _freq: u32) {}
+    fn set_sub_rx(&mut self, _id: u32, _sub: &prefs::SubRx, _offset: i64, _run: bool) {}
+    fn set_diversity(&mut self, _enabled: bool, _gain: f32, _phase: f32) {}
+    fn set_tx(&mut self, _mode: u32, _profile: &prefs::TxProfile) {}
+    fn tx_meter(&self, _mt: common_defs::TxMeterType) -> f32 {
+        return NO_SIGNAL;
+    }
+    fn set_cw(&mut self, _cw: &prefs::Cw) {}
+    fn set_paddles(&mut self, _dot: bool, _dash: bool) {}
+    fn set_repeater(&mut self, _repeater: &prefs::Repeater) {}
+    fn set_vox(&mut self, _vox: &prefs::Vox) {}
+    fn vox_keyed(&self) -> bool {
+        return false;
+    }
+    fn mic_level(&self) -> f32 {
+        return NO_SIGNAL;
+    }
+    fn rx_level(&self) -> f32 {
+        return NO_SIGNAL;
+    }
+    fn test_mode(&self) -> TestMode {
+        return TestMode::Off;
+    }
+    fn set_test_mode(&mut self, _mode: TestMode) {}
+    fn set_test(&mut self, _mode: TestMode, _test: &prefs::TestSignal) {}
+
+    // Keyed by a web client or CAT where the engine runs
+    fn ptt(&self) -> bool {
+        return self.state.as_ref().map_or(false, |s| s.ptt);
+    }
+}
+
+fn parse_state(v: &Value) -> Option<EngineState> {
+    let mut receivers = Vec::new();
+    for r in v["receivers"].as_array()?.iter() {
+        receivers.push(RxState {
+            freq: r["freq"].as_u64()? as u32,
+            mode: radio::mode_by_name(r["mode"].as_str()?)?,
+            filter: radio::filter_by_width(r["width"].as_u64()? as u32),
+        });
+    }
+    return Some(EngineState {
+        hw: v["hw"].as_bool().unwrap_or(false),
+        run: v["run"].as_bool()?,
+        rx: v["rx"].as_u64()? as u32,
+        rate: v["rate"].as_u64()? as u32,
+        gain: v["gain"].as_f64()? as f32,
+        ptt: v["ptt"].as_bool().unwrap_or(false),
+        receivers: receivers,
+    });
+}
+
+//==================================================================================
+// Runtime object for the connection thread.
+// Connects to the engine web server at /ws and reconnects when the link is lost.
+// Opus audio for the selected receiver is decoded into the local audio ring buffers.
+pub struct EngineLink {
+    addr : String,
+    token : String,
+    receiver : crossbeam_channel::Receiver<messages::EngineMsg>,
+    sender : crossbeam_channel::Sender<messages::EngineEvent>,
+    rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>,
+    decoder : Option<opus::Decoder>,
+    stream : Option<TcpStream>,
+    inbuf : Vec<u8>,
+}
+
+// Implementation methods on EngineLink
+impl EngineLink {
+	// Create a new instance
+    pub fn new(
+        addr : String, token : String,
+        receiver : crossbeam_channel::Receiver<messages::EngineMsg>,
+        sender : crossbeam_channel::Sender<messages::EngineEvent>,
+        rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>) -> EngineLink {
+
+        let decoder = match opus::Decoder::new() {
+            Ok(d) => Some(d),
+            Err(e) => {
+                println!("No engine audio: {}", e);
+                None
+            },
+        };
+        EngineLink {
+            addr: addr,
+            token: token,
+            receiver: receiver,
+            sender: sender,
+            rb_local_audio: rb_local_audio,
+            decoder: decoder,
+            stream: None,
+            inbuf: Vec::new(),
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn engine_link_run(&mut self) {
+        loop {
+            if self.stream.is_none() {
+                match self.connect() {
+                    Ok(()) => {
+                        self.sender.send(messages::EngineEvent::Connected(self.decoder.is_some())).unwrap();
+                    },
+                    Err(e) => {
+                        self.sender.send(messages::EngineEvent::Disconnected(e)).unwrap();
+                        if self.wait(RETRY_MS) {
+                            break;
+                        }
+                        continue;
+                    },
+                }
+            }
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(messages::EngineMsg::Terminate) => break,
+                Ok(messages::EngineMsg::Command(cmd)) => self.write(cmd.as_bytes()),
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.read();
+        }
+        if let Some(mut stream) = self.stream.take() {
+            let close = websocket::encode_masked(websocket::OP_CLOSE, &1000u16.to_be_bytes(), websocket::new_mask());
+            let _ = stream.write_all(&close);
+        }
+    }
+
+    // Pause between attempts, true to give up
+    fn wait(&mut self, ms: u64) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(ms) {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(messages::EngineMsg::Terminate) => return true,
+                // Nowhere to send it, the engine state is taken afresh on connect
+                Ok(messages::EngineMsg::Command(_)) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => return true,
+            }
+        }
+        return false;
+    }
+
+    // Connect and upgrade to a WebSocket with the token
+    fn connect(&mut self) -> Result<(), String> {
+        let timeout = Duration::from_millis(CONNECT_MS);
+        let addr = self.addr.to_socket_addrs().map_err(|e| e.to_string())?.next().ok_or("no address")?;
+        let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+        let _ = stream.set_nodelay(true);
+        stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+        let key = websocket::client_key();
+        let request = format!(
+            "GET /ws HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\nAuthorization: Bearer {}\r\n\r\n",
+            self.addr, key, self.token);
+        stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+        // Anything after the response is the first frames
+        let mut buf = Vec::new();
+        let end;
+        loop {
+            let mut chunk = [0u8; 1024];
+            let n = stream.read(&mut chunk).map_err(|e| e.to_string())?;
+            if n == 0 {
+                return Err(String::from("closed"));
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                end = pos;
+                break;
+            }
+            if buf.len() > MAX_RESPONSE {
+                return Err(String::from("bad response"));
+            }
+        }
+        let response = String::from_utf8_lossy(&buf[..end]).to_string();
+        let mut lines = response.split("\r\n");
+        let status = lines.next().unwrap_or("");
+        if status.contains(" 401 ") {
+            return Err(String::from("refused, bad token"));
+        }
+        if !status.contains(" 101 ") {
+            return Err(format!("refused, {}", status));
+        }
+        let accept = lines
+            .filter_map(|l| l.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-accept"))
+            .map(|(_, value)| String::from(value.trim()));
+        if accept != Some(websocket::accept_key(&key)) {
+            return Err(String::from("bad handshake"));
+        }
+
+        // Short reads from now on so commands are not held up
+        stream.set_read_timeout(Some(Duration::from_millis(1))).map_err(|e| e.to_string())?;
+        self.inbuf = buf[end + 4..].to_vec();
+        self.stream = Some(stream);
+        return Ok(());
+    }
+
+    fn lost(&mut self, reason: &str) {
+        self.stream = None;
+        self.inbuf.clear();
+        self.sender.send(messages::EngineEvent::Disconnected(String::from(reason))).unwrap();
+    }
+
+    fn write(&mut self, text: &[u8]) {
+        self.write_frame(websocket::OP_TEXT, text);
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) {
+        let frame = websocket::encode_masked(opcode, payload, websocket::new_mask());
+        let r = match self.stream.as_mut() {
+            Some(stream) => stream.write_all(&frame),
+            None => return,
+        };
+        if let Err(e) = r {
+            self.lost(&e.to_string());
+        }
+    }
+
+    fn read(&mut self) {
+        loop {
+            let mut chunk = [0u8; 16384];
+            let r = match self.stream.as_mut() {
+                Some(stream) => stream.read(&mut chunk),
+                None => return,
+            };
+            match r {
+                Ok(0) => {
+                    self.lost("closed");
+                    return;
+                },
+                Ok(n) => self.inbuf.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => {
+                    self.lost(&e.to_string());
+                    return;
+                },
+            }
+        }
+        self.frames();
+    }
+
+    fn frames(&mut self) {
+        loop {
+            let (frame, n) = match websocket::decode_server(&self.inbuf, MAX_FRAME) {
+                Ok(Some(f)) => f,
+                Ok(None) => return,
+                Err(e) => {
+                    self.lost(e);
+                    return;
+                },
+            };
+            self.inbuf.drain(..n);
+            match frame.opcode {
+                websocket::OP_TEXT => {
+                    let text = String::from_utf8_lossy(&frame.payload).to_string();
+                    self.sender.send(messages::EngineEvent::Text(text)).unwrap();
+                },
+                websocket::OP_BINARY => self.binary(&frame.payload),
+                websocket::OP_PING => self.write_frame(websocket::OP_PONG, &frame.payload),
+                websocket::OP_CLOSE => {
+                    self.lost("closed by engine");
+                    return;
+                },
+                _ => (),
+            }
+            if self.stream.is_none() {
+                return;
+            }
+        }
+    }
+
+    fn binary(&mut self, data: &[u8]) {
+        if data.len() == 0 {
+            return;
+        }
+        match data[0] {
+            BIN_SPECTRUM => {
+                let row = data[1..].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+                self.sender.send(messages::EngineEvent::Spectrum(row)).unwrap();
+            },
+            BIN_AUDIO if data.len() > 4 => self.audio(data[1] as usize, &data[4..]),
+            _ => (),
+        }
+    }
+
+    // Mono from the engine, the local audio takes 16 bit LE stereo
+    fn audio(&mut self, rx: usize, packet: &[u8]) {
+        let decoder = match self.decoder.as_mut() {
+            Some(d) => d,
+            None => return,
+        };
+        if rx < 1 || rx > self.rb_local_audio.len() {
+            return;
+        }
+        let pcm = decoder.decode(Some(packet));
+        let mut bytes = Vec::with_capacity(pcm.len() * 4);
+        for s in pcm.iter() {
+            let v = (f32::max(-1.0, f32::min(1.0, *s)) * 32767.0) as i16;
+            bytes.extend_from_slice(&v.to_le_bytes());
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        // Dropped when the local audio is not taking it
+        let _ = self.rb_local_audio[rx - 1].write().write(&bytes);
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn engine_link_start(
+    addr : String, token : String,
+    receiver : crossbeam_channel::Receiver<messages::EngineMsg>,
+    sender : crossbeam_channel::Sender<messages::EngineEvent>,
+    rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        engine_link_run(addr, token, receiver, sender, rb_local_audio);
+    });
+    return join_handle;
+}
+
+fn engine_link_run(
+    addr : String, token : String,
+    receiver : crossbeam_channel::Receiver<messages::EngineMsg>,
+    sender : crossbeam_channel::Sender<messages::EngineEvent>,
+    rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>) {
+    println!("Engine link running");
+
+    // Instantiate the runtime object
+    let mut i_link = EngineLink::new(addr, token, receiver, sender, rb_local_audio);
+
+    // Exits when the link loop exits
+    i_link.engine_link_run();
+
+    println!("Engine link exiting");
+}
@@ -0,0 +1,573 @@
+/*
+local_client.rs
+
+Module - local_client
+The radio engine in this process
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::sync::{Arc, Mutex};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::common_defs;
+use crate::app::common::cc_out_defs;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::common::prefs;
+use crate::app::protocol;
+use crate::app::dsp;
+use crate::app::udp::hw_control;
+use crate::app::client::radio_client::RadioClient;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+use crate::app::pipeline::test_signal::TestMode;
+
+// Hardware restart for a change of receivers or sample rate.
+// Each step is taken on a separate poll so the rest of the system has a chance to reset.
+enum RestartState {None, Stop, Start, RateWait}
+
+// What the DSP channel of a sub-receiver is currently set to
+struct SubChannel {
+    running: bool,
+    offset: Option<i64>,
+    mode_filter: Option<(ModeId, FilterId)>,
+}
+
+//==================================================================================
+// The engine in this process, changes go straight to the hardware and DSP.
+// The last setting is kept where the panels set everything every frame.
+pub struct LocalClient {
+    i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    hw : Rc<RefCell<hw_control::HWData>>,
+    pipeline_sender : crossbeam_channel::Sender<messages::PipelineMsg>,
+    w_sender : crossbeam_channel::Sender<messages::WriterMsg>,
+    restart_state : RestartState,
+    restart_after_rate : bool,
+    num_rx : Option<u32>,
+    smpl_rate : u32,
+    // RX1 frequency last set, the TX and repeater shift are worked out from it
+    rx1_freq : u32,
+    // RX2 frequency last set, it follows RX1 in diversity
+    rx2_freq : u32,
+    div_active : bool,
+    subs : Vec<SubChannel>,
+    tx : Option<(u32, prefs::TxProfile)>,
+    cw : Option<prefs::Cw>,
+    // Shift direction, shift and reverse, and CTCSS encode
+    shift : Option<(u32, u32, bool)>,
+    ctcss_tx : Option<(bool, f32)>,
+}
+
+impl LocalClient {
+    pub fn new(
+            i_cc : Arc<Mutex<protocol::cc_out::CCData>>, hw : Rc<RefCell<hw_control::HWData>>,
+            pipeline_sender : crossbeam_channel::Sender<messages::PipelineMsg>,
+            w_sender : crossbeam_channel::Sender<messages::WriterMsg>) -> LocalClient {
+        let mut subs = Vec::new();
+        for _ in 0..common_defs::MAX_SUB_RX {
+            subs.push(SubChannel {running: false, offset: None, mode_filter: None});
+        }
+        LocalClient {
+            i_cc: i_cc,
+            hw: hw,
+            pipeline_sender: pipeline_sender,
+            w_sender: w_sender,
+            restart_state: RestartState::None,
+            restart_after_rate: false,
+            num_rx: None,
+            smpl_rate: globals::get_smpl_rate(),
+            rx1_freq: 0,
+            rx2_freq: 0,
+            div_active: false,
+            subs: subs,
+            tx: None,
+            cw: None,
+            shift: None,
+            ctcss_tx: None,
+        }
+    }
+
+    // RX1 and TX share the VFO frequency.
+    // In FM a repeater shift moves TX to the repeater input, reverse swaps them
+    // so the receiver listens on the input and the transmitter uses the output.
+    // In split TX is on the RX2 frequency.
+    fn set_rx1_tx_freq(&mut self, freq: u32) {
+        let mut rx = freq;
+        let mut tx = freq;
+        if globals::get_mode(1) == ModeId::Fm as u32 {
+            let shift = globals::get_rpt_shift();
+            let dir = globals::get_rpt_shift_dir();
+            if dir == common_defs::RptShift::Plus as u32 {
+                tx = freq.saturating_add(shift);
+            } else if dir == common_defs::RptShift::Minus as u32 {
+                tx = freq.saturating_sub(shift);
+            }
+            if globals::get_rpt_reverse() {
+                rx = tx;
+                tx = freq;
+            }
+        }
+        if globals::get_split() {
+            tx = globals::get_rx_freq(2);
+        }
+        let mut cc = self.i_cc.lock().unwrap();
+        cc.cc_set_rx1_freq(rx);
+        cc.cc_set_tx_freq(tx);
+        // Diversity needs both receivers on the same frequency
+        if self.div_active {
+            cc.cc_set_rx2_freq(rx);
+        }
+    }
+
+    //==================================================================================
+    // Restart sequence
+    // Stop the hardware so no data is in flight, set the new speed and ask the pipeline to
+    // drain and reconfigure. The hardware is restarted when the pipeline signals completion.
+    fn do_rate_change(&mut self, rate: u32) {
+        if globals::get_run_state() {
+            self.hw.borrow_mut().do_stop();
+            globals::set_run_state(false);
+            self.restart_after_rate = true;
+        }
+        globals::set_smpl_rate(rate);
+        match rate {
+            common_defs::SMPLS_96K => self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S96kHz),
+            common_defs::SMPLS_192K => self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S192kHz),
+            common_defs::SMPLS_384K => self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S384kHz),
+            _ => self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S48kHz),
+        }
+        globals::set_rate_change_pending(true);
+        self.pipeline_sender.send(messages::PipelineMsg::RateChange(rate)).unwrap();
+        self.restart_state = RestartState::RateWait;
+    }
+
+    // Wait for the pipeline to complete the rate change then restart
+    fn do_rate_wait(&mut self) {
+        if globals::get_rate_change_pending() {
+            return;
+        }
+        // The display is read on this thread so is reconfigured here
+        self.update_disp();
+        // Make sure the hardware has the new speed before it starts streaming
+        self.w_sender.send(messages::WriterMsg::PrimeHardware).unwrap();
+        self.restart_state = RestartState::None;
+        // Another rate may have been asked for in the meantime
+        if self.restart_after_rate && self.smpl_rate == globals::get_smpl_rate() {
+            self.hw.borrow_mut().do_start(false);
+            globals::set_run_state(true);
+            self.restart_after_rate = false;
+        }
+    }
+
+    // Stop if we have changed number of radios
+    fn do_stop(&mut self) {
+        self.restart_state = RestartState::None;
+        if globals::get_run_state() {
+            self.hw.borrow_mut().do_stop();
+            globals::set_run_state(false);
+            self.restart_state = RestartState::Start;
+        }
+    }
+
+    // Start if we have changed number of radios
+    fn do_start(&mut self) {
+        self.hw.borrow_mut().do_start(false);
+        globals::set_run_state(true);
+        self.restart_state = RestartState::None;
+    }
+
+    // The analyzer calculates its overlap and buffer sizes from the rate and width
+    fn update_disp(&mut self) {
+        dsp::dsp_interface::wdsp_update_disp(
+            0, common_defs::FFT_SZ, common_defs::WindowTypes::Rectangular as i32, 
+            common_defs::SUB_SPANS, common_defs::IN_SZ, globals::get_disp_width() as i32, 
+            common_defs::AvMode::PanTimeAvLin as i32, common_defs::OVER_FRAMES, 
+            globals::get_smpl_rate() as i32, common_defs::FRAME_RATE);
+    }
+}
+
+impl RadioClient for LocalClient {
+    // Carry on with any restart
+    fn poll(&mut self) {
+        match self.restart_state {
+            RestartState::None => {
+                if self.smpl_rate != globals::get_smpl_rate() {
+                    self.do_rate_change(self.smpl_rate);
+                }
+            },
+            RestartState::Stop => self.do_stop(),
+            RestartState::Start => self.do_start(),
+            RestartState::RateWait => self.do_rate_wait(),
+        }
+    }
+
+    fn is_remote(&self) -> bool {
+        return false;
+    }
+
+    fn status(&self) -> String {
+        return String::new();
+    }
+
+    fn discovered(&self) -> bool {
+        return globals::get_discover_state();
+    }
+
+    fn running(&self) -> bool {
+        return globals::get_run_state();
+    }
+
+    fn set_run(&mut self, run: bool) {
+        if run && !globals::get_run_state() && globals::get_discover_state() {
+            self.hw.borrow_mut().do_start(false);
+            globals::set_run_state(true);
+        } else if !run && globals::get_run_state() {
+            self.hw.borrow_mut().do_stop();
+            globals::set_run_state(false);
+        }
+    }
+
+    fn sel_rx(&self) -> u32 {
+        return globals::get_sel_rx();
+    }
+
+    fn set_sel_rx(&mut self, rx: u32) {
+        globals::set_sel_rx(rx);
+    }
+
+    fn set_freq(&mut self, rx: u32, freq: u32) {
+        match rx {
+            1 => {
+                self.rx1_freq = freq;
+                self.set_rx1_tx_freq(freq);
+            },
+            2 => {
+                self.rx2_freq = freq;
+                if !self.div_active {
+                    self.i_cc.lock().unwrap().cc_set_rx2_freq(freq);
+                }
+            },
+            3 => self.i_cc.lock().unwrap().cc_set_rx3_freq(freq),
+            _ => (),
+        }
+    }
+
+    fn set_mode(&mut self, rx: u32, mode: ModeId) {
+        let was_fm = globals::get_mode(rx as i32) == ModeId::Fm as u32;
+        globals::set_mode(rx as i32, mode as u32);
+        dsp::dsp_interface::set_mode_filter(rx as i32 - 1, rx as i32);
+        // The repeater shift only applies in FM
+        if rx == 1 && was_fm != (mode == ModeId::Fm) && self.rx1_freq > 0 {
+            self.set_rx1_tx_freq(self.rx1_freq);
+        }
+    }
+
+    fn set_filter(&mut self, rx: u32, filter: FilterId) {
+        globals::set_filter(rx as i32, filter as u32);
+        dsp::dsp_interface::set_mode_filter(rx as i32 - 1, rx as i32);
+    }
+
+    fn set_af_gain(&mut self, gain: f32) {
+        globals::set_af_gain(gain);
+    }
+
+    fn num_rx(&self) -> u32 {
+        return globals::get_num_rx();
+    }
+
+    fn set_num_rx(&mut self, num_rx: u32) {
+        if self.num_rx == Some(num_rx) {
+            return;
+        }
+        if num_rx != globals::get_num_rx() && globals::get_run_state() {
+            self.restart_state = RestartState::Stop;
+        }
+        globals::set_num_rx(num_rx);
+        match num_rx {
+            2 => self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx2),
+            3 => self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx3),
+            _ => self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx1),
+        }
+        self.num_rx = Some(num_rx);
+    }
+
+    // Changed on the next poll
+    fn set_smpl_rate(&mut self, rate: u32) {
+        self.smpl_rate = rate;
+    }
+
+    fn smpl_rate(&self) -> u32 {
+        return globals::get_smpl_rate();
+    }
+
+    fn display(&mut self, out: &mut [f32; common_defs::DSP_BLK_SZ as usize]) -> bool {
+        return dsp::dsp_interface::wdsp_get_display_data(0, out);
+    }
+
+    // Remote clients are sent this many pixels
+    fn set_disp_width(&mut self, width: u32) {
+        globals::set_disp_width(width);
+        // A rate change updates the display once the pipeline has changed over
+        if !globals::get_rate_change_pending() {
+            self.update_disp();
+        }
+    }
+
+    fn meter(&self, mt: common_defs::MeterType) -> f32 {
+        return self.rx_meter(globals::get_sel_rx(), mt);
+    }
+
+    fn rx_meter(&self, rx: u32, mt: common_defs::MeterType) -> f32 {
+        return dsp::dsp_interface::wdsp_get_rx_meter(rx as i32 - 1, mt as i32) as f32;
+    }
+
+    fn set_rf_gain(&mut self, attn: u32, preamp: bool) {
+        let cc_attn;
+        match attn {
+            1 => cc_attn = cc_out_defs::CCOAlexAttn::Attn10db,
+            2 => cc_attn = cc_out_defs::CCOAlexAttn::Attn20db,
+            3 => cc_attn = cc_out_defs::CCOAlexAttn::Attn30db,
+            _ => cc_attn = cc_out_defs::CCOAlexAttn::Attn0db,
+        }
+        let cc_preamp;
+        if preamp {
+            cc_preamp = cc_out_defs::CCOPreamp::PreAmpOn;
+        } else {
+            cc_preamp = cc_out_defs::CCOPreamp::PreAmpOff;
+        }
+        self.i_cc.lock().unwrap().cc_alex_attn(cc_attn);
+        self.i_cc.lock().unwrap().cc_preamp(cc_preamp);
+        globals::set_rf_attn(attn);
+        globals::set_rf_preamp(preamp);
+    }
+
+    fn set_rx_audio(&mut self, rx: u32, channel: &prefs::MixerChannel) {
+        let rx = rx as i32;
+        globals::set_rx_vol(rx, channel.vol);
+        globals::set_rx_pan(rx, channel.pan);
+        globals::set_rx_mute(rx, channel.mute);
+        globals::set_rx_route(rx, channel.route);
+    }
+
+    fn set_notches(&mut self, rx: u32, notches: &[(u32, u32)]) {
+        dsp::dsp_interface::wdsp_set_notches(rx as i32 - 1, notches);
+    }
+
+    fn set_notch_tune(&mut self, rx: u32, freq: u32) {
+        dsp::dsp_interface::wdsp_set_notch_tune(rx as i32 - 1, freq);
+    }
+
+    fn set_sub_rx(&mut self, id: u32, sub: &prefs::SubRx, offset: i64, run: bool) {
+        let ch = (common_defs::SUB_RX_CH + id) as i32;
+        let state = match self.subs.get_mut(id as usize) {
+            Some(state) => state,
+            None => return,
+        };
+        if state.offset != Some(offset) {
+            dsp::dsp_interface::wdsp_set_shift(ch, offset as f64);
+            state.offset = Some(offset);
+        }
+        if state.mode_filter != Some((sub.mode, sub.filter)) {
+            dsp::dsp_interface::wdsp_set_mode_filter(ch, sub.mode as u32, sub.filter as u32);
+            state.mode_filter = Some((sub.mode, sub.filter));
+        }
+        if run != state.running {
+            if run {
+                dsp::dsp_interface::wdsp_set_ch_state(ch, 1, 0);
+                globals::set_sub_enabled(id, true);
+            } else {
+                // Stop the pipeline using the channel before stopping it
+                globals::set_sub_enabled(id, false);
+                dsp::dsp_interface::wdsp_set_ch_state(ch, 0, 1);
+            }
+            state.running = run;
+        }
+        globals::set_sub_vol(id, sub.vol);
+        globals::set_sub_pan(id, sub.pan);
+        globals::set_sub_mute(id, sub.mute);
+    }
+
+    // RX2 must be on the second ADC and on the RX1 frequency
+    fn set_diversity(&mut self, enabled: bool, gain: f32, phase: f32) {
+        globals::set_diversity(enabled);
+        globals::set_div_gain(gain);
+        globals::set_div_phase(phase);
+        let active = enabled && globals::get_num_rx() >= 2;
+        if active == self.div_active {
+            return;
+        }
+        let mut cc = self.i_cc.lock().unwrap();
+        if active {
+            cc.cc_rx2_adc(cc_out_defs::CCOAdc::Adc2);
+            cc.cc_set_rx2_freq(self.rx1_freq);
+        } else {
+            // Back to normal
+            cc.cc_rx2_adc(cc_out_defs::CCOAdc::Adc1);
+            cc.cc_set_rx2_freq(self.rx2_freq);
+        }
+        self.div_active = active;
+    }
+
+    // Set anything that has changed on the TX channel
+    fn set_tx(&mut self, mode: u32, profile: &prefs::TxProfile) {
+        let ch = common_defs::TX_CH as i32;
+        let p = profile;
+        let (last_mode, a) = match self.tx.as_ref() {
+            Some((m, a)) => (Some(*m), Some(a)),
+            None => (None, None),
+        };
+        if last_mode != Some(mode) || a.map_or(true, |a| p.low != a.low || p.high != a.high) {
+            dsp::dsp_interface::wdsp_set_tx_mode_filter(ch, mode, p.low, p.high);
+        }
+        if a.map_or(true, |a| p.mic_gain != a.mic_gain) {
+            dsp::dsp_interface::wdsp_set_mic_gain(ch, p.mic_gain);
+        }
+        if a.map_or(true, |a| p.eq_enabled != a.eq_enabled || p.eq_preamp != a.eq_preamp || p.eq != a.eq) {
+            dsp::dsp_interface::wdsp_set_tx_eq(ch, p.eq_enabled, p.eq_preamp, &p.eq);
+        }
+        if a.map_or(true, |a| p.leveler_enabled != a.leveler_enabled || p.leveler_gain != a.leveler_gain) {
+            dsp::dsp_interface::wdsp_set_leveler(ch, p.leveler_enabled, p.leveler_gain);
+        }
+        if a.map_or(true, |a| p.comp_enabled != a.comp_enabled || p.comp_gain != a.comp_gain || p.cfc_enabled != a.cfc_enabled) {
+            dsp::dsp_interface::wdsp_set_compressor(ch, p.comp_enabled, p.comp_gain, p.cfc_enabled);
+        }
+        if a.map_or(true, |a| p.cessb_enabled != a.cessb_enabled) {
+            dsp::dsp_interface::wdsp_set_cessb(ch, p.cessb_enabled);
+        }
+        if a.map_or(true, |a| p.alc_gain != a.alc_gain) {
+            dsp::dsp_interface::wdsp_set_alc(ch, p.alc_gain);
+        }
+        if last_mode != Some(mode) || a != Some(p) {
+            self.tx = Some((mode, p.clone()));
+        }
+    }
+
+    fn tx_meter(&self, mt: common_defs::TxMeterType) -> f32 {
+        return dsp::dsp_interface::wdsp_get_tx_meter(common_defs::TX_CH as i32, mt as i32) as f32;
+    }
+
+    // The software keyer is not used when the hardware keyer is selected
+    fn set_cw(&mut self, cw: &prefs::Cw) {
+        globals::set_cw_keyer(cw.keyer && !cw.hw_keyer);
+        globals::set_keyer_mode_b(cw.mode_b);
+        globals::set_keyer_wpm(cw.wpm);
+        globals::set_keyer_weight(cw.weight);
+        globals::set_sidetone_freq(cw.sidetone_freq);
+        globals::set_sidetone_vol(cw.sidetone_vol);
+        globals::set_cw_hang(cw.hang);
+        if self.cw.as_ref() == Some(cw) {
+            return;
+        }
+        // The hardware keyer takes the key input directly and generates its own sidetone
+        let mut cc = self.i_cc.lock().unwrap();
+        if cw.keyer && cw.hw_keyer {
+            cc.cc_cw_keyer(cc_out_defs::CCOCwKeyer::CwInternal);
+        } else {
+            cc.cc_cw_keyer(cc_out_defs::CCOCwKeyer::CwExternal);
+        }
+        if cw.mode_b {
+            cc.cc_keyer_mode(cc_out_defs::CCOKeyerMode::KeyerModeB);
+        } else {
+            cc.cc_keyer_mode(cc_out_defs::CCOKeyerMode::KeyerModeA);
+        }
+        cc.cc_keyer_speed(cw.wpm as u8);
+        cc.cc_keyer_weight(cw.weight as u8);
+        cc.cc_sidetone_vol((cw.sidetone_vol * 127.0) as u8);
+        cc.cc_sidetone_freq(cw.sidetone_freq as u16);
+        cc.cc_cw_hang(cw.hang as u16);
+        self.cw = Some(cw.clone());
+    }
+
+    fn set_paddles(&mut self, dot: bool, dash: bool) {
+        globals::set_key_dot(dot);
+        globals::set_key_dash(dash);
+    }
+
+    fn set_repeater(&mut self, repeater: &prefs::Repeater) {
+        globals::set_rpt_shift_dir(repeater.shift_dir);
+        globals::set_rpt_shift(repeater.shift);
+        globals::set_rpt_reverse(repeater.reverse);
+        globals::set_tone_sql(repeater.tone_sql);
+        globals::set_sql_ctcss(repeater.sql_ctcss);
+        globals::set_sql_dcs(repeater.sql_dcs);
+
+        // Retune so the new shift takes effect now rather than on the next VFO change
+        let shift = (repeater.shift_dir, repeater.shift, repeater.reverse);
+        if self.shift != Some(shift) {
+            if self.rx1_freq > 0 {
+                self.set_rx1_tx_freq(self.rx1_freq);
+            }
+            self.shift = Some(shift);
+        }
+
+        let ctcss_tx = (repeater.ctcss_tx, repeater.ctcss_tx_freq);
+        if self.ctcss_tx != Some(ctcss_tx) {
+            dsp::dsp_interface::wdsp_set_ctcss(common_defs::TX_CH as i32, ctcss_tx.0, ctcss_tx.1);
+            self.ctcss_tx = Some(ctcss_tx);
+        }
+    }
+
+    fn set_vox(&mut self, vox: &prefs::Vox) {
+        globals::set_vox(vox.enabled);
+        globals::set_vox_threshold(vox.threshold);
+        globals::set_vox_attack(vox.attack);
+        globals::set_vox_hang(vox.hang);
+        globals::set_anti_vox(vox.anti_vox);
+        globals::set_anti_vox_gain(vox.anti_vox_gain);
+    }
+
+    fn ptt(&self) -> bool {
+        return globals::get_ptt();
+    }
+
+    fn vox_keyed(&self) -> bool {
+        return globals::get_vox() && globals::get_vox_active();
+    }
+
+    fn mic_level(&self) -> f32 {
+        return globals::get_mic_level();
+    }
+
+    fn rx_level(&self) -> f32 {
+        return globals::get_rx_level();
+    }
+
+    fn test_mode(&self) -> TestMode {
+        return TestMode::from_u32(globals::get_test_mode());
+    }
+
+    fn set_test_mode(&mut self, mode: TestMode) {
+        globals::set_test_mode(mode as u32);
+    }
+
+    fn set_test(&mut self, mode: TestMode, test: &prefs::TestSignal) {
+        if mode == TestMode::Tune {
+            globals::set_test_level(test.tune_level);
+        } else {
+            globals::set_test_level(test.tone_level);
+        }
+        globals::set_test_max_level(test.max_level);
+        globals::set_test_tone1(test.tone1);
+        globals::set_test_tone2(test.tone2);
+        globals::set_test_timeout(test.timeout);
+    }
+}
@@ -0,0 +1,117 @@
+/*
+radio_client.rs
+
+Module - radio_client
+Interface between the UI and the radio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use crate::app::common::common_defs;
+use crate::app::common::prefs;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+use crate::app::pipeline::test_signal::TestMode;
+
+//==================================================================================
+// Everything the main UI panels need from the radio.
+// The prefs remain the UI settings, the panels pass changes on through the client
+// every frame so a client only acts on real changes.
+// LocalClient drives the engine in this process, EngineClient a headless engine
+// on another machine through its web server.
+pub trait RadioClient {
+    // Start of each UI frame, changes made at a remote engine are copied to the prefs
+    fn poll(&mut self);
+
+    // Running against a remote engine, panels for local only features are not shown
+    fn is_remote(&self) -> bool;
+
+    // Connection state for the UI, empty when there is nothing to say
+    fn status(&self) -> String;
+
+    // Hardware is there to start
+    fn discovered(&self) -> bool;
+    fn running(&self) -> bool;
+    fn set_run(&mut self, run: bool);
+
+    fn sel_rx(&self) -> u32;
+    fn set_sel_rx(&mut self, rx: u32);
+
+    // rx is 1 based
+    fn set_freq(&mut self, rx: u32, freq: u32);
+    fn set_mode(&mut self, rx: u32, mode: ModeId);
+    fn set_filter(&mut self, rx: u32, filter: FilterId);
+    fn set_af_gain(&mut self, gain: f32);
+
+    // The hardware is restarted for these when it is running
+    fn num_rx(&self) -> u32;
+    fn set_num_rx(&mut self, num_rx: u32);
+    fn set_smpl_rate(&mut self, rate: u32);
+
+    // Span of the display
+    fn smpl_rate(&self) -> u32;
+
+    // Latest display pixels for the selected receiver, true when they are new
+    fn display(&mut self, out: &mut [f32; common_defs::DSP_BLK_SZ as usize]) -> bool;
+    // Pixels across our display
+    fn set_disp_width(&mut self, width: u32);
+
+    // Selected receiver meter in dBm without calibration
+    fn meter(&self, mt: common_defs::MeterType) -> f32;
+    fn rx_meter(&self, rx: u32, mt: common_defs::MeterType) -> f32;
+    fn set_rf_gain(&mut self, attn: u32, preamp: bool);
+
+    // Local audio of a receiver
+    fn set_rx_audio(&mut self, rx: u32, channel: &prefs::MixerChannel);
+
+    // Notches are (freq, width) at RF and follow the receiver frequency
+    fn set_notches(&mut self, rx: u32, notches: &[(u32, u32)]);
+    fn set_notch_tune(&mut self, rx: u32, freq: u32);
+
+    // Sub-receiver id at an offset from the selected receiver, run when enabled and in the passband
+    fn set_sub_rx(&mut self, id: u32, sub: &prefs::SubRx, offset: i64, run: bool);
+
+    // RX2 on the second ADC following RX1, gain and phase of RX2
+    fn set_diversity(&mut self, enabled: bool, gain: f32, phase: f32);
+
+    // TX chain for the mode, the level at a stage of the chain in dB
+    fn set_tx(&mut self, mode: u32, profile: &prefs::TxProfile);
+    fn tx_meter(&self, mt: common_defs::TxMeterType) -> f32;
+
+    // Keyer settings and the keyboard paddles
+    fn set_cw(&mut self, cw: &prefs::Cw);
+    fn set_paddles(&mut self, dot: bool, dash: bool);
+
+    // Shift, tones and squelch, RX1 and TX are retuned for a new shift
+    fn set_repeater(&mut self, repeater: &prefs::Repeater);
+
+    // VOX settings, the key state and the Mic and receiver levels in dB VOX works from
+    fn set_vox(&mut self, vox: &prefs::Vox);
+    fn ptt(&self) -> bool;
+    fn vox_keyed(&self) -> bool;
+    fn mic_level(&self) -> f32;
+    fn rx_level(&self) -> f32;
+
+    // Test signal settings for the mode, the pipeline turns the test off when it times out
+    fn test_mode(&self) -> TestMode;
+    fn set_test_mode(&mut self, mode: TestMode);
+    fn set_test(&mut self, mode: TestMode, test: &prefs::TestSignal);
+}
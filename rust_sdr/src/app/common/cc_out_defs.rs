@@ -124,4 +124,24 @@ pub enum CCOAlexTxRly{
 	TxRlyTx1,
 	TxRlyTx2,
 	TxRlyTx3
+}
+
+// ADC assignment for a receiver
+pub enum CCOAdc {
+	Adc1,
+	Adc2,
+	Adc3
+}
+
+// Hardware keyer mode
+pub enum CCOKeyerMode {
+	KeyerStraight,
+	KeyerModeA,
+	KeyerModeB
+}
+
+// CW from the hardware keyer or from the IQ stream
+pub enum CCOCwKeyer {
+	CwExternal,
+	CwInternal
 }
\ No newline at end of file
@@ -25,7 +25,7 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-//use serde:: {Serialize, Deserialize};
+use serde:: {Serialize, Deserialize};
 
 // UDP frame sz
 pub const FRAME_SZ : u32 = 1032;
@@ -69,6 +69,37 @@ pub const SMPLS_96K : u32 = 96000;
 pub const SMPLS_192K : u32 = 192000;
 pub const SMPLS_384K : u32 = 384000;
 
+// Mode enumerations
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+pub enum ModeId {
+    Lsb, 
+    Usb,
+    Dsb,
+    CwL,
+    CwU,
+    Fm,
+    Am,
+    DigU,
+    Spec,
+    DigL,
+    Sam,
+    Drm,
+}
+
+// Filter enumerations
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+pub enum FilterId {
+    F6_0KHz,
+    F4_0KHz,
+    F2_7KHz,
+    F2_4KHz,
+    F2_1KHz,
+    F1_0KHz,
+    F500Hz,
+    F250Hz,
+    F100Hz,
+}
+
 // Misc
 pub const MAX_RADIOS : u32 = 3;
 pub const NUM_RX : u32 = 1;
@@ -79,6 +110,23 @@ pub const BYTES_PER_SAMPLE : u32 = 6;   // 2*24 bits
 pub const MIC_BYTES_PER_SAMPLE : u32 = 2;   // 16 bits
 pub const FRAMES_IN_RING : u32 = 8;     // Number of frames to accommodate in the IQ ring buffer
 
+// Ring buffer capacity in bytes for the IQ data at the given sample rate.
+// Data arrives faster at higher rates so the buffer must scale to hold the same time span.
+pub fn iq_ring_capacity(num_rx: u32, rate: u32) -> usize {
+	let scale = rate_decimation(rate) as u32;
+	return (num_rx * PROT_SZ * 2 * BYTES_PER_SAMPLE * FRAMES_IN_RING * scale) as usize;
+}
+
+// Ratio of the IQ sample rate to the fixed 48KHz audio rate
+pub fn rate_decimation(rate: u32) -> usize {
+	match rate {
+		SMPLS_96K => return 2,
+		SMPLS_192K => return 4,
+		SMPLS_384K => return 8,
+		_ => return 1,
+	}
+}
+
 // DSP
 // Defaults
 pub const FFT_SZ: i32 = 8192;
@@ -92,12 +140,151 @@ pub const DISPLAY_WIDTH: i32 = 300;
 // Audio
 // Gain factor for local audio
 pub const AUDIO_GAIN: f32 = 10.0;
+// Default receiver volume in the local audio mixer
+pub const RX_VOL: f32 = 1.0;
+// Local audio devices
+pub const AUDIO_MAIN: u32 = 0;
+pub const AUDIO_AUX: u32 = 1;
+// Digital mode decoders
+pub const DEC_RTTY_45: u32 = 0;
+pub const DEC_RTTY_50: u32 = 1;
+pub const DEC_PSK31: u32 = 2;
+// Default audio offset of a digital signal
+pub const DEC_OFFSET: f32 = 1500.0;
+
+// Diversity, default gain of RX2 relative to RX1
+pub const DIV_GAIN: f32 = 1.0;
+
+// Software sub-receivers, each uses its own DSP channel after the main receivers
+pub const MAX_SUB_RX: u32 = 4;
+pub const SUB_RX_CH: u32 = MAX_RADIOS;
+
+// TX channel follows the sub-receivers
+pub const TX_CH: u32 = SUB_RX_CH + MAX_SUB_RX;
+// Mic samples exchanged with the TX channel, at 48K
+pub const MIC_BLK_SZ: u32 = DSP_BLK_SZ;
+// TX IQ buffered while waiting for the receiver audio it is sent with
+pub const TX_FIFO_SZ: usize = (DSP_BLK_SZ * 2 * 4) as usize;
+// TX audio passband
+pub const TX_LOW: i32 = 150;
+pub const TX_HIGH: i32 = 2850;
+// Graphic equaliser bands and their centre frequencies
+pub const TX_EQ_BANDS: u32 = 10;
+pub const TX_EQ_FREQS: [&str; TX_EQ_BANDS as usize] = ["32", "63", "125", "250", "500", "1K", "2K", "4K", "8K", "16K"];
+// Profile used when none have been saved
+pub const TX_DEFAULT_PROFILE: &str = "Default";
+
+// VOX defaults, threshold and anti-VOX gain in dB, attack and hang in ms
+pub const VOX_THRESHOLD: f32 = -30.0;
+pub const VOX_ATTACK: u32 = 10;
+pub const VOX_HANG: u32 = 500;
+pub const ANTI_VOX_GAIN: f32 = 6.0;
+
+// CW keyer defaults
+pub const KEYER_WPM: u32 = 20;
+pub const KEYER_WEIGHT: u32 = 50;
+pub const SIDETONE_FREQ: u32 = 600;
+pub const SIDETONE_VOL: f32 = 0.5;
+// Semi-break-in hang in ms
+pub const CW_HANG: u32 = 300;
+// Amplitude of the CW carrier on the TX IQ
+pub const CW_LEVEL: f64 = 0.9;
+
+// Test signal defaults, levels are a fraction of full drive
+pub const TUNE_LEVEL: f32 = 0.1;
+pub const TWO_TONE_LEVEL: f32 = 0.5;
+pub const TWO_TONE_1: u32 = 700;
+pub const TWO_TONE_2: u32 = 1900;
+pub const TEST_MAX_LEVEL: f32 = 0.5;
+// Longest a test may transmit for in seconds
+pub const TEST_TIMEOUT: u32 = 60;
+
+// Default directory for recordings
+pub const RECORD_DIR: &str = "recordings";
+
+// Hamlib rigctld default port
+pub const RIGCTLD_PORT: u32 = 4532;
+
+// Default link to the TS-2000 CAT pseudo-terminal
+pub const CAT_PTY_LINK: &str = "/tmp/rustsdr-cat";
+
+// Audio bridge default ports, RX2 and RX3 follow RX1 on even ports
+pub const BRIDGE_RX_PORT: u32 = 5004;
+pub const BRIDGE_TX_PORT: u32 = 5010;
+
+// Web server default port
+pub const WEB_PORT: u32 = 8073;
+
+// HPSDR Protocol 1 clients discover and talk to the radio on this port
+pub const HPSDR_PORT: u32 = 1024;
+
+// rtl_tcp default port
+pub const RTL_TCP_PORT: u32 = 1234;
+
+// FM repeater defaults, shift in Hz
+pub const RPT_SHIFT: u32 = 100000;
+pub const CTCSS_DEFAULT: f32 = 88.5;
+pub const DCS_DEFAULT: u32 = 0o023;
+// Standard CTCSS tones in Hz
+pub const CTCSS_TONES: [f32; 51] = [
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5,
+    94.8, 97.4, 100.0, 103.5, 107.2, 110.9, 114.8, 118.8, 123.0, 127.3,
+    131.8, 136.5, 141.3, 146.2, 150.0, 151.4, 156.7, 159.8, 162.2, 165.5,
+    167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6,
+    199.5, 203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3,
+    254.1,
+];
+// Standard DCS codes in octal
+pub const DCS_CODES: [u32; 104] = [
+    0o023, 0o025, 0o026, 0o031, 0o032, 0o036, 0o043, 0o047, 0o051, 0o053, 0o054, 0o065, 0o071,
+    0o072, 0o073, 0o074, 0o114, 0o115, 0o116, 0o122, 0o125, 0o131, 0o132, 0o134, 0o143, 0o145,
+    0o152, 0o155, 0o156, 0o162, 0o165, 0o172, 0o174, 0o205, 0o212, 0o223, 0o225, 0o226, 0o243,
+    0o244, 0o245, 0o246, 0o251, 0o252, 0o255, 0o261, 0o263, 0o265, 0o266, 0o271, 0o274, 0o306,
+    0o311, 0o315, 0o325, 0o331, 0o332, 0o343, 0o346, 0o351, 0o356, 0o364, 0o365, 0o371, 0o411,
+    0o412, 0o413, 0o423, 0o431, 0o432, 0o445, 0o446, 0o452, 0o454, 0o455, 0o462, 0o464, 0o465,
+    0o466, 0o503, 0o506, 0o516, 0o523, 0o526, 0o532, 0o546, 0o565, 0o606, 0o612, 0o624, 0o627,
+    0o631, 0o632, 0o654, 0o662, 0o664, 0o703, 0o712, 0o723, 0o731, 0o732, 0o734, 0o743, 0o754,
+];
+
+// Notch filters
+// Width of a new notch and the limits when dragged
+pub const NOTCH_WIDTH: u32 = 100;
+pub const NOTCH_MIN_WIDTH: u32 = 20;
+pub const NOTCH_MAX_WIDTH: u32 = 2000;
+
+// Bands, notches are saved per band
+// Name, low edge, high edge in Hz
+pub const BANDS: [(&str, u32, u32); 11] = [
+	("160m", 1800000, 2000000),
+	("80m", 3500000, 4000000),
+	("60m", 5250000, 5450000),
+	("40m", 7000000, 7300000),
+	("30m", 10100000, 10150000),
+	("20m", 14000000, 14350000),
+	("17m", 18068000, 18168000),
+	("15m", 21000000, 21450000),
+	("12m", 24890000, 24990000),
+	("10m", 28000000, 29700000),
+	("6m", 50000000, 54000000),
+];
+// Anywhere outside the bands
+pub const GEN_BAND: &str = "GEN";
+
+// Band name for the given frequency
+pub fn band_for_freq(freq: u32) -> &'static str {
+	for (name, low, high) in BANDS.iter() {
+		if freq >= *low && freq <= *high {
+			return name;
+		}
+	}
+	return GEN_BAND;
+}
 
 // Block size
 pub const DSP_BLK_SZ : u32 = 1024;
 // Channel type
 pub const CH_RX : u32 = 0;
-pub const CH_TX : u32 = 0;
+pub const CH_TX : u32 = 1;
 // Channel state
 pub const STATE_STOPPED: u32 = 0;
 pub const STATE_RUN: u32 = 1;
@@ -123,6 +310,7 @@ pub enum AvMode {
 }
 
 // Metering
+#[derive(Clone, Copy, PartialEq)]
 pub enum MeterType {
 	SPeak,
 	SAverage,
@@ -133,6 +321,28 @@ pub enum MeterType {
 	AGCAVerage,
 }
 
+// TX metering, the level at each stage of the chain
+#[derive(Clone, Copy, PartialEq)]
+pub enum TxMeterType {
+	MicPk,
+	MicAv,
+	EqPk,
+	EqAv,
+	LvlrPk,
+	LvlrAv,
+	LvlrGain,
+	CfcPk,
+	CfcAv,
+	CfcGain,
+	CompPk,
+	CompAv,
+	AlcPk,
+	AlcAv,
+	AlcGain,
+	OutPk,
+	OutAv,
+}
+
 // Temp
 #[derive(PartialEq)]
 pub enum EnumModePos {
@@ -140,3 +350,11 @@ pub enum EnumModePos {
     Upper,
     Both,
 }
+
+// Repeater transmit offset
+#[derive(Clone, Copy, PartialEq)]
+pub enum RptShift {
+    Simplex,
+    Plus,
+    Minus,
+}
@@ -71,9 +71,31 @@ pub fn i8be_to_f64le(in_data: &Vec<u8>, out_data: &mut [f64; (common_defs::DSP_B
     }
 }
 
+// Convert Mic input buffer in i16 BE to output buffer f64 LE
+// Input side of TX DSP
+pub fn i16be_to_f64le(in_data: &Vec<u8>, out_data: &mut [f64; (common_defs::MIC_BLK_SZ * 2) as usize]) {
+    // The in_data is MIC_BLK_SZ mono 16 bit samples in BE format.
+    // The output is complex with the Mic in I and Q zero.
+
+    let base: i32 = 2;
+    let scale: f64 = 1.0 /(base.pow(15)) as f64;
+
+    let mut in_index: usize = 0;
+    let mut out_index: usize = 0;
+    let mut as_int: i16;
+    while in_index + 1 < in_data.len() && out_index + 1 < out_data.len() {
+        as_int = ((in_data[in_index] as i16) << 8) | (in_data[in_index+1] as i16);
+        out_data[out_index] = (as_int as f64) * scale;
+        out_data[out_index+1] = 0.0;
+        in_index += common_defs::MIC_BYTES_PER_SAMPLE as usize;
+        out_index += 2;
+    }
+}
+
 // Convert input buffer in f64 LE to output buffer i8 BE
 // Output side of DSP to hardware
-pub fn f64le_to_i8be(sample_sz: usize, in_data: &[f64; (common_defs::DSP_BLK_SZ * 2) as usize], out_data: &mut [u8; common_defs::DSP_BLK_SZ as usize * 8]) {
+// tx_data is interleaved TX IQ, any samples not covered are sent as zero
+pub fn f64le_to_i8be(sample_sz: usize, in_data: &[f64; (common_defs::DSP_BLK_SZ * 2) as usize], tx_data: &[f64], out_data: &mut [u8; common_defs::DSP_BLK_SZ as usize * 8]) {
     // This conversion is the opposite of the i8be_to_f64le() and is the output side of the DSP.
     // The converted data is suitable for insertion into the ring buffer to the UDP writer.
 
@@ -88,7 +110,7 @@ pub fn f64le_to_i8be(sample_sz: usize, in_data: &[f64; (common_defs::DSP_BLK_SZ
     let mut q: i16;
     
     // We get 1024 f64 audio interleaved left/right
-    // We 'will' get f64 samples interleaved IQ output data when TX is implemented
+    // and f64 samples interleaved IQ output data from TX
     // This means we have 1024*sizeof f64(8)*left/right(2) bytes of data to iterate on the input
     // However the output is 16 bit packed so we have 1024*2*2 to iterate on the output
     // Both in and out are interleaved
@@ -97,17 +119,22 @@ pub fn f64le_to_i8be(sample_sz: usize, in_data: &[f64; (common_defs::DSP_BLK_SZ
     while dest <= (sample_sz - 8) as usize {
         l = (in_data[src] * scale) as i16;
         r = (in_data[src+1] * scale) as i16;
-        i = 0 as i16;
-        q = 0 as i16;
+        if src + 1 < tx_data.len() {
+            i = (tx_data[src] * scale) as i16;
+            q = (tx_data[src+1] * scale) as i16;
+        } else {
+            i = 0 as i16;
+            q = 0 as i16;
+        }
         out_data[dest] = ((l >> 8) & 0xff) as u8;
         out_data[dest+1] = (l & 0xff) as u8;
         out_data[dest+2] = ((r >> 8) & 0xff) as u8;
         out_data[dest+3] = (r & 0xff) as u8;
 
-        out_data[dest+4] = i as u8;
-        out_data[dest+5] = i as u8;
-        out_data[dest+6] = q as u8;
-        out_data[dest+7] = q as u8;
+        out_data[dest+4] = ((i >> 8) & 0xff) as u8;
+        out_data[dest+5] = (i & 0xff) as u8;
+        out_data[dest+6] = ((q >> 8) & 0xff) as u8;
+        out_data[dest+7] = (q & 0xff) as u8;
 
         dest += 8;
         src += 2;
@@ -176,4 +203,17 @@ pub fn i8le_to_f32le(in_data: &Vec<u8>, out_data: &mut Vec<f32>, sz: u32) {
         src += 4;
         dest += 2;
     }
-}
\ No newline at end of file
+}
+
+// Interleaved IQ in f32 to the 24 bit BE format received from the hardware.
+// Used to feed IQ from a file into the receiver ring buffers.
+pub fn f32le_to_i24be(in_data: &[f32], out_data: &mut Vec<u8>) {
+    let scale: f32 = 8388607.0;
+    out_data.clear();
+    for s in in_data.iter() {
+        let as_int = (f32::max(-1.0, f32::min(1.0, *s)) * scale) as i32;
+        out_data.push(((as_int >> 16) & 0xff) as u8);
+        out_data.push(((as_int >> 8) & 0xff) as u8);
+        out_data.push((as_int & 0xff) as u8);
+    }
+}
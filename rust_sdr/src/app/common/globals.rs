@@ -114,6 +114,30 @@ pub fn set_smpl_rate(rate: u32) {
     INT_SETTINGS.lock().unwrap().insert("SMPL_RATE".to_string(), rate);
 }
 
+//========================================
+pub fn get_rate_change_pending() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("RATE_CHANGE_PENDING") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_rate_change_pending(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("RATE_CHANGE_PENDING".to_string(), state);
+}
+
+//========================================
+pub fn get_disp_width() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("DISP_WIDTH") {
+        Some(width) => return width.clone(),
+        None => return common_defs::DISPLAY_WIDTH as u32,
+    }
+}
+
+pub fn set_disp_width(width: u32) {
+    INT_SETTINGS.lock().unwrap().insert("DISP_WIDTH".to_string(), width);
+}
+
 //========================================
 pub fn get_mode(rx_id: i32) -> u32 {
     let mut s = "MODE";
@@ -164,4 +188,998 @@ pub fn set_filter(rx_id: i32, filter: u32) {
         _ => (),
     }
     INT_SETTINGS.lock().unwrap().insert(s.to_string(), filter);
-}
\ No newline at end of file
+}
+
+//========================================
+// Frequency of each receiver for the remote control servers
+pub fn get_rx_freq(rx_id: i32) -> u32 {
+    let mut s = "FREQ";
+    match rx_id {
+        1 => s = "FREQ_RX1",
+        2 => s = "FREQ_RX2",
+        3 => s = "FREQ_RX3",
+        _ => (),
+    }
+    match INT_SETTINGS.lock().unwrap().get(s) {
+        Some(freq) => return freq.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_rx_freq(rx_id: i32, freq: u32) {
+    let mut s = "FREQ";
+    match rx_id {
+        1 => s = "FREQ_RX1",
+        2 => s = "FREQ_RX2",
+        3 => s = "FREQ_RX3",
+        _ => (),
+    }
+    INT_SETTINGS.lock().unwrap().insert(s.to_string(), freq);
+}
+
+//========================================
+// Local audio mixer settings per receiver
+pub fn get_rx_vol(rx_id: i32) -> f32 {
+    let mut s = "VOL";
+    match rx_id {
+        1 => s = "VOL_RX1",
+        2 => s = "VOL_RX2",
+        3 => s = "VOL_RX3",
+        _ => (),
+    }
+    match FLOAT_SETTINGS.lock().unwrap().get(s) {
+        Some(vol) => return vol.clone(),
+        None => return common_defs::RX_VOL,
+    }
+}
+
+pub fn set_rx_vol(rx_id: i32, vol: f32) {
+    let mut s = "VOL";
+    match rx_id {
+        1 => s = "VOL_RX1",
+        2 => s = "VOL_RX2",
+        3 => s = "VOL_RX3",
+        _ => (),
+    }
+    FLOAT_SETTINGS.lock().unwrap().insert(s.to_string(), vol);
+}
+
+//========================================
+pub fn get_rx_pan(rx_id: i32) -> f32 {
+    let mut s = "PAN";
+    match rx_id {
+        1 => s = "PAN_RX1",
+        2 => s = "PAN_RX2",
+        3 => s = "PAN_RX3",
+        _ => (),
+    }
+    match FLOAT_SETTINGS.lock().unwrap().get(s) {
+        Some(pan) => return pan.clone(),
+        None => return 0.0,
+    }
+}
+
+pub fn set_rx_pan(rx_id: i32, pan: f32) {
+    let mut s = "PAN";
+    match rx_id {
+        1 => s = "PAN_RX1",
+        2 => s = "PAN_RX2",
+        3 => s = "PAN_RX3",
+        _ => (),
+    }
+    FLOAT_SETTINGS.lock().unwrap().insert(s.to_string(), pan);
+}
+
+//========================================
+pub fn get_rx_mute(rx_id: i32) -> bool {
+    let mut s = "MUTE";
+    match rx_id {
+        1 => s = "MUTE_RX1",
+        2 => s = "MUTE_RX2",
+        3 => s = "MUTE_RX3",
+        _ => (),
+    }
+    match BOOL_SETTINGS.lock().unwrap().get(s) {
+        Some(mute) => return mute.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_rx_mute(rx_id: i32, mute: bool) {
+    let mut s = "MUTE";
+    match rx_id {
+        1 => s = "MUTE_RX1",
+        2 => s = "MUTE_RX2",
+        3 => s = "MUTE_RX3",
+        _ => (),
+    }
+    BOOL_SETTINGS.lock().unwrap().insert(s.to_string(), mute);
+}
+
+//========================================
+// Audio device for the receiver, 0 = main device, 1 = auxiliary device
+pub fn get_rx_route(rx_id: i32) -> u32 {
+    let mut s = "ROUTE";
+    match rx_id {
+        1 => s = "ROUTE_RX1",
+        2 => s = "ROUTE_RX2",
+        3 => s = "ROUTE_RX3",
+        _ => (),
+    }
+    match INT_SETTINGS.lock().unwrap().get(s) {
+        Some(route) => return route.clone(),
+        None => return common_defs::AUDIO_MAIN,
+    }
+}
+
+pub fn set_rx_route(rx_id: i32, route: u32) {
+    let mut s = "ROUTE";
+    match rx_id {
+        1 => s = "ROUTE_RX1",
+        2 => s = "ROUTE_RX2",
+        3 => s = "ROUTE_RX3",
+        _ => (),
+    }
+    INT_SETTINGS.lock().unwrap().insert(s.to_string(), route);
+}
+
+//========================================
+pub fn get_aux_audio_state() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("AUX_AUDIO") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_aux_audio_state(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("AUX_AUDIO".to_string(), state);
+}
+
+//========================================
+pub fn get_dec_type() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("DEC_TYPE") {
+        Some(dec_type) => return dec_type.clone(),
+        None => return common_defs::DEC_RTTY_45,
+    }
+}
+
+pub fn set_dec_type(dec_type: u32) {
+    INT_SETTINGS.lock().unwrap().insert("DEC_TYPE".to_string(), dec_type);
+}
+
+//========================================
+pub fn get_dec_offset() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("DEC_OFFSET") {
+        Some(offset) => return offset.clone(),
+        None => return common_defs::DEC_OFFSET,
+    }
+}
+
+pub fn set_dec_offset(offset: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("DEC_OFFSET".to_string(), offset);
+}
+
+//========================================
+// Diversity combining of RX1 and RX2
+pub fn get_diversity() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("DIVERSITY") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_diversity(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("DIVERSITY".to_string(), state);
+}
+
+pub fn get_div_gain() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("DIV_GAIN") {
+        Some(gain) => return gain.clone(),
+        None => return common_defs::DIV_GAIN,
+    }
+}
+
+pub fn set_div_gain(gain: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("DIV_GAIN".to_string(), gain);
+}
+
+pub fn get_div_phase() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("DIV_PHASE") {
+        Some(phase) => return phase.clone(),
+        None => return 0.0,
+    }
+}
+
+pub fn set_div_phase(phase: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("DIV_PHASE".to_string(), phase);
+}
+
+//========================================
+// Software sub-receivers, id is 0 to MAX_SUB_RX-1
+pub fn get_sub_enabled(id: u32) -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get(&format!("SUB_ENABLED_{}", id)) {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_sub_enabled(id: u32, state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert(format!("SUB_ENABLED_{}", id), state);
+}
+
+pub fn get_sub_vol(id: u32) -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get(&format!("SUB_VOL_{}", id)) {
+        Some(vol) => return vol.clone(),
+        None => return common_defs::RX_VOL,
+    }
+}
+
+pub fn set_sub_vol(id: u32, vol: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert(format!("SUB_VOL_{}", id), vol);
+}
+
+pub fn get_sub_pan(id: u32) -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get(&format!("SUB_PAN_{}", id)) {
+        Some(pan) => return pan.clone(),
+        None => return 0.0,
+    }
+}
+
+pub fn set_sub_pan(id: u32, pan: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert(format!("SUB_PAN_{}", id), pan);
+}
+
+pub fn get_sub_mute(id: u32) -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get(&format!("SUB_MUTE_{}", id)) {
+        Some(mute) => return mute.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_sub_mute(id: u32, mute: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert(format!("SUB_MUTE_{}", id), mute);
+}
+
+//========================================
+// Inputs from the hardware
+pub fn get_ptt() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("PTT") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_ptt(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("PTT".to_string(), state);
+}
+
+pub fn get_dot() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("DOT") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_dot(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("DOT".to_string(), state);
+}
+
+pub fn get_dash() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("DASH") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_dash(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("DASH".to_string(), state);
+}
+
+//========================================
+// VOX, the pipeline sets VOX_ACTIVE and the writer keys MOX from it
+pub fn get_vox() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("VOX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_vox(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("VOX".to_string(), state);
+}
+
+pub fn get_vox_active() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("VOX_ACTIVE") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_vox_active(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("VOX_ACTIVE".to_string(), state);
+}
+
+pub fn get_vox_threshold() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("VOX_THRESHOLD") {
+        Some(level) => return level.clone(),
+        None => return common_defs::VOX_THRESHOLD,
+    }
+}
+
+pub fn set_vox_threshold(level: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("VOX_THRESHOLD".to_string(), level);
+}
+
+pub fn get_vox_attack() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("VOX_ATTACK") {
+        Some(ms) => return ms.clone(),
+        None => return common_defs::VOX_ATTACK,
+    }
+}
+
+pub fn set_vox_attack(ms: u32) {
+    INT_SETTINGS.lock().unwrap().insert("VOX_ATTACK".to_string(), ms);
+}
+
+pub fn get_vox_hang() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("VOX_HANG") {
+        Some(ms) => return ms.clone(),
+        None => return common_defs::VOX_HANG,
+    }
+}
+
+pub fn set_vox_hang(ms: u32) {
+    INT_SETTINGS.lock().unwrap().insert("VOX_HANG".to_string(), ms);
+}
+
+pub fn get_anti_vox() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("ANTI_VOX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_anti_vox(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("ANTI_VOX".to_string(), state);
+}
+
+pub fn get_anti_vox_gain() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("ANTI_VOX_GAIN") {
+        Some(gain) => return gain.clone(),
+        None => return common_defs::ANTI_VOX_GAIN,
+    }
+}
+
+pub fn set_anti_vox_gain(gain: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("ANTI_VOX_GAIN".to_string(), gain);
+}
+
+// Levels for the VOX display
+pub fn get_mic_level() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("MIC_LEVEL") {
+        Some(level) => return level.clone(),
+        None => return -120.0,
+    }
+}
+
+pub fn set_mic_level(level: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("MIC_LEVEL".to_string(), level);
+}
+
+pub fn get_rx_level() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("RX_LEVEL") {
+        Some(level) => return level.clone(),
+        None => return -120.0,
+    }
+}
+
+pub fn set_rx_level(level: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("RX_LEVEL".to_string(), level);
+}
+
+//========================================
+// CW keyer, the pipeline sets CW_MOX while sending and during the hang time
+pub fn get_cw_keyer() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("CW_KEYER") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_cw_keyer(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("CW_KEYER".to_string(), state);
+}
+
+pub fn get_keyer_mode_b() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("KEYER_MODE_B") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_keyer_mode_b(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("KEYER_MODE_B".to_string(), state);
+}
+
+pub fn get_keyer_wpm() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("KEYER_WPM") {
+        Some(wpm) => return wpm.clone(),
+        None => return common_defs::KEYER_WPM,
+    }
+}
+
+pub fn set_keyer_wpm(wpm: u32) {
+    INT_SETTINGS.lock().unwrap().insert("KEYER_WPM".to_string(), wpm);
+}
+
+pub fn get_keyer_weight() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("KEYER_WEIGHT") {
+        Some(weight) => return weight.clone(),
+        None => return common_defs::KEYER_WEIGHT,
+    }
+}
+
+pub fn set_keyer_weight(weight: u32) {
+    INT_SETTINGS.lock().unwrap().insert("KEYER_WEIGHT".to_string(), weight);
+}
+
+pub fn get_sidetone_freq() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("SIDETONE_FREQ") {
+        Some(freq) => return freq.clone(),
+        None => return common_defs::SIDETONE_FREQ,
+    }
+}
+
+pub fn set_sidetone_freq(freq: u32) {
+    INT_SETTINGS.lock().unwrap().insert("SIDETONE_FREQ".to_string(), freq);
+}
+
+pub fn get_sidetone_vol() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("SIDETONE_VOL") {
+        Some(vol) => return vol.clone(),
+        None => return common_defs::SIDETONE_VOL,
+    }
+}
+
+pub fn set_sidetone_vol(vol: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("SIDETONE_VOL".to_string(), vol);
+}
+
+pub fn get_cw_hang() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("CW_HANG") {
+        Some(hang) => return hang.clone(),
+        None => return common_defs::CW_HANG,
+    }
+}
+
+pub fn set_cw_hang(hang: u32) {
+    INT_SETTINGS.lock().unwrap().insert("CW_HANG".to_string(), hang);
+}
+
+// Paddles on the keyboard
+pub fn get_key_dot() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("KEY_DOT") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_key_dot(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("KEY_DOT".to_string(), state);
+}
+
+pub fn get_key_dash() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("KEY_DASH") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_key_dash(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("KEY_DASH".to_string(), state);
+}
+
+pub fn get_cw_mox() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("CW_MOX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_cw_mox(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("CW_MOX".to_string(), state);
+}
+
+// Test signals
+pub fn get_test_mode() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("TEST_MODE") {
+        Some(mode) => return mode.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_test_mode(mode: u32) {
+    INT_SETTINGS.lock().unwrap().insert("TEST_MODE".to_string(), mode);
+}
+
+pub fn get_test_level() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("TEST_LEVEL") {
+        Some(level) => return level.clone(),
+        None => return common_defs::TUNE_LEVEL,
+    }
+}
+
+pub fn set_test_level(level: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("TEST_LEVEL".to_string(), level);
+}
+
+pub fn get_test_max_level() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("TEST_MAX_LEVEL") {
+        Some(level) => return level.clone(),
+        None => return common_defs::TEST_MAX_LEVEL,
+    }
+}
+
+pub fn set_test_max_level(level: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("TEST_MAX_LEVEL".to_string(), level);
+}
+
+pub fn get_test_tone1() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("TEST_TONE1") {
+        Some(freq) => return freq.clone(),
+        None => return common_defs::TWO_TONE_1,
+    }
+}
+
+pub fn set_test_tone1(freq: u32) {
+    INT_SETTINGS.lock().unwrap().insert("TEST_TONE1".to_string(), freq);
+}
+
+pub fn get_test_tone2() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("TEST_TONE2") {
+        Some(freq) => return freq.clone(),
+        None => return common_defs::TWO_TONE_2,
+    }
+}
+
+pub fn set_test_tone2(freq: u32) {
+    INT_SETTINGS.lock().unwrap().insert("TEST_TONE2".to_string(), freq);
+}
+
+pub fn get_test_timeout() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("TEST_TIMEOUT") {
+        Some(secs) => return secs.clone(),
+        None => return common_defs::TEST_TIMEOUT,
+    }
+}
+
+pub fn set_test_timeout(secs: u32) {
+    INT_SETTINGS.lock().unwrap().insert("TEST_TIMEOUT".to_string(), secs);
+}
+
+pub fn get_test_mox() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("TEST_MOX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_test_mox(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("TEST_MOX".to_string(), state);
+}
+
+// FM repeater, shift direction from RptShift
+pub fn get_rpt_shift_dir() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("RPT_SHIFT_DIR") {
+        Some(dir) => return dir.clone(),
+        None => return common_defs::RptShift::Simplex as u32,
+    }
+}
+
+pub fn set_rpt_shift_dir(dir: u32) {
+    INT_SETTINGS.lock().unwrap().insert("RPT_SHIFT_DIR".to_string(), dir);
+}
+
+pub fn get_rpt_shift() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("RPT_SHIFT") {
+        Some(shift) => return shift.clone(),
+        None => return common_defs::RPT_SHIFT,
+    }
+}
+
+pub fn set_rpt_shift(shift: u32) {
+    INT_SETTINGS.lock().unwrap().insert("RPT_SHIFT".to_string(), shift);
+}
+
+pub fn get_rpt_reverse() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("RPT_REVERSE") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_rpt_reverse(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("RPT_REVERSE".to_string(), state);
+}
+
+// Tone squelch type from SqlType
+pub fn get_tone_sql() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("TONE_SQL") {
+        Some(sql) => return sql.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_tone_sql(sql: u32) {
+    INT_SETTINGS.lock().unwrap().insert("TONE_SQL".to_string(), sql);
+}
+
+pub fn get_sql_ctcss() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("SQL_CTCSS") {
+        Some(freq) => return freq.clone(),
+        None => return common_defs::CTCSS_DEFAULT,
+    }
+}
+
+pub fn set_sql_ctcss(freq: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("SQL_CTCSS".to_string(), freq);
+}
+
+pub fn get_sql_dcs() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("SQL_DCS") {
+        Some(code) => return code.clone(),
+        None => return common_defs::DCS_DEFAULT,
+    }
+}
+
+pub fn set_sql_dcs(code: u32) {
+    INT_SETTINGS.lock().unwrap().insert("SQL_DCS".to_string(), code);
+}
+
+// Set by the recorder while an IQ recording is open
+pub fn get_iq_recording() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("IQ_RECORDING") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_iq_recording(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("IQ_RECORDING".to_string(), state);
+}
+
+pub fn get_iq_rec_secs() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("IQ_REC_SECS") {
+        Some(secs) => return secs.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_iq_rec_secs(secs: u32) {
+    INT_SETTINGS.lock().unwrap().insert("IQ_REC_SECS".to_string(), secs);
+}
+
+// Set by the recorder while an audio recording is open
+pub fn get_audio_recording() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("AUDIO_RECORDING") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_audio_recording(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("AUDIO_RECORDING".to_string(), state);
+}
+
+pub fn get_audio_rec_secs() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("AUDIO_REC_SECS") {
+        Some(secs) => return secs.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_audio_rec_secs(secs: u32) {
+    INT_SETTINGS.lock().unwrap().insert("AUDIO_REC_SECS".to_string(), secs);
+}
+
+// The audio recording was started by the daily schedule
+pub fn get_audio_rec_sched() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("AUDIO_REC_SCHED") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_audio_rec_sched(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("AUDIO_REC_SCHED".to_string(), state);
+}
+
+// Receiver being recorded, 0 for all receivers mixed
+pub fn get_audio_rec_src() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("AUDIO_REC_SRC") {
+        Some(src) => return src.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_audio_rec_src(src: u32) {
+    INT_SETTINGS.lock().unwrap().insert("AUDIO_REC_SRC".to_string(), src);
+}
+
+// Set by the player while an IQ file replaces the hardware as the source
+pub fn get_playback() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("PLAYBACK") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_playback(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("PLAYBACK".to_string(), state);
+}
+
+pub fn get_play_running() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("PLAY_RUNNING") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_play_running(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("PLAY_RUNNING".to_string(), state);
+}
+
+pub fn get_play_pos() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("PLAY_POS") {
+        Some(secs) => return secs.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_play_pos(secs: u32) {
+    INT_SETTINGS.lock().unwrap().insert("PLAY_POS".to_string(), secs);
+}
+
+pub fn get_play_len() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("PLAY_LEN") {
+        Some(secs) => return secs.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_play_len(secs: u32) {
+    INT_SETTINGS.lock().unwrap().insert("PLAY_LEN".to_string(), secs);
+}
+
+// Centre frequency recorded in the file, 0 if unknown
+pub fn get_play_freq() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("PLAY_FREQ") {
+        Some(freq) => return freq.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_play_freq(freq: u32) {
+    INT_SETTINGS.lock().unwrap().insert("PLAY_FREQ".to_string(), freq);
+}
+
+//========================================
+// Remote control
+// PTT from a CAT client
+pub fn get_cat_ptt() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("CAT_PTT") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_cat_ptt(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("CAT_PTT".to_string(), state);
+}
+
+// Split transmits on the RX2 frequency
+pub fn get_split() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("SPLIT") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_split(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("SPLIT".to_string(), state);
+}
+
+// Calibration offset of the S meter in dB
+pub fn get_meter_cal() -> f32 {
+    match FLOAT_SETTINGS.lock().unwrap().get("METER_CAL") {
+        Some(cal) => return cal.clone(),
+        None => return 0.0,
+    }
+}
+
+pub fn set_meter_cal(cal: f32) {
+    FLOAT_SETTINGS.lock().unwrap().insert("METER_CAL".to_string(), cal);
+}
+
+// Number of connected remote clients
+pub fn get_rigctl_clients() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("RIGCTL_CLIENTS") {
+        Some(n) => return n.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_rigctl_clients(n: u32) {
+    INT_SETTINGS.lock().unwrap().insert("RIGCTL_CLIENTS".to_string(), n);
+}
+
+// Device of the CAT pty, empty when closed
+pub fn get_cat_pty_name() -> String {
+    match STR_SETTINGS.lock().unwrap().get("CAT_PTY_NAME") {
+        Some(name) => return name.clone(),
+        None => return String::new(),
+    }
+}
+
+pub fn set_cat_pty_name(name: String) {
+    STR_SETTINGS.lock().unwrap().insert("CAT_PTY_NAME".to_string(), name);
+}
+
+// Receiver audio is being sent to other applications
+pub fn get_audio_bridge() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("AUDIO_BRIDGE") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_audio_bridge(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("AUDIO_BRIDGE".to_string(), state);
+}
+
+// TX audio from the bridge is replacing the Mic
+pub fn get_bridge_tx() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("BRIDGE_TX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_bridge_tx(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("BRIDGE_TX".to_string(), state);
+}
+
+// Number of connected web clients
+pub fn get_web_clients() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("WEB_CLIENTS") {
+        Some(n) => return n.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_web_clients(n: u32) {
+    INT_SETTINGS.lock().unwrap().insert("WEB_CLIENTS".to_string(), n);
+}
+
+// Any web client wants receiver audio
+pub fn get_web_audio() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("WEB_AUDIO") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_web_audio(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("WEB_AUDIO".to_string(), state);
+}
+
+// Web client Mic audio is replacing the Mic
+pub fn get_web_tx() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("WEB_TX") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_web_tx(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("WEB_TX".to_string(), state);
+}
+
+// Receiver being sent to a Protocol 1 client, 0 when none
+pub fn get_hpsdr_rx() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("HPSDR_RX") {
+        Some(rx) => return rx.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_hpsdr_rx(rx: u32) {
+    INT_SETTINGS.lock().unwrap().insert("HPSDR_RX".to_string(), rx);
+}
+
+// Address of the Protocol 1 client, empty when none
+pub fn get_hpsdr_client() -> String {
+    match STR_SETTINGS.lock().unwrap().get("HPSDR_CLIENT") {
+        Some(addr) => return addr.clone(),
+        None => return String::new(),
+    }
+}
+
+pub fn set_hpsdr_client(addr: String) {
+    STR_SETTINGS.lock().unwrap().insert("HPSDR_CLIENT".to_string(), addr);
+}
+
+// Receiver being sent to an rtl_tcp client, 0 when none
+pub fn get_rtl_tcp_rx() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("RTL_TCP_RX") {
+        Some(rx) => return rx.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_rtl_tcp_rx(rx: u32) {
+    INT_SETTINGS.lock().unwrap().insert("RTL_TCP_RX".to_string(), rx);
+}
+
+// Address of the rtl_tcp client, empty when none
+pub fn get_rtl_tcp_client() -> String {
+    match STR_SETTINGS.lock().unwrap().get("RTL_TCP_CLIENT") {
+        Some(addr) => return addr.clone(),
+        None => return String::new(),
+    }
+}
+
+pub fn set_rtl_tcp_client(addr: String) {
+    STR_SETTINGS.lock().unwrap().insert("RTL_TCP_CLIENT".to_string(), addr);
+}
+
+// Attenuator step 0-3 as set in the meter, restored when a remote client lets go of the gain
+pub fn get_rf_attn() -> u32 {
+    match INT_SETTINGS.lock().unwrap().get("RF_ATTN") {
+        Some(attn) => return attn.clone(),
+        None => return 0,
+    }
+}
+
+pub fn set_rf_attn(attn: u32) {
+    INT_SETTINGS.lock().unwrap().insert("RF_ATTN".to_string(), attn);
+}
+
+// Preamp as set in the meter
+pub fn get_rf_preamp() -> bool {
+    match BOOL_SETTINGS.lock().unwrap().get("RF_PREAMP") {
+        Some(state) => return state.clone(),
+        None => return false,
+    }
+}
+
+pub fn set_rf_preamp(state: bool) {
+    BOOL_SETTINGS.lock().unwrap().insert("RF_PREAMP".to_string(), state);
+}
+
+// Attenuator step and preamp a remote client has set in place of the meter settings
+pub fn get_rf_remote() -> Option<(u32, bool)> {
+    match BOOL_SETTINGS.lock().unwrap().get("RF_REMOTE") {
+        Some(true) => (),
+        _ => return None,
+    }
+    let attn = match INT_SETTINGS.lock().unwrap().get("RF_REMOTE_ATTN") {
+        Some(attn) => attn.clone(),
+        None => 0,
+    };
+    let preamp = match BOOL_SETTINGS.lock().unwrap().get("RF_REMOTE_PREAMP") {
+        Some(state) => state.clone(),
+        None => false,
+    };
+    return Some((attn, preamp));
+}
+
+pub fn set_rf_remote(attn: u32, preamp: bool) {
+    INT_SETTINGS.lock().unwrap().insert("RF_REMOTE_ATTN".to_string(), attn);
+    BOOL_SETTINGS.lock().unwrap().insert("RF_REMOTE_PREAMP".to_string(), preamp);
+    BOOL_SETTINGS.lock().unwrap().insert("RF_REMOTE".to_string(), true);
+}
+
+pub fn clear_rf_remote() {
+    BOOL_SETTINGS.lock().unwrap().insert("RF_REMOTE".to_string(), false);
+}
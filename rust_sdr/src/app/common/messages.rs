@@ -24,6 +24,11 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
+use crate::app::record::recorder;
+use crate::app::audio::audio_bridge;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+
 pub enum ReaderMsg {
     Terminate,
     StartListening,
@@ -47,5 +52,122 @@ pub enum PipelineMsg {
     Terminate,
     StartPipeline,
     StopPipeline,
+    RateChange(u32),
+}
+
+pub enum DecoderMsg {
+    Text(String),
+    Wpm(u32),
+    // Audio spectrum line in dB for the tuning waterfall
+    Spectrum(Vec<f32>),
+}
+
+// Listen for rigctl clients on the address
+pub enum RigctlMsg {
+    Terminate,
+    Listen(String),
+    Close,
+}
+
+// Open the TS-2000 CAT pty with a link to it, the link may be empty
+pub enum CatPtyMsg {
+    Terminate,
+    Open(String),
+    Close,
+}
+
+pub enum BridgeMsg {
+    Terminate,
+    Start(audio_bridge::BridgeSpec),
+    Stop,
+    // Receiver, 1 based, and its mono audio at 48K
+    RxAudio(u32, Vec<f32>),
+}
+
+// Changes from remote clients for the UI to apply, receivers are 1 based
+#[derive(Debug)]
+pub enum CatMsg {
+    SetFreq(u32, u32),
+    SetMode(u32, ModeId),
+    SetFilter(u32, FilterId),
+    SetRx(u32),
+    SetSplit(bool),
+    SetAfGain(f32),
+    // Start or stop the hardware
+    SetRun(bool),
+}
+
+// Listen for web clients on the address with the access token
+pub enum WebMsg {
+    Terminate,
+    Listen(String, String),
+    Close,
+    // Latest display row of the selected receiver
+    Spectrum(Vec<f32>),
+    // Receiver audio, mono 48K
+    RxAudio(u32, Vec<f32>),
+    // Text from the CW and digital decoders
+    Decoded(String),
 }
 
+// Share the receiver with Protocol 1 clients on the address
+pub enum HpsdrMsg {
+    Terminate,
+    Listen(String, u32),
+    Close,
+    // 24 bit IQ of the shared receiver as the hardware sends it
+    Iq(Vec<u8>),
+}
+
+// rtl_tcp server for one receiver
+pub enum RtlTcpMsg {
+    Terminate,
+    Listen(String, u32),
+    Close,
+    // Interleaved IQ of the shared receiver at the hardware rate
+    Iq(Vec<f32>),
+}
+
+// To the connection with a remote engine, commands are JSON text
+pub enum EngineMsg {
+    Terminate,
+    Command(String),
+}
+
+// From the connection with a remote engine
+pub enum EngineEvent {
+    // Engine audio can be played
+    Connected(bool),
+    // Why the connection failed or was lost
+    Disconnected(String),
+    // JSON state, meter or error
+    Text(String),
+    // Latest display row of the selected receiver
+    Spectrum(Vec<f32>),
+}
+
+pub enum RecorderMsg {
+    Terminate,
+    StartIq(recorder::IqSpec),
+    StopIq,
+    // Interleaved IQ of the selected receiver
+    IqData(Vec<f32>),
+    StartAudio(recorder::AudioSpec),
+    StopAudio,
+    // Daily audio recording, None when there is no schedule
+    Schedule(Option<recorder::Schedule>),
+    // Interleaved stereo audio at 48K
+    AudioData(Vec<f32>),
+}
+
+pub enum PlayerMsg {
+    Terminate,
+    // IQ file path, WAV or SigMF
+    Open(String),
+    Play,
+    Pause,
+    Stop,
+    // Position as a fraction of the file length
+    Seek(f32),
+    Loop(bool),
+}
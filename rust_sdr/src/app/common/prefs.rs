@@ -25,13 +25,14 @@ bob@bobcowdery.plus.com
 */
 
 use serde:: {Serialize, Deserialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 
 use crate::app::common::common_defs;
-use crate::app::ui::egui_main::components::egui_mode::ModeId;
-use crate::app::ui::egui_main::components::egui_filter::FilterId;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
 
 //===========================================================================================
 // State for prefs
@@ -90,12 +91,423 @@ pub struct Radio {
     pub rx3: Interacts,
 }
 
+// Local audio mixer settings for one receiver
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct MixerChannel {
+    pub vol: f32,
+    pub pan: f32,
+    pub mute: bool,
+    pub route: u32,
+}
+
+impl Default for MixerChannel {
+    fn default() -> Self {
+        Self {
+            vol: common_defs::RX_VOL,
+            pan: 0.0,
+            mute: false,
+            route: common_defs::AUDIO_MAIN,
+        }
+    }
+}
+
+// Added after the first release so defaults are used for older prefs files
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct Audio {
+    pub aux_device: String,
+    pub rx1: MixerChannel,
+    pub rx2: MixerChannel,
+    pub rx3: MixerChannel,
+}
+
+// Digital mode decoder settings
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Decoder {
+    pub dec_type: u32,
+    pub offset: f32,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            dec_type: common_defs::DEC_RTTY_45,
+            offset: common_defs::DEC_OFFSET,
+        }
+    }
+}
+
+// Diversity combining settings
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Diversity {
+    pub enabled: bool,
+    pub gain: f32,
+    pub phase: f32,
+}
+
+impl Default for Diversity {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gain: common_defs::DIV_GAIN,
+            phase: 0.0,
+        }
+    }
+}
+
+// Meter source, RF gain and calibration offsets
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Meter {
+    pub source: u32,
+    pub attn: u32,
+    pub preamp: bool,
+    // Offset in dB keyed by band, attenuator and preamp
+    pub cal: HashMap<String, f32>,
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self {
+            source: common_defs::MeterType::SAverage as u32,
+            attn: 0,
+            preamp: false,
+            cal: HashMap::new(),
+        }
+    }
+}
+
+// Manual notch at an absolute RF frequency
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Notch {
+    pub freq: u32,
+    pub width: u32,
+}
+
+// Software sub-receiver within the passband of the selected receiver
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SubRx {
+    pub enabled: bool,
+    pub frequency: u32,
+    pub mode: ModeId,
+    pub filter: FilterId,
+    pub vol: f32,
+    pub pan: f32,
+    pub mute: bool,
+}
+
+impl Default for SubRx {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: 7100000,
+            mode: ModeId::Lsb,
+            filter: FilterId::F2_4KHz,
+            vol: common_defs::RX_VOL,
+            pan: 0.0,
+            mute: false,
+        }
+    }
+}
+
+// TX audio processing settings, saved as named profiles
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(default)]
+pub struct TxProfile {
+    pub mic_gain: f32,
+    pub low: i32,
+    pub high: i32,
+    pub eq_enabled: bool,
+    pub eq_preamp: i32,
+    pub eq: Vec<i32>,
+    pub leveler_enabled: bool,
+    pub leveler_gain: f32,
+    pub comp_enabled: bool,
+    pub comp_gain: f32,
+    pub cfc_enabled: bool,
+    pub cessb_enabled: bool,
+    pub alc_gain: f32,
+}
+
+impl Default for TxProfile {
+    fn default() -> Self {
+        Self {
+            mic_gain: 0.0,
+            low: common_defs::TX_LOW,
+            high: common_defs::TX_HIGH,
+            eq_enabled: false,
+            eq_preamp: 0,
+            eq: vec![0; common_defs::TX_EQ_BANDS as usize],
+            leveler_enabled: true,
+            leveler_gain: 15.0,
+            comp_enabled: false,
+            comp_gain: 10.0,
+            cfc_enabled: false,
+            cessb_enabled: false,
+            alc_gain: 3.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Tx {
+    pub profile: String,
+    pub profiles: HashMap<String, TxProfile>,
+}
+
+impl Default for Tx {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+        profiles.insert(String::from(common_defs::TX_DEFAULT_PROFILE), TxProfile::default());
+        Self {
+            profile: String::from(common_defs::TX_DEFAULT_PROFILE),
+            profiles: profiles,
+        }
+    }
+}
+
+impl Tx {
+    // Settings of the current profile
+    pub fn current(&self) -> TxProfile {
+        match self.profiles.get(&self.profile) {
+            Some(profile) => return profile.clone(),
+            None => return TxProfile::default(),
+        }
+    }
+}
+
+// VOX and anti-VOX settings
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Vox {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub attack: u32,
+    pub hang: u32,
+    pub anti_vox: bool,
+    pub anti_vox_gain: f32,
+}
+
+impl Default for Vox {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: common_defs::VOX_THRESHOLD,
+            attack: common_defs::VOX_ATTACK,
+            hang: common_defs::VOX_HANG,
+            anti_vox: false,
+            anti_vox_gain: common_defs::ANTI_VOX_GAIN,
+        }
+    }
+}
+
+// CW keyer settings
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Cw {
+    pub keyer: bool,
+    pub hw_keyer: bool,
+    pub keyboard: bool,
+    pub mode_b: bool,
+    pub wpm: u32,
+    pub weight: u32,
+    pub sidetone_freq: u32,
+    pub sidetone_vol: f32,
+    pub hang: u32,
+}
+
+impl Default for Cw {
+    fn default() -> Self {
+        Self {
+            keyer: false,
+            hw_keyer: false,
+            keyboard: false,
+            mode_b: false,
+            wpm: common_defs::KEYER_WPM,
+            weight: common_defs::KEYER_WEIGHT,
+            sidetone_freq: common_defs::SIDETONE_FREQ,
+            sidetone_vol: common_defs::SIDETONE_VOL,
+            hang: common_defs::CW_HANG,
+        }
+    }
+}
+
+// Test signal settings, a test is never running at startup
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct TestSignal {
+    pub tune_level: f32,
+    pub tone_level: f32,
+    pub tone1: u32,
+    pub tone2: u32,
+    pub max_level: f32,
+    pub timeout: u32,
+}
+
+impl Default for TestSignal {
+    fn default() -> Self {
+        Self {
+            tune_level: common_defs::TUNE_LEVEL,
+            tone_level: common_defs::TWO_TONE_LEVEL,
+            tone1: common_defs::TWO_TONE_1,
+            tone2: common_defs::TWO_TONE_2,
+            max_level: common_defs::TEST_MAX_LEVEL,
+            timeout: common_defs::TEST_TIMEOUT,
+        }
+    }
+}
+
+// FM repeater settings
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Repeater {
+    pub shift_dir: u32,
+    pub shift: u32,
+    pub reverse: bool,
+    pub ctcss_tx: bool,
+    pub ctcss_tx_freq: f32,
+    pub tone_sql: u32,
+    pub sql_ctcss: f32,
+    pub sql_dcs: u32,
+}
+
+impl Default for Repeater {
+    fn default() -> Self {
+        Self {
+            shift_dir: common_defs::RptShift::Simplex as u32,
+            shift: common_defs::RPT_SHIFT,
+            reverse: false,
+            ctcss_tx: false,
+            ctcss_tx_freq: common_defs::CTCSS_DEFAULT,
+            tone_sql: 0,
+            sql_ctcss: common_defs::CTCSS_DEFAULT,
+            sql_dcs: common_defs::DCS_DEFAULT,
+        }
+    }
+}
+
+// Recording settings, limits of 0 are no limit
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Record {
+    pub dir: String,
+    pub iq_format: u32,
+    pub max_mb: u32,
+    pub max_mins: u32,
+    // Receiver audio, source 0 is all receivers mixed
+    pub audio_format: u32,
+    pub audio_src: u32,
+    // Daily scheduled audio recording in UTC
+    pub sched_enabled: bool,
+    pub sched_hour: u32,
+    pub sched_min: u32,
+    pub sched_mins: u32,
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Self {
+            dir: String::from(common_defs::RECORD_DIR),
+            iq_format: 0,
+            max_mb: 0,
+            max_mins: 0,
+            audio_format: 0,
+            audio_src: 1,
+            sched_enabled: false,
+            sched_hour: 0,
+            sched_min: 0,
+            sched_mins: 60,
+        }
+    }
+}
+
+// Remote control servers
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Remote {
+    pub rigctld: bool,
+    pub rigctld_port: u32,
+    // Accept connections from other hosts, otherwise only from this machine
+    pub rigctld_lan: bool,
+    // TS-2000 CAT on a pty linked from cat_pty_link
+    pub cat_pty: bool,
+    pub cat_pty_link: String,
+    // RTP audio bridge, RX2 and RX3 are on the even ports after RX1
+    pub bridge: bool,
+    pub bridge_host: String,
+    pub bridge_rx_port: u32,
+    pub bridge_tx_port: u32,
+    // Web server, clients must give the token
+    pub web: bool,
+    pub web_port: u32,
+    pub web_lan: bool,
+    pub web_token: String,
+    // Protocol 1 server for another application, it gets one receiver
+    pub hpsdr: bool,
+    pub hpsdr_port: u32,
+    pub hpsdr_rx: u32,
+    // rtl_tcp server, the client gets one receiver at the rate it asks for
+    pub rtl_tcp: bool,
+    pub rtl_tcp_port: u32,
+    pub rtl_tcp_lan: bool,
+    pub rtl_tcp_rx: u32,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self {
+            rigctld: false,
+            rigctld_port: common_defs::RIGCTLD_PORT,
+            rigctld_lan: false,
+            cat_pty: false,
+            cat_pty_link: String::from(common_defs::CAT_PTY_LINK),
+            bridge: false,
+            bridge_host: String::from("127.0.0.1"),
+            bridge_rx_port: common_defs::BRIDGE_RX_PORT,
+            bridge_tx_port: common_defs::BRIDGE_TX_PORT,
+            web: false,
+            web_port: common_defs::WEB_PORT,
+            web_lan: false,
+            web_token: String::new(),
+            hpsdr: false,
+            hpsdr_port: common_defs::HPSDR_PORT,
+            hpsdr_rx: 2,
+            rtl_tcp: false,
+            rtl_tcp_port: common_defs::RTL_TCP_PORT,
+            rtl_tcp_lan: false,
+            rtl_tcp_rx: 2,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Prefs {
     pub prefs_path: String,
     pub frame: Frame,
     pub windows: Windows,
     pub radio: Radio,
+    #[serde(default)]
+    pub audio: Audio,
+    #[serde(default)]
+    pub decoder: Decoder,
+    // Notches keyed by band name
+    #[serde(default)]
+    pub notches: HashMap<String, Vec<Notch>>,
+    #[serde(default)]
+    pub diversity: Diversity,
+    #[serde(default)]
+    pub meter: Meter,
+    // Always MAX_SUB_RX entries
+    #[serde(default)]
+    pub sub_rx: Vec<SubRx>,
+    #[serde(default)]
+    pub tx: Tx,
+    #[serde(default)]
+    pub vox: Vox,
+    #[serde(default)]
+    pub cw: Cw,
+    #[serde(default)]
+    pub test: TestSignal,
+    #[serde(default)]
+    pub repeater: Repeater,
+    #[serde(default)]
+    pub record: Record,
+    #[serde(default)]
+    pub remote: Remote,
 }
 
 //===========================================================================================
@@ -163,7 +575,20 @@ impl Prefs {
                         filter: FilterId::F2_4KHz,
                     }},
                 }
-            }
+            },
+            audio: Audio::default(),
+            decoder: Decoder::default(),
+            notches: HashMap::new(),
+            diversity: Diversity::default(),
+            meter: Meter::default(),
+            sub_rx: vec![SubRx::default(); common_defs::MAX_SUB_RX as usize],
+            tx: Tx::default(),
+            vox: Vox::default(),
+            cw: Cw::default(),
+            test: TestSignal::default(),
+            repeater: Repeater::default(),
+            record: Record::default(),
+            remote: Remote::default(),
         }
     }
 
@@ -224,6 +649,92 @@ impl Prefs {
                 self.radio.rx3.frequency = prefs.radio.rx3.frequency;
                 self.radio.rx3.mode = prefs.radio.rx3.mode;
                 self.radio.rx3.filter = prefs.radio.rx3.filter;
+
+                self.audio.aux_device = prefs.audio.aux_device;
+                self.audio.rx1 = prefs.audio.rx1;
+                self.audio.rx2 = prefs.audio.rx2;
+                self.audio.rx3 = prefs.audio.rx3;
+
+                self.decoder.dec_type = prefs.decoder.dec_type;
+                self.decoder.offset = prefs.decoder.offset;
+
+                self.notches = prefs.notches;
+
+                self.diversity.enabled = prefs.diversity.enabled;
+                self.diversity.gain = prefs.diversity.gain;
+                self.diversity.phase = prefs.diversity.phase;
+
+                self.meter.source = prefs.meter.source;
+                self.meter.attn = prefs.meter.attn;
+                self.meter.preamp = prefs.meter.preamp;
+                self.meter.cal = prefs.meter.cal;
+
+                self.sub_rx = prefs.sub_rx;
+                self.sub_rx.resize(common_defs::MAX_SUB_RX as usize, SubRx::default());
+
+                self.tx.profile = prefs.tx.profile;
+                self.tx.profiles = prefs.tx.profiles;
+                if self.tx.profiles.len() == 0 {
+                    self.tx = Tx::default();
+                }
+
+                self.vox.enabled = prefs.vox.enabled;
+                self.vox.threshold = prefs.vox.threshold;
+                self.vox.attack = prefs.vox.attack;
+                self.vox.hang = prefs.vox.hang;
+                self.vox.anti_vox = prefs.vox.anti_vox;
+                self.vox.anti_vox_gain = prefs.vox.anti_vox_gain;
+
+                self.cw.keyer = prefs.cw.keyer;
+                self.cw.hw_keyer = prefs.cw.hw_keyer;
+                self.cw.keyboard = prefs.cw.keyboard;
+                self.cw.mode_b = prefs.cw.mode_b;
+                self.cw.wpm = prefs.cw.wpm;
+                self.cw.weight = prefs.cw.weight;
+                self.cw.sidetone_freq = prefs.cw.sidetone_freq;
+                self.cw.sidetone_vol = prefs.cw.sidetone_vol;
+                self.cw.hang = prefs.cw.hang;
+
+                self.test.tune_level = prefs.test.tune_level;
+                self.test.tone_level = prefs.test.tone_level;
+                self.test.tone1 = prefs.test.tone1;
+                self.test.tone2 = prefs.test.tone2;
+                self.test.max_level = prefs.test.max_level;
+                self.test.timeout = prefs.test.timeout;
+
+                self.repeater.shift_dir = prefs.repeater.shift_dir;
+                self.repeater.shift = prefs.repeater.shift;
+                self.repeater.reverse = prefs.repeater.reverse;
+                self.repeater.ctcss_tx = prefs.repeater.ctcss_tx;
+                self.repeater.ctcss_tx_freq = prefs.repeater.ctcss_tx_freq;
+                self.repeater.tone_sql = prefs.repeater.tone_sql;
+                self.repeater.sql_ctcss = prefs.repeater.sql_ctcss;
+                self.repeater.sql_dcs = prefs.repeater.sql_dcs;
+
+                self.record.dir = prefs.record.dir;
+                self.record.iq_format = prefs.record.iq_format;
+                self.record.max_mb = prefs.record.max_mb;
+                self.record.max_mins = prefs.record.max_mins;
+                self.record.audio_format = prefs.record.audio_format;
+                self.record.audio_src = prefs.record.audio_src;
+                self.record.sched_enabled = prefs.record.sched_enabled;
+                self.record.sched_hour = prefs.record.sched_hour;
+                self.record.sched_min = prefs.record.sched_min;
+                self.record.sched_mins = prefs.record.sched_mins;
+
+                self.remote.rigctld = prefs.remote.rigctld;
+                self.remote.rigctld_port = prefs.remote.rigctld_port;
+                self.remote.rigctld_lan = prefs.remote.rigctld_lan;
+                self.remote.cat_pty = prefs.remote.cat_pty;
+                self.remote.cat_pty_link = prefs.remote.cat_pty_link;
+                self.remote.bridge = prefs.remote.bridge;
+                self.remote.bridge_host = prefs.remote.bridge_host;
+                self.remote.bridge_rx_port = prefs.remote.bridge_rx_port;
+                self.remote.bridge_tx_port = prefs.remote.bridge_tx_port;
+                self.remote.web = prefs.remote.web;
+                self.remote.web_port = prefs.remote.web_port;
+                self.remote.web_lan = prefs.remote.web_lan;
+                self.remote.web_token = prefs.remote.web_token;
                 
             },
         }
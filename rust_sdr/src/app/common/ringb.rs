@@ -41,6 +41,18 @@ impl SyncByteRingBuf {
         }
     }
     
+    pub fn capacity(&self) -> usize {
+        self.q.lock().expect("poisoned").capacity()
+    }
+
+    // Discard all content and set a new capacity
+    pub fn reset(&self, cap: usize) {
+        let mut q = self.q.lock().expect("poisoned");
+        q.clear();
+        q.shrink_to(cap);
+        q.reserve(cap);
+    }
+
     pub fn read(&self) -> SyncByteRingBufReadGuard<'_> {
         SyncByteRingBufReadGuard(self.q.lock().expect("poisoned"))
     }
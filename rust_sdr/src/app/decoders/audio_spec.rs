@@ -0,0 +1,180 @@
+/*
+audio_spec.rs
+
+Module - audio_spec
+Audio spectrum of the decoder input for the tuning waterfall
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f32::consts::PI;
+
+// 48KHz averaged down to 6KHz gives 0-3KHz
+const DECIM: usize = 8;
+const FFT_SZ: usize = 512;
+// Number of output bins covering 0 to SPAN Hz
+pub const BINS: usize = FFT_SZ / 2;
+pub const SPAN: f32 = 3000.0;
+// Lines per second
+const LINE_RATE: usize = 10;
+
+//==================================================================================
+// Spectrum state
+pub struct AudioSpec {
+    avg: f32,
+    avg_count: usize,
+    buf: Vec<f32>,
+    buf_pos: usize,
+    window: Vec<f32>,
+    // Samples at the reduced rate between lines
+    interval: usize,
+    count: usize,
+    re: Vec<f32>,
+    im: Vec<f32>,
+}
+
+impl AudioSpec {
+    // Create a new instance for the audio rate
+    pub fn new(sample_rate: u32) -> AudioSpec {
+        let mut window = vec![0.0; FFT_SZ];
+        for i in 0..FFT_SZ {
+            window[i] = 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SZ as f32).cos();
+        }
+        AudioSpec {
+            avg: 0.0,
+            avg_count: 0,
+            buf: vec![0.0; FFT_SZ],
+            buf_pos: 0,
+            window: window,
+            interval: sample_rate as usize / DECIM / LINE_RATE,
+            count: 0,
+            re: vec![0.0; FFT_SZ],
+            im: vec![0.0; FFT_SZ],
+        }
+    }
+
+    // Process a block of audio samples.
+    // Returns a line of BINS dB values when one is due.
+    pub fn process(&mut self, samples: &[f32]) -> Option<Vec<f32>> {
+        let mut line = None;
+        for s in samples.iter() {
+            self.avg += *s;
+            self.avg_count += 1;
+            if self.avg_count < DECIM {
+                continue;
+            }
+            // Keep the latest FFT_SZ samples
+            self.buf[self.buf_pos] = self.avg / DECIM as f32;
+            self.buf_pos = (self.buf_pos + 1) % FFT_SZ;
+            self.avg = 0.0;
+            self.avg_count = 0;
+            self.count += 1;
+            if self.count >= self.interval {
+                self.count = 0;
+                line = Some(self.line());
+            }
+        }
+        return line;
+    }
+
+    // Windowed FFT of the buffer as dB
+    fn line(&mut self) -> Vec<f32> {
+        for i in 0..FFT_SZ {
+            // Oldest sample first
+            self.re[i] = self.buf[(self.buf_pos + i) % FFT_SZ] * self.window[i];
+            self.im[i] = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+        let mut out = Vec::with_capacity(BINS);
+        for i in 0..BINS {
+            let p = (self.re[i] * self.re[i] + self.im[i] * self.im[i]) / (FFT_SZ * FFT_SZ) as f32;
+            out.push(10.0 * f32::max(p, 1e-20).log10());
+        }
+        return out;
+    }
+}
+
+// In place radix 2 FFT, length must be a power of 2
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    // Bit reverse
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    // Butterflies
+    let mut len = 2;
+    while len <= n {
+        let ang = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wi, wr) = (ang * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let tr = re[b] * wr - im[b] * wi;
+                let ti = re[b] * wi + im[b] * wr;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_in_correct_bin() {
+        let mut s = AudioSpec::new(48000);
+        let tone: Vec<f32> = (0..48000).map(|n| (2.0 * PI * 1500.0 * n as f32 / 48000.0).sin()).collect();
+        let mut last = None;
+        for block in tone.chunks(1024) {
+            if let Some(line) = s.process(block) {
+                last = Some(line);
+            }
+        }
+        let line = last.unwrap();
+        assert_eq!(line.len(), BINS);
+        let mut peak = 0;
+        for i in 0..BINS {
+            if line[i] > line[peak] {
+                peak = i;
+            }
+        }
+        let hz = peak as f32 * SPAN / BINS as f32;
+        assert!((hz - 1500.0).abs() < 2.0 * SPAN / BINS as f32, "peak at {}", hz);
+    }
+}
@@ -0,0 +1,404 @@
+/*
+cw_decoder.rs
+
+Module - cw_decoder
+Decode morse from demodulated CW audio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+// Speed limits in WPM. A dot is 1200/WPM ms.
+pub const MIN_WPM: u32 = 5;
+pub const MAX_WPM: u32 = 50;
+const START_WPM: u32 = 20;
+
+// Time constants in seconds
+// Envelope smoothing, short enough for a 50 WPM dot
+const ENV_TC: f32 = 0.003;
+// Signal peak tracking
+const PEAK_ATTACK_TC: f32 = 0.005;
+const PEAK_DECAY_TC: f32 = 1.0;
+// Noise floor tracking
+const NOISE_FALL_TC: f32 = 0.05;
+const NOISE_RISE_TC: f32 = 5.0;
+
+// Key down/up levels as a fraction of the noise to peak range
+const KEY_DOWN_LEVEL: f32 = 0.6;
+const KEY_UP_LEVEL: f32 = 0.4;
+// Peak must be this far above the noise floor to be a signal
+const MIN_SNR: f32 = 2.5;
+// Key transitions shorter than this are treated as noise
+const GLITCH_MS: f32 = 8.0;
+// Weight of a new element in the speed estimate
+const SPEED_ALPHA: f32 = 0.3;
+
+//==================================================================================
+// Decoder state
+pub struct CwDecoder {
+    sample_rate: f32,
+    // Filter coefficients
+    env_alpha: f32,
+    peak_attack: f32,
+    peak_decay: f32,
+    noise_fall: f32,
+    noise_rise: f32,
+    // Signal tracking
+    env: f32,
+    peak: f32,
+    noise: f32,
+    // Key state and samples in the current state
+    key_down: bool,
+    count: u32,
+    // Length of the previous state, restored if a glitch is rejected
+    prev_count: u32,
+    // Average element lengths in ms
+    dot_ms: f32,
+    dash_ms: f32,
+    // Length of the last mark in ms
+    last_ms: f32,
+    // Elements of the current character
+    code: String,
+    // Set when a word space is due
+    in_word: bool,
+    // Decoded text not yet collected
+    text: String,
+}
+
+impl CwDecoder {
+    // Create a new instance for the given audio rate
+    pub fn new(sample_rate: u32) -> CwDecoder {
+        let sr = sample_rate as f32;
+        let dot = 1200.0 / START_WPM as f32;
+        CwDecoder {
+            sample_rate: sr,
+            env_alpha: coeff(ENV_TC, sr),
+            peak_attack: coeff(PEAK_ATTACK_TC, sr),
+            peak_decay: coeff(PEAK_DECAY_TC, sr),
+            noise_fall: coeff(NOISE_FALL_TC, sr),
+            noise_rise: coeff(NOISE_RISE_TC, sr),
+            env: 0.0,
+            peak: 0.0,
+            // Audio is full scale at 1.0, falls quickly to the real level
+            noise: 1.0,
+            key_down: false,
+            count: 0,
+            prev_count: 0,
+            dot_ms: dot,
+            dash_ms: dot * 3.0,
+            last_ms: 0.0,
+            code: String::new(),
+            in_word: false,
+            text: String::new(),
+        }
+    }
+
+    // Reset all tracking, used when the decoder is switched in
+    pub fn reset(&mut self) {
+        let sr = self.sample_rate as u32;
+        *self = CwDecoder::new(sr);
+    }
+
+    // Current speed estimate
+    pub fn wpm(&self) -> u32 {
+        let dot = (self.dot_ms + self.dash_ms / 3.0) / 2.0;
+        let wpm = (1200.0 / dot).round() as u32;
+        return u32::max(MIN_WPM, u32::min(MAX_WPM, wpm));
+    }
+
+    // Process a block of audio samples.
+    // Returns any text decoded.
+    pub fn process(&mut self, samples: &[f32]) -> String {
+        for s in samples.iter() {
+            self.process_sample(*s);
+        }
+        return std::mem::take(&mut self.text);
+    }
+
+    fn process_sample(&mut self, sample: f32) {
+        // Envelope of the rectified audio
+        self.env += (sample.abs() - self.env) * self.env_alpha;
+
+        // Track the signal peak and the noise floor
+        if self.env > self.peak {
+            self.peak += (self.env - self.peak) * self.peak_attack;
+        } else {
+            self.peak += (self.env - self.peak) * self.peak_decay;
+        }
+        if self.env < self.noise {
+            self.noise += (self.env - self.noise) * self.noise_fall;
+        } else {
+            self.noise += (self.env - self.noise) * self.noise_rise;
+        }
+
+        // Decide key state with hysteresis
+        let mut key = self.key_down;
+        if self.peak < self.noise * MIN_SNR || self.peak <= 0.0 {
+            key = false;
+        } else {
+            let level = (self.env - self.noise) / (self.peak - self.noise);
+            if self.key_down && level < KEY_UP_LEVEL {
+                key = false;
+            } else if !self.key_down && level > KEY_DOWN_LEVEL {
+                key = true;
+            }
+        }
+
+        if key != self.key_down {
+            self.transition(key);
+        }
+        self.count += 1;
+
+        if !self.key_down {
+            self.check_gap();
+        }
+    }
+
+    // Key state has changed
+    fn transition(&mut self, key: bool) {
+        let ms = self.to_ms(self.count);
+        self.key_down = key;
+        if ms < GLITCH_MS {
+            // Too short to be real, continue the previous state
+            self.count = self.prev_count + self.count;
+            self.prev_count = 0;
+            return;
+        }
+        if !key {
+            // End of a mark
+            self.element(ms);
+        }
+        self.prev_count = self.count;
+        self.count = 0;
+    }
+
+    // Classify a mark as dot or dash and adapt the speed
+    fn element(&mut self, ms: f32) {
+        let min_dot = 1200.0 / MAX_WPM as f32;
+        let max_dot = 1200.0 / MIN_WPM as f32;
+        // A dot next to a dash gives a fast estimate when the speed changes
+        let ratio = ms / f32::max(self.last_ms, 1.0);
+        if ratio > 2.0 && ratio < 4.0 {
+            self.dot_ms = self.last_ms;
+            self.dash_ms = ms;
+        } else if ratio < 0.5 && ratio > 0.25 {
+            self.dot_ms = ms;
+            self.dash_ms = self.last_ms;
+        }
+        self.last_ms = ms;
+        if ms < (self.dot_ms + self.dash_ms) / 2.0 {
+            self.code.push('.');
+            self.dot_ms += (ms - self.dot_ms) * SPEED_ALPHA;
+            self.dot_ms = f32::max(min_dot, f32::min(max_dot, self.dot_ms));
+            // Keep the dash in a sensible ratio to the dot
+            self.dash_ms = f32::max(self.dot_ms * 2.0, f32::min(self.dot_ms * 4.0, self.dash_ms));
+        } else {
+            self.code.push('-');
+            self.dash_ms += (ms - self.dash_ms) * SPEED_ALPHA;
+            self.dash_ms = f32::max(min_dot * 3.0, f32::min(max_dot * 3.0, self.dash_ms));
+            self.dot_ms = f32::max(self.dash_ms / 4.0, f32::min(self.dash_ms / 2.0, self.dot_ms));
+        }
+        // Nothing is longer than this so give up
+        if self.code.len() > 8 {
+            self.code.clear();
+        }
+    }
+
+    // Check the current space for character and word ends
+    fn check_gap(&mut self) {
+        let ms = self.to_ms(self.count);
+        let dot = (self.dot_ms + self.dash_ms / 3.0) / 2.0;
+        if self.code.len() > 0 && ms > dot * 2.0 {
+            // Character space is 3 dots
+            match lookup(&self.code) {
+                Some(c) => self.text.push(c),
+                None => self.text.push('*'),
+            }
+            self.code.clear();
+            self.in_word = true;
+        } else if self.in_word && ms > dot * 5.0 {
+            // Word space is 7 dots
+            self.text.push(' ');
+            self.in_word = false;
+        }
+    }
+
+    fn to_ms(&self, samples: u32) -> f32 {
+        return samples as f32 * 1000.0 / self.sample_rate;
+    }
+}
+
+//==================================================================================
+// Helpers
+
+// One pole filter coefficient for a time constant
+fn coeff(tc: f32, sample_rate: f32) -> f32 {
+    return 1.0 - f32::exp(-1.0 / (tc * sample_rate));
+}
+
+// Morse code table
+pub fn lookup(code: &str) -> Option<char> {
+    let c = match code {
+        ".-" => 'A', "-..." => 'B', "-.-." => 'C', "-.." => 'D', "." => 'E',
+        "..-." => 'F', "--." => 'G', "...." => 'H', ".." => 'I', ".---" => 'J',
+        "-.-" => 'K', ".-.." => 'L', "--" => 'M', "-." => 'N', "---" => 'O',
+        ".--." => 'P', "--.-" => 'Q', ".-." => 'R', "..." => 'S', "-" => 'T',
+        "..-" => 'U', "...-" => 'V', ".--" => 'W', "-..-" => 'X', "-.--" => 'Y',
+        "--.." => 'Z',
+        "-----" => '0', ".----" => '1', "..---" => '2', "...--" => '3', "....-" => '4',
+        "....." => '5', "-...." => '6', "--..." => '7', "---.." => '8', "----." => '9',
+        ".-.-.-" => '.', "--..--" => ',', "..--.." => '?', "-..-." => '/', "-...-" => '=',
+        ".-.-." => '+', "-....-" => '-', "-.--." => '(', "-.--.-" => ')', ".----." => '\'',
+        "---..." => ':', ".-..-." => '"', ".--.-." => '@',
+        _ => return None,
+    };
+    return Some(c);
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: u32 = 48000;
+    const TONE: f32 = 700.0;
+
+    // Simple repeatable noise source
+    struct Noise {
+        state: u32,
+    }
+
+    impl Noise {
+        fn next(&mut self) -> f32 {
+            self.state = self.state.wrapping_mul(1664525).wrapping_add(1013904223);
+            return (self.state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+        }
+    }
+
+    fn encode(c: char) -> &'static str {
+        for code in [
+                ".-", "-...", "-.-.", "-..", ".", "..-.", "--.", "....", "..", ".---",
+                "-.-", ".-..", "--", "-.", "---", ".--.", "--.-", ".-.", "...", "-",
+                "..-", "...-", ".--", "-..-", "-.--", "--..",
+                "-----", ".----", "..---", "...--", "....-",
+                ".....", "-....", "--...", "---..", "----."] {
+            if lookup(code) == Some(c) {
+                return code;
+            }
+        }
+        panic!("no code for {}", c);
+    }
+
+    // Generate keyed tone for the text at the given speed and amplitude.
+    // Edges are shaped to avoid key clicks as a real transmitter would.
+    fn keyed_tone(text: &str, wpm: u32, amplitude: f32, noise: f32) -> Vec<f32> {
+        let dot = (SR as f32 * 1.2 / wpm as f32) as usize;
+        let edge = (SR as f32 * 0.004) as usize;
+        let mut key: Vec<bool> = Vec::new();
+        key.resize(SR as usize / 2, false);
+        for word in text.split(' ') {
+            for c in word.chars() {
+                for e in encode(c).chars() {
+                    let len = if e == '.' {dot} else {dot * 3};
+                    key.resize(key.len() + len, true);
+                    key.resize(key.len() + dot, false);
+                }
+                key.resize(key.len() + dot * 2, false);
+            }
+            key.resize(key.len() + dot * 4, false);
+        }
+        key.resize(key.len() + SR as usize * 2, false);
+
+        let mut n = Noise {state: 12345};
+        let mut shape = 0.0;
+        let mut out = Vec::with_capacity(key.len());
+        for (i, k) in key.iter().enumerate() {
+            if *k {
+                shape = f32::min(1.0, shape + 1.0 / edge as f32);
+            } else {
+                shape = f32::max(0.0, shape - 1.0 / edge as f32);
+            }
+            let t = i as f32 / SR as f32;
+            let s = (2.0 * std::f32::consts::PI * TONE * t).sin() * amplitude * shape;
+            out.push(s + n.next() * noise);
+        }
+        return out;
+    }
+
+    // Feed in blocks as the pipeline does
+    fn decode(d: &mut CwDecoder, audio: &[f32]) -> String {
+        let mut text = String::new();
+        for block in audio.chunks(1024) {
+            text.push_str(&d.process(block));
+        }
+        return text;
+    }
+
+    #[test]
+    fn decodes_at_20_wpm() {
+        let mut d = CwDecoder::new(SR);
+        let text = decode(&mut d, &keyed_tone("PARIS PARIS CQ", 20, 0.5, 0.0));
+        assert_eq!(text.trim(), "PARIS PARIS CQ");
+        assert!((d.wpm() as i32 - 20).abs() <= 2, "wpm {}", d.wpm());
+    }
+
+    #[test]
+    fn adapts_to_slow_speed() {
+        let mut d = CwDecoder::new(SR);
+        let text = decode(&mut d, &keyed_tone("TEST PARIS PARIS", 5, 0.5, 0.0));
+        // Allow the first word for the speed to settle
+        assert!(text.trim().ends_with("PARIS PARIS"), "decoded {}", text);
+        assert!((d.wpm() as i32 - 5).abs() <= 1, "wpm {}", d.wpm());
+    }
+
+    #[test]
+    fn adapts_to_fast_speed() {
+        let mut d = CwDecoder::new(SR);
+        let text = decode(&mut d, &keyed_tone("TEST PARIS PARIS", 50, 0.5, 0.0));
+        assert!(text.trim().ends_with("PARIS PARIS"), "decoded {}", text);
+        assert!((d.wpm() as i32 - 50).abs() <= 4, "wpm {}", d.wpm());
+    }
+
+    #[test]
+    fn decodes_in_noise() {
+        let mut d = CwDecoder::new(SR);
+        let text = decode(&mut d, &keyed_tone("CQ DE G3UKB K", 25, 0.5, 0.1));
+        assert_eq!(text.trim(), "CQ DE G3UKB K");
+    }
+
+    #[test]
+    fn tracks_level_change() {
+        let mut d = CwDecoder::new(SR);
+        let mut audio = keyed_tone("PARIS", 20, 0.8, 0.01);
+        audio.extend(keyed_tone("PARIS PARIS", 20, 0.05, 0.01));
+        let text = decode(&mut d, &audio);
+        assert!(text.trim().ends_with("PARIS PARIS"), "decoded {}", text);
+    }
+
+    #[test]
+    fn ignores_noise() {
+        let mut d = CwDecoder::new(SR);
+        let mut n = Noise {state: 999};
+        let audio: Vec<f32> = (0..SR * 5).map(|_| n.next() * 0.2).collect();
+        let text = decode(&mut d, &audio);
+        assert_eq!(text.trim(), "");
+    }
+}
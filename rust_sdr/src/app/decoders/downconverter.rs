@@ -0,0 +1,146 @@
+/*
+downconverter.rs
+
+Module - downconverter
+Shift an audio tone to baseband and reduce the sample rate for the decoders
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f64::consts::PI;
+
+//==================================================================================
+// Downconverter state
+// Audio is mixed with a local oscillator at the centre frequency, averaged down
+// to an intermediate rate, then low pass filtered and decimated to the output rate.
+pub struct Downconverter {
+    in_rate: f64,
+    // Local oscillator
+    phase: f64,
+    phase_inc: f64,
+    // First stage, simple average
+    avg_decim: usize,
+    avg_i: f32,
+    avg_q: f32,
+    avg_count: usize,
+    // Second stage, FIR low pass
+    fir_decim: usize,
+    taps: Vec<f32>,
+    hist_i: Vec<f32>,
+    hist_q: Vec<f32>,
+    hist_pos: usize,
+    fir_count: usize,
+}
+
+impl Downconverter {
+    // Create a new instance.
+    // The output rate is in_rate / (avg_decim * fir_decim).
+    pub fn new(in_rate: u32, center: f32, avg_decim: usize, fir_decim: usize, cutoff: f32, num_taps: usize) -> Downconverter {
+        let fir_rate = in_rate as f64 / avg_decim as f64;
+        // Blackman windowed sinc
+        let fc = cutoff as f64 / fir_rate;
+        let mut taps = vec![0.0; num_taps];
+        let mut sum = 0.0;
+        let mid = (num_taps - 1) as f64 / 2.0;
+        for k in 0..num_taps {
+            let t = k as f64 - mid;
+            let sinc = if t == 0.0 {2.0 * fc} else {(2.0 * PI * fc * t).sin() / (PI * t)};
+            let a = 2.0 * PI * k as f64 / (num_taps - 1) as f64;
+            let w = 0.42 - 0.5 * a.cos() + 0.08 * (2.0 * a).cos();
+            taps[k] = (sinc * w) as f32;
+            sum += sinc * w;
+        }
+        for k in 0..num_taps {
+            taps[k] = (taps[k] as f64 / sum) as f32;
+        }
+
+        let mut d = Downconverter {
+            in_rate: in_rate as f64,
+            phase: 0.0,
+            phase_inc: 0.0,
+            avg_decim: avg_decim,
+            avg_i: 0.0,
+            avg_q: 0.0,
+            avg_count: 0,
+            fir_decim: fir_decim,
+            taps: taps,
+            hist_i: vec![0.0; num_taps],
+            hist_q: vec![0.0; num_taps],
+            hist_pos: 0,
+            fir_count: 0,
+        };
+        d.set_center(center);
+        return d;
+    }
+
+    // Output sample rate
+    pub fn out_rate(&self) -> f32 {
+        return (self.in_rate / (self.avg_decim * self.fir_decim) as f64) as f32;
+    }
+
+    // Retune the local oscillator
+    pub fn set_center(&mut self, center: f32) {
+        self.phase_inc = 2.0 * PI * center as f64 / self.in_rate;
+    }
+
+    pub fn center(&self) -> f32 {
+        return (self.phase_inc * self.in_rate / (2.0 * PI)) as f32;
+    }
+
+    // Process audio and append baseband (I, Q) samples to out
+    pub fn process(&mut self, samples: &[f32], out: &mut Vec<(f32, f32)>) {
+        let n = self.taps.len();
+        for s in samples.iter() {
+            self.avg_i += *s * self.phase.cos() as f32;
+            self.avg_q -= *s * self.phase.sin() as f32;
+            self.phase += self.phase_inc;
+            if self.phase > 2.0 * PI {
+                self.phase -= 2.0 * PI;
+            }
+            self.avg_count += 1;
+            if self.avg_count < self.avg_decim {
+                continue;
+            }
+            // Averaged sample into the filter history
+            self.hist_i[self.hist_pos] = self.avg_i / self.avg_decim as f32;
+            self.hist_q[self.hist_pos] = self.avg_q / self.avg_decim as f32;
+            self.hist_pos = (self.hist_pos + 1) % n;
+            self.avg_i = 0.0;
+            self.avg_q = 0.0;
+            self.avg_count = 0;
+
+            self.fir_count += 1;
+            if self.fir_count < self.fir_decim {
+                continue;
+            }
+            self.fir_count = 0;
+            let mut acc_i = 0.0;
+            let mut acc_q = 0.0;
+            for k in 0..n {
+                let h = self.taps[k];
+                let idx = (self.hist_pos + k) % n;
+                acc_i += self.hist_i[idx] * h;
+                acc_q += self.hist_q[idx] * h;
+            }
+            out.push((acc_i, acc_q));
+        }
+    }
+}
@@ -0,0 +1,301 @@
+/*
+psk_decoder.rs
+
+Module - psk_decoder
+Decode BPSK31 Varicode from demodulated audio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f32::consts::PI;
+
+use crate::app::decoders::downconverter;
+
+pub const BAUD: f32 = 31.25;
+
+// Rate reduction, 48KHz to 500Hz which is 16 samples per symbol
+const AVG_DECIM: usize = 16;
+const FIR_DECIM: usize = 6;
+const FIR_TAPS: usize = 127;
+const FIR_CUTOFF: f32 = 60.0;
+const SPS: usize = 16;
+// Symbol timing and AFC loop gains
+const TIMING_ALPHA: f32 = 0.02;
+const AFC_ALPHA: f32 = 0.05;
+// Maximum AFC pull from the set frequency
+const AFC_LIMIT: f32 = 15.0;
+// Below this baseband level there is no signal
+const SQUELCH: f32 = 1e-4;
+
+// Varicode for ASCII 0..127
+const VARICODE: [&str; 128] = [
+    "1010101011", "1011011011", "1011101101", "1101110111", "1011101011", "1101011111", "1011101111", "1011111101",
+    "1011111111", "11101111", "11101", "1101101111", "1011011101", "11111", "1101110101", "1110101011",
+    "1011110111", "1011110101", "1110101101", "1110101111", "1101011011", "1101101011", "1101101101", "1101010111",
+    "1101111011", "1101111101", "1110110111", "1101010101", "1101011101", "1110111011", "1011111011", "1101111111",
+    "1", "111111111", "101011111", "111110101", "111011011", "1011010101", "1010111011", "101111111",
+    "11111011", "11110111", "101101111", "111011111", "1110101", "110101", "1010111", "110101111",
+    "10110111", "10111101", "11101101", "11111111", "101110111", "101011011", "101101011", "110101101",
+    "110101011", "110110111", "11110101", "110111101", "111101101", "1010101", "111010111", "1010101111",
+    "1010111101", "1111101", "11101011", "10101101", "10110101", "1110111", "11011011", "11111101",
+    "101010101", "1111111", "111111101", "101111101", "11010111", "10111011", "11011101", "10101011",
+    "11010101", "111011101", "10101111", "1101111", "1101101", "101010111", "110110101", "101011101",
+    "101110101", "101111011", "1010101101", "111110111", "111101111", "111111011", "1010111111", "101101101",
+    "1011011111", "1011", "1011111", "101111", "101101", "11", "111101", "1011011",
+    "101011", "1101", "111101011", "10111111", "11011", "111011", "1111", "111",
+    "111111", "110111111", "10101", "10111", "101", "110111", "1111011", "1101011",
+    "11011111", "1011101", "111010101", "1010110111", "110111011", "1010110101", "1011010111", "1110110101",
+];
+
+//==================================================================================
+// Decoder state
+pub struct PskDecoder {
+    dc: downconverter::Downconverter,
+    baseband: Vec<(f32, f32)>,
+    // Set frequency and current AFC correction
+    center: f32,
+    afc: f32,
+    // Short matched filter over half a symbol
+    mf: Vec<(f32, f32)>,
+    mf_pos: usize,
+    mf_sum: (f32, f32),
+    // Symbol timing, average amplitude at each sample phase
+    amp: [f32; SPS],
+    phase: usize,
+    best: usize,
+    last: (f32, f32),
+    // Varicode shift register
+    reg: u32,
+    codes: Vec<u32>,
+    text: String,
+}
+
+impl PskDecoder {
+    // Create a new instance for the audio rate and audio centre frequency
+    pub fn new(sample_rate: u32, center: f32) -> PskDecoder {
+        // Convert the table to bit patterns once
+        let mut codes = Vec::with_capacity(VARICODE.len());
+        for v in VARICODE.iter() {
+            codes.push(u32::from_str_radix(v, 2).unwrap());
+        }
+        PskDecoder {
+            dc: downconverter::Downconverter::new(sample_rate, center, AVG_DECIM, FIR_DECIM, FIR_CUTOFF, FIR_TAPS),
+            baseband: Vec::new(),
+            center: center,
+            afc: 0.0,
+            mf: vec![(0.0, 0.0); SPS / 2],
+            mf_pos: 0,
+            mf_sum: (0.0, 0.0),
+            amp: [0.0; SPS],
+            phase: 0,
+            best: 0,
+            last: (0.0, 0.0),
+            reg: 0,
+            codes: codes,
+            text: String::new(),
+        }
+    }
+
+    // Set the audio frequency of the signal
+    pub fn set_center(&mut self, center: f32) {
+        self.center = center;
+        self.afc = 0.0;
+        self.dc.set_center(center);
+    }
+
+    // Frequency being decoded including any AFC correction
+    pub fn center(&self) -> f32 {
+        return self.center + self.afc;
+    }
+
+    // Process a block of audio samples.
+    // Returns any text decoded.
+    pub fn process(&mut self, samples: &[f32]) -> String {
+        self.baseband.clear();
+        self.dc.process(samples, &mut self.baseband);
+        for i in 0..self.baseband.len() {
+            let z = self.baseband[i];
+            self.process_baseband(z);
+        }
+        return std::mem::take(&mut self.text);
+    }
+
+    fn process_baseband(&mut self, z: (f32, f32)) {
+        // Matched filter
+        let old = self.mf[self.mf_pos];
+        self.mf[self.mf_pos] = z;
+        self.mf_pos = (self.mf_pos + 1) % self.mf.len();
+        self.mf_sum.0 += z.0 - old.0;
+        self.mf_sum.1 += z.1 - old.1;
+        let s = self.mf_sum;
+
+        // Amplitude peaks at the symbol centre, track the best sample phase
+        let a = (s.0 * s.0 + s.1 * s.1).sqrt();
+        self.amp[self.phase] += (a - self.amp[self.phase]) * TIMING_ALPHA;
+        if self.phase == self.best {
+            self.symbol(s);
+            // Choose the phase for the next symbol
+            let mut best = 0;
+            for i in 1..SPS {
+                if self.amp[i] > self.amp[best] {
+                    best = i;
+                }
+            }
+            self.best = best;
+        }
+        self.phase = (self.phase + 1) % SPS;
+    }
+
+    // Differential detection of one symbol
+    fn symbol(&mut self, s: (f32, f32)) {
+        let re = s.0 * self.last.0 + s.1 * self.last.1;
+        let im = s.1 * self.last.0 - s.0 * self.last.1;
+        self.last = s;
+        let level = (s.0 * s.0 + s.1 * s.1).sqrt() / self.mf.len() as f32;
+        if level < SQUELCH {
+            self.reg = 0;
+            return;
+        }
+
+        // AFC, doubling the phase change removes the modulation
+        let err = (2.0 * re * im).atan2(re * re - im * im) / 2.0;
+        let err_hz = err * BAUD / (2.0 * PI);
+        self.afc = f32::max(-AFC_LIMIT, f32::min(AFC_LIMIT, self.afc + err_hz * AFC_ALPHA));
+        self.dc.set_center(self.center + self.afc);
+
+        // No phase change is a 1, a reversal is a 0
+        let bit = if re > 0.0 {1} else {0};
+        self.reg = (self.reg << 1) | bit;
+        if self.reg & 0b11 == 0 {
+            // Two zeros end a character
+            let code = self.reg >> 2;
+            if code != 0 {
+                self.character(code);
+            }
+            self.reg = 0;
+        } else if self.reg > 0xfff {
+            // Longer than any code
+            self.reg = 0;
+        }
+    }
+
+    fn character(&mut self, code: u32) {
+        for (i, c) in self.codes.iter().enumerate() {
+            if *c == code {
+                let ch = i as u8 as char;
+                if ch == '\n' || !ch.is_control() {
+                    self.text.push(ch);
+                }
+                return;
+            }
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: u32 = 48000;
+
+    // BPSK31 with cosine shaped reversals
+    fn bpsk(text: &str, center: f32, noise: f32) -> Vec<f32> {
+        let mut bits: Vec<u8> = vec![0; 32];
+        for c in text.bytes() {
+            for b in VARICODE[c as usize].bytes() {
+                bits.push(b - b'0');
+            }
+            bits.push(0);
+            bits.push(0);
+        }
+        bits.extend(vec![0; 32]);
+
+        let sym_len = (SR as f32 / BAUD) as usize;
+        let mut out = Vec::new();
+        let mut amp: f32 = 1.0;
+        let mut state: u32 = 3;
+        let mut n = 0;
+        for bit in bits.iter() {
+            for i in 0..sym_len {
+                let a;
+                if *bit == 0 {
+                    // Reversal, amplitude passes through zero
+                    a = amp * (PI * i as f32 / sym_len as f32).cos();
+                } else {
+                    a = amp;
+                }
+                let t = n as f32 / SR as f32;
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                let r = (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                out.push(a * 0.3 * (2.0 * PI * center * t).cos() + r * noise);
+                n += 1;
+            }
+            if *bit == 0 {
+                amp = -amp;
+            }
+        }
+        return out;
+    }
+
+    fn decode(d: &mut PskDecoder, audio: &[f32]) -> String {
+        let mut text = String::new();
+        for block in audio.chunks(1024) {
+            text.push_str(&d.process(block));
+        }
+        return text;
+    }
+
+    #[test]
+    fn varicode_is_valid() {
+        // Every code starts and ends with 1, has no 00 and is unique
+        for (i, v) in VARICODE.iter().enumerate() {
+            assert!(v.starts_with('1') && v.ends_with('1'), "code {}", i);
+            assert!(!v.contains("00"), "code {}", i);
+            for (j, w) in VARICODE.iter().enumerate() {
+                assert!(i == j || v != w, "codes {} and {}", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_on_frequency() {
+        let mut d = PskDecoder::new(SR, 1000.0);
+        let text = decode(&mut d, &bpsk("cq cq de G3UKB pse k", 1000.0, 0.0));
+        assert!(text.ends_with("cq cq de G3UKB pse k"), "decoded {}", text);
+    }
+
+    #[test]
+    fn afc_pulls_in() {
+        let mut d = PskDecoder::new(SR, 1500.0);
+        let text = decode(&mut d, &bpsk("the quick brown fox jumps over the lazy dog", 1506.0, 0.0));
+        assert!(text.ends_with("lazy dog"), "decoded {}", text);
+        assert!((d.center() - 1506.0).abs() < 1.0, "center {}", d.center());
+    }
+
+    #[test]
+    fn decodes_in_noise() {
+        let mut d = PskDecoder::new(SR, 800.0);
+        let text = decode(&mut d, &bpsk("RST 599 599 73", 800.0, 0.5));
+        assert!(text.ends_with("RST 599 599 73"), "decoded {}", text);
+    }
+}
@@ -0,0 +1,353 @@
+/*
+rtty_decoder.rs
+
+Module - rtty_decoder
+Decode Baudot RTTY from demodulated audio
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f32::consts::PI;
+
+use crate::app::decoders::downconverter;
+
+// Standard amateur shift, mark is the higher RF frequency
+pub const SHIFT: f32 = 170.0;
+pub const BAUD_45: f32 = 45.45;
+pub const BAUD_50: f32 = 50.0;
+
+// Rate reduction, 48KHz to 1500Hz
+const AVG_DECIM: usize = 8;
+const FIR_DECIM: usize = 4;
+const FIR_TAPS: usize = 127;
+// Passes both tones and their keying sidebands
+const FIR_CUTOFF: f32 = 200.0;
+// Below this baseband level there is no signal
+const SQUELCH: f32 = 1e-4;
+
+// Baudot (ITA2) shift codes
+const LTRS: u8 = 0x1f;
+const FIGS: u8 = 0x1b;
+// Letters and US figures, indexed by the 5 bit code
+const LETTERS: &[u8; 32] = b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0";
+const FIGURES: &[u8; 32] = b"\x003\n- \x0787\r$4',!:(5\")2#6019?&\0./;\0";
+
+#[derive(PartialEq)]
+enum RxState {
+    // Waiting for a mark to space transition
+    Idle,
+    // Checking the start bit at its centre
+    Start,
+    Data,
+    Stop,
+}
+
+//==================================================================================
+// Decoder state
+pub struct RttyDecoder {
+    dc: downconverter::Downconverter,
+    baseband: Vec<(f32, f32)>,
+    // Mark is the upper audio tone unless reversed (LSB)
+    reverse: bool,
+    // Samples per bit at the baseband rate
+    spb: f32,
+    // Discriminator
+    last: (f32, f32),
+    avg: Vec<f32>,
+    avg_pos: usize,
+    avg_sum: f32,
+    // Receiver
+    state: RxState,
+    last_bit: bool,
+    count: f32,
+    bit_num: u32,
+    code: u8,
+    figs: bool,
+    text: String,
+}
+
+impl RttyDecoder {
+    // Create a new instance for the audio rate, speed and audio centre frequency
+    pub fn new(sample_rate: u32, baud: f32, center: f32) -> RttyDecoder {
+        let dc = downconverter::Downconverter::new(sample_rate, center, AVG_DECIM, FIR_DECIM, FIR_CUTOFF, FIR_TAPS);
+        let spb = dc.out_rate() / baud;
+        // Smooth the discriminator over half a bit
+        let avg_len = usize::max(1, (spb / 2.0) as usize);
+        RttyDecoder {
+            dc: dc,
+            baseband: Vec::new(),
+            reverse: false,
+            spb: spb,
+            last: (0.0, 0.0),
+            avg: vec![0.0; avg_len],
+            avg_pos: 0,
+            avg_sum: 0.0,
+            state: RxState::Idle,
+            last_bit: true,
+            count: 0.0,
+            bit_num: 0,
+            code: 0,
+            figs: false,
+            text: String::new(),
+        }
+    }
+
+    // Set the audio frequency midway between mark and space
+    pub fn set_center(&mut self, center: f32) {
+        self.dc.set_center(center);
+    }
+
+    pub fn center(&self) -> f32 {
+        return self.dc.center();
+    }
+
+    // On LSB the mark tone is the lower audio frequency
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+    }
+
+    // Process a block of audio samples.
+    // Returns any text decoded.
+    pub fn process(&mut self, samples: &[f32]) -> String {
+        self.baseband.clear();
+        self.dc.process(samples, &mut self.baseband);
+        for i in 0..self.baseband.len() {
+            let z = self.baseband[i];
+            self.process_baseband(z);
+        }
+        return std::mem::take(&mut self.text);
+    }
+
+    fn process_baseband(&mut self, z: (f32, f32)) {
+        // Frequency discriminator, phase change between samples
+        let re = z.0 * self.last.0 + z.1 * self.last.1;
+        let im = z.1 * self.last.0 - z.0 * self.last.1;
+        self.last = z;
+        let mut f = im.atan2(re) / PI;
+        if (z.0 * z.0 + z.1 * z.1).sqrt() < SQUELCH {
+            // Treat no signal as idle mark
+            f = if self.reverse {-1.0} else {1.0};
+        }
+        self.avg_sum += f - self.avg[self.avg_pos];
+        self.avg[self.avg_pos] = f;
+        self.avg_pos = (self.avg_pos + 1) % self.avg.len();
+        let mut bit = self.avg_sum > 0.0;
+        if self.reverse {
+            bit = !bit;
+        }
+        self.receive(bit);
+        self.last_bit = bit;
+    }
+
+    // Asynchronous receiver, 1 start bit, 5 data bits, 1.5 stop bits
+    fn receive(&mut self, bit: bool) {
+        match self.state {
+            RxState::Idle => {
+                if self.last_bit && !bit {
+                    // Leading edge of a start bit, move to its centre
+                    self.state = RxState::Start;
+                    self.count = self.spb / 2.0;
+                }
+            },
+            RxState::Start => {
+                self.count -= 1.0;
+                if self.count <= 0.0 {
+                    if bit {
+                        // Noise, not a start bit
+                        self.state = RxState::Idle;
+                    } else {
+                        self.state = RxState::Data;
+                        self.count += self.spb;
+                        self.bit_num = 0;
+                        self.code = 0;
+                    }
+                }
+            },
+            RxState::Data => {
+                self.count -= 1.0;
+                if self.count <= 0.0 {
+                    // LSB first
+                    if bit {
+                        self.code |= 1 << self.bit_num;
+                    }
+                    self.bit_num += 1;
+                    self.count += self.spb;
+                    if self.bit_num == 5 {
+                        self.state = RxState::Stop;
+                    }
+                }
+            },
+            RxState::Stop => {
+                self.count -= 1.0;
+                if self.count <= 0.0 {
+                    if bit {
+                        self.character(self.code);
+                    }
+                    // Either way look for the next start bit
+                    self.state = RxState::Idle;
+                }
+            },
+        }
+    }
+
+    // Convert a Baudot code to text
+    fn character(&mut self, code: u8) {
+        match code {
+            LTRS => self.figs = false,
+            FIGS => self.figs = true,
+            _ => {
+                let c;
+                if self.figs {
+                    c = FIGURES[code as usize];
+                } else {
+                    c = LETTERS[code as usize];
+                }
+                if c == b' ' {
+                    // Unshift on space
+                    self.figs = false;
+                }
+                if c != 0 && c != b'\r' && c != 0x07 {
+                    self.text.push(c as char);
+                }
+            },
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: u32 = 48000;
+
+    // Find the Baudot code and shift for a character
+    fn encode(c: u8) -> (u8, bool) {
+        for i in 0..32 {
+            if LETTERS[i] == c && i as u8 != LTRS && i as u8 != FIGS && c != 0 {
+                return (i as u8, false);
+            }
+        }
+        for i in 0..32 {
+            if FIGURES[i] == c && c != 0 {
+                return (i as u8, true);
+            }
+        }
+        panic!("no code for {}", c);
+    }
+
+    // Continuous phase FSK for the text
+    fn afsk(text: &str, baud: f32, center: f32, upper_mark: bool, noise: f32) -> Vec<f32> {
+        let mut bits: Vec<bool> = Vec::new();
+        let bit_len = SR as f32 / baud;
+        let push = |code: u8, bits: &mut Vec<bool>| {
+            bits.push(false);
+            for b in 0..5 {
+                bits.push(code & (1 << b) != 0);
+            }
+            // 1.5 stop bits are handled by the caller as whole bits here
+            bits.push(true);
+            bits.push(true);
+        };
+        // Idle mark then LTRS to sync
+        for _ in 0..20 {
+            bits.push(true);
+        }
+        push(LTRS, &mut bits);
+        let mut figs = false;
+        for c in text.bytes() {
+            let (code, f) = encode(c);
+            if c != b' ' && f != figs {
+                push(if f {FIGS} else {LTRS}, &mut bits);
+                figs = f;
+            }
+            if c == b' ' {
+                figs = false;
+            }
+            push(code, &mut bits);
+        }
+        for _ in 0..20 {
+            bits.push(true);
+        }
+
+        let mut out = Vec::new();
+        let mut phase: f32 = 0.0;
+        let mut state: u32 = 7;
+        let total = (bits.len() as f32 * bit_len) as usize;
+        for i in 0..total {
+            let bit = bits[(i as f32 / bit_len) as usize];
+            let mark = if upper_mark {center + SHIFT / 2.0} else {center - SHIFT / 2.0};
+            let space = if upper_mark {center - SHIFT / 2.0} else {center + SHIFT / 2.0};
+            let f = if bit {mark} else {space};
+            phase += 2.0 * PI * f / SR as f32;
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let n = (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+            out.push(phase.sin() * 0.3 + n * noise);
+        }
+        return out;
+    }
+
+    fn decode(d: &mut RttyDecoder, audio: &[f32]) -> String {
+        let mut text = String::new();
+        for block in audio.chunks(1024) {
+            text.push_str(&d.process(block));
+        }
+        return text;
+    }
+
+    #[test]
+    fn decodes_45_baud() {
+        let mut d = RttyDecoder::new(SR, BAUD_45, 2210.0);
+        let text = decode(&mut d, &afsk("CQ CQ DE G3UKB", BAUD_45, 2210.0, true, 0.0));
+        assert_eq!(text, "CQ CQ DE G3UKB");
+    }
+
+    #[test]
+    fn decodes_50_baud_with_figures() {
+        let mut d = RttyDecoder::new(SR, BAUD_50, 1500.0);
+        let text = decode(&mut d, &afsk("UR RST 599 599", BAUD_50, 1500.0, true, 0.0));
+        assert_eq!(text, "UR RST 599 599");
+    }
+
+    #[test]
+    fn decodes_reversed() {
+        let mut d = RttyDecoder::new(SR, BAUD_45, 1000.0);
+        d.set_reverse(true);
+        let text = decode(&mut d, &afsk("RYRYRY TEST", BAUD_45, 1000.0, false, 0.0));
+        assert_eq!(text, "RYRYRY TEST");
+    }
+
+    #[test]
+    fn decodes_in_noise() {
+        let mut d = RttyDecoder::new(SR, BAUD_45, 2210.0);
+        let text = decode(&mut d, &afsk("THE QUICK BROWN FOX", BAUD_45, 2210.0, true, 0.3));
+        assert_eq!(text, "THE QUICK BROWN FOX");
+    }
+
+    #[test]
+    fn ignores_off_frequency() {
+        // Nothing within the filter so nothing decoded
+        let mut d = RttyDecoder::new(SR, BAUD_45, 800.0);
+        let text = decode(&mut d, &afsk("RYRYRY", BAUD_45, 2210.0, true, 0.0));
+        assert_eq!(text, "");
+    }
+}
@@ -84,6 +84,32 @@ extern "C" {
 	fn SetRXAMode(ch_id: i32, mode: i32);
 	fn SetRXABandpassRun(ch_id: i32, run: i32);
 	fn SetRXABandpassFreqs(ch_id: i32, low: f64, high: f64);
+	fn SetRXAShiftRun(ch_id: i32, run: i32);
+	fn SetRXAShiftFreq(ch_id: i32, fshift: f64);
+
+	fn RXANBPAddNotch(ch_id: i32, notch: i32, fcenter: f64, fwidth: f64, active: i32) -> i32;
+	fn RXANBPDeleteNotch(ch_id: i32, notch: i32) -> i32;
+	fn RXANBPGetNumNotches(ch_id: i32, nnotches: *mut i32);
+	fn RXANBPSetNotchesRun(ch_id: i32, run: i32);
+	fn RXANBPSetTuneFrequency(ch_id: i32, tunefreq: f64);
+
+	fn SetTXAMode(ch_id: i32, mode: i32);
+	fn SetTXABandpassRun(ch_id: i32, run: i32);
+	fn SetTXABandpassFreqs(ch_id: i32, low: f64, high: f64);
+	fn SetTXAPanelRun(ch_id: i32, run: i32);
+	fn SetTXAPanelGain1(ch_id: i32, gain: f64);
+	fn SetTXAEQRun(ch_id: i32, run: i32);
+	fn SetTXAGrphEQ10(ch_id: i32, txeq: *mut i32);
+	fn SetTXALevelerSt(ch_id: i32, state: i32);
+	fn SetTXALevelerTop(ch_id: i32, maxgain: f64);
+	fn SetTXACompressorRun(ch_id: i32, run: i32);
+	fn SetTXACompressorGain(ch_id: i32, gain: f64);
+	fn SetTXACFCOMPRun(ch_id: i32, run: i32);
+	fn SetTXAosctrlRun(ch_id: i32, run: i32);
+	fn SetTXAALCMaxGain(ch_id: i32, maxgain: f64);
+	fn GetTXAMeter(ch_id: i32, mt: i32) -> f64;
+	fn SetTXACTCSSRun(ch_id: i32, run: i32);
+	fn SetTXACTCSSFreq(ch_id: i32, freq: f64);
 }
 
 // Run WDSP wisdom to optimise the FFT sizes
@@ -174,11 +200,16 @@ pub fn wdsp_exchange(ch_id: i32, in_buf: &mut [f64; (common_defs::DSP_BLK_SZ * 2
 }
 
 pub fn set_mode_filter(ch_id: i32, rx_id: i32) {
+	wdsp_set_mode_filter(ch_id, globals::get_mode(rx_id), globals::get_filter(rx_id));
+}
+
+// Set mode and filter on a channel that is not one of the main receivers
+pub fn wdsp_set_mode_filter(ch_id: i32, mode: u32, filter: u32) {
 	let mut low: i32 = 0;
 	let mut high: i32 = 0;
 	
-	let filter = globals::get_filter(rx_id) as i32;
-	let mode = globals::get_mode(rx_id) as i32;
+	let filter = filter as i32;
+	let mode = mode as i32;
 	let new_low;
 	let new_high;
 	match filter {
@@ -215,11 +246,129 @@ pub fn set_mode_filter(ch_id: i32, rx_id: i32) {
 	
 }
 
+// Shift the input so a signal at offset Hz from the centre is at baseband
+pub fn wdsp_set_shift(ch_id: i32, offset: f64) {
+	unsafe {
+		SetRXAShiftFreq(ch_id, -offset);
+		SetRXAShiftRun(ch_id, 1);
+	}
+}
+
+// Replace the notch database for a channel
+// Each notch is (centre, width) in Hz at an absolute RF frequency
+pub fn wdsp_set_notches(ch_id: i32, notches: &[(u32, u32)]) {
+	let mut num: i32 = 0;
+	unsafe {
+		RXANBPGetNumNotches(ch_id, &mut num);
+		for _ in 0..num {
+			RXANBPDeleteNotch(ch_id, 0);
+		}
+		for (i, (fcenter, fwidth)) in notches.iter().enumerate() {
+			RXANBPAddNotch(ch_id, i as i32, *fcenter as f64, *fwidth as f64, 1);
+		}
+		RXANBPSetNotchesRun(ch_id, (notches.len() > 0) as i32);
+	}
+}
+
+// The notch database uses this to position notches relative to the passband
+pub fn wdsp_set_notch_tune(ch_id: i32, freq: u32) {
+	unsafe {RXANBPSetTuneFrequency(ch_id, freq as f64)};
+}
+
 // Get metering data
 pub fn wdsp_get_rx_meter(ch_id: i32, mt: i32) -> f64 {
 	return unsafe{GetRXAMeter(ch_id, mt)};
 }
 
+//========================================
+// TX processing chain
+
+// Set the TX mode and the audio passband
+// low and high are positive and are moved to the correct side for the mode
+pub fn wdsp_set_tx_mode_filter(ch_id: i32, mode: u32, low: i32, high: i32) {
+	let mode = mode as i32;
+	let new_low;
+	let new_high;
+	if mode == 0 || mode == 3 || mode == 9 {
+		// Low sideband
+		new_low = high.neg();
+		new_high = low.neg();
+	} else if mode == 1 || mode == 4 || mode == 7 {
+		// High sideband
+		new_low = low;
+		new_high = high;
+	} else {
+		// Both sidebands
+		new_low = high.neg();
+		new_high = high;
+	}
+	unsafe {
+		SetTXAMode(ch_id, mode);
+		SetTXABandpassRun(ch_id, 1);
+		SetTXABandpassFreqs(ch_id, new_low as f64, new_high as f64);
+	}
+}
+
+// Mic gain in dB
+pub fn wdsp_set_mic_gain(ch_id: i32, gain_db: f32) {
+	unsafe {
+		SetTXAPanelRun(ch_id, 1);
+		SetTXAPanelGain1(ch_id, f64::powf(10.0, gain_db as f64 / 20.0));
+	}
+}
+
+// Graphic equaliser, preamp followed by the band gains in dB
+pub fn wdsp_set_tx_eq(ch_id: i32, run: bool, preamp: i32, bands: &[i32]) {
+	let mut txeq: [i32; (common_defs::TX_EQ_BANDS + 1) as usize] = [0; (common_defs::TX_EQ_BANDS + 1) as usize];
+	txeq[0] = preamp;
+	for (i, gain) in bands.iter().take(common_defs::TX_EQ_BANDS as usize).enumerate() {
+		txeq[i + 1] = *gain;
+	}
+	unsafe {
+		SetTXAGrphEQ10(ch_id, txeq.as_mut_ptr());
+		SetTXAEQRun(ch_id, run as i32);
+	}
+}
+
+// Leveler with its maximum gain in dB
+pub fn wdsp_set_leveler(ch_id: i32, run: bool, max_gain: f32) {
+	unsafe {
+		SetTXALevelerTop(ch_id, max_gain as f64);
+		SetTXALevelerSt(ch_id, run as i32);
+	}
+}
+
+// Speech compressor with its gain in dB and the optional multi-band CFC
+pub fn wdsp_set_compressor(ch_id: i32, run: bool, gain: f32, cfc: bool) {
+	unsafe {
+		SetTXACompressorGain(ch_id, gain as f64);
+		SetTXACompressorRun(ch_id, run as i32);
+		SetTXACFCOMPRun(ch_id, cfc as i32);
+	}
+}
+
+// Controlled envelope SSB, overshoot control after the compressor
+pub fn wdsp_set_cessb(ch_id: i32, run: bool) {
+	unsafe {SetTXAosctrlRun(ch_id, run as i32)};
+}
+
+// ALC maximum gain in dB, the ALC itself always runs
+pub fn wdsp_set_alc(ch_id: i32, max_gain: f32) {
+	unsafe {SetTXAALCMaxGain(ch_id, max_gain as f64)};
+}
+
+// CTCSS tone on FM transmit
+pub fn wdsp_set_ctcss(ch_id: i32, run: bool, freq: f32) {
+	unsafe {
+		SetTXACTCSSFreq(ch_id, freq as f64);
+		SetTXACTCSSRun(ch_id, run as i32);
+	}
+}
+
+pub fn wdsp_get_tx_meter(ch_id: i32, mt: i32) -> f64 {
+	return unsafe{GetTXAMeter(ch_id, mt)};
+}
+
 // Open WDSP display
 pub fn wdsp_open_disp(
 	disp_id: i32, fft_size: i32, win_type: i32, 
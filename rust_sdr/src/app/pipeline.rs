@@ -24,4 +24,9 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-pub mod pipeline;
\ No newline at end of file
+pub mod pipeline;
+pub mod combiner;
+pub mod vox;
+pub mod keyer;
+pub mod test_signal;
+pub mod tone_squelch;
\ No newline at end of file
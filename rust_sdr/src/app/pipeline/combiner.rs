@@ -0,0 +1,119 @@
+/*
+combiner.rs
+
+Module - combiner
+Diversity combiner for two receivers on the same frequency
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+//==================================================================================
+// Combiner state
+// The auxiliary receiver is scaled and phase shifted then added to the main receiver.
+// With the right gain and phase a noise source common to both antennas cancels.
+pub struct Combiner {
+    gain: f64,
+    phase: f64,
+    // Rotation for the current gain and phase
+    rot_i: f64,
+    rot_q: f64,
+}
+
+impl Combiner {
+    pub fn new() -> Combiner {
+        let mut c = Combiner {
+            gain: 0.0,
+            phase: 0.0,
+            rot_i: 0.0,
+            rot_q: 0.0,
+        };
+        c.set(1.0, 0.0);
+        return c;
+    }
+
+    // Gain as a linear ratio, phase in degrees
+    pub fn set(&mut self, gain: f64, phase: f64) {
+        if gain == self.gain && phase == self.phase {
+            return;
+        }
+        self.gain = gain;
+        self.phase = phase;
+        let rad = phase.to_radians();
+        self.rot_i = gain * rad.cos();
+        self.rot_q = gain * rad.sin();
+    }
+
+    // Combine interleaved I/Q, main = main + aux * gain * e^(j*phase)
+    pub fn combine(&self, main: &mut [f64], aux: &[f64]) {
+        let n = usize::min(main.len(), aux.len());
+        for i in (0..n - n % 2).step_by(2) {
+            let ai = aux[i];
+            let aq = aux[i + 1];
+            main[i] += ai * self.rot_i - aq * self.rot_q;
+            main[i + 1] += ai * self.rot_q + aq * self.rot_i;
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Interleaved I/Q tone with the given amplitude and phase
+    fn tone(amp: f64, phase: f64) -> Vec<f64> {
+        let mut v = Vec::new();
+        for n in 0..256 {
+            let a = 0.1 * n as f64 + phase.to_radians();
+            v.push(amp * a.cos());
+            v.push(amp * a.sin());
+        }
+        return v;
+    }
+
+    fn power(v: &[f64]) -> f64 {
+        return v.iter().map(|x| x * x).sum::<f64>() / v.len() as f64;
+    }
+
+    #[test]
+    fn nulls_common_signal() {
+        // Same source arriving at half the level and 60 degrees later on the second antenna
+        let mut main = tone(1.0, 0.0);
+        let aux = tone(0.5, 60.0);
+        let mut c = Combiner::new();
+        c.set(2.0, 120.0);
+        c.combine(&mut main, &aux);
+        assert!(power(&main) < 1e-20, "residual {}", power(&main));
+    }
+
+    #[test]
+    fn adds_in_phase() {
+        // Double the amplitude is four times the power
+        let mut main = tone(1.0, 0.0);
+        let aux = tone(1.0, 0.0);
+        let p = power(&main);
+        let mut c = Combiner::new();
+        c.set(1.0, 0.0);
+        c.combine(&mut main, &aux);
+        assert!((power(&main) - 4.0 * p).abs() < 1e-9);
+    }
+}
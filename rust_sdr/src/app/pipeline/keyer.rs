@@ -0,0 +1,316 @@
+/*
+keyer.rs
+
+Module - keyer
+Iambic CW keyer producing a shaped keying envelope
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f32::consts::PI;
+
+// Rise and fall time of the keying envelope
+const RISE_MS: u32 = 5;
+
+#[derive(PartialEq)]
+enum State {
+    Idle,
+    // Samples remaining and whether this is a dot
+    Mark(usize, bool),
+    Space(usize, bool),
+}
+
+//==================================================================================
+// Keyer state
+// Mode A sends the element opposite to the last while paddles are squeezed.
+// Mode B also remembers the opposite paddle being pressed during an element and
+// sends it after the element, giving the extra element when a squeeze is released.
+pub struct Keyer {
+    sample_rate: u32,
+    mode_b: bool,
+    dot_len: usize,
+    dash_len: usize,
+    space_len: usize,
+    state: State,
+    dot_mem: bool,
+    dash_mem: bool,
+    // Envelope position 0-1 and step per sample
+    ramp: f32,
+    ramp_step: f32,
+    // Semi-break-in
+    hang: usize,
+    since_key: usize,
+}
+
+impl Keyer {
+    pub fn new(sample_rate: u32) -> Keyer {
+        let mut k = Keyer {
+            sample_rate: sample_rate,
+            mode_b: false,
+            dot_len: 0,
+            dash_len: 0,
+            space_len: 0,
+            state: State::Idle,
+            dot_mem: false,
+            dash_mem: false,
+            ramp: 0.0,
+            ramp_step: 1000.0 / (sample_rate * RISE_MS) as f32,
+            hang: 0,
+            since_key: usize::MAX,
+        };
+        k.set(20, 50, false);
+        k.set_hang(300);
+        return k;
+    }
+
+    // Speed in WPM, weight 50 gives equal mark and space
+    pub fn set(&mut self, wpm: u32, weight: u32, mode_b: bool) {
+        // PARIS timing, a dot is 1.2/wpm seconds
+        let unit = (self.sample_rate as f32 * 1.2 / u32::max(wpm, 1) as f32) as usize;
+        let weight = u32::max(10, u32::min(90, weight)) as usize;
+        self.dot_len = unit * weight / 50;
+        self.dash_len = 3 * unit + self.dot_len - unit;
+        self.space_len = 2 * unit - self.dot_len;
+        self.mode_b = mode_b;
+    }
+
+    // Time the transmitter stays keyed after the last element
+    pub fn set_hang(&mut self, hang_ms: u32) {
+        self.hang = (self.sample_rate as usize * hang_ms as usize) / 1000;
+    }
+
+    // True while sending or within the hang time
+    pub fn keyed(&self) -> bool {
+        return self.ramp > 0.0 || self.since_key < self.hang;
+    }
+
+    // Run the keyer for a block with the paddle states and write the envelope to out
+    pub fn process(&mut self, dot: bool, dash: bool, out: &mut [f32]) {
+        for o in out.iter_mut() {
+            self.step(dot, dash);
+            let key = match self.state {
+                State::Mark(_, _) => true,
+                _ => false,
+            };
+            if key {
+                self.ramp = f32::min(1.0, self.ramp + self.ramp_step);
+            } else {
+                self.ramp = f32::max(0.0, self.ramp - self.ramp_step);
+            }
+            // Raised cosine edges
+            *o = 0.5 - 0.5 * (PI * self.ramp).cos();
+            if self.ramp > 0.0 {
+                self.since_key = 0;
+            } else if self.since_key < usize::MAX {
+                self.since_key += 1;
+            }
+        }
+    }
+
+    // Advance the state machine by one sample
+    fn step(&mut self, dot: bool, dash: bool) {
+        match self.state {
+            State::Idle => {
+                if dot {
+                    self.start(true);
+                } else if dash {
+                    self.start(false);
+                }
+            },
+            State::Mark(remaining, is_dot) => {
+                self.remember(dot, dash, is_dot);
+                if remaining <= 1 {
+                    self.state = State::Space(self.space_len, is_dot);
+                } else {
+                    self.state = State::Mark(remaining - 1, is_dot);
+                }
+            },
+            State::Space(remaining, last_dot) => {
+                self.remember(dot, dash, last_dot);
+                if remaining > 1 {
+                    self.state = State::Space(remaining - 1, last_dot);
+                    return;
+                }
+                // Alternate when squeezed, otherwise repeat the held paddle
+                if last_dot {
+                    if dash || self.dash_mem {
+                        self.start(false);
+                    } else if dot {
+                        self.start(true);
+                    } else {
+                        self.state = State::Idle;
+                    }
+                } else {
+                    if dot || self.dot_mem {
+                        self.start(true);
+                    } else if dash {
+                        self.start(false);
+                    } else {
+                        self.state = State::Idle;
+                    }
+                }
+            },
+        }
+    }
+
+    // Mode B memory of the opposite paddle
+    fn remember(&mut self, dot: bool, dash: bool, is_dot: bool) {
+        if !self.mode_b {
+            return;
+        }
+        if is_dot && dash {
+            self.dash_mem = true;
+        }
+        if !is_dot && dot {
+            self.dot_mem = true;
+        }
+    }
+
+    fn start(&mut self, is_dot: bool) {
+        self.dot_mem = false;
+        self.dash_mem = false;
+        if is_dot {
+            self.state = State::Mark(self.dot_len, true);
+        } else {
+            self.state = State::Mark(self.dash_len, false);
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    // Lengths in samples of each mark in the envelope
+    fn marks(env: &[f32]) -> Vec<usize> {
+        let mut v = Vec::new();
+        let mut len = 0;
+        for e in env.iter() {
+            if *e > 0.5 {
+                len += 1;
+            } else if len > 0 {
+                v.push(len);
+                len = 0;
+            }
+        }
+        if len > 0 {
+            v.push(len);
+        }
+        return v;
+    }
+
+    // Press the paddles briefly then release and run on
+    fn tap(k: &mut Keyer, dot: bool, dash: bool) -> Vec<f32> {
+        let mut env = vec![0.0; 10];
+        k.process(dot, dash, &mut env);
+        let mut rest = vec![0.0; RATE as usize];
+        k.process(false, false, &mut rest);
+        env.extend(rest);
+        return env;
+    }
+
+    fn near(a: usize, b: usize) -> bool {
+        return (a as i64 - b as i64).abs() < (RATE / 500) as i64;
+    }
+
+    #[test]
+    fn dot_and_dash_timing() {
+        // 20 WPM is a 60ms dot
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, false);
+        let m = marks(&tap(&mut k, true, false));
+        assert_eq!(m.len(), 1);
+        assert!(near(m[0], 2880), "dot {}", m[0]);
+        let m = marks(&tap(&mut k, false, true));
+        assert_eq!(m.len(), 1);
+        assert!(near(m[0], 3 * 2880), "dash {}", m[0]);
+    }
+
+    #[test]
+    fn weight_lengthens_mark() {
+        let mut k = Keyer::new(RATE);
+        k.set(20, 60, false);
+        let m = marks(&tap(&mut k, true, false));
+        assert!(near(m[0], 2880 * 60 / 50), "dot {}", m[0]);
+    }
+
+    #[test]
+    fn squeeze_alternates() {
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, false);
+        let mut env = vec![0.0; RATE as usize];
+        k.process(true, true, &mut env);
+        let m = marks(&env);
+        assert!(m.len() >= 3);
+        assert!(near(m[0], 2880));
+        assert!(near(m[1], 3 * 2880));
+        assert!(near(m[2], 2880));
+    }
+
+    #[test]
+    fn mode_b_extra_element() {
+        // A short squeeze sends a dot in mode A and dot dash in mode B
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, false);
+        assert_eq!(marks(&tap(&mut k, true, true)).len(), 1);
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, true);
+        let m = marks(&tap(&mut k, true, true));
+        assert_eq!(m.len(), 2);
+        assert!(near(m[1], 3 * 2880));
+    }
+
+    #[test]
+    fn break_in_hang() {
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, false);
+        k.set_hang(100);
+        assert!(!k.keyed());
+        let mut env = vec![0.0; 100];
+        k.process(true, false, &mut env);
+        assert!(k.keyed());
+        // Dot, its ramp and the hang are over within 300ms
+        let mut env = vec![0.0; (RATE / 10) as usize];
+        k.process(false, false, &mut env);
+        assert!(k.keyed());
+        let mut env = vec![0.0; (RATE / 5) as usize];
+        k.process(false, false, &mut env);
+        assert!(!k.keyed());
+    }
+
+    #[test]
+    fn no_hang_keys_while_sending() {
+        let mut k = Keyer::new(RATE);
+        k.set(20, 50, false);
+        k.set_hang(0);
+        let mut env = vec![0.0; 100];
+        k.process(true, false, &mut env);
+        assert!(k.keyed());
+        // Dot and its ramp are over within 100ms
+        let mut env = vec![0.0; (RATE / 10) as usize];
+        k.process(false, false, &mut env);
+        assert!(!k.keyed());
+    }
+}
@@ -28,6 +28,7 @@ use std::thread;
 use std::time::Duration;
 use std::sync::{Arc, Mutex, Condvar};
 use std::io::{Read, Write};
+use std::collections::VecDeque;
 
 use crate::app::common::messages;
 use crate::app::common::globals;
@@ -35,6 +36,13 @@ use crate::app::common::common_defs;
 use crate::app::common::ringb;
 use crate::app::common::converters;
 use crate::app::dsp;
+use crate::app::decoders;
+use crate::app::pipeline::combiner;
+use crate::app::pipeline::vox;
+use crate::app::pipeline::keyer;
+use crate::app::pipeline::test_signal;
+use crate::app::pipeline::tone_squelch;
+use crate::app::common::common_defs::ModeId;
 
 enum ACTIONS {
     ActionNone,
@@ -46,19 +54,51 @@ enum ACTIONS {
 // Runtime object for thread
 pub struct PipelineData{
     receiver : crossbeam_channel::Receiver<messages::PipelineMsg>,
-    rb_iq : Arc<ringb::SyncByteRingBuf>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
     iq_cond : Arc<(Mutex<bool>, Condvar)>,
     rb_audio : Arc< ringb::SyncByteRingBuf>,
-    rb_local_audio : Arc<ringb::SyncByteRingBuf>,
-    iq_data : Vec<u8>,
+    rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    iq_data : Vec<Vec<u8>>,
+    mic_data : Vec<u8>,
+    mic_iq_data : [f64; (common_defs::MIC_BLK_SZ * 2) as usize],
+    tx_iq_data : [f64; (common_defs::MIC_BLK_SZ * 2) as usize],
+    tx_fifo : VecDeque<f64>,
+    tx_frame : Vec<f64>,
+    vox : vox::Vox,
+    rx_level : f32,
+    keyer : keyer::Keyer,
+    cw_env : Vec<f32>,
+    sidetone_fifo : VecDeque<f64>,
+    sidetone_phase : f64,
+    sig_gen : test_signal::SigGen,
+    tone_sql : Vec<tone_squelch::ToneSquelch>,
     dec_iq_data : [f64; (common_defs::DSP_BLK_SZ * 2) as usize],
     disp_iq_data : [f32; (common_defs::DSP_BLK_SZ * 2) as usize],
     proc_iq_data : [f64; (common_defs::DSP_BLK_SZ * 2) as usize],
+    div_iq_data : [f64; (common_defs::DSP_BLK_SZ * 2) as usize],
+    sub_iq_data : [f64; (common_defs::DSP_BLK_SZ * 2) as usize],
+    combiner : combiner::Combiner,
     output_frame : [u8; common_defs::DSP_BLK_SZ as usize * 8],
     audio_frame : [u8; common_defs::DSP_BLK_SZ as usize * 4],
     run : bool,
-    #[allow(dead_code)]
     num_rx : u32,
+    dec_sender : crossbeam_channel::Sender<messages::DecoderMsg>,
+    rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+    rec_audio : Vec<f32>,
+    bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+    web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+    rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+    cw_decoder : decoders::cw_decoder::CwDecoder,
+    cw_active : bool,
+    cw_wpm : u32,
+    rtty_decoder : decoders::rtty_decoder::RttyDecoder,
+    psk_decoder : decoders::psk_decoder::PskDecoder,
+    digi_active : bool,
+    dec_type : u32,
+    dec_offset : f32,
+    audio_spec : decoders::audio_spec::AudioSpec,
+    dec_audio : Vec<f32>,
 }
 
 // Implementation methods on UDPRData
@@ -66,8 +106,18 @@ impl PipelineData {
 	// Create a new instance and initialise the default arrays
     pub fn new (
         receiver : crossbeam_channel::Receiver<messages::PipelineMsg>, 
-        rb_iq : Arc<ringb::SyncByteRingBuf>, iq_cond : Arc<(Mutex<bool>, Condvar)>,
-        rb_audio :Arc<ringb::SyncByteRingBuf>, rb_local_audio :Arc<ringb::SyncByteRingBuf>) -> PipelineData {
+        rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>, iq_cond : Arc<(Mutex<bool>, Condvar)>,
+        rb_audio :Arc<ringb::SyncByteRingBuf>, rb_local_audio :Vec<Arc<ringb::SyncByteRingBuf>>,
+        rb_mic :Arc<ringb::SyncByteRingBuf>,
+        dec_sender : crossbeam_channel::Sender<messages::DecoderMsg>,
+        rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+        bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+        web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+        rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>) -> PipelineData {
+
+        // Sweep and noise cover the default TX passband
+        let mut sig_gen = test_signal::SigGen::new(common_defs::SMPLS_48K);
+        sig_gen.set_passband(common_defs::TX_LOW as u32, common_defs::TX_HIGH as u32);
 
 		PipelineData {
             receiver: receiver,
@@ -75,12 +125,37 @@ impl PipelineData {
             iq_cond: iq_cond,
             rb_audio: rb_audio,
             rb_local_audio: rb_local_audio,
-            // Read size from rb gives us 1024 samples interleaved
-            iq_data: vec![0; (common_defs::DSP_BLK_SZ * common_defs::BYTES_PER_SAMPLE) as usize],
+            rb_mic: rb_mic,
+            // Read size from rb gives us 1024 samples interleaved for each receiver
+            iq_data: vec![vec![0; (common_defs::DSP_BLK_SZ * common_defs::BYTES_PER_SAMPLE) as usize]; common_defs::MAX_RADIOS as usize],
+            // Mic is exchanged with the TX channel in blocks at 48K
+            mic_data: vec![0; (common_defs::MIC_BLK_SZ * common_defs::MIC_BYTES_PER_SAMPLE) as usize],
+            mic_iq_data : [0.0; (common_defs::MIC_BLK_SZ * 2) as usize],
+            tx_iq_data : [0.0; (common_defs::MIC_BLK_SZ * 2) as usize],
+            // TX IQ waiting to go out with the receiver audio
+            tx_fifo: VecDeque::with_capacity(common_defs::TX_FIFO_SZ),
+            tx_frame: Vec::with_capacity((common_defs::DSP_BLK_SZ * 2) as usize),
+            // VOX on the Mic with anti-VOX from the selected receiver audio level
+            vox: vox::Vox::new(common_defs::SMPLS_48K),
+            rx_level: -120.0,
+            // The software keyer replaces the Mic in CW
+            keyer: keyer::Keyer::new(common_defs::SMPLS_48K),
+            cw_env: vec![0.0; common_defs::MIC_BLK_SZ as usize],
+            sidetone_fifo: VecDeque::with_capacity(common_defs::TX_FIFO_SZ / 2),
+            sidetone_phase: 0.0,
+            // Test signals replace the Mic
+            sig_gen: sig_gen,
+            // FM tone squelch decodes from the IQ of each receiver
+            tone_sql: (0..common_defs::MAX_RADIOS).map(|_| tone_squelch::ToneSquelch::new(globals::get_smpl_rate())).collect(),
             // Exchange size with DSP is 1024 I and 1024 Q samples interleaved as f64
             dec_iq_data : [0.0; (common_defs::DSP_BLK_SZ * 2)as usize],
             disp_iq_data : [0.0; (common_defs::DSP_BLK_SZ * 2)as usize],
             proc_iq_data : [0.0; (common_defs::DSP_BLK_SZ * 2) as usize],
+            // RX2 data for diversity combining
+            div_iq_data : [0.0; (common_defs::DSP_BLK_SZ * 2) as usize],
+            // Audio from a sub-receiver
+            sub_iq_data : [0.0; (common_defs::DSP_BLK_SZ * 2) as usize],
+            combiner: combiner::Combiner::new(),
             // Output contiguous audio and TX IQ data
             output_frame : [0; (common_defs::DSP_BLK_SZ as usize * 8) as usize],
             // Local audio out
@@ -88,6 +163,26 @@ impl PipelineData {
             run: false,
             // Current number of receivers
             num_rx: globals::get_num_rx(),
+            // Text decoders run on the selected receiver audio
+            dec_sender: dec_sender,
+            rec_sender: rec_sender,
+            // Receiver audio to record, one receiver or the mix
+            rec_audio: Vec::with_capacity((common_defs::DSP_BLK_SZ * 2) as usize),
+            // Receiver audio for other applications
+            bridge_sender: bridge_sender,
+            web_sender: web_sender,
+            // IQ for rtl_tcp clients
+            rtl_sender: rtl_sender,
+            cw_decoder: decoders::cw_decoder::CwDecoder::new(common_defs::SMPLS_48K),
+            cw_active: false,
+            cw_wpm: 0,
+            rtty_decoder: decoders::rtty_decoder::RttyDecoder::new(common_defs::SMPLS_48K, decoders::rtty_decoder::BAUD_45, common_defs::DEC_OFFSET),
+            psk_decoder: decoders::psk_decoder::PskDecoder::new(common_defs::SMPLS_48K, common_defs::DEC_OFFSET),
+            digi_active: false,
+            dec_type: common_defs::DEC_RTTY_45,
+            dec_offset: common_defs::DEC_OFFSET,
+            audio_spec: decoders::audio_spec::AudioSpec::new(common_defs::SMPLS_48K),
+            dec_audio: Vec::with_capacity(common_defs::DSP_BLK_SZ as usize),
 		}
 	}
 
@@ -117,14 +212,27 @@ impl PipelineData {
         if *locked == true {
             // We were signaled so data available
             *locked = false;
-            
-            if self.rb_iq.read().available() >= (common_defs::DSP_BLK_SZ * common_defs::BYTES_PER_SAMPLE) as usize {
-                let read_result = self.rb_iq.read().read(&mut self.iq_data);
-                match read_result {
-                    Ok(_sz) => {
-                        action = ACTIONS::ActionData;
+
+            // The receivers are written in step so all should have a block available
+            let num_rx = self.check_num_rx();
+            let blk_sz = (common_defs::DSP_BLK_SZ * common_defs::BYTES_PER_SAMPLE) as usize;
+            let mut ready = true;
+            for rx in 0..num_rx {
+                if self.rb_iq[rx].read().available() < blk_sz {
+                    ready = false;
+                }
+            }
+            if ready {
+                action = ACTIONS::ActionData;
+                for rx in 0..num_rx {
+                    let read_result = self.rb_iq[rx].read().read(&mut self.iq_data[rx]);
+                    match read_result {
+                        Ok(_sz) => (),
+                        Err(e) => {
+                            println!("Read error on rb_iq for RX{} {:?}. Skipping cycle.", rx + 1, e);
+                            action = ACTIONS::ActionNone;
+                        }
                     }
-                    Err(e) => println!("Read error on rb_iq {:?}. Skipping cycle.", e),
                 }
             }
         } else {
@@ -136,6 +244,7 @@ impl PipelineData {
                         messages::PipelineMsg::Terminate => action = ACTIONS::ActionTerm,
                         messages::PipelineMsg::StartPipeline => self.run = true,
                         messages::PipelineMsg::StopPipeline => self.run = false,
+                        messages::PipelineMsg::RateChange(rate) => self.change_rate(rate),
                     };
                 },
                 // Do nothing if there are no message matches
@@ -145,30 +254,263 @@ impl PipelineData {
         return action;
     }
 
+    // Return the number of active receivers.
+    // If this has changed discard any data buffered for the old configuration.
+    fn check_num_rx(&mut self) -> usize {
+        let num_rx = u32::min(globals::get_num_rx(), common_defs::MAX_RADIOS);
+        if num_rx != self.num_rx {
+            self.num_rx = num_rx;
+            for rb in self.rb_iq.iter() {
+                rb.reset(rb.capacity());
+            }
+            for rb in self.rb_local_audio.iter() {
+                rb.reset(rb.capacity());
+            }
+        }
+        return self.num_rx as usize;
+    }
+
+    // Reconfigure for a new sample rate.
+    // The hardware is stopped before this is requested so no new data is arriving.
+    fn change_rate(&mut self, rate: u32) {
+        println!("Pipeline changing sample rate to {}", rate);
+        // Stop the DSP channels, draining any pending output
+        for ch in 0..common_defs::MAX_RADIOS as i32 {
+            dsp::dsp_interface::wdsp_set_ch_state(ch, 0, 1);
+        }
+
+        // Discard anything buffered at the old rate and size the IQ buffers for the new rate
+        for rb in self.rb_iq.iter() {
+            rb.reset(common_defs::iq_ring_capacity(1, rate));
+        }
+        self.rb_audio.reset(self.rb_audio.capacity());
+        for rb in self.rb_local_audio.iter() {
+            rb.reset(rb.capacity());
+        }
+        self.rb_mic.reset(self.rb_mic.capacity());
+        self.tx_fifo.clear();
+        // A recording cannot change rate part way through
+        if globals::get_iq_recording() {
+            self.rec_sender.send(messages::RecorderMsg::StopIq).unwrap();
+        }
+        for sql in self.tone_sql.iter_mut() {
+            sql.set_rate(rate);
+        }
+
+        // Reconfigure the DSP channels and restart
+        for ch in 0..common_defs::MAX_RADIOS as i32 {
+            dsp::dsp_interface::wdsp_set_input_rate(ch, rate as i32);
+            dsp::dsp_interface::wdsp_set_dsp_rate(ch, rate as i32);
+            dsp::dsp_interface::wdsp_set_ch_state(ch, 1, 0);
+        }
+        // Sub-receivers always process at 48K, only restart those in use
+        for id in 0..common_defs::MAX_SUB_RX {
+            let ch = (common_defs::SUB_RX_CH + id) as i32;
+            dsp::dsp_interface::wdsp_set_ch_state(ch, 0, 1);
+            dsp::dsp_interface::wdsp_set_input_rate(ch, rate as i32);
+            if globals::get_sub_enabled(id) {
+                dsp::dsp_interface::wdsp_set_ch_state(ch, 1, 0);
+            }
+        }
+
+        // Signal the UI it can restart the hardware.
+        // The display analyzer is read by the UI so it reconfigures that for the new rate.
+        globals::set_rate_change_pending(false);
+    }
+
     // Run the pipeline sequence
     fn sequence(&mut self) {
+        // Each receiver has its own DSP channel, channel = RX - 1
+        // The selected receiver drives the display and the hardware audio
+        // All receivers are sent to the local audio mixer
+        // If the selected receiver is not active fall back to the highest active receiver
+        let sel_rx = usize::min(globals::get_sel_rx() as usize, self.num_rx as usize);
+        // The TX IQ goes out with the selected receiver audio
+        self.sequence_tx();
+        self.rec_audio.clear();
+        for rx in 0..self.num_rx as usize {
+            self.sequence_rx(rx, rx + 1 == sel_rx);
+        }
+        if self.rec_audio.len() > 0 {
+            let audio = self.rec_audio.clone();
+            self.rec_sender.send(messages::RecorderMsg::AudioData(audio)).unwrap();
+        }
+    }
+
+    // Run the TX chain on any complete Mic blocks
+    fn sequence_tx(&mut self) {
+        loop {
+            {
+                let mut rb = self.rb_mic.read();
+                if rb.available() < self.mic_data.len() {
+                    return;
+                }
+                match rb.read(&mut self.mic_data) {
+                    Ok(_sz) => (),
+                    Err(e) => {
+                        println!("Read error on rb_mic {:?}. Skipping cycle.", e);
+                        return;
+                    }
+                }
+            }
+            converters::i16be_to_f64le(&self.mic_data, &mut self.mic_iq_data);
+            self.vox_update();
+            let error;
+            if self.test_signal() {
+                globals::set_cw_mox(false);
+                error = 0;
+            } else if self.cw_keying() {
+                globals::set_test_mox(false);
+                self.key_cw();
+                error = 0;
+            } else {
+                globals::set_cw_mox(false);
+                globals::set_test_mox(false);
+                error = dsp::dsp_interface::wdsp_exchange(common_defs::TX_CH as i32, &mut self.mic_iq_data, &mut self.tx_iq_data);
+            }
+            if error != 0 {
+                println!("DSP returned an error on TX, starvation!");
+                continue;
+            }
+            // Drop the oldest if the audio is not taking it
+            if self.tx_fifo.len() + self.tx_iq_data.len() > common_defs::TX_FIFO_SZ {
+                let excess = self.tx_fifo.len() + self.tx_iq_data.len() - common_defs::TX_FIFO_SZ;
+                self.tx_fifo.drain(..usize::min(excess, self.tx_fifo.len()));
+            }
+            self.tx_fifo.extend(self.tx_iq_data.iter());
+        }
+    }
+
+    // Generate any test signal for one block, true while it is transmitting
+    fn test_signal(&mut self) -> bool {
+        let mode = test_signal::TestMode::from_u32(globals::get_test_mode());
+        self.sig_gen.set_mode(mode);
+        self.sig_gen.set_level(globals::get_test_level(), globals::get_test_max_level());
+        self.sig_gen.set_tones(globals::get_test_tone1(), globals::get_test_tone2());
+        self.sig_gen.set_timeout(globals::get_test_timeout());
+        let rx_mode = globals::get_mode(globals::get_sel_rx() as i32);
+        self.sig_gen.set_lsb(rx_mode == ModeId::Lsb as u32 || rx_mode == ModeId::CwL as u32 || rx_mode == ModeId::DigL as u32);
+        if !self.sig_gen.running() {
+            if mode != test_signal::TestMode::Off {
+                // Timed out, tell the UI
+                println!("Test signal timed out");
+                globals::set_test_mode(test_signal::TestMode::Off as u32);
+            }
+            return false;
+        }
+        self.sig_gen.process(&mut self.tx_iq_data);
+        globals::set_test_mox(true);
+        return true;
+    }
+
+    // The software keyer is used in the CW modes
+    fn cw_keying(&self) -> bool {
+        let mode = globals::get_mode(globals::get_sel_rx() as i32);
+        return globals::get_cw_keyer() && (mode == ModeId::CwL as u32 || mode == ModeId::CwU as u32);
+    }
+
+    // Run the keyer for one block from the hardware or keyboard paddles.
+    // The carrier is at the TX frequency and the sidetone goes to the audio.
+    fn key_cw(&mut self) {
+        let dot = globals::get_dot() || globals::get_key_dot();
+        let dash = globals::get_dash() || globals::get_key_dash();
+        self.keyer.set(globals::get_keyer_wpm(), globals::get_keyer_weight(), globals::get_keyer_mode_b());
+        self.keyer.set_hang(globals::get_cw_hang());
+        self.keyer.process(dot, dash, &mut self.cw_env);
+
+        let vol = globals::get_sidetone_vol() as f64;
+        let step = 2.0 * std::f64::consts::PI * globals::get_sidetone_freq() as f64 / common_defs::SMPLS_48K as f64;
+        if self.sidetone_fifo.len() + self.cw_env.len() > self.sidetone_fifo.capacity() {
+            // Audio is not taking it
+            self.sidetone_fifo.clear();
+        }
+        for i in 0..self.cw_env.len() {
+            let env = self.cw_env[i] as f64;
+            self.tx_iq_data[2 * i] = env * common_defs::CW_LEVEL;
+            self.tx_iq_data[2 * i + 1] = 0.0;
+            self.sidetone_phase = (self.sidetone_phase + step) % (2.0 * std::f64::consts::PI);
+            self.sidetone_fifo.push_back(env * self.sidetone_phase.sin() * vol);
+        }
+        globals::set_cw_mox(self.keyer.keyed());
+    }
+
+    // Semi-break-in mutes the receiver while keyed, the sidetone is added to the audio
+    fn mix_sidetone(&mut self, proc_iq_sz: usize) {
+        let mute = globals::get_cw_mox();
+        if !mute && self.sidetone_fifo.len() == 0 {
+            return;
+        }
+        // Audio is interleaved L/R, it is already at the output level so the sum is clamped again
+        for i in (0..proc_iq_sz - proc_iq_sz % 2).step_by(2) {
+            if mute {
+                self.proc_iq_data[i] = 0.0;
+                self.proc_iq_data[i + 1] = 0.0;
+            }
+            match self.sidetone_fifo.pop_front() {
+                Some(s) => {
+                    self.proc_iq_data[i] = f64::max(-1.0, f64::min(1.0, self.proc_iq_data[i] + s));
+                    self.proc_iq_data[i + 1] = f64::max(-1.0, f64::min(1.0, self.proc_iq_data[i + 1] + s));
+                },
+                None => (),
+            }
+        }
+    }
+
+    // Run VOX on the latest Mic block
+    fn vox_update(&mut self) {
+        let mic_db = vox::peak_db(&self.mic_iq_data, 2);
+        globals::set_mic_level(mic_db);
+        globals::set_rx_level(self.rx_level);
+        if !globals::get_vox() || globals::get_ptt() {
+            // PTT takes priority and VOX must start afresh when it is released
+            self.vox.reset();
+        } else {
+            self.vox.set(globals::get_vox_threshold(), globals::get_vox_attack(), globals::get_vox_hang());
+            self.vox.set_anti_vox(globals::get_anti_vox(), globals::get_anti_vox_gain());
+            self.vox.process(mic_db, self.rx_level, common_defs::MIC_BLK_SZ as usize);
+        }
+        globals::set_vox_active(self.vox.active());
+    }
+
+    // Run the sequence for one receiver
+    fn sequence_rx(&mut self, rx: usize, selected: bool) {
         // We just exchange for now
         // Convert and scale input to output data.
-        converters::i8be_to_f64le(&self.iq_data, &mut self.dec_iq_data);
+        converters::i8be_to_f64le(&self.iq_data[rx], &mut self.dec_iq_data);
+        if selected && globals::get_iq_recording() {
+            // Raw IQ before any combining
+            let iq: Vec<f32> = self.dec_iq_data.iter().map(|x| *x as f32).collect();
+            self.rec_sender.send(messages::RecorderMsg::IqData(iq)).unwrap();
+        }
+        if globals::get_rtl_tcp_rx() == rx as u32 + 1 {
+            let iq: Vec<f32> = self.dec_iq_data.iter().map(|x| *x as f32).collect();
+            // The servers close before the pipeline, a closed server just misses the IQ
+            let _ = self.rtl_sender.send(messages::RtlTcpMsg::Iq(iq));
+        }
+        if rx == 0 && self.num_rx >= 2 && globals::get_diversity() {
+            // Combine RX2 into RX1 before the DSP
+            converters::i8be_to_f64le(&self.iq_data[1], &mut self.div_iq_data);
+            self.combiner.set(globals::get_div_gain() as f64, globals::get_div_phase() as f64);
+            self.combiner.combine(&mut self.dec_iq_data, &self.div_iq_data);
+        }
         let error: i32;
         // At 48K : 1024 in 1024 out
         // At 96K : 1024 in 512 out
-        // At 102K : 1024 in 256 out
-        let mut proc_iq_sz = self.proc_iq_data.len();
-        let mut output_sz = self.output_frame.len();
-        let mut audio_sz = self.audio_frame.len();
-        if globals::get_smpl_rate() == common_defs::SMPLS_96K {
-            proc_iq_sz = proc_iq_sz/2;
-            output_sz = output_sz/2;
-            audio_sz = audio_sz/2;
-        } else if globals::get_smpl_rate() == common_defs::SMPLS_192K {
-            proc_iq_sz = proc_iq_sz/4;
-            output_sz = output_sz/4;
-            audio_sz = audio_sz/4;
-        }
+        // At 192K : 1024 in 256 out
+        // At 384K : 1024 in 128 out
+        let decim = common_defs::rate_decimation(globals::get_smpl_rate());
+        let proc_iq_sz = self.proc_iq_data.len()/decim;
+        let output_sz = self.output_frame.len()/decim;
+        let audio_sz = self.audio_frame.len()/decim;
         
-        //error = dsp::dsp_interface::wdsp_exchange(globals::get_sel_rx() as i32 -1, &mut self.dec_iq_data,  &mut self.proc_iq_data);
-        error = dsp::dsp_interface::wdsp_exchange(0, &mut self.dec_iq_data,  &mut self.proc_iq_data);
+        error = dsp::dsp_interface::wdsp_exchange(rx as i32, &mut self.dec_iq_data,  &mut self.proc_iq_data);
+        if error == 0 {
+            self.tone_squelch(rx, proc_iq_sz);
+        }
+        if selected && error == 0 {
+            // Sub-receivers share the passband of the selected receiver
+            self.mix_sub_rx(proc_iq_sz);
+        }
         for i in 0..proc_iq_sz {
             self.proc_iq_data[i] = self.proc_iq_data[i] * 0.2;
             if self.proc_iq_data[i]  > 1.0 {
@@ -179,43 +521,59 @@ impl PipelineData {
             }
         }
 
-        // Pass data to spectrum
-        for i in 0..self.dec_iq_data.len() {
-            self.disp_iq_data[i] = self.dec_iq_data[i] as f32;
+        if selected {
+            self.mix_sidetone(proc_iq_sz);
+            // Speaker level for anti-VOX, audio is interleaved L/R
+            self.rx_level = vox::peak_db(&self.proc_iq_data[..proc_iq_sz], 2);
+            // Pass data to spectrum
+            for i in 0..self.dec_iq_data.len() {
+                self.disp_iq_data[i] = self.dec_iq_data[i] as f32;
+            }
+            dsp::dsp_interface::wdsp_write_spec_data(0, &mut self.disp_iq_data);
         }
-        //dsp::dsp_interface::wdsp_write_spec_data(globals::get_sel_rx() as i32 -1, &mut self.disp_iq_data);
-        dsp::dsp_interface::wdsp_write_spec_data(0, &mut self.disp_iq_data);
         
         // Process IQ data
         if error == 0 {
             // We have output data from the DSP
-            // Encode the data into a form suitable for the hardware
-            // Convert and scale input to output data.
-            converters::f64le_to_i8be(output_sz, &self.proc_iq_data, &mut self.output_frame);
-            // Copy data to the output ring buffer
-            let mut v_output_frame: Vec<u8> = self.output_frame.to_vec();
-            v_output_frame.resize(output_sz, 0);
-            let r = self.rb_audio.write().write(&v_output_frame);
-            match r {
-                Err(_e) => {
-                    // UDP writer not ready yet. Try next time.
-                    //println!("Write error on rb_audio, skipping block {:?}", e);
-                }
-                Ok(_sz) => {
-                    // We could signal data available but may not be necessary
-                    // At the moment the writer thread just takes data when available
+            if selected {
+                // Encode the data into a form suitable for the hardware
+                // Convert and scale input to output data.
+                // One TX IQ sample goes with each audio sample
+                let tx_sz = usize::min(output_sz / 8 * 2, self.tx_fifo.len());
+                self.tx_frame.clear();
+                self.tx_frame.extend(self.tx_fifo.drain(..tx_sz));
+                converters::f64le_to_i8be(output_sz, &self.proc_iq_data, &self.tx_frame, &mut self.output_frame);
+                // Copy data to the output ring buffer
+                let mut v_output_frame: Vec<u8> = self.output_frame.to_vec();
+                v_output_frame.resize(output_sz, 0);
+                let r = self.rb_audio.write().write(&v_output_frame);
+                match r {
+                    Err(_e) => {
+                        // UDP writer not ready yet. Try next time.
+                        //println!("Write error on rb_audio, skipping block {:?}", e);
+                    }
+                    Ok(_sz) => {
+                        // We could signal data available but may not be necessary
+                        // At the moment the writer thread just takes data when available
+                    }
                 }
             }
+            if selected {
+                // Run any decoder for the current mode
+                self.decode(rx + 1, proc_iq_sz);
+            }
+            self.record_audio(rx, proc_iq_sz);
+            self.remote_audio(rx, proc_iq_sz);
             // Now encode and copy data for local audio output
             // Convert and scale input to output data.
             converters::f64le_to_i8le(audio_sz, &self.proc_iq_data, &mut self.audio_frame);
-            // Copy data to the local audio ring buffer 
+            // Copy data to the local audio ring buffer for this receiver
             let mut v_audio_frame = self.audio_frame.to_vec();
             v_audio_frame.resize(audio_sz, 0);
-            let r = self.rb_local_audio.write().write(&v_audio_frame);
+            let r = self.rb_local_audio[rx].write().write(&v_audio_frame);
             match r {
                 Err(_e) => {
-                    // Audio system not up yet. Try next time.
+                    // Audio system not up yet or this receiver is not routed. Try next time.
                     //println!("Write error on rb_local_audio, skipping block {:?}", e);
                 }
                 Ok(_sz) => {
@@ -224,7 +582,167 @@ impl PipelineData {
                 }
             }
         } else {
-            println!("DSP returned an error, starvation!");
+            println!("DSP returned an error on RX{}, starvation!", rx + 1);
+        }
+    }
+
+    // Add this receiver to the audio being recorded
+    fn record_audio(&mut self, rx: usize, proc_iq_sz: usize) {
+        if !globals::get_audio_recording() {
+            return;
+        }
+        let src = globals::get_audio_rec_src() as usize;
+        if src != 0 && src != rx + 1 {
+            return;
+        }
+        if self.rec_audio.len() < proc_iq_sz {
+            self.rec_audio.resize(proc_iq_sz, 0.0);
+        }
+        for i in 0..proc_iq_sz {
+            self.rec_audio[i] += self.proc_iq_data[i] as f32;
+        }
+    }
+
+    // Send this receiver as mono to the audio bridge and web audio clients
+    fn remote_audio(&mut self, rx: usize, proc_iq_sz: usize) {
+        let bridge = globals::get_audio_bridge();
+        let web = globals::get_web_audio();
+        if !bridge && !web {
+            return;
+        }
+        // Audio is interleaved L/R
+        let audio: Vec<f32> = self.proc_iq_data[..proc_iq_sz - proc_iq_sz % 2]
+            .chunks(2)
+            .map(|lr| ((lr[0] + lr[1]) * 0.5) as f32)
+            .collect();
+        // As for the IQ a closed server just misses the audio
+        if web {
+            let _ = self.web_sender.send(messages::WebMsg::RxAudio(rx as u32 + 1, audio.clone()));
+        }
+        if bridge {
+            let _ = self.bridge_sender.send(messages::BridgeMsg::RxAudio(rx as u32 + 1, audio));
+        }
+    }
+
+    // Mute the receiver audio in FM unless the selected CTCSS tone or DCS code is present
+    fn tone_squelch(&mut self, rx: usize, proc_iq_sz: usize) {
+        let sql = &mut self.tone_sql[rx];
+        if globals::get_mode(rx as i32 + 1) != ModeId::Fm as u32 {
+            sql.set_type(tone_squelch::SqlType::Off);
+            return;
+        }
+        sql.set_type(tone_squelch::SqlType::from_u32(globals::get_tone_sql()));
+        sql.set_ctcss(globals::get_sql_ctcss());
+        sql.set_dcs(globals::get_sql_dcs());
+        if !sql.process(&self.dec_iq_data) {
+            for i in 0..proc_iq_sz {
+                self.proc_iq_data[i] = 0.0;
+            }
+        }
+    }
+
+    // Run each enabled sub-receiver on the input and mix its audio into the output.
+    // The volume is relative to the receiver as the mix goes through the same audio route.
+    fn mix_sub_rx(&mut self, proc_iq_sz: usize) {
+        for id in 0..common_defs::MAX_SUB_RX {
+            if !globals::get_sub_enabled(id) {
+                continue;
+            }
+            let ch = (common_defs::SUB_RX_CH + id) as i32;
+            // The channel may modify the input so give it a copy
+            self.div_iq_data = self.dec_iq_data;
+            let error = dsp::dsp_interface::wdsp_exchange(ch, &mut self.div_iq_data, &mut self.sub_iq_data);
+            if error != 0 || globals::get_sub_mute(id) {
+                continue;
+            }
+            // Audio is interleaved L/R
+            let vol = globals::get_sub_vol(id);
+            let pan = globals::get_sub_pan(id);
+            let left = (vol * f32::min(1.0, 1.0 - pan)) as f64;
+            let right = (vol * f32::min(1.0, 1.0 + pan)) as f64;
+            for i in (0..proc_iq_sz - proc_iq_sz % 2).step_by(2) {
+                self.proc_iq_data[i] += self.sub_iq_data[i] * left;
+                self.proc_iq_data[i + 1] += self.sub_iq_data[i + 1] * right;
+            }
+        }
+    }
+
+    // Run the text decoder on the demodulated audio if in a decoder mode
+    fn decode(&mut self, rx: usize, proc_iq_sz: usize) {
+        let mode = globals::get_mode(rx as i32);
+        let cw = mode == ModeId::CwL as u32 || mode == ModeId::CwU as u32;
+        let digi = mode == ModeId::DigU as u32 || mode == ModeId::DigL as u32;
+        let dec_type = globals::get_dec_type();
+        if cw != self.cw_active {
+            // Start afresh when switched in
+            self.cw_active = cw;
+            self.cw_decoder.reset();
+            self.cw_wpm = 0;
+        }
+        if digi != self.digi_active || dec_type != self.dec_type {
+            // Start afresh when switched in or the decoder type changes
+            self.digi_active = digi;
+            self.dec_type = dec_type;
+            self.dec_offset = globals::get_dec_offset();
+            let mut baud = decoders::rtty_decoder::BAUD_45;
+            if dec_type == common_defs::DEC_RTTY_50 {
+                baud = decoders::rtty_decoder::BAUD_50;
+            }
+            self.rtty_decoder = decoders::rtty_decoder::RttyDecoder::new(common_defs::SMPLS_48K, baud, self.dec_offset);
+            self.psk_decoder = decoders::psk_decoder::PskDecoder::new(common_defs::SMPLS_48K, self.dec_offset);
+        }
+        if !cw && !digi {
+            return;
+        }
+        // Audio is interleaved L/R, both the same so take the left channel
+        self.dec_audio.clear();
+        for i in (0..proc_iq_sz).step_by(2) {
+            self.dec_audio.push(self.proc_iq_data[i] as f32);
+        }
+        // Audio spectrum for tuning
+        match self.audio_spec.process(&self.dec_audio) {
+            Some(line) => self.dec_sender.send(messages::DecoderMsg::Spectrum(line)).unwrap(),
+            None => (),
+        }
+        if digi {
+            self.decode_digi(mode == ModeId::DigL as u32);
+            return;
+        }
+        let text = self.cw_decoder.process(&self.dec_audio);
+        if text.len() > 0 {
+            self.dec_sender.send(messages::DecoderMsg::Text(text)).unwrap();
+        }
+        let wpm = self.cw_decoder.wpm();
+        if wpm != self.cw_wpm {
+            self.cw_wpm = wpm;
+            self.dec_sender.send(messages::DecoderMsg::Wpm(wpm)).unwrap();
+        }
+    }
+
+    // Run the selected digital mode decoder
+    fn decode_digi(&mut self, lsb: bool) {
+        // Retune if the offset was changed from the waterfall
+        let offset = globals::get_dec_offset();
+        if offset != self.dec_offset {
+            self.dec_offset = offset;
+            self.rtty_decoder.set_center(offset);
+            self.psk_decoder.set_center(offset);
+        }
+        let text;
+        if self.dec_type == common_defs::DEC_PSK31 {
+            text = self.psk_decoder.process(&self.dec_audio);
+            // Let the display follow the AFC unless retuned meanwhile
+            if globals::get_dec_offset() == offset {
+                self.dec_offset = self.psk_decoder.center();
+                globals::set_dec_offset(self.dec_offset);
+            }
+        } else {
+            // Mark and space are inverted on LSB
+            self.rtty_decoder.set_reverse(lsb);
+            text = self.rtty_decoder.process(&self.dec_audio);
+        }
+        if text.len() > 0 {
+            self.dec_sender.send(messages::DecoderMsg::Text(text)).unwrap();
         }
     }
 }
@@ -233,26 +751,38 @@ impl PipelineData {
 // Thread startup
 pub fn pipeline_start(
     receiver : crossbeam_channel::Receiver<messages::PipelineMsg>, 
-    rb_iq : Arc<ringb::SyncByteRingBuf>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
     iq_cond : Arc<(Mutex<bool>, Condvar)>,
     rb_audio : Arc<ringb::SyncByteRingBuf>,
-    rb_local_audio : Arc<ringb::SyncByteRingBuf>) -> thread::JoinHandle<()> {
+    rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    dec_sender : crossbeam_channel::Sender<messages::DecoderMsg>,
+    rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+    bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+    web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+    rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>) -> thread::JoinHandle<()> {
     let join_handle = thread::spawn(  move || {
-        pipeline_run(receiver, rb_iq, iq_cond, rb_audio, rb_local_audio);
+        pipeline_run(receiver, rb_iq, iq_cond, rb_audio, rb_local_audio, rb_mic, dec_sender, rec_sender, bridge_sender, web_sender, rtl_sender);
     });
     return join_handle;
 }
 
 fn pipeline_run(
         receiver : crossbeam_channel::Receiver<messages::PipelineMsg>, 
-        rb_iq : Arc<ringb::SyncByteRingBuf>, 
+        rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>, 
         iq_cond : Arc<(Mutex<bool>, Condvar)>, 
         rb_audio : Arc<ringb::SyncByteRingBuf>,
-        rb_local_audio : Arc<ringb::SyncByteRingBuf>){
+        rb_local_audio : Vec<Arc<ringb::SyncByteRingBuf>>,
+        rb_mic : Arc<ringb::SyncByteRingBuf>,
+        dec_sender : crossbeam_channel::Sender<messages::DecoderMsg>,
+        rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+        bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+        web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+        rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>){
     println!("Pipeline running");
 
     // Instantiate the runtime object
-    let mut i_pipeline = PipelineData::new(receiver,rb_iq, iq_cond, rb_audio, rb_local_audio);
+    let mut i_pipeline = PipelineData::new(receiver,rb_iq, iq_cond, rb_audio, rb_local_audio, rb_mic, dec_sender, rec_sender, bridge_sender, web_sender, rtl_sender);
 
     // Exits when the reader loop exits
     i_pipeline.pipeline_run();
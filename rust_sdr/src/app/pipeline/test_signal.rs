@@ -0,0 +1,292 @@
+/*
+test_signal.rs
+
+Module - test_signal
+Tune carrier, two-tone and other test signals for the TX IQ path
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f64::consts::PI;
+
+// Rise and fall time at the start and end of a test
+const RAMP_MS: u32 = 5;
+// Time for one sweep across the passband
+const SWEEP_MS: u32 = 2000;
+
+// Test signal types
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TestMode {
+    Off,
+    Tune,
+    TwoTone,
+    Sweep,
+    Noise,
+}
+
+impl TestMode {
+    pub fn from_u32(mode: u32) -> TestMode {
+        match mode {
+            1 => return TestMode::Tune,
+            2 => return TestMode::TwoTone,
+            3 => return TestMode::Sweep,
+            4 => return TestMode::Noise,
+            _ => return TestMode::Off,
+        }
+    }
+}
+
+//==================================================================================
+// Signal generator
+// The output is complex baseband IQ at the TX frequency. Tones are placed on the
+// upper or lower side of the carrier to match the sideband. Each test is limited
+// to the timeout and its peak level never exceeds the maximum level.
+pub struct SigGen {
+    sample_rate: u32,
+    mode: TestMode,
+    level: f64,
+    max_level: f64,
+    tone1: f64,
+    tone2: f64,
+    sweep_low: f64,
+    sweep_high: f64,
+    lsb: bool,
+    // Oscillator phases and the current sweep frequency
+    phase1: f64,
+    phase2: f64,
+    sweep_freq: f64,
+    // Noise generator state
+    seed: u32,
+    // Envelope position 0-1 and step per sample
+    ramp: f64,
+    ramp_step: f64,
+    // Samples since the test started and the limit
+    elapsed: usize,
+    timeout: usize,
+}
+
+impl SigGen {
+    pub fn new(sample_rate: u32) -> SigGen {
+        let mut s = SigGen {
+            sample_rate: sample_rate,
+            mode: TestMode::Off,
+            level: 0.0,
+            max_level: 1.0,
+            tone1: 700.0,
+            tone2: 1900.0,
+            sweep_low: 150.0,
+            sweep_high: 2850.0,
+            lsb: false,
+            phase1: 0.0,
+            phase2: 0.0,
+            sweep_freq: 0.0,
+            seed: 0x12345678,
+            ramp: 0.0,
+            ramp_step: 1000.0 / (sample_rate * RAMP_MS) as f64,
+            elapsed: 0,
+            timeout: 0,
+        };
+        s.set_timeout(60);
+        return s;
+    }
+
+    // A new test restarts the timeout
+    pub fn set_mode(&mut self, mode: TestMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.elapsed = 0;
+            self.sweep_freq = self.sweep_low;
+        }
+    }
+
+    // Level 0-1 of full drive, limited to the maximum
+    pub fn set_level(&mut self, level: f32, max_level: f32) {
+        self.max_level = f64::max(0.0, f64::min(1.0, max_level as f64));
+        self.level = f64::max(0.0, f64::min(self.max_level, level as f64));
+    }
+
+    pub fn set_tones(&mut self, tone1: u32, tone2: u32) {
+        self.tone1 = tone1 as f64;
+        self.tone2 = tone2 as f64;
+    }
+
+    // Range of the sweep and noise, normally the TX filter
+    pub fn set_passband(&mut self, low: u32, high: u32) {
+        self.sweep_low = low as f64;
+        self.sweep_high = f64::max(low as f64, high as f64);
+    }
+
+    pub fn set_lsb(&mut self, lsb: bool) {
+        self.lsb = lsb;
+    }
+
+    pub fn set_timeout(&mut self, secs: u32) {
+        self.timeout = self.sample_rate as usize * secs as usize;
+    }
+
+    pub fn timed_out(&self) -> bool {
+        return self.elapsed >= self.timeout;
+    }
+
+    // True while a test is running or ramping down
+    pub fn running(&self) -> bool {
+        return (self.mode != TestMode::Off && !self.timed_out()) || self.ramp > 0.0;
+    }
+
+    // Generate a block of interleaved IQ
+    pub fn process(&mut self, out: &mut [f64]) {
+        let rate = self.sample_rate as f64;
+        let sweep_step = (self.sweep_high - self.sweep_low) * 1000.0 / (rate * SWEEP_MS as f64);
+        for i in 0..out.len() / 2 {
+            let on = self.mode != TestMode::Off && !self.timed_out();
+            if on {
+                self.ramp = f64::min(1.0, self.ramp + self.ramp_step);
+                self.elapsed += 1;
+            } else {
+                self.ramp = f64::max(0.0, self.ramp - self.ramp_step);
+            }
+            let env = self.level * (0.5 - 0.5 * (PI * self.ramp).cos());
+            let (re, im) = match self.mode {
+                TestMode::Off => (0.0, 0.0),
+                TestMode::Tune => (1.0, 0.0),
+                TestMode::TwoTone => {
+                    // Each tone at half amplitude so the peak is the level
+                    self.phase1 = (self.phase1 + 2.0 * PI * self.tone1 / rate) % (2.0 * PI);
+                    self.phase2 = (self.phase2 + 2.0 * PI * self.tone2 / rate) % (2.0 * PI);
+                    (0.5 * (self.phase1.cos() + self.phase2.cos()), 0.5 * (self.phase1.sin() + self.phase2.sin()))
+                },
+                TestMode::Sweep => {
+                    self.sweep_freq += sweep_step;
+                    if self.sweep_freq > self.sweep_high {
+                        self.sweep_freq = self.sweep_low;
+                    }
+                    self.phase1 = (self.phase1 + 2.0 * PI * self.sweep_freq / rate) % (2.0 * PI);
+                    (self.phase1.cos(), self.phase1.sin())
+                },
+                TestMode::Noise => (self.noise(), self.noise()),
+            };
+            out[2 * i] = env * re;
+            // Mirror for the lower sideband
+            if self.lsb {
+                out[2 * i + 1] = -env * im;
+            } else {
+                out[2 * i + 1] = env * im;
+            }
+        }
+    }
+
+    // Uniform noise in -0.7 to 0.7 so the IQ magnitude stays within 1
+    fn noise(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        return 0.7 * (2.0 * (self.seed as f64 / u32::MAX as f64) - 1.0);
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    fn peak(iq: &[f64]) -> f64 {
+        let mut p: f64 = 0.0;
+        for i in 0..iq.len() / 2 {
+            p = f64::max(p, (iq[2 * i] * iq[2 * i] + iq[2 * i + 1] * iq[2 * i + 1]).sqrt());
+        }
+        return p;
+    }
+
+    // Magnitude of the component at f Hz
+    fn corr(iq: &[f64], f: f64) -> f64 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for i in 0..iq.len() / 2 {
+            let p = -2.0 * PI * f * i as f64 / RATE as f64;
+            re += iq[2 * i] * p.cos() - iq[2 * i + 1] * p.sin();
+            im += iq[2 * i] * p.sin() + iq[2 * i + 1] * p.cos();
+        }
+        return (re * re + im * im).sqrt() / (iq.len() / 2) as f64;
+    }
+
+    #[test]
+    fn tune_carrier_at_level() {
+        let mut s = SigGen::new(RATE);
+        s.set_level(0.25, 1.0);
+        s.set_mode(TestMode::Tune);
+        let mut out = vec![0.0; 2 * RATE as usize / 10];
+        s.process(&mut out);
+        // Ramped up then a steady carrier
+        assert!(out[0].abs() < 0.01);
+        let n = out.len();
+        assert!((out[n - 2] - 0.25).abs() < 1e-9);
+        assert!(out[n - 1].abs() < 1e-9);
+    }
+
+    #[test]
+    fn level_limited() {
+        let mut s = SigGen::new(RATE);
+        s.set_level(0.9, 0.3);
+        for mode in [TestMode::Tune, TestMode::TwoTone, TestMode::Sweep, TestMode::Noise] {
+            s.set_mode(mode);
+            let mut out = vec![0.0; 2 * RATE as usize / 5];
+            s.process(&mut out);
+            assert!(peak(&out) <= 0.3 + 1e-6, "{:?} {}", mode, peak(&out));
+        }
+    }
+
+    #[test]
+    fn two_tone_frequencies() {
+        // USB tones are positive frequencies
+        let mut s = SigGen::new(RATE);
+        s.set_level(1.0, 1.0);
+        s.set_tones(700, 1900);
+        s.set_mode(TestMode::TwoTone);
+        let mut out = vec![0.0; 2 * RATE as usize];
+        s.process(&mut out);
+        assert!((corr(&out, 700.0) - 0.5).abs() < 0.02);
+        assert!((corr(&out, 1900.0) - 0.5).abs() < 0.02);
+        assert!(corr(&out, -700.0) < 0.01);
+        s.set_lsb(true);
+        s.process(&mut out);
+        assert!((corr(&out, -700.0) - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn timeout_stops_test() {
+        let mut s = SigGen::new(RATE);
+        s.set_level(0.5, 1.0);
+        s.set_timeout(1);
+        s.set_mode(TestMode::Tune);
+        let mut out = vec![0.0; 2 * RATE as usize];
+        s.process(&mut out);
+        assert!(s.timed_out());
+        // Ramps down and stops
+        s.process(&mut out);
+        assert!(!s.running());
+        assert!(out[out.len() - 2].abs() < 1e-9);
+        // A new test restarts the timer
+        s.set_mode(TestMode::TwoTone);
+        assert!(s.running());
+    }
+}
@@ -0,0 +1,377 @@
+/*
+tone_squelch.rs
+
+Module - tone_squelch
+CTCSS and DCS decode for FM tone squelch
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::f64::consts::PI;
+
+// Bandwidth of the IQ ahead of the discriminator
+const IQ_CUTOFF: f64 = 10000.0;
+// Sub-audible band after the discriminator
+const SUB_CUTOFF: f64 = 250.0;
+// CTCSS decision interval in ms and fraction of sub-audible power in the tone
+const CTCSS_BLK_MS: u32 = 200;
+const CTCSS_RATIO: f64 = 0.3;
+// DCS bit rate, bits in a word and how many words may be missed
+const DCS_BAUD: f64 = 134.4;
+const DCS_BITS: u32 = 23;
+const DCS_HOLD_WORDS: f64 = 2.5;
+// Golay (23,12) generator polynomial
+const GOLAY_POLY: u32 = 0xC75;
+
+// Tone squelch types
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SqlType {
+    Off,
+    Ctcss,
+    Dcs,
+}
+
+impl SqlType {
+    pub fn from_u32(sql: u32) -> SqlType {
+        match sql {
+            1 => return SqlType::Ctcss,
+            2 => return SqlType::Dcs,
+            _ => return SqlType::Off,
+        }
+    }
+}
+
+// The 23 bit DCS word for an octal code, bit 0 is sent first.
+// 9 code bits, the fixed 100 pattern then 11 Golay parity bits.
+pub fn dcs_word(code: u32) -> u32 {
+    let data = (code & 0x1ff) | (1 << 9);
+    let mut rem = data << 11;
+    for i in (11..23).rev() {
+        if rem & (1 << i) != 0 {
+            rem ^= GOLAY_POLY << (i - 11);
+        }
+    }
+    return data | ((rem & 0x7ff) << 12);
+}
+
+//==================================================================================
+// Tone squelch state
+// The WDSP FM audio filter removes the sub-audible tones so decoding is done on
+// the receiver IQ with a simple discriminator and low pass filter.
+pub struct ToneSquelch {
+    sample_rate: u32,
+    sql_type: SqlType,
+    // IQ low pass and the previous sample for the discriminator
+    iq_alpha: f64,
+    lp_i: f64,
+    lp_q: f64,
+    last_i: f64,
+    last_q: f64,
+    // Two pole sub-audible low pass
+    sub_alpha: f64,
+    sub1: f64,
+    sub2: f64,
+    // Goertzel state for CTCSS
+    coeff: f64,
+    s1: f64,
+    s2: f64,
+    energy: f64,
+    count: usize,
+    blk_len: usize,
+    // DCS bit timing, shift register and samples since the last match
+    word: u32,
+    bit_len: f64,
+    bit_pos: f64,
+    next_sample: f64,
+    last_sign: bool,
+    reg: u32,
+    since_match: usize,
+    hold: usize,
+    open: bool,
+}
+
+impl ToneSquelch {
+    pub fn new(sample_rate: u32) -> ToneSquelch {
+        let mut t = ToneSquelch {
+            sample_rate: sample_rate,
+            sql_type: SqlType::Off,
+            iq_alpha: 0.0,
+            lp_i: 0.0,
+            lp_q: 0.0,
+            last_i: 0.0,
+            last_q: 0.0,
+            sub_alpha: 0.0,
+            sub1: 0.0,
+            sub2: 0.0,
+            coeff: 0.0,
+            s1: 0.0,
+            s2: 0.0,
+            energy: 0.0,
+            count: 0,
+            blk_len: 0,
+            word: 0,
+            bit_len: 0.0,
+            bit_pos: 0.0,
+            next_sample: 0.0,
+            last_sign: false,
+            reg: 0,
+            since_match: usize::MAX,
+            hold: 0,
+            open: false,
+        };
+        t.set_rate(sample_rate);
+        t.set_ctcss(88.5);
+        t.set_dcs(0o023);
+        return t;
+    }
+
+    // Rate of the IQ being decoded
+    pub fn set_rate(&mut self, sample_rate: u32) {
+        let rate = sample_rate as f64;
+        self.sample_rate = sample_rate;
+        self.iq_alpha = 1.0 - (-2.0 * PI * IQ_CUTOFF / rate).exp();
+        self.sub_alpha = 1.0 - (-2.0 * PI * SUB_CUTOFF / rate).exp();
+        self.blk_len = (sample_rate * CTCSS_BLK_MS / 1000) as usize;
+        self.bit_len = rate / DCS_BAUD;
+        self.hold = (self.bit_len * DCS_BITS as f64 * DCS_HOLD_WORDS) as usize;
+        self.reset();
+    }
+
+    pub fn set_type(&mut self, sql_type: SqlType) {
+        if sql_type != self.sql_type {
+            self.sql_type = sql_type;
+            self.reset();
+        }
+    }
+
+    pub fn set_ctcss(&mut self, freq: f32) {
+        let coeff = 2.0 * (2.0 * PI * freq as f64 / self.sample_rate as f64).cos();
+        if coeff != self.coeff {
+            self.coeff = coeff;
+            self.reset();
+        }
+    }
+
+    // Code in octal, e.g. 0o023
+    pub fn set_dcs(&mut self, code: u32) {
+        let word = dcs_word(code);
+        if word != self.word {
+            self.word = word;
+            self.reset();
+        }
+    }
+
+    // Closed until the tone or code is found
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        self.energy = 0.0;
+        self.count = 0;
+        self.reg = 0;
+        self.since_match = usize::MAX;
+        self.open = false;
+    }
+
+    // Open when off or the tone or code is present
+    pub fn open(&self) -> bool {
+        return self.sql_type == SqlType::Off || self.open;
+    }
+
+    // Decode a block of interleaved IQ and return the squelch state
+    pub fn process(&mut self, iq: &[f64]) -> bool {
+        if self.sql_type == SqlType::Off {
+            return true;
+        }
+        for i in 0..iq.len() / 2 {
+            self.lp_i += self.iq_alpha * (iq[2 * i] - self.lp_i);
+            self.lp_q += self.iq_alpha * (iq[2 * i + 1] - self.lp_q);
+            // Phase step between samples is the instantaneous frequency
+            let re = self.lp_i * self.last_i + self.lp_q * self.last_q;
+            let im = self.lp_q * self.last_i - self.lp_i * self.last_q;
+            self.last_i = self.lp_i;
+            self.last_q = self.lp_q;
+            let disc = im.atan2(re);
+            self.sub1 += self.sub_alpha * (disc - self.sub1);
+            self.sub2 += self.sub_alpha * (self.sub1 - self.sub2);
+            let x = self.sub2;
+            match self.sql_type {
+                SqlType::Ctcss => self.ctcss(x),
+                SqlType::Dcs => self.dcs(x),
+                SqlType::Off => (),
+            }
+        }
+        return self.open;
+    }
+
+    // Goertzel on the tone over each block, open if the tone holds most of the power
+    fn ctcss(&mut self, x: f64) {
+        let s = x + self.coeff * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s;
+        self.energy += x * x;
+        self.count += 1;
+        if self.count >= self.blk_len {
+            let power = self.s1 * self.s1 + self.s2 * self.s2 - self.coeff * self.s1 * self.s2;
+            let mut ratio = 0.0;
+            if self.energy > 0.0 {
+                ratio = 2.0 * power / (self.count as f64 * self.energy);
+            }
+            self.open = ratio > CTCSS_RATIO;
+            self.s1 = 0.0;
+            self.s2 = 0.0;
+            self.energy = 0.0;
+            self.count = 0;
+        }
+    }
+
+    // Slice the NRZ data and look for the word in either polarity
+    fn dcs(&mut self, x: f64) {
+        let sign = x > 0.0;
+        self.bit_pos += 1.0;
+        if sign != self.last_sign {
+            // Resynchronise on each transition, sample mid bit
+            self.last_sign = sign;
+            self.bit_pos = 0.0;
+            self.next_sample = self.bit_len / 2.0;
+        }
+        if self.bit_pos >= self.next_sample {
+            self.next_sample += self.bit_len;
+            self.reg = (self.reg >> 1) | ((sign as u32) << (DCS_BITS - 1));
+            if self.reg == self.word || self.reg == !self.word & 0x7fffff {
+                self.since_match = 0;
+            }
+        }
+        if self.since_match < usize::MAX {
+            self.since_match += 1;
+        }
+        self.open = self.since_match < self.hold;
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+    const DEV: f64 = 600.0;
+
+    // FM modulate a baseband signal given as frequency deviation in Hz
+    fn fm(devs: &[f64]) -> Vec<f64> {
+        let mut out = Vec::with_capacity(devs.len() * 2);
+        let mut phase: f64 = 0.0;
+        for d in devs.iter() {
+            phase += 2.0 * PI * d / RATE as f64;
+            out.push(phase.cos());
+            out.push(phase.sin());
+        }
+        return out;
+    }
+
+    fn tone(freq: f64, secs: f64) -> Vec<f64> {
+        let n = (RATE as f64 * secs) as usize;
+        return fm(&(0..n).map(|i| DEV * (2.0 * PI * freq * i as f64 / RATE as f64).sin()).collect::<Vec<f64>>());
+    }
+
+    fn dcs(code: u32, secs: f64, invert: bool) -> Vec<f64> {
+        let word = dcs_word(code);
+        let n = (RATE as f64 * secs) as usize;
+        let bit_len = RATE as f64 / DCS_BAUD;
+        let devs: Vec<f64> = (0..n).map(|i| {
+            let bit = (i as f64 / bit_len) as u32 % DCS_BITS;
+            let one = (word >> bit) & 1 == 1;
+            if one != invert { DEV } else { -DEV }
+        }).collect();
+        return fm(&devs);
+    }
+
+    #[test]
+    fn dcs_word_format() {
+        // Code in the low bits then the fixed pattern and parity
+        let w = dcs_word(0o023);
+        assert_eq!(w & 0x1ff, 0o023);
+        assert_eq!((w >> 9) & 0x7, 0b001);
+        assert!(w < 1 << 23);
+        assert_ne!(dcs_word(0o023), dcs_word(0o025));
+    }
+
+    #[test]
+    fn ctcss_detects_tone() {
+        let mut t = ToneSquelch::new(RATE);
+        t.set_type(SqlType::Ctcss);
+        t.set_ctcss(100.0);
+        assert!(!t.open());
+        assert!(t.process(&tone(100.0, 0.5)));
+        // A different tone closes it
+        assert!(!t.process(&tone(131.8, 0.5)));
+    }
+
+    #[test]
+    fn ctcss_with_speech() {
+        // Voice at several times the tone deviation
+        let mut t = ToneSquelch::new(RATE);
+        t.set_type(SqlType::Ctcss);
+        t.set_ctcss(88.5);
+        let n = RATE as usize / 2;
+        let devs: Vec<f64> = (0..n).map(|i| {
+            let s = i as f64 / RATE as f64;
+            DEV * (2.0 * PI * 88.5 * s).sin() + 3000.0 * (2.0 * PI * 1000.0 * s).sin()
+        }).collect();
+        assert!(t.process(&fm(&devs)));
+    }
+
+    #[test]
+    fn noise_stays_closed() {
+        let mut t = ToneSquelch::new(RATE);
+        t.set_type(SqlType::Ctcss);
+        let mut seed: u32 = 1;
+        let iq: Vec<f64> = (0..RATE).map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as f64 / u32::MAX as f64 - 0.5
+        }).collect();
+        assert!(!t.process(&iq));
+        t.set_type(SqlType::Dcs);
+        assert!(!t.process(&iq));
+    }
+
+    #[test]
+    fn dcs_detects_code() {
+        let mut t = ToneSquelch::new(RATE);
+        t.set_type(SqlType::Dcs);
+        t.set_dcs(0o023);
+        assert!(t.process(&dcs(0o023, 1.0, false)));
+        // Inverted by the transmitter or receiver still matches
+        t.reset();
+        assert!(t.process(&dcs(0o023, 1.0, true)));
+        // Another code drops out after the hold
+        assert!(!t.process(&dcs(0o754, 1.0, false)));
+    }
+
+    #[test]
+    fn off_is_open() {
+        let mut t = ToneSquelch::new(RATE);
+        assert!(t.open());
+        assert!(t.process(&[0.0; 64]));
+    }
+}
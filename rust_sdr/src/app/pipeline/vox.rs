@@ -0,0 +1,192 @@
+/*
+vox.rs
+
+Module - vox
+Voice operated switch with anti-VOX
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+// Level reported for silence
+const MIN_DB: f32 = -120.0;
+
+// Peak level of a block in dB relative to full scale
+// step allows only the I or left channel of interleaved data to be measured
+pub fn peak_db(samples: &[f64], step: usize) -> f32 {
+    let mut peak: f64 = 0.0;
+    for s in samples.iter().step_by(usize::max(step, 1)) {
+        peak = f64::max(peak, s.abs());
+    }
+    if peak <= 0.0 {
+        return MIN_DB;
+    }
+    return f32::max(MIN_DB, 20.0 * peak.log10() as f32);
+}
+
+//==================================================================================
+// VOX state
+// The mic must be above the threshold for the attack time to key and below it
+// for the hang time to unkey. With anti-VOX the mic must also be louder than the
+// receiver audio by the anti-VOX gain so speaker pickup does not key the transmitter.
+pub struct Vox {
+    sample_rate: u32,
+    threshold: f32,
+    attack: usize,
+    hang: usize,
+    anti_vox: bool,
+    anti_gain: f32,
+    // Samples continuously above and below the threshold
+    above: usize,
+    below: usize,
+    active: bool,
+}
+
+impl Vox {
+    pub fn new(sample_rate: u32) -> Vox {
+        let mut v = Vox {
+            sample_rate: sample_rate,
+            threshold: 0.0,
+            attack: 0,
+            hang: 0,
+            anti_vox: false,
+            anti_gain: 0.0,
+            above: 0,
+            below: 0,
+            active: false,
+        };
+        v.set(-30.0, 10, 500);
+        return v;
+    }
+
+    // Threshold in dB, attack and hang in ms
+    pub fn set(&mut self, threshold: f32, attack_ms: u32, hang_ms: u32) {
+        self.threshold = threshold;
+        self.attack = (self.sample_rate as usize * attack_ms as usize) / 1000;
+        self.hang = (self.sample_rate as usize * hang_ms as usize) / 1000;
+    }
+
+    // Margin in dB the mic must exceed the receiver audio by
+    pub fn set_anti_vox(&mut self, enabled: bool, gain: f32) {
+        self.anti_vox = enabled;
+        self.anti_gain = gain;
+    }
+
+    // Drop out immediately, used when PTT takes over
+    pub fn reset(&mut self) {
+        self.above = 0;
+        self.below = 0;
+        self.active = false;
+    }
+
+    pub fn active(&self) -> bool {
+        return self.active;
+    }
+
+    // Update for a block of num samples with the given mic and receiver levels in dB
+    pub fn process(&mut self, mic_db: f32, rx_db: f32, num: usize) -> bool {
+        let mut trigger = mic_db > self.threshold;
+        if self.anti_vox && mic_db < rx_db + self.anti_gain {
+            trigger = false;
+        }
+        if trigger {
+            self.below = 0;
+            self.above += num;
+            if self.above >= self.attack {
+                self.active = true;
+            }
+        } else {
+            self.above = 0;
+            if self.active {
+                self.below += num;
+                if self.below >= self.hang {
+                    self.active = false;
+                    self.below = 0;
+                }
+            }
+        }
+        return self.active;
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLK: usize = 480;
+
+    #[test]
+    fn peak_level() {
+        let v = vec![0.5, 0.9, -0.1, 0.0];
+        assert!((peak_db(&v, 1) - 20.0 * 0.9f32.log10()).abs() < 1e-4);
+        // Left channel only
+        assert!((peak_db(&v, 2) - 20.0 * 0.5f32.log10()).abs() < 1e-4);
+        assert_eq!(peak_db(&[0.0; 8], 1), MIN_DB);
+    }
+
+    #[test]
+    fn attack_and_hang() {
+        // 10ms blocks, 20ms attack, 50ms hang
+        let mut v = Vox::new(48000);
+        v.set(-30.0, 20, 50);
+        assert!(!v.process(-10.0, MIN_DB, BLK));
+        assert!(v.process(-10.0, MIN_DB, BLK));
+        for _ in 0..4 {
+            assert!(v.process(-60.0, MIN_DB, BLK));
+        }
+        assert!(!v.process(-60.0, MIN_DB, BLK));
+    }
+
+    #[test]
+    fn speech_gap_within_hang() {
+        let mut v = Vox::new(48000);
+        v.set(-30.0, 0, 50);
+        assert!(v.process(-10.0, MIN_DB, BLK));
+        assert!(v.process(-60.0, MIN_DB, BLK));
+        // Speech resumes before the hang expires
+        assert!(v.process(-10.0, MIN_DB, BLK));
+        for _ in 0..4 {
+            assert!(v.process(-60.0, MIN_DB, BLK));
+        }
+    }
+
+    #[test]
+    fn anti_vox_rejects_speaker() {
+        let mut v = Vox::new(48000);
+        v.set(-30.0, 0, 0);
+        v.set_anti_vox(true, 10.0);
+        // Speaker audio picked up at a lower level than the receiver audio
+        assert!(!v.process(-20.0, -15.0, BLK));
+        // Operator speaking well above it
+        assert!(v.process(-2.0, -15.0, BLK));
+    }
+
+    #[test]
+    fn reset_drops_out() {
+        let mut v = Vox::new(48000);
+        v.set(-30.0, 0, 1000);
+        assert!(v.process(-10.0, MIN_DB, BLK));
+        v.reset();
+        assert!(!v.active());
+        assert!(!v.process(-60.0, MIN_DB, BLK));
+    }
+}
@@ -23,4 +23,28 @@ Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
 The authors can be reached by email at:
 
 bob@bobcowdery.plus.com
-*/
\ No newline at end of file
+*/
+
+use std::mem::MaybeUninit;
+
+use crate::app::common::common_defs;
+use crate::app::common::globals;
+
+// C0 bits common to all C&C in frames
+const CC_IN_PTT: u8 = 0x01;
+const CC_IN_DASH: u8 = 0x02;
+const CC_IN_DOT: u8 = 0x04;
+
+//==================================================================================
+// Decode the C&C bytes of both USB frames in an EP6 frame.
+// The PTT, dot and dash inputs are in C0 of every frame.
+pub fn cc_in_decode(udp_frame : &[MaybeUninit<u8>; common_defs::FRAME_SZ as usize]) {
+	let mut c0: u8 = 0;
+	for offset in [common_defs::FRAME_CC_1_OFFSET, common_defs::FRAME_CC_2_OFFSET].iter() {
+		// Either frame asserting an input counts
+		c0 |= unsafe{udp_frame[*offset as usize].assume_init()};
+	}
+	globals::set_ptt(c0 & CC_IN_PTT != 0);
+	globals::set_dash(c0 & CC_IN_DASH != 0);
+	globals::set_dot(c0 & CC_IN_DOT != 0);
+}
@@ -30,14 +30,15 @@ bob@bobcowdery.plus.com
 use crate::app::common::cc_out_defs:: {
 	CCOSpeed,CCO10MhzRef,CCO122MhzRef,CCOBoardConfig,CCOMicSrc,
 	CCOAlexAttn,CCOPreamp,CCORxAnt,CCOAlexRxOut,CCOAlexTxRly,
-	CCODuplex,CCONumRx,CCOAlexAuto,CCOAlexBypass,CCOAlexHpfLpf
+	CCODuplex,CCONumRx,CCOAlexAuto,CCOAlexBypass,CCOAlexHpfLpf,
+	CCOAdc,CCOKeyerMode,CCOCwKeyer
 };
 
 //========================================================================
 // Constants
 // Round robin sequence for sending CC bytes
-// Note 0-6 for CCOBufferIdx 
-const RR_CC:  usize = 7;
+// Note 0-11 for CCOBufferIdx 
+const RR_CC:  usize = 11;
 
 //========================================================================
 // Enumerations for bit fields in the CC structure
@@ -50,7 +51,12 @@ enum CCOBufferIdx {
 	BRx2F,
 	BRx3F,
 	BMisc1,
-	BMisc2
+	BMisc2,
+	BMisc3,
+	BAdc,
+	BKeyer,
+	BCw,
+	BCwHang
 }
 
 // CC byte index
@@ -141,6 +147,24 @@ static CCO_ALEX_HPF_6_5_M: u8 = 0xf7;
 static CCO_ALEX_HPF_1_5_B: [u8; 2] = [ 0x00,0x10 ];
 static CCO_ALEX_HPF_1_5_M: u8 = 0xef;
 
+// ADC assignment for each receiver
+static CCO_RX1_ADC_B: [u8; 3] = [ 0x00,0x01,0x02 ];
+static CCO_RX1_ADC_M: u8 = 0xfc;
+static CCO_RX2_ADC_B: [u8; 3] = [ 0x00,0x04,0x08 ];
+static CCO_RX2_ADC_M: u8 = 0xf3;
+static CCO_RX3_ADC_B: [u8; 3] = [ 0x00,0x10,0x20 ];
+static CCO_RX3_ADC_M: u8 = 0xcf;
+
+// Hardware keyer
+static CCO_KEYER_MODE_B: [u8; 3] = [ 0x00,0x40,0x80 ];
+static CCO_KEYER_MODE_M: u8 = 0x3f;
+static CCO_KEYER_SPEED_M: u8 = 0xc0;
+static CCO_KEYER_WEIGHT_M: u8 = 0x80;
+static CCO_CW_KEYER_B: [u8; 2] = [ 0x00,0x01 ];
+static CCO_CW_KEYER_M: u8 = 0xfe;
+static CCO_CW_HANG_LOW_M: u8 = 0xfc;
+static CCO_SIDETONE_FREQ_LOW_M: u8 = 0xf0;
+
 //========================================================================
 // Implementations
 
@@ -153,7 +177,7 @@ pub struct CCData{
 	// Default MOX state
 	cc_mox_state: bool,
 	// Default array contains the C0 values that define how C1-C4 are defined
-	cc_array : [[u8; 5];8],
+	cc_array : [[u8; 5];12],
 	// Single row of the array is returned as next in sequence
 	cc_el : [u8; 5],
 }
@@ -176,6 +200,10 @@ impl CCData {
 					[ 0x0a, 0x00, 0x00, 0x00, 0x00 ],
 					[ 0x0c, 0x00, 0x00, 0x00, 0x00 ],
 					[ 0x0e, 0x00, 0x00, 0x00, 0x00 ],
+					[ 0x1c, 0x00, 0x00, 0x00, 0x00 ],
+					[ 0x16, 0x00, 0x00, 0x00, 0x00 ],
+					[ 0x1e, 0x00, 0x00, 0x00, 0x00 ],
+					[ 0x20, 0x00, 0x00, 0x00, 0x00 ],
 				]
 			),
 			cc_el: ([ 0x00, 0x00, 0x00, 0x00, 0x00 ]),
@@ -186,16 +214,14 @@ impl CCData {
 	pub fn cc_out_next_seq(&mut self) -> [u8; 5] {
 		self.cc_el = self.cc_array[self.cc_idx];
 		
-		// Check for MOX
-		if self.cc_idx == 0 {
-			if self.cc_mox_state {
-				// Need to set the MOX bit
-				self.cc_array[0] [0]= self.cc_array[0] [0] | 0x01;
-			}
-			else {
-				// Need to reset the MOX bit
-				self.cc_array[0] [0] = self.cc_array[0] [0] & 0xfe;
-			}
+		// MOX is bit 0 of C0 in every frame whatever the round robin index
+		if self.cc_mox_state {
+			// Need to set the MOX bit
+			self.cc_el[0] = self.cc_el[0] | 0x01;
+		}
+		else {
+			// Need to reset the MOX bit
+			self.cc_el[0] = self.cc_el[0] & 0xfe;
 		}
 
 		//if self.cc_idx == 0 { 
@@ -409,6 +435,59 @@ impl CCData {
 		self.cc_update(CCOBufferIdx::BMisc1 as usize, CCOByteIdx::CC3 as usize, setting, CCO_ALEX_HPF_1_5_M);
 	}
 
+	//========================================
+	// ADC assignment, for radios with more than one ADC
+	pub fn cc_rx1_adc(&mut self, adc: CCOAdc) {
+		let setting = CCO_RX1_ADC_B[adc as usize];
+		self.cc_update(CCOBufferIdx::BAdc as usize, CCOByteIdx::CC1 as usize, setting, CCO_RX1_ADC_M);
+	}
+	pub fn cc_rx2_adc(&mut self, adc: CCOAdc) {
+		let setting = CCO_RX2_ADC_B[adc as usize];
+		self.cc_update(CCOBufferIdx::BAdc as usize, CCOByteIdx::CC1 as usize, setting, CCO_RX2_ADC_M);
+	}
+	pub fn cc_rx3_adc(&mut self, adc: CCOAdc) {
+		let setting = CCO_RX3_ADC_B[adc as usize];
+		self.cc_update(CCOBufferIdx::BAdc as usize, CCOByteIdx::CC1 as usize, setting, CCO_RX3_ADC_M);
+	}
+
+	//========================================
+	// Hardware keyer, an alternative to keying from the IQ stream
+	pub fn cc_keyer_mode(&mut self, mode: CCOKeyerMode) {
+		let setting = CCO_KEYER_MODE_B[mode as usize];
+		self.cc_update(CCOBufferIdx::BKeyer as usize, CCOByteIdx::CC3 as usize, setting, CCO_KEYER_MODE_M);
+	}
+	// Speed 1-60 WPM
+	pub fn cc_keyer_speed(&mut self, wpm: u8) {
+		self.cc_update(CCOBufferIdx::BKeyer as usize, CCOByteIdx::CC3 as usize, wpm & !CCO_KEYER_SPEED_M, CCO_KEYER_SPEED_M);
+	}
+	// Weight 0-100
+	pub fn cc_keyer_weight(&mut self, weight: u8) {
+		self.cc_update(CCOBufferIdx::BKeyer as usize, CCOByteIdx::CC4 as usize, weight & !CCO_KEYER_WEIGHT_M, CCO_KEYER_WEIGHT_M);
+	}
+	// Use the keyer in the hardware
+	pub fn cc_cw_keyer(&mut self, keyer: CCOCwKeyer) {
+		let setting = CCO_CW_KEYER_B[keyer as usize];
+		self.cc_update(CCOBufferIdx::BCw as usize, CCOByteIdx::CC1 as usize, setting, CCO_CW_KEYER_M);
+	}
+	// Sidetone volume 0-127
+	pub fn cc_sidetone_vol(&mut self, vol: u8) {
+		self.cc_put_byte(CCOBufferIdx::BCw as usize, CCOByteIdx::CC2 as usize, vol);
+	}
+	// Delay from PTT to RF in ms
+	pub fn cc_cw_ptt_delay(&mut self, delay: u8) {
+		self.cc_put_byte(CCOBufferIdx::BCw as usize, CCOByteIdx::CC3 as usize, delay);
+	}
+	// Break-in hang time in ms, 10 bits split over C1 and C2
+	pub fn cc_cw_hang(&mut self, hang: u16) {
+		self.cc_put_byte(CCOBufferIdx::BCwHang as usize, CCOByteIdx::CC1 as usize, ((hang >> 2) & 0xff) as u8);
+		self.cc_update(CCOBufferIdx::BCwHang as usize, CCOByteIdx::CC2 as usize, (hang & 0x03) as u8, CCO_CW_HANG_LOW_M);
+	}
+	// Sidetone frequency in Hz, 12 bits split over C3 and C4
+	pub fn cc_sidetone_freq(&mut self, freq: u16) {
+		self.cc_put_byte(CCOBufferIdx::BCwHang as usize, CCOByteIdx::CC3 as usize, ((freq >> 4) & 0xff) as u8);
+		self.cc_update(CCOBufferIdx::BCwHang as usize, CCOByteIdx::CC4 as usize, (freq & 0x0f) as u8, CCO_SIDETONE_FREQ_LOW_M);
+	}
+
 	//========================================
 	// Frequency setting
 
@@ -428,6 +507,10 @@ impl CCData {
 		self.cc_common_set_freq(CCOBufferIdx::BRx1TxF, freq_in_hz);
 		self.cc_common_set_freq(CCOBufferIdx::BRx1F, freq_in_hz);
 	}
+	// RX 1 freq only, when split from TX
+	pub fn cc_set_rx1_freq(&mut self, freq_in_hz: u32) {
+		self.cc_common_set_freq(CCOBufferIdx::BRx1F, freq_in_hz);
+	}
 	// RX 2 freq
 	pub fn cc_set_rx2_freq(&mut self, freq_in_hz: u32) {
 		self.cc_common_set_freq(CCOBufferIdx::BRx2F, freq_in_hz);
@@ -458,6 +541,13 @@ impl CCData {
 		self.cc_duplex(CCODuplex::DuplexOff);
 		self.cc_num_rx(CCONumRx::NumRx1);
 		self.cc_alex_auto(CCOAlexAuto::AlexAuto);
+		self.cc_rx1_adc(CCOAdc::Adc1);
+		self.cc_rx2_adc(CCOAdc::Adc1);
+		self.cc_rx3_adc(CCOAdc::Adc1);
+		self.cc_cw_keyer(CCOCwKeyer::CwExternal);
+		self.cc_keyer_mode(CCOKeyerMode::KeyerModeA);
+		self.cc_keyer_speed(20);
+		self.cc_keyer_weight(50);
 		self.cc_set_rx_tx_freq(7150000);
 		self.cc_set_tx_freq(7150000);
 	}
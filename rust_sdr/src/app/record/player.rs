@@ -0,0 +1,323 @@
+/*
+player.rs
+
+Module - player
+Playback of IQ files into the receiver ring buffers in place of the UDP reader
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, Condvar};
+
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::common::converters;
+use crate::app::common::ringb;
+use crate::app::record::wav;
+use crate::app::record::sigmf;
+use crate::app::audio::resampler::Resampler;
+
+// Give up catching up if playback falls this far behind
+const MAX_LAG: Duration = Duration::from_millis(500);
+
+enum IqSource {
+    Wav(wav::WavReader),
+    Sigmf(sigmf::SigmfReader),
+}
+
+impl IqSource {
+    // The type is taken from the extension
+    fn open(path: &str) -> io::Result<IqSource> {
+        let p = Path::new(path);
+        match p.extension().and_then(|e| e.to_str()) {
+            Some(sigmf::DATA_EXT) | Some(sigmf::META_EXT) => return Ok(IqSource::Sigmf(sigmf::SigmfReader::open(p)?)),
+            _ => {
+                let r = wav::WavReader::open(p)?;
+                if r.channels() != 2 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "IQ files must have 2 channels"));
+                }
+                return Ok(IqSource::Wav(r));
+            },
+        }
+    }
+
+    fn rate(&self) -> u32 {
+        match self {
+            IqSource::Wav(r) => return r.rate(),
+            IqSource::Sigmf(r) => return r.rate(),
+        }
+    }
+
+    fn centre(&self) -> Option<u32> {
+        match self {
+            IqSource::Wav(r) => return r.centre(),
+            IqSource::Sigmf(r) => return r.centre(),
+        }
+    }
+
+    fn frames(&self) -> u64 {
+        match self {
+            IqSource::Wav(r) => return r.frames(),
+            IqSource::Sigmf(r) => return r.frames(),
+        }
+    }
+
+    fn position(&self) -> u64 {
+        match self {
+            IqSource::Wav(r) => return r.position(),
+            IqSource::Sigmf(r) => return r.position(),
+        }
+    }
+
+    fn seek(&mut self, frame: u64) -> io::Result<()> {
+        match self {
+            IqSource::Wav(r) => return r.seek(frame),
+            IqSource::Sigmf(r) => return r.seek(frame),
+        }
+    }
+
+    fn read(&mut self, out: &mut Vec<f32>, frames: usize) -> io::Result<usize> {
+        match self {
+            IqSource::Wav(r) => return r.read(out, frames),
+            IqSource::Sigmf(r) => return r.read(out, frames),
+        }
+    }
+}
+
+//==================================================================================
+// Runtime object for thread
+pub struct PlayerData {
+    receiver : crossbeam_channel::Receiver<messages::PlayerMsg>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+    iq_cond : Arc<(Mutex<bool>, Condvar)>,
+    source : Option<IqSource>,
+    playing : bool,
+    looping : bool,
+    resampler : Resampler,
+    in_data : Vec<f32>,
+    out_data : Vec<f32>,
+    iq_bytes : Vec<u8>,
+    // When the next block is due
+    next_blk : Instant,
+}
+
+// Implementation methods on PlayerData
+impl PlayerData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::PlayerMsg>,
+        rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+        iq_cond : Arc<(Mutex<bool>, Condvar)>) -> PlayerData {
+
+        PlayerData {
+            receiver: receiver,
+            rb_iq: rb_iq,
+            iq_cond: iq_cond,
+            source: None,
+            playing: false,
+            looping: false,
+            resampler: Resampler::new(common_defs::SMPLS_48K, common_defs::SMPLS_48K, 2),
+            in_data: Vec::new(),
+            out_data: Vec::with_capacity((common_defs::DSP_BLK_SZ * 2) as usize),
+            iq_bytes: Vec::with_capacity((common_defs::DSP_BLK_SZ * common_defs::BYTES_PER_SAMPLE) as usize),
+            next_blk: Instant::now(),
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn player_run(&mut self) {
+        loop {
+            // Wake for the next block when playing
+            let mut wait = Duration::from_millis(100);
+            if self.playing {
+                wait = self.next_blk.saturating_duration_since(Instant::now());
+            }
+            match self.receiver.recv_timeout(wait) {
+                Ok(msg) => {
+                    match msg {
+                        messages::PlayerMsg::Terminate => break,
+                        messages::PlayerMsg::Open(path) => self.open(&path),
+                        messages::PlayerMsg::Play => self.play(),
+                        messages::PlayerMsg::Pause => {
+                            self.playing = false;
+                            globals::set_play_running(false);
+                        },
+                        messages::PlayerMsg::Stop => self.close(),
+                        messages::PlayerMsg::Seek(frac) => self.seek(frac),
+                        messages::PlayerMsg::Loop(state) => self.looping = state,
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            if self.playing && Instant::now() >= self.next_blk {
+                self.next_block();
+            }
+        }
+        self.close();
+    }
+
+    // Open a file ready to play, the receivers take IQ from the file from now on
+    fn open(&mut self, path: &str) {
+        self.close();
+        match IqSource::open(path) {
+            Ok(source) => {
+                println!("Playing IQ from {}, {} Hz at {} sps", path, source.centre().unwrap_or(0), source.rate());
+                globals::set_play_freq(source.centre().unwrap_or(0));
+                globals::set_play_len((source.frames() / u64::max(source.rate() as u64, 1)) as u32);
+                globals::set_play_pos(0);
+                self.source = Some(source);
+                self.resampler.reset();
+                // Discard any hardware IQ still buffered
+                for rb in self.rb_iq.iter() {
+                    rb.reset(rb.capacity());
+                }
+                globals::set_playback(true);
+            },
+            Err(e) => {
+                println!("Failed to open IQ file {}: {:?}", path, e);
+            },
+        }
+    }
+
+    fn close(&mut self) {
+        self.playing = false;
+        globals::set_play_running(false);
+        if self.source.take().is_some() {
+            println!("IQ playback stopped");
+        }
+        globals::set_playback(false);
+    }
+
+    fn play(&mut self) {
+        if self.source.is_some() {
+            self.playing = true;
+            self.next_blk = Instant::now();
+            globals::set_play_running(true);
+        }
+    }
+
+    // Position as a fraction of the file
+    fn seek(&mut self, frac: f32) {
+        match self.source.as_mut() {
+            Some(source) => {
+                let frame = (source.frames() as f64 * f32::max(0.0, f32::min(1.0, frac)) as f64) as u64;
+                match source.seek(frame) {
+                    Ok(()) => (),
+                    Err(e) => println!("Seek error on IQ file {:?}", e),
+                }
+                self.resampler.reset();
+            },
+            None => (),
+        }
+    }
+
+    // Read, rate match and write one DSP block to each active receiver
+    fn next_block(&mut self) {
+        let rate = globals::get_smpl_rate();
+        let blk = common_defs::DSP_BLK_SZ as usize;
+        let source = self.source.as_mut().unwrap();
+        self.resampler.set_rates(source.rate(), rate);
+        let need = self.resampler.frames_needed(blk);
+        self.in_data.clear();
+        let r = source.read(&mut self.in_data, need);
+        match r {
+            Ok(n) => {
+                if n < need {
+                    // End of file
+                    if self.looping {
+                        let _ = source.seek(0);
+                    } else {
+                        self.close();
+                        return;
+                    }
+                }
+            },
+            Err(e) => {
+                println!("Read error on IQ file {:?}", e);
+                self.close();
+                return;
+            },
+        }
+        globals::set_play_pos((source.position() / u64::max(source.rate() as u64, 1)) as u32);
+        self.resampler.push(&self.in_data);
+        self.out_data.resize(blk * 2, 0.0);
+        if self.resampler.pull(&mut self.out_data) < blk {
+            // Wait for more input after a loop
+            return;
+        }
+        converters::f32le_to_i24be(&self.out_data, &mut self.iq_bytes);
+
+        // Every receiver sees the file
+        let num_rx = u32::min(globals::get_num_rx(), common_defs::MAX_RADIOS) as usize;
+        for rx in 0..num_rx {
+            let r = self.rb_iq[rx].write().write(&self.iq_bytes);
+            match r {
+                Err(_e) => {
+                    // Pipeline not keeping up, skip
+                },
+                Ok(_sz) => (),
+            }
+        }
+        let mut locked = self.iq_cond.0.lock().unwrap();
+        *locked = true;
+        self.iq_cond.1.notify_one();
+
+        // Pace to the sample rate
+        self.next_blk += Duration::from_secs_f64(blk as f64 / rate as f64);
+        let now = Instant::now();
+        if now > self.next_blk + MAX_LAG {
+            self.next_blk = now;
+        }
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn player_start(
+    receiver : crossbeam_channel::Receiver<messages::PlayerMsg>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+    iq_cond : Arc<(Mutex<bool>, Condvar)>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        player_run(receiver, rb_iq, iq_cond);
+    });
+    return join_handle;
+}
+
+fn player_run(
+    receiver : crossbeam_channel::Receiver<messages::PlayerMsg>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+    iq_cond : Arc<(Mutex<bool>, Condvar)>) {
+    println!("Player running");
+
+    // Instantiate the runtime object
+    let mut i_player = PlayerData::new(receiver, rb_iq, iq_cond);
+
+    // Exits when the player loop exits
+    i_player.player_run();
+
+    println!("Player exiting");
+}
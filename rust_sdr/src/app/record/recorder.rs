@@ -0,0 +1,458 @@
+/*
+recorder.rs
+
+Module - recorder
+Recorder thread writing to disk away from the pipeline
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::Duration;
+use std::path::PathBuf;
+use std::fs;
+
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::record::timestamp::UtcTime;
+use crate::app::record::wav;
+use crate::app::record::sigmf;
+use crate::app::record::flac;
+
+// Recorded in the SigMF metadata
+const HARDWARE: &str = "HPSDR Protocol 1";
+// Recorded in the audio file tags
+const SOFTWARE: &str = "RustSDR";
+
+// IQ file formats
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IqFormat {
+    WavFloat,
+    Wav24,
+    Sigmf,
+}
+
+impl IqFormat {
+    pub fn from_u32(format: u32) -> IqFormat {
+        match format {
+            1 => return IqFormat::Wav24,
+            2 => return IqFormat::Sigmf,
+            _ => return IqFormat::WavFloat,
+        }
+    }
+}
+
+// What to record, limits of 0 are no limit
+pub struct IqSpec {
+    pub dir: String,
+    pub format: IqFormat,
+    pub frequency: u32,
+    pub rate: u32,
+    pub max_bytes: u64,
+    pub max_secs: u32,
+}
+
+// Audio file formats
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn from_u32(format: u32) -> AudioFormat {
+        match format {
+            1 => return AudioFormat::Flac,
+            _ => return AudioFormat::Wav,
+        }
+    }
+}
+
+// Tuning of a recorded receiver
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioRx {
+    pub rx: u32,
+    pub frequency: u32,
+    pub mode: String,
+}
+
+impl AudioRx {
+    pub fn describe(&self) -> String {
+        return format!("RX{} {:.6} MHz {}", self.rx, self.frequency as f64 / 1.0e6, self.mode);
+    }
+}
+
+// What to record, source 0 is all receivers mixed
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioSpec {
+    pub dir: String,
+    pub format: AudioFormat,
+    pub source: u32,
+    pub receivers: Vec<AudioRx>,
+    pub max_bytes: u64,
+    pub max_secs: u32,
+}
+
+// Daily audio recording at a UTC time, the spec has the duration as its time limit
+#[derive(Clone, PartialEq, Debug)]
+pub struct Schedule {
+    pub hour: u32,
+    pub minute: u32,
+    pub spec: AudioSpec,
+}
+
+impl Schedule {
+    // True the first time it is called in the scheduled minute of a day
+    pub fn due(&self, now: &UtcTime, last_day: &mut u32) -> bool {
+        let day = now.year * 10000 + now.month * 100 + now.day;
+        if now.hour != self.hour || now.minute != self.minute || *last_day == day {
+            return false;
+        }
+        *last_day = day;
+        return true;
+    }
+}
+
+// Files are named by time and frequency, the extension is added by the format
+pub fn file_base(dir: &str, start: &UtcTime, frequency: u32, kind: &str) -> PathBuf {
+    let mut path = PathBuf::from(dir);
+    path.push(format!("rust_sdr_{}_{}Hz_{}", start.file_stamp(), frequency, kind));
+    return path;
+}
+
+enum IqFile {
+    Wav(wav::WavWriter),
+    Sigmf(sigmf::SigmfWriter),
+}
+
+enum AudioFile {
+    Wav(wav::WavWriter),
+    Flac(flac::FlacWriter),
+}
+
+//==================================================================================
+// Runtime object for thread
+pub struct RecorderData {
+    receiver : crossbeam_channel::Receiver<messages::RecorderMsg>,
+    iq : Option<IqFile>,
+    iq_spec : Option<IqSpec>,
+    iq_samples : u64,
+    audio : Option<AudioFile>,
+    audio_spec : Option<AudioSpec>,
+    audio_samples : u64,
+    schedule : Option<Schedule>,
+    // Day of the last scheduled start so it only fires once
+    sched_day : u32,
+}
+
+// Implementation methods on RecorderData
+impl RecorderData {
+	// Create a new instance
+    pub fn new(receiver : crossbeam_channel::Receiver<messages::RecorderMsg>) -> RecorderData {
+        RecorderData {
+            receiver: receiver,
+            iq: None,
+            iq_spec: None,
+            iq_samples: 0,
+            audio: None,
+            audio_spec: None,
+            audio_samples: 0,
+            schedule: None,
+            sched_day: 0,
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn recorder_run(&mut self) {
+        loop {
+            // Wait for data or a timeout so the thread is not spinning
+            match self.receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::RecorderMsg::Terminate => break,
+                        messages::RecorderMsg::StartIq(spec) => self.start_iq(spec),
+                        messages::RecorderMsg::StopIq => self.stop_iq(),
+                        messages::RecorderMsg::IqData(data) => self.write_iq(&data),
+                        messages::RecorderMsg::StartAudio(spec) => self.start_audio(spec),
+                        messages::RecorderMsg::StopAudio => self.stop_audio(),
+                        messages::RecorderMsg::AudioData(data) => self.write_audio(&data),
+                        messages::RecorderMsg::Schedule(schedule) => self.schedule = schedule,
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.scheduled();
+        }
+        self.stop_iq();
+        self.stop_audio();
+    }
+
+    //==================================================================================
+    // IQ recording
+    fn start_iq(&mut self, spec: IqSpec) {
+        self.stop_iq();
+        let _ = fs::create_dir_all(&spec.dir);
+        let start = UtcTime::now();
+        let base = file_base(&spec.dir, &start, spec.frequency, "IQ");
+        let r = match spec.format {
+            IqFormat::WavFloat | IqFormat::Wav24 => {
+                let mut format = wav::SampleFormat::Float32;
+                if spec.format == IqFormat::Wav24 {
+                    format = wav::SampleFormat::Pcm24;
+                }
+                wav::WavWriter::create(&base.with_extension("wav"), format, 2, spec.rate, Some((spec.frequency, start)))
+                    .map(|w| IqFile::Wav(w))
+            },
+            IqFormat::Sigmf => {
+                sigmf::SigmfWriter::create(&base, spec.rate, spec.frequency, HARDWARE, start)
+                    .map(|w| IqFile::Sigmf(w))
+            },
+        };
+        match r {
+            Ok(file) => {
+                println!("Recording IQ to {}", base.display());
+                self.iq = Some(file);
+                self.iq_spec = Some(spec);
+                self.iq_samples = 0;
+                globals::set_iq_rec_secs(0);
+                globals::set_iq_recording(true);
+            },
+            Err(e) => {
+                println!("Failed to create IQ recording {}: {:?}", base.display(), e);
+                globals::set_iq_recording(false);
+            },
+        }
+    }
+
+    fn stop_iq(&mut self) {
+        globals::set_iq_recording(false);
+        let r = match self.iq.take() {
+            Some(IqFile::Wav(w)) => w.finish(UtcTime::now()),
+            Some(IqFile::Sigmf(w)) => w.finish(),
+            None => return,
+        };
+        match r {
+            Ok(()) => println!("IQ recording stopped"),
+            Err(e) => println!("Error closing IQ recording {:?}", e),
+        }
+        self.iq_spec = None;
+    }
+
+    // Stop at the size or time limit, WAV files are also limited by the 32 bit sizes
+    fn write_iq(&mut self, data: &[f32]) {
+        let r = match self.iq.as_mut() {
+            Some(IqFile::Wav(w)) => w.write(data).map(|_| (w.bytes(), wav::WAV_MAX_BYTES)),
+            Some(IqFile::Sigmf(w)) => w.write(data).map(|_| (w.bytes(), u64::MAX)),
+            // Data still in the channel after a stop
+            None => return,
+        };
+        let spec = self.iq_spec.as_ref().unwrap();
+        self.iq_samples += (data.len() / 2) as u64;
+        let secs = (self.iq_samples / spec.rate as u64) as u32;
+        globals::set_iq_rec_secs(secs);
+        match r {
+            Ok((bytes, max)) => {
+                let over_size = bytes >= max || (spec.max_bytes > 0 && bytes >= spec.max_bytes);
+                let over_time = spec.max_secs > 0 && secs >= spec.max_secs;
+                if over_size || over_time {
+                    println!("IQ recording limit reached");
+                    self.stop_iq();
+                }
+            },
+            Err(e) => {
+                println!("Write error on IQ recording {:?}", e);
+                self.stop_iq();
+            },
+        }
+    }
+
+    //==================================================================================
+    // Scheduled recording
+    // Checked here rather than in the UI so it happens whether or not the panel is drawn.
+    // A recording already running is left alone.
+    fn scheduled(&mut self) {
+        let spec = match self.schedule.as_ref() {
+            Some(s) if s.due(&UtcTime::now(), &mut self.sched_day) => s.spec.clone(),
+            _ => return,
+        };
+        if self.audio.is_none() {
+            self.start_audio(spec);
+            globals::set_audio_rec_sched(self.audio.is_some());
+        }
+    }
+
+    //==================================================================================
+    // Audio recording
+    // Receiver audio is 16 bit stereo at 48K, tagged with the tuning and start time
+    fn start_audio(&mut self, spec: AudioSpec) {
+        self.stop_audio();
+        let _ = fs::create_dir_all(&spec.dir);
+        let start = UtcTime::now();
+        let frequency = spec.receivers.first().map(|r| r.frequency).unwrap_or(0);
+        let mut kind = String::from("AF");
+        if spec.source > 0 {
+            kind = format!("RX{}", spec.source);
+        }
+        let desc: Vec<String> = spec.receivers.iter().map(|r| r.describe()).collect();
+        let desc = desc.join(", ");
+        let base = file_base(&spec.dir, &start, frequency, &kind);
+        let r = match spec.format {
+            AudioFormat::Wav => {
+                wav::WavWriter::create(&base.with_extension("wav"), wav::SampleFormat::Pcm16, 2, common_defs::SMPLS_48K, None)
+                    .map(|mut w| {
+                        w.add_info(b"INAM", &desc);
+                        w.add_info(b"ICMT", &format!("Start {} {}", start.iso8601(), desc));
+                        w.add_info(b"ICRD", &start.iso8601());
+                        w.add_info(b"ISFT", SOFTWARE);
+                        AudioFile::Wav(w)
+                    })
+            },
+            AudioFormat::Flac => {
+                // Vorbis comments may repeat, one frequency and mode for each receiver
+                let mut tags = vec![
+                    (String::from("TITLE"), desc.clone()),
+                    (String::from("DATE"), start.iso8601()),
+                    (String::from("ENCODER"), String::from(SOFTWARE)),
+                ];
+                for r in spec.receivers.iter() {
+                    tags.push((String::from("FREQUENCY"), r.frequency.to_string()));
+                    tags.push((String::from("MODE"), r.mode.clone()));
+                }
+                flac::FlacWriter::create(&base.with_extension("flac"), 2, common_defs::SMPLS_48K, &tags)
+                    .map(|w| AudioFile::Flac(w))
+            },
+        };
+        match r {
+            Ok(file) => {
+                println!("Recording audio to {}", base.display());
+                self.audio = Some(file);
+                globals::set_audio_rec_src(spec.source);
+                self.audio_spec = Some(spec);
+                self.audio_samples = 0;
+                globals::set_audio_rec_secs(0);
+                globals::set_audio_recording(true);
+            },
+            Err(e) => {
+                println!("Failed to create audio recording {}: {:?}", base.display(), e);
+                globals::set_audio_recording(false);
+            },
+        }
+    }
+
+    fn stop_audio(&mut self) {
+        globals::set_audio_recording(false);
+        globals::set_audio_rec_sched(false);
+        let r = match self.audio.take() {
+            Some(AudioFile::Wav(w)) => w.finish(UtcTime::now()),
+            Some(AudioFile::Flac(w)) => w.finish(),
+            None => return,
+        };
+        match r {
+            Ok(()) => println!("Audio recording stopped"),
+            Err(e) => println!("Error closing audio recording {:?}", e),
+        }
+        self.audio_spec = None;
+    }
+
+    // Stop at the size or time limit
+    fn write_audio(&mut self, data: &[f32]) {
+        let r = match self.audio.as_mut() {
+            Some(AudioFile::Wav(w)) => w.write(data).map(|_| (w.bytes(), wav::WAV_MAX_BYTES)),
+            Some(AudioFile::Flac(w)) => w.write(data).map(|_| (w.bytes(), u64::MAX)),
+            None => return,
+        };
+        let spec = self.audio_spec.as_ref().unwrap();
+        self.audio_samples += (data.len() / 2) as u64;
+        let secs = (self.audio_samples / common_defs::SMPLS_48K as u64) as u32;
+        globals::set_audio_rec_secs(secs);
+        match r {
+            Ok((bytes, max)) => {
+                let over_size = bytes >= max || (spec.max_bytes > 0 && bytes >= spec.max_bytes);
+                let over_time = spec.max_secs > 0 && secs >= spec.max_secs;
+                if over_size || over_time {
+                    println!("Audio recording limit reached");
+                    self.stop_audio();
+                }
+            },
+            Err(e) => {
+                println!("Write error on audio recording {:?}", e);
+                self.stop_audio();
+            },
+        }
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn recorder_start(receiver : crossbeam_channel::Receiver<messages::RecorderMsg>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        recorder_run(receiver);
+    });
+    return join_handle;
+}
+
+fn recorder_run(receiver : crossbeam_channel::Receiver<messages::RecorderMsg>) {
+    println!("Recorder running");
+
+    // Instantiate the runtime object
+    let mut i_recorder = RecorderData::new(receiver);
+
+    // Exits when the recorder loop exits
+    i_recorder.recorder_run();
+
+    println!("Recorder exiting");
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> UtcTime {
+        return UtcTime { year: 2024, month: 3, day: day, weekday: 0, hour: hour, minute: minute, second: 0, millis: 0 };
+    }
+
+    #[test]
+    fn schedule_once_a_day() {
+        let s = Schedule {
+            hour: 6,
+            minute: 30,
+            spec: AudioSpec {
+                dir: String::from("."),
+                format: AudioFormat::Wav,
+                source: 1,
+                receivers: vec![AudioRx { rx: 1, frequency: 7100000, mode: String::from("LSB") }],
+                max_bytes: 0,
+                max_secs: 3600,
+            },
+        };
+        let mut last_day = 0;
+        assert!(!s.due(&at(1, 6, 29), &mut last_day));
+        assert!(s.due(&at(1, 6, 30), &mut last_day));
+        // Checked many times in the minute
+        assert!(!s.due(&at(1, 6, 30), &mut last_day));
+        assert!(!s.due(&at(1, 6, 31), &mut last_day));
+        assert!(s.due(&at(2, 6, 30), &mut last_day));
+    }
+}
@@ -0,0 +1,181 @@
+/*
+sigmf.rs
+
+Module - sigmf
+SigMF recording, a raw data file with a JSON metadata file
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write, Seek, SeekFrom, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::app::record::timestamp::UtcTime;
+
+pub const SIGMF_VERSION: &str = "1.0.0";
+pub const DATA_EXT: &str = "sigmf-data";
+pub const META_EXT: &str = "sigmf-meta";
+// Complex 32 bit float little endian
+pub const DATATYPE: &str = "cf32_le";
+
+//==================================================================================
+// Writer
+// The metadata is written when the recording finishes.
+pub struct SigmfWriter {
+    data: BufWriter<File>,
+    meta_path: PathBuf,
+    rate: u32,
+    frequency: u32,
+    hardware: String,
+    start: UtcTime,
+    data_bytes: u64,
+}
+
+impl SigmfWriter {
+    // base is the path without an extension
+    pub fn create(base: &Path, rate: u32, frequency: u32, hardware: &str, start: UtcTime) -> io::Result<SigmfWriter> {
+        let data = BufWriter::new(File::create(base.with_extension(DATA_EXT))?);
+        return Ok(SigmfWriter {
+            data: data,
+            meta_path: base.with_extension(META_EXT),
+            rate: rate,
+            frequency: frequency,
+            hardware: String::from(hardware),
+            start: start,
+            data_bytes: 0,
+        });
+    }
+
+    pub fn bytes(&self) -> u64 {
+        return self.data_bytes;
+    }
+
+    // Interleaved IQ
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for s in samples.iter() {
+            self.data.write_all(&s.to_le_bytes())?;
+        }
+        self.data_bytes += samples.len() as u64 * 4;
+        return Ok(());
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.data.flush()?;
+        let meta = json!({
+            "global": {
+                "core:datatype": DATATYPE,
+                "core:sample_rate": self.rate,
+                "core:version": SIGMF_VERSION,
+                "core:hw": self.hardware,
+                "core:recorder": "rust_sdr",
+            },
+            "captures": [{
+                "core:sample_start": 0,
+                "core:frequency": self.frequency,
+                "core:datetime": self.start.iso8601(),
+            }],
+            "annotations": [],
+        });
+        let text = serde_json::to_string_pretty(&meta).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(&self.meta_path, text)?;
+        return Ok(());
+    }
+}
+
+//==================================================================================
+// Reader
+// Complex float and 16 bit integer data are supported.
+pub struct SigmfReader {
+    data: BufReader<File>,
+    sample_bytes: u64,
+    rate: u32,
+    frequency: Option<u32>,
+    frames: u64,
+    pos: u64,
+}
+
+impl SigmfReader {
+    // Either the metadata or data file may be given
+    pub fn open(path: &Path) -> io::Result<SigmfReader> {
+        let text = fs::read_to_string(path.with_extension(META_EXT))?;
+        let meta: serde_json::Value = serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let datatype = meta["global"]["core:datatype"].as_str().unwrap_or("");
+        let sample_bytes = match datatype {
+            "cf32_le" => 4,
+            "ci16_le" => 2,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported datatype {}", datatype))),
+        };
+        let rate = meta["global"]["core:sample_rate"].as_f64().unwrap_or(0.0) as u32;
+        let frequency = meta["captures"][0]["core:frequency"].as_f64().map(|f| f as u32);
+        let file = File::open(path.with_extension(DATA_EXT))?;
+        let frames = file.metadata()?.len() / (2 * sample_bytes);
+        return Ok(SigmfReader {
+            data: BufReader::new(file),
+            sample_bytes: sample_bytes,
+            rate: rate,
+            frequency: frequency,
+            frames: frames,
+            pos: 0,
+        });
+    }
+
+    pub fn rate(&self) -> u32 {
+        return self.rate;
+    }
+
+    // Frequency of the first capture
+    pub fn centre(&self) -> Option<u32> {
+        return self.frequency;
+    }
+
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    pub fn position(&self) -> u64 {
+        return self.pos;
+    }
+
+    pub fn seek(&mut self, frame: u64) -> io::Result<()> {
+        self.pos = u64::min(frame, self.frames);
+        self.data.seek(SeekFrom::Start(self.pos * 2 * self.sample_bytes))?;
+        return Ok(());
+    }
+
+    // Append up to frames IQ frames to out, returns the number read, 0 at the end
+    pub fn read(&mut self, out: &mut Vec<f32>, frames: usize) -> io::Result<usize> {
+        let n = u64::min(frames as u64, self.frames - self.pos) as usize;
+        let mut buf = vec![0; n * 2 * self.sample_bytes as usize];
+        self.data.read_exact(&mut buf)?;
+        for b in buf.chunks_exact(self.sample_bytes as usize) {
+            if self.sample_bytes == 4 {
+                out.push(f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+            } else {
+                out.push(i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0);
+            }
+        }
+        self.pos += n as u64;
+        return Ok(n);
+    }
+}
@@ -0,0 +1,127 @@
+/*
+timestamp.rs
+
+Module - timestamp
+UTC date and time for file names and metadata
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH, Duration};
+
+//==================================================================================
+// Broken down UTC time
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UtcTime {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+    // 0 is Sunday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millis: u32,
+}
+
+impl UtcTime {
+    pub fn now() -> UtcTime {
+        return UtcTime::from_system_time(SystemTime::now());
+    }
+
+    pub fn from_system_time(t: SystemTime) -> UtcTime {
+        let d = t.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        let secs = d.as_secs();
+        let days = (secs / 86400) as i64;
+        let rem = secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+        return UtcTime {
+            year: year as u32,
+            month: month,
+            day: day,
+            // 1st Jan 1970 was a Thursday
+            weekday: ((days + 4) % 7) as u32,
+            hour: (rem / 3600) as u32,
+            minute: ((rem % 3600) / 60) as u32,
+            second: (rem % 60) as u32,
+            millis: d.subsec_millis(),
+        };
+    }
+
+    // For file names, 20231018_142501Z
+    pub fn file_stamp(&self) -> String {
+        return format!("{:04}{:02}{:02}_{:02}{:02}{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second);
+    }
+
+    // ISO 8601, 2023-10-18T14:25:01.000Z
+    pub fn iso8601(&self) -> String {
+        return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millis);
+    }
+}
+
+// Days since 1970-01-01 to year, month, day in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let mut year = yoe + era * 400;
+    if month <= 2 {
+        year += 1;
+    }
+    return (year, month, day);
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> UtcTime {
+        return UtcTime::from_system_time(UNIX_EPOCH + Duration::from_millis(secs * 1000 + 250));
+    }
+
+    #[test]
+    fn epoch() {
+        let t = at(0);
+        assert_eq!((t.year, t.month, t.day, t.weekday), (1970, 1, 1, 4));
+        assert_eq!(t.iso8601(), "1970-01-01T00:00:00.250Z");
+    }
+
+    #[test]
+    fn known_dates() {
+        // 2000-02-29 12:34:56, a Tuesday
+        let t = at(951827696);
+        assert_eq!((t.year, t.month, t.day, t.weekday), (2000, 2, 29, 2));
+        assert_eq!(t.file_stamp(), "20000229_123456Z");
+        // 2023-12-31 23:59:59, a Sunday
+        let t = at(1704067199);
+        assert_eq!((t.year, t.month, t.day, t.weekday), (2023, 12, 31, 0));
+        assert_eq!((t.hour, t.minute, t.second), (23, 59, 59));
+    }
+}
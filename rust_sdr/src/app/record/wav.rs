@@ -0,0 +1,407 @@
+/*
+wav.rs
+
+Module - wav
+WAV file writer with SDR auxi metadata
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::fs::File;
+use std::io::{self, Read, Write, Seek, SeekFrom, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::app::record::timestamp::UtcTime;
+
+// Format tags, extensible has the tag in its sub-format
+const WAVE_PCM: u16 = 1;
+const WAVE_FLOAT: u16 = 3;
+const WAVE_EXTENSIBLE: u16 = 0xfffe;
+// The auxi chunk as written by SpectraVue and SDR#
+const AUXI_SZ: u32 = 164;
+// Offset of the stop time within the auxi chunk data
+const AUXI_STOP: u64 = 16;
+// RIFF sizes are 32 bit
+pub const WAV_MAX_BYTES: u64 = 0xffff_0000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SampleFormat {
+    Float32,
+    Pcm24,
+    Pcm16,
+}
+
+impl SampleFormat {
+    pub fn bytes(&self) -> u16 {
+        match self {
+            SampleFormat::Float32 => return 4,
+            SampleFormat::Pcm24 => return 3,
+            SampleFormat::Pcm16 => return 2,
+        }
+    }
+}
+
+//==================================================================================
+// Writer
+// Sizes are filled in and the stop time set in the auxi chunk when finished.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    format: SampleFormat,
+    data_bytes: u64,
+    // File positions of the auxi data and the data chunk size
+    auxi_pos: Option<u64>,
+    data_size_pos: u64,
+    // LIST INFO text written after the data
+    info: Vec<([u8; 4], String)>,
+}
+
+impl WavWriter {
+    // Interleaved samples of the given number of channels.
+    // With a centre frequency an auxi chunk records it with the start time.
+    pub fn create(path: &Path, format: SampleFormat, channels: u16, rate: u32, centre: Option<(u32, UtcTime)>) -> io::Result<WavWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let align = channels * format.bytes();
+        let mut tag = WAVE_PCM;
+        if format == SampleFormat::Float32 {
+            tag = WAVE_FLOAT;
+        }
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&tag.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&rate.to_le_bytes())?;
+        file.write_all(&(rate * align as u32).to_le_bytes())?;
+        file.write_all(&align.to_le_bytes())?;
+        file.write_all(&(format.bytes() * 8).to_le_bytes())?;
+        let mut pos: u64 = 36;
+
+        let mut auxi_pos = None;
+        match centre {
+            Some((freq, start)) => {
+                file.write_all(b"auxi")?;
+                file.write_all(&AUXI_SZ.to_le_bytes())?;
+                let mut auxi = vec![0; AUXI_SZ as usize];
+                auxi[0..16].copy_from_slice(&system_time(&start));
+                auxi[16..32].copy_from_slice(&system_time(&start));
+                // Centre, A/D rate, IF frequency, bandwidth
+                auxi[32..36].copy_from_slice(&freq.to_le_bytes());
+                auxi[36..40].copy_from_slice(&rate.to_le_bytes());
+                auxi[44..48].copy_from_slice(&rate.to_le_bytes());
+                file.write_all(&auxi)?;
+                auxi_pos = Some(pos + 8);
+                pos += 8 + AUXI_SZ as u64;
+            },
+            None => (),
+        }
+
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        return Ok(WavWriter {
+            file: file,
+            format: format,
+            data_bytes: 0,
+            auxi_pos: auxi_pos,
+            data_size_pos: pos + 4,
+            info: Vec::new(),
+        });
+    }
+
+    // Add an INFO item such as INAM or ICMT
+    pub fn add_info(&mut self, id: &[u8; 4], text: &str) {
+        self.info.push((*id, String::from(text)));
+    }
+
+    // Bytes of sample data written
+    pub fn bytes(&self) -> u64 {
+        return self.data_bytes;
+    }
+
+    // Interleaved samples in -1.0 to 1.0
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for s in samples.iter() {
+            let s = f32::max(-1.0, f32::min(1.0, *s));
+            match self.format {
+                SampleFormat::Float32 => self.file.write_all(&s.to_le_bytes())?,
+                SampleFormat::Pcm24 => {
+                    let v = (s * 8388607.0) as i32;
+                    self.file.write_all(&v.to_le_bytes()[0..3])?;
+                },
+                SampleFormat::Pcm16 => self.file.write_all(&((s * 32767.0) as i16).to_le_bytes())?,
+            }
+        }
+        self.data_bytes += samples.len() as u64 * self.format.bytes() as u64;
+        return Ok(());
+    }
+
+    // Complete the header
+    pub fn finish(mut self, stop: UtcTime) -> io::Result<()> {
+        // Data chunks are padded to an even size
+        if self.data_bytes % 2 == 1 {
+            self.file.write_all(&[0])?;
+        }
+        if self.info.len() > 0 {
+            let mut list: Vec<u8> = Vec::new();
+            list.extend_from_slice(b"INFO");
+            for (id, text) in self.info.iter() {
+                // Text is null terminated and padded to an even size
+                let mut t = text.clone().into_bytes();
+                t.push(0);
+                list.extend_from_slice(id);
+                list.extend_from_slice(&(t.len() as u32).to_le_bytes());
+                if t.len() % 2 == 1 {
+                    t.push(0);
+                }
+                list.extend_from_slice(&t);
+            }
+            self.file.write_all(b"LIST")?;
+            self.file.write_all(&(list.len() as u32).to_le_bytes())?;
+            self.file.write_all(&list)?;
+        }
+        let end = self.file.stream_position()?;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&((end - 8) as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(self.data_size_pos))?;
+        self.file.write_all(&(self.data_bytes as u32).to_le_bytes())?;
+        match self.auxi_pos {
+            Some(pos) => {
+                self.file.seek(SeekFrom::Start(pos + AUXI_STOP))?;
+                self.file.write_all(&system_time(&stop))?;
+            },
+            None => (),
+        }
+        self.file.flush()?;
+        return Ok(());
+    }
+}
+
+//==================================================================================
+// Reader
+// Samples are returned interleaved as f32 whatever the file format.
+pub struct WavReader {
+    file: BufReader<File>,
+    format: SampleFormat,
+    channels: u16,
+    rate: u32,
+    centre: Option<u32>,
+    data_start: u64,
+    frames: u64,
+    pos: u64,
+}
+
+impl WavReader {
+    pub fn open(path: &Path) -> io::Result<WavReader> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut id = [0; 4];
+        let mut size = [0; 4];
+        file.read_exact(&mut id)?;
+        file.read_exact(&mut size)?;
+        if &id != b"RIFF" {
+            return Err(invalid("not a RIFF file"));
+        }
+        file.read_exact(&mut id)?;
+        if &id != b"WAVE" {
+            return Err(invalid("not a WAVE file"));
+        }
+        let mut fmt: Option<(SampleFormat, u16, u32)> = None;
+        let mut centre = None;
+        // Walk the chunks until the data
+        loop {
+            file.read_exact(&mut id)?;
+            file.read_exact(&mut size)?;
+            let sz = u32::from_le_bytes(size) as u64;
+            if &id == b"data" {
+                let (format, channels, rate) = fmt.ok_or(invalid("no fmt chunk"))?;
+                let data_start = file.stream_position()?;
+                let align = (channels * format.bytes()) as u64;
+                return Ok(WavReader {
+                    file: file,
+                    format: format,
+                    channels: channels,
+                    rate: rate,
+                    centre: centre,
+                    data_start: data_start,
+                    frames: sz / align,
+                    pos: 0,
+                });
+            }
+            let mut chunk = vec![0; sz as usize + (sz % 2) as usize];
+            file.read_exact(&mut chunk)?;
+            if &id == b"fmt " && chunk.len() >= 16 {
+                let mut tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+                if tag == WAVE_EXTENSIBLE && chunk.len() >= 26 {
+                    tag = u16::from_le_bytes([chunk[24], chunk[25]]);
+                }
+                let channels = u16::from_le_bytes([chunk[2], chunk[3]]);
+                let rate = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                let bits = u16::from_le_bytes([chunk[14], chunk[15]]);
+                let format = match (tag, bits) {
+                    (WAVE_FLOAT, 32) => SampleFormat::Float32,
+                    (WAVE_PCM, 24) => SampleFormat::Pcm24,
+                    (WAVE_PCM, 16) => SampleFormat::Pcm16,
+                    _ => return Err(invalid("unsupported sample format")),
+                };
+                fmt = Some((format, channels, rate));
+            } else if &id == b"auxi" && chunk.len() >= 36 {
+                centre = Some(u32::from_le_bytes([chunk[32], chunk[33], chunk[34], chunk[35]]));
+            }
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        return self.channels;
+    }
+
+    pub fn rate(&self) -> u32 {
+        return self.rate;
+    }
+
+    // Centre frequency from the auxi chunk
+    pub fn centre(&self) -> Option<u32> {
+        return self.centre;
+    }
+
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    pub fn position(&self) -> u64 {
+        return self.pos;
+    }
+
+    pub fn seek(&mut self, frame: u64) -> io::Result<()> {
+        self.pos = u64::min(frame, self.frames);
+        let align = (self.channels * self.format.bytes()) as u64;
+        self.file.seek(SeekFrom::Start(self.data_start + self.pos * align))?;
+        return Ok(());
+    }
+
+    // Append up to frames frames to out, returns the number read, 0 at the end
+    pub fn read(&mut self, out: &mut Vec<f32>, frames: usize) -> io::Result<usize> {
+        let n = u64::min(frames as u64, self.frames - self.pos) as usize;
+        let sz = self.format.bytes() as usize;
+        let mut buf = vec![0; n * self.channels as usize * sz];
+        self.file.read_exact(&mut buf)?;
+        for b in buf.chunks_exact(sz) {
+            let s = match self.format {
+                SampleFormat::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                SampleFormat::Pcm24 => (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0,
+                SampleFormat::Pcm16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            };
+            out.push(s);
+        }
+        self.pos += n as u64;
+        return Ok(n);
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, msg);
+}
+
+// Windows SYSTEMTIME as used in the auxi chunk
+fn system_time(t: &UtcTime) -> [u8; 16] {
+    let mut b = [0; 16];
+    let fields = [t.year, t.month, t.weekday, t.day, t.hour, t.minute, t.second, t.millis];
+    for i in 0..fields.len() {
+        b[2 * i..2 * i + 2].copy_from_slice(&(fields[i] as u16).to_le_bytes());
+    }
+    return b;
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn round_trip(format: SampleFormat, name: &str) {
+        let path = env::temp_dir().join(name);
+        let t = UtcTime::now();
+        let samples = [0.5, -0.5, 0.25, -1.0, 0.0, 0.125];
+        let mut w = WavWriter::create(&path, format, 2, 192000, Some((7100000, t))).unwrap();
+        w.write(&samples).unwrap();
+        assert_eq!(w.bytes(), 6 * format.bytes() as u64);
+        w.finish(t).unwrap();
+
+        let mut r = WavReader::open(&path).unwrap();
+        assert_eq!((r.channels(), r.rate(), r.frames()), (2, 192000, 3));
+        assert_eq!(r.centre(), Some(7100000));
+        let mut out = Vec::new();
+        assert_eq!(r.read(&mut out, 10).unwrap(), 3);
+        for i in 0..samples.len() {
+            assert!((out[i] - samples[i]).abs() < 1e-4, "{:?} {} {}", format, out[i], samples[i]);
+        }
+        // Seek back to the second frame
+        r.seek(1).unwrap();
+        out.clear();
+        assert_eq!(r.read(&mut out, 1).unwrap(), 1);
+        assert!((out[0] - 0.25).abs() < 1e-4);
+        assert_eq!(r.read(&mut out, 10).unwrap(), 1);
+        assert_eq!(r.read(&mut out, 10).unwrap(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn float_round_trip() {
+        round_trip(SampleFormat::Float32, "rust_sdr_test_f32.wav");
+    }
+
+    #[test]
+    fn pcm_round_trip() {
+        round_trip(SampleFormat::Pcm24, "rust_sdr_test_24.wav");
+        round_trip(SampleFormat::Pcm16, "rust_sdr_test_16.wav");
+    }
+
+    #[test]
+    fn plain_wav_has_no_centre() {
+        let path = env::temp_dir().join("rust_sdr_test_plain.wav");
+        let w = WavWriter::create(&path, SampleFormat::Pcm16, 1, 48000, None).unwrap();
+        w.finish(UtcTime::now()).unwrap();
+        let r = WavReader::open(&path).unwrap();
+        assert_eq!((r.channels(), r.frames(), r.centre()), (1, 0, None));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn info_follows_data() {
+        let path = env::temp_dir().join("rust_sdr_test_info.wav");
+        let mut w = WavWriter::create(&path, SampleFormat::Pcm16, 2, 48000, None).unwrap();
+        w.add_info(b"INAM", "7.100000 MHz LSB");
+        w.write(&[0.5, -0.5, 0.25, 0.0]).unwrap();
+        w.finish(UtcTime::now()).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let riff = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        assert_eq!(riff, data.len() - 8);
+        // data chunk of 8 bytes then LIST INFO INAM with 17 bytes of text padded to 18
+        assert_eq!(&data[52..56], b"LIST");
+        assert_eq!(&data[64..68], b"INAM");
+        assert_eq!(&data[72..88], b"7.100000 MHz LSB");
+        assert_eq!(data.len(), 72 + 18);
+        let mut r = WavReader::open(&path).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(r.read(&mut out, 10).unwrap(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
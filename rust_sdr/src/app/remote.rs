@@ -0,0 +1,37 @@
+/*
+remote.rs
+
+Module - remote
+Remote control of the radio by other applications
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+pub mod radio;
+pub mod rigctld;
+pub mod ts2000;
+pub mod websocket;
+pub mod web_server;
+pub mod engine;
+pub mod hpsdr;
+pub mod hpsdr_server;
+pub mod rtl_tcp;
+pub mod rtl_tcp_server;
@@ -0,0 +1,263 @@
+/*
+engine.rs
+
+Module - engine
+Headless radio engine, controlled through the web server
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::{Duration, Instant};
+use std::io::{self, BufRead};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::common_defs;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::common::prefs;
+use crate::app::client::radio_client::RadioClient;
+use crate::app::client::local_client::LocalClient;
+use crate::app::remote::websocket::new_token;
+
+// Loop interval, about the UI frame time
+const LOOP_MS: u64 = 25;
+// Spectrum rows to web clients at most this often
+const SPECTRUM_MS: u64 = 100;
+
+//==================================================================================
+// Stands in for the UI when there is none.
+// Changes from the remote servers are applied to the prefs and the radio in the
+// same way the UI does it. The engine runs until "quit" is entered.
+pub struct Engine {
+    prefs : Rc<RefCell<prefs::Prefs>>,
+    client : LocalClient,
+    cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>,
+    // The decoders send here whether or not anyone is listening
+    dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>,
+    web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+    hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+    rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+    out_real : [f32; common_defs::DSP_BLK_SZ as usize],
+    spectrum_time : Instant,
+    // RX2 frequency last published, TX follows it in split
+    rx2_freq : u32,
+}
+
+impl Engine {
+    pub fn new(
+            prefs : Rc<RefCell<prefs::Prefs>>,
+            client : LocalClient,
+            cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>,
+            dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>,
+            web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+            hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+            rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>) -> Engine {
+
+        let rx2_freq = prefs.borrow().radio.rx2.frequency;
+        Engine {
+            prefs: prefs,
+            client: client,
+            cat_receiver: cat_receiver,
+            dec_receiver: dec_receiver,
+            web_sender: web_sender,
+            hpsdr_sender: hpsdr_sender,
+            rtl_sender: rtl_sender,
+            out_real: [0.0; common_defs::DSP_BLK_SZ as usize],
+            spectrum_time: Instant::now(),
+            rx2_freq: rx2_freq,
+        }
+    }
+
+    // Only returns when told to quit
+    pub fn engine_run(&mut self) {
+        self.restore();
+        self.listen();
+
+        let (quit_s, quit_r) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                match line {
+                    Ok(l) if l.trim() == "quit" => {
+                        let _ = quit_s.send(());
+                        break;
+                    },
+                    Ok(_) => println!("Enter quit to close the engine"),
+                    // No console, run until killed
+                    Err(_) => break,
+                }
+            }
+        });
+
+        loop {
+            if quit_r.try_recv().is_ok() {
+                break;
+            }
+            loop {
+                match self.cat_receiver.try_recv() {
+                    Ok(msg) => self.apply(msg),
+                    Err(_) => break,
+                }
+            }
+            // Carry on with any restart
+            self.client.poll();
+            self.publish();
+            self.spectrum();
+            self.decoded();
+            thread::sleep(Duration::from_millis(LOOP_MS));
+        }
+        self.client.set_run(false);
+    }
+
+    // What the UI panels would set at startup
+    fn restore(&mut self) {
+        let (num_rx, sel_rx, af_gain, rate, freqs) = {
+            let p = self.prefs.borrow();
+            (p.radio.num_rx, p.radio.sel_rx, p.radio.af_gain, p.radio.smpl_rate,
+                [p.radio.rx1.frequency, p.radio.rx2.frequency, p.radio.rx3.frequency])
+        };
+        self.client.set_num_rx(num_rx);
+        self.client.set_sel_rx(sel_rx);
+        self.client.set_af_gain(af_gain);
+        for (i, freq) in freqs.iter().enumerate() {
+            self.client.set_freq(i as u32 + 1, *freq);
+        }
+        // The hardware is not running yet, the pipeline changes over on the next poll
+        self.client.set_smpl_rate(rate);
+    }
+
+    // Web clients are the only way in so always listen on the LAN.
+    // The Protocol 1 and rtl_tcp servers are opened when they are enabled in the prefs.
+    fn listen(&mut self) {
+        let mut p = self.prefs.borrow_mut();
+        if p.remote.web_token.len() == 0 {
+            p.remote.web_token = new_token();
+        }
+        let addr = format!("0.0.0.0:{}", p.remote.web_port);
+        println!("Engine access token {}", p.remote.web_token);
+        self.web_sender.send(messages::WebMsg::Listen(addr, p.remote.web_token.clone())).unwrap();
+        if p.remote.hpsdr {
+            let addr = format!("0.0.0.0:{}", p.remote.hpsdr_port);
+            self.hpsdr_sender.send(messages::HpsdrMsg::Listen(addr, p.remote.hpsdr_rx)).unwrap();
+        }
+        if p.remote.rtl_tcp {
+            let mut host = "127.0.0.1";
+            if p.remote.rtl_tcp_lan {
+                host = "0.0.0.0";
+            }
+            let addr = format!("{}:{}", host, p.remote.rtl_tcp_port);
+            self.rtl_sender.send(messages::RtlTcpMsg::Listen(addr, p.remote.rtl_tcp_rx)).unwrap();
+        }
+    }
+
+    fn apply(&mut self, msg: messages::CatMsg) {
+        match msg {
+            messages::CatMsg::SetFreq(rx, freq) => {
+                match rx {
+                    1 => self.prefs.borrow_mut().radio.rx1.frequency = freq,
+                    2 => self.prefs.borrow_mut().radio.rx2.frequency = freq,
+                    3 => self.prefs.borrow_mut().radio.rx3.frequency = freq,
+                    _ => return,
+                }
+                self.client.set_freq(rx, freq);
+            },
+            messages::CatMsg::SetMode(rx, mode) => {
+                match rx {
+                    1 => self.prefs.borrow_mut().radio.rx1.mode = mode,
+                    2 => self.prefs.borrow_mut().radio.rx2.mode = mode,
+                    3 => self.prefs.borrow_mut().radio.rx3.mode = mode,
+                    _ => return,
+                }
+                self.client.set_mode(rx, mode);
+            },
+            messages::CatMsg::SetFilter(rx, filter) => {
+                match rx {
+                    1 => self.prefs.borrow_mut().radio.rx1.filter = filter,
+                    2 => self.prefs.borrow_mut().radio.rx2.filter = filter,
+                    3 => self.prefs.borrow_mut().radio.rx3.filter = filter,
+                    _ => return,
+                }
+                self.client.set_filter(rx, filter);
+            },
+            messages::CatMsg::SetRx(rx) => {
+                self.prefs.borrow_mut().radio.sel_rx = rx;
+                self.client.set_sel_rx(rx);
+            },
+            messages::CatMsg::SetSplit(split) => {
+                globals::set_split(split);
+                let freq = self.prefs.borrow().radio.rx1.frequency;
+                self.client.set_freq(1, freq);
+            },
+            messages::CatMsg::SetAfGain(gain) => {
+                self.prefs.borrow_mut().radio.af_gain = gain;
+                self.client.set_af_gain(gain);
+            },
+            messages::CatMsg::SetRun(run) => self.client.set_run(run),
+        }
+    }
+
+    // The servers read the receiver settings from the globals
+    fn publish(&mut self) {
+        let p = self.prefs.borrow();
+        let rx = [&p.radio.rx1, &p.radio.rx2, &p.radio.rx3];
+        for (i, r) in rx.iter().enumerate() {
+            let id = i as i32 + 1;
+            globals::set_rx_freq(id, r.frequency);
+            globals::set_mode(id, r.mode as u32);
+            globals::set_filter(id, r.filter as u32);
+        }
+        // Retune TX when RX2 moves in split
+        if p.radio.rx2.frequency != self.rx2_freq {
+            self.rx2_freq = p.radio.rx2.frequency;
+            if globals::get_split() {
+                self.client.set_freq(1, p.radio.rx1.frequency);
+            }
+        }
+    }
+
+    // Decoded text goes to web clients, the tuning spectrum and speed are for the UI only
+    fn decoded(&mut self) {
+        loop {
+            match self.dec_receiver.try_recv() {
+                Ok(messages::DecoderMsg::Text(text)) => {
+                    if globals::get_web_clients() > 0 {
+                        self.web_sender.send(messages::WebMsg::Decoded(text)).unwrap();
+                    }
+                },
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
+    }
+
+    // Display rows for web clients, the display is at its default width
+    fn spectrum(&mut self) {
+        if !self.client.display(&mut self.out_real) || globals::get_web_clients() == 0 {
+            return;
+        }
+        if self.spectrum_time.elapsed() < Duration::from_millis(SPECTRUM_MS) {
+            return;
+        }
+        self.spectrum_time = Instant::now();
+        let width = usize::min(globals::get_disp_width() as usize, self.out_real.len());
+        self.web_sender.send(messages::WebMsg::Spectrum(self.out_real[..width].to_vec())).unwrap();
+    }
+}
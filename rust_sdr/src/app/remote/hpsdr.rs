@@ -0,0 +1,256 @@
+/*
+hpsdr.rs
+
+Module - hpsdr
+HPSDR Protocol 1 packets for the radio side
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use crate::app::common::common_defs;
+
+// Every packet starts with the sync bytes then the command
+const SYNC: [u8; 2] = [0xEF, 0xFE];
+const CMD_DISCOVER: u8 = 0x02;
+const CMD_START: u8 = 0x04;
+// Start flags, bit 1 is the wideband data which is not offered
+const START_IQ: u8 = 0x01;
+
+// Discovery reply, the status says if the radio is already sending
+pub const DISCOVER_REPLY_SZ: usize = 60;
+const REPLY_IDLE: u8 = 0x02;
+const REPLY_SENDING: u8 = 0x03;
+// Reported as a Hermes
+const BOARD_HERMES: u8 = 0x01;
+const CODE_VERSION: u8 = 32;
+
+// Each USB frame in a data packet starts with these before the C&C bytes
+const USB_SYNC: [u8; 3] = [0x7F, 0x7F, 0x7F];
+
+// C&C address is C0 bits 1-7, bit 0 is MOX
+const CC_ADDR_CONFIG: u8 = 0x00;
+const CC_ADDR_RX1_FREQ: u8 = 0x02;
+
+// One receiver so 63 samples of IQ and Mic in each of the 2 USB frames
+pub const SMPLS_PER_USB_FRAME: usize = 63;
+pub const IQ_BYTES_PER_PACKET: usize = 2 * SMPLS_PER_USB_FRAME * common_defs::BYTES_PER_SAMPLE as usize;
+
+// Full scale of a 24 bit sample
+const IQ24_SCALE: f32 = 8388608.0;
+
+// What a client sent
+#[derive(PartialEq, Debug)]
+pub enum Request {
+    Discover,
+    // Start the IQ stream or stop
+    Start(bool),
+    // C&C bytes of both USB frames of an EP2 packet, the TX audio and IQ are not used
+    Control([[u8; 5]; 2]),
+}
+
+//==================================================================================
+// None if it is not a Protocol 1 packet we take
+pub fn parse(data: &[u8]) -> Option<Request> {
+    if data.len() < 4 || data[..2] != SYNC {
+        return None;
+    }
+    match data[2] {
+        CMD_DISCOVER => return Some(Request::Discover),
+        CMD_START => return Some(Request::Start(data[3] & START_IQ != 0)),
+        common_defs::DATA_PKT => {
+            if data[3] != common_defs::EP2 || data.len() != common_defs::FRAME_SZ as usize {
+                return None;
+            }
+            let mut cc = [[0u8; 5]; 2];
+            let offsets = [common_defs::FRAME_CC_1_OFFSET, common_defs::FRAME_CC_2_OFFSET];
+            for (i, offset) in offsets.iter().enumerate() {
+                let o = *offset as usize;
+                if data[o - 3..o] != USB_SYNC {
+                    return None;
+                }
+                cc[i].copy_from_slice(&data[o..o + 5]);
+            }
+            return Some(Request::Control(cc));
+        },
+        _ => return None,
+    }
+}
+
+// The receiver 1 frequency in Hz if this is its C&C
+pub fn cc_rx1_freq(cc: &[u8; 5]) -> Option<u32> {
+    if cc[0] >> 1 != CC_ADDR_RX1_FREQ {
+        return None;
+    }
+    return Some(u32::from_be_bytes([cc[1], cc[2], cc[3], cc[4]]));
+}
+
+// The sample rate asked for if this is the general settings C&C
+pub fn cc_smpl_rate(cc: &[u8; 5]) -> Option<u32> {
+    if cc[0] >> 1 != CC_ADDR_CONFIG {
+        return None;
+    }
+    return Some(common_defs::SMPLS_48K << (cc[1] & 0x03));
+}
+
+pub fn discover_reply(mac: &[u8; 6], sending: bool) -> Vec<u8> {
+    let mut p = vec![0u8; DISCOVER_REPLY_SZ];
+    p[..2].copy_from_slice(&SYNC);
+    p[2] = if sending { REPLY_SENDING } else { REPLY_IDLE };
+    p[3..9].copy_from_slice(mac);
+    p[9] = CODE_VERSION;
+    p[10] = BOARD_HERMES;
+    return p;
+}
+
+// An EP6 packet from IQ_BYTES_PER_PACKET of 24 bit IQ as the hardware sends it.
+// The Mic is silent and the C&C is the general status with nothing keyed and no overload.
+pub fn ep6_packet(seq: u32, iq: &[u8]) -> Vec<u8> {
+    let bps = common_defs::BYTES_PER_SAMPLE as usize;
+    let mut p = vec![0u8; common_defs::FRAME_SZ as usize];
+    p[..2].copy_from_slice(&SYNC);
+    p[2] = common_defs::DATA_PKT;
+    p[3] = common_defs::EP6;
+    let seq_offset = common_defs::FRAME_SEQ_OFFSET as usize;
+    p[seq_offset..seq_offset + 4].copy_from_slice(&seq.to_be_bytes());
+    let offsets = [common_defs::FRAME_CC_1_OFFSET, common_defs::FRAME_CC_2_OFFSET];
+    for (frame, offset) in offsets.iter().enumerate() {
+        let cc = *offset as usize;
+        p[cc - 3..cc].copy_from_slice(&USB_SYNC);
+        let mut index = cc + 5;
+        for smpl in 0..SMPLS_PER_USB_FRAME {
+            let src = (frame * SMPLS_PER_USB_FRAME + smpl) * bps;
+            p[index..index + bps].copy_from_slice(&iq[src..src + bps]);
+            index += bps + common_defs::MIC_BYTES_PER_SAMPLE as usize;
+        }
+    }
+    return p;
+}
+
+// 24 bit big endian IQ as the hardware sends it to +-1.0 and back, to change its rate
+pub fn iq24_to_f32(iq: &[u8]) -> Vec<f32> {
+    let mut out = Vec::with_capacity(iq.len() / 3);
+    for s in iq.chunks_exact(3) {
+        let v = i32::from_be_bytes([s[0], s[1], s[2], 0]) >> 8;
+        out.push(v as f32 / IQ24_SCALE);
+    }
+    return out;
+}
+
+pub fn f32_to_iq24(iq: &[f32], out: &mut Vec<u8>) {
+    for x in iq.iter() {
+        let v = f32::min(f32::max(x * IQ24_SCALE, -IQ24_SCALE), IQ24_SCALE - 1.0) as i32;
+        out.extend_from_slice(&v.to_be_bytes()[1..]);
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ep2(cc1: [u8; 5], cc2: [u8; 5]) -> Vec<u8> {
+        let mut p = vec![0u8; common_defs::FRAME_SZ as usize];
+        p[..4].copy_from_slice(&[0xEF, 0xFE, 0x01, 0x02]);
+        p[8..11].copy_from_slice(&USB_SYNC);
+        p[11..16].copy_from_slice(&cc1);
+        p[520..523].copy_from_slice(&USB_SYNC);
+        p[523..528].copy_from_slice(&cc2);
+        return p;
+    }
+
+    #[test]
+    fn parses_requests() {
+        let mut discover = vec![0u8; 63];
+        discover[..3].copy_from_slice(&[0xEF, 0xFE, 0x02]);
+        assert_eq!(parse(&discover), Some(Request::Discover));
+        assert_eq!(parse(&[0xEF, 0xFE, 0x04, 0x01]), Some(Request::Start(true)));
+        assert_eq!(parse(&[0xEF, 0xFE, 0x04, 0x03]), Some(Request::Start(true)));
+        assert_eq!(parse(&[0xEF, 0xFE, 0x04, 0x00]), Some(Request::Start(false)));
+        assert_eq!(parse(&[0xEF, 0xFE, 0x04]), None);
+        assert_eq!(parse(&[0x12, 0x34, 0x02, 0x00]), None);
+
+        let cc1 = [0x00, 0x02, 0x00, 0x00, 0x00];
+        let cc2 = [0x05, 0x00, 0x6C, 0x5E, 0xB8];
+        assert_eq!(parse(&ep2(cc1, cc2)), Some(Request::Control([cc1, cc2])));
+        // Wrong length or lost sync
+        assert_eq!(parse(&ep2(cc1, cc2)[..1000]), None);
+        let mut bad = ep2(cc1, cc2);
+        bad[521] = 0;
+        assert_eq!(parse(&bad), None);
+    }
+
+    #[test]
+    fn decodes_cc() {
+        // MOX bit does not change the address
+        assert_eq!(cc_rx1_freq(&[0x05, 0x00, 0x6C, 0x5E, 0xB8]), Some(7102136));
+        assert_eq!(cc_rx1_freq(&[0x02, 0x00, 0x6C, 0x5E, 0xB8]), None);
+        assert_eq!(cc_smpl_rate(&[0x00, 0x02, 0x00, 0x00, 0x00]), Some(192000));
+        assert_eq!(cc_smpl_rate(&[0x01, 0xFC, 0x00, 0x00, 0x00]), Some(48000));
+        assert_eq!(cc_smpl_rate(&[0x04, 0x00, 0x00, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn discover_reply_format() {
+        let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let p = discover_reply(&mac, false);
+        assert_eq!(p.len(), DISCOVER_REPLY_SZ);
+        assert_eq!(&p[..3], &[0xEF, 0xFE, 0x02]);
+        assert_eq!(&p[3..9], &mac);
+        assert_eq!(p[10], BOARD_HERMES);
+        assert_eq!(discover_reply(&mac, true)[2], 0x03);
+    }
+
+    #[test]
+    fn ep6_layout() {
+        let iq: Vec<u8> = (0..IQ_BYTES_PER_PACKET).map(|i| (i % 251) as u8).collect();
+        let p = ep6_packet(0x01020304, &iq);
+        assert_eq!(p.len(), common_defs::FRAME_SZ as usize);
+        assert_eq!(&p[..8], &[0xEF, 0xFE, 0x01, 0x06, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(&p[8..11], &USB_SYNC);
+        assert_eq!(&p[520..523], &USB_SYNC);
+        // First sample then its silent Mic, the second sample follows
+        assert_eq!(&p[16..22], &iq[..6]);
+        assert_eq!(&p[22..24], &[0, 0]);
+        assert_eq!(&p[24..30], &iq[6..12]);
+        // The second frame carries on from the last sample of the first
+        assert_eq!(&p[16 + 62 * 8..16 + 62 * 8 + 6], &iq[62 * 6..63 * 6]);
+        assert_eq!(&p[528..534], &iq[63 * 6..64 * 6]);
+        assert_eq!(&p[528 + 62 * 8..528 + 62 * 8 + 6], &iq[125 * 6..]);
+    }
+
+    #[test]
+    fn iq24_round_trip() {
+        let iq = [0x7F, 0xFF, 0xFF, 0x80, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF];
+        let f = iq24_to_f32(&iq);
+        assert_eq!(f.len(), 4);
+        assert!((f[0] - 1.0).abs() < 1e-6);
+        assert_eq!(f[1], -1.0);
+        assert!(f[2] > 0.0 && f[3] < 0.0);
+        let mut back = Vec::new();
+        f32_to_iq24(&f, &mut back);
+        assert_eq!(&back[..], &iq[..]);
+        // Out of range is clipped
+        back.clear();
+        f32_to_iq24(&[1.5, -1.5], &mut back);
+        assert_eq!(&back[..], &[0x7F, 0xFF, 0xFF, 0x80, 0x00, 0x00]);
+    }
+}
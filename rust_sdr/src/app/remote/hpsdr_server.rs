@@ -0,0 +1,315 @@
+/*
+hpsdr_server.rs
+
+Module - hpsdr_server
+HPSDR Protocol 1 server sharing one receiver with another application
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::{Duration, Instant};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::remote::hpsdr::{self, Request};
+use crate::app::audio::resampler::Resampler;
+
+// Socket poll interval
+const POLL_MS: u64 = 5;
+
+// The client has gone when no EP2 arrives for this long
+const CLIENT_IDLE_MS: u64 = 3000;
+
+// IQ is dropped when the client is not being sent it fast enough, about 1 second at 384K
+const MAX_PENDING: usize = 384000 * 6;
+
+// Locally administered so it cannot clash with real hardware
+const MAC: [u8; 6] = [0x02, 0x52, 0x53, 0x44, 0x52, 0x01];
+
+//==================================================================================
+// Runtime object for thread.
+// Answers discovery as a Hermes and streams one receiver to the client that starts it.
+// The client tunes that receiver with its RX1 frequency, everything else it sends
+// is ignored as this application keeps control of the radio. The IQ is resampled
+// when the client asks for a rate other than ours.
+pub struct HpsdrData {
+    receiver : crossbeam_channel::Receiver<messages::HpsdrMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    sock : Option<UdpSocket>,
+    // Receiver to share, 1 based
+    rx : u32,
+    client : Option<SocketAddr>,
+    last_seen : Instant,
+    seq : u32,
+    pending : Vec<u8>,
+    // Last frequency and rate the client asked for
+    freq : Option<u32>,
+    rate : Option<u32>,
+    // Hardware rate the resampler was made for, None when the rates match
+    in_rate : u32,
+    resampler : Option<Resampler>,
+}
+
+// Implementation methods on HpsdrData
+impl HpsdrData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::HpsdrMsg>,
+        cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> HpsdrData {
+
+        HpsdrData {
+            receiver: receiver,
+            cat_sender: cat_sender,
+            sock: None,
+            rx: 1,
+            client: None,
+            last_seen: Instant::now(),
+            seq: 0,
+            pending: Vec::new(),
+            freq: None,
+            rate: None,
+            in_rate: 0,
+            resampler: None,
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn hpsdr_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::HpsdrMsg::Terminate => break,
+                        messages::HpsdrMsg::Listen(addr, rx) => self.listen(&addr, rx),
+                        messages::HpsdrMsg::Close => self.close(),
+                        messages::HpsdrMsg::Iq(iq) => self.iq(&iq),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.service();
+            if self.client.is_some() && self.last_seen.elapsed() > Duration::from_millis(CLIENT_IDLE_MS) {
+                self.stop("timed out");
+            }
+        }
+        self.close();
+    }
+
+    fn listen(&mut self, addr: &str, rx: u32) {
+        self.close();
+        self.rx = rx;
+        let r = UdpSocket::bind(addr).and_then(|s| {
+            s.set_nonblocking(true)?;
+            Ok(s)
+        });
+        match r {
+            Ok(sock) => {
+                println!("HPSDR server on {} sharing RX{}", addr, rx);
+                self.sock = Some(sock);
+            },
+            Err(e) => println!("Failed to open the HPSDR server on {}: {:?}", addr, e),
+        }
+    }
+
+    fn close(&mut self) {
+        self.stop("server closed");
+        if self.sock.take().is_some() {
+            println!("HPSDR server closed");
+        }
+    }
+
+    // Read and act on everything waiting
+    fn service(&mut self) {
+        loop {
+            let mut data = [0u8; 2048];
+            let r = match self.sock.as_ref() {
+                Some(sock) => sock.recv_from(&mut data),
+                None => return,
+            };
+            match r {
+                Ok((sz, addr)) => {
+                    if let Some(request) = hpsdr::parse(&data[..sz]) {
+                        self.request(request, addr);
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                // An ICMP unreachable from a client that has gone shows up here
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn request(&mut self, request: Request, addr: SocketAddr) {
+        let ours = self.client == Some(addr);
+        match request {
+            Request::Discover => {
+                let reply = hpsdr::discover_reply(&MAC, self.client.is_some() && !ours);
+                self.send(&reply, addr);
+            },
+            Request::Start(true) => {
+                if ours {
+                    return;
+                }
+                if let Some(client) = self.client {
+                    println!("HPSDR client {} refused, sending to {}", addr, client);
+                    return;
+                }
+                println!("HPSDR client {} started on RX{}", addr, self.rx);
+                if self.rx > globals::get_num_rx() {
+                    println!("RX{} is not running, the HPSDR client will get no IQ", self.rx);
+                }
+                self.client = Some(addr);
+                self.last_seen = Instant::now();
+                self.seq = 0;
+                self.pending.clear();
+                globals::set_hpsdr_rx(self.rx);
+                globals::set_hpsdr_client(addr.to_string());
+            },
+            Request::Start(false) => {
+                if ours {
+                    self.stop("stopped");
+                }
+            },
+            Request::Control(cc) => {
+                if !ours {
+                    return;
+                }
+                self.last_seen = Instant::now();
+                for c in cc.iter() {
+                    // RX1 and the selected receiver are tuned from our VFO so the client
+                    // only tunes the shared receiver while it is neither
+                    if let Some(freq) = hpsdr::cc_rx1_freq(c) {
+                        if self.freq != Some(freq) && self.rx != 1 && self.rx != globals::get_sel_rx() {
+                            self.freq = Some(freq);
+                            self.cat(messages::CatMsg::SetFreq(self.rx, freq));
+                        }
+                    }
+                    if let Some(rate) = hpsdr::cc_smpl_rate(c) {
+                        if self.rate != Some(rate) {
+                            self.rate = Some(rate);
+                            // Made again at the new rate with the next IQ
+                            self.in_rate = 0;
+                            if rate != globals::get_smpl_rate() {
+                                println!("HPSDR client at {} resampled from {}", rate, globals::get_smpl_rate());
+                            }
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    fn stop(&mut self, reason: &str) {
+        if let Some(client) = self.client.take() {
+            println!("HPSDR client {} {}", client, reason);
+        }
+        self.pending.clear();
+        self.freq = None;
+        self.rate = None;
+        self.in_rate = 0;
+        self.resampler = None;
+        globals::set_hpsdr_rx(0);
+        globals::set_hpsdr_client(String::new());
+    }
+
+    // IQ of the shared receiver from the reader, sent on in EP6 packets at the client rate
+    fn iq(&mut self, iq: &[u8]) {
+        let client = match self.client {
+            Some(c) => c,
+            None => return,
+        };
+        let in_rate = globals::get_smpl_rate();
+        if in_rate != self.in_rate {
+            let out_rate = self.rate.unwrap_or(in_rate);
+            self.in_rate = in_rate;
+            self.resampler = None;
+            if out_rate != in_rate {
+                self.resampler = Some(Resampler::new(in_rate, out_rate, 2));
+            }
+        }
+        match self.resampler.as_mut() {
+            Some(r) => {
+                let mut out = Vec::with_capacity(iq.len() / 3);
+                r.process(&hpsdr::iq24_to_f32(iq), &mut out);
+                hpsdr::f32_to_iq24(&out, &mut self.pending);
+            },
+            None => self.pending.extend_from_slice(iq),
+        }
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+        }
+        while self.pending.len() >= hpsdr::IQ_BYTES_PER_PACKET {
+            let packet = hpsdr::ep6_packet(self.seq, &self.pending[..hpsdr::IQ_BYTES_PER_PACKET]);
+            self.pending.drain(..hpsdr::IQ_BYTES_PER_PACKET);
+            self.seq = self.seq.wrapping_add(1);
+            self.send(&packet, client);
+        }
+    }
+
+    fn send(&self, packet: &[u8], addr: SocketAddr) {
+        if let Some(sock) = self.sock.as_ref() {
+            match sock.send_to(packet, addr) {
+                Ok(_) => (),
+                // Full, the client will see a sequence gap
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => println!("HPSDR server send error {:?}", e),
+            }
+        }
+    }
+
+    // Tuning goes through the UI which owns the settings
+    fn cat(&self, msg: messages::CatMsg) {
+        match self.cat_sender.send(msg) {
+            Ok(()) => (),
+            Err(e) => println!("Failed to send CAT command to the UI {:?}", e),
+        }
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn hpsdr_start(
+    receiver : crossbeam_channel::Receiver<messages::HpsdrMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        hpsdr_run(receiver, cat_sender);
+    });
+    return join_handle;
+}
+
+fn hpsdr_run(
+    receiver : crossbeam_channel::Receiver<messages::HpsdrMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) {
+    println!("HPSDR server running");
+
+    // Instantiate the runtime object
+    let mut i_hpsdr = HpsdrData::new(receiver, cat_sender);
+
+    // Exits when the server loop exits
+    i_hpsdr.hpsdr_run();
+
+    println!("HPSDR server exiting");
+}
@@ -0,0 +1,230 @@
+/*
+radio.rs
+
+Module - radio
+Radio state as seen by the remote control servers
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::common::common_defs;
+use crate::app::dsp;
+use crate::app::udp;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+
+// In discriminant order so the global value indexes them
+const MODES: [ModeId; 12] = [
+    ModeId::Lsb, ModeId::Usb, ModeId::Dsb, ModeId::CwL, ModeId::CwU, ModeId::Fm,
+    ModeId::Am, ModeId::DigU, ModeId::Spec, ModeId::DigL, ModeId::Sam, ModeId::Drm,
+];
+
+// Names as shown on the mode buttons, also in discriminant order
+const MODE_NAMES: [&str; 12] = [
+    "LSB", "USB", "DSB", "CW-L", "CW-U", "FM", "AM", "DIG-U", "SPEC", "DIG-L", "SAM", "DRM",
+];
+
+// Filter bandwidths in Hz, also in discriminant order
+const FILTERS: [(FilterId, u32); 9] = [
+    (FilterId::F6_0KHz, 6000),
+    (FilterId::F4_0KHz, 4000),
+    (FilterId::F2_7KHz, 2700),
+    (FilterId::F2_4KHz, 2400),
+    (FilterId::F2_1KHz, 2100),
+    (FilterId::F1_0KHz, 1000),
+    (FilterId::F500Hz, 500),
+    (FilterId::F250Hz, 250),
+    (FilterId::F100Hz, 100),
+];
+
+// Receiver meters, also in discriminant order
+const METERS: [common_defs::MeterType; 7] = [
+    common_defs::MeterType::SPeak, common_defs::MeterType::SAverage,
+    common_defs::MeterType::InputPeak, common_defs::MeterType::InputAverage,
+    common_defs::MeterType::AGCGain, common_defs::MeterType::AGCPEak, common_defs::MeterType::AGCAVerage,
+];
+
+// S9 on HF
+const S9_DBM: f32 = -73.0;
+
+// Tuning range of the hardware, rigctld advertises this in \dump_state
+pub const MAX_FREQ: u32 = 55000000;
+
+pub fn mode_name(mode: ModeId) -> &'static str {
+    return MODE_NAMES[mode as usize];
+}
+
+pub fn mode_by_name(name: &str) -> Option<ModeId> {
+    return MODE_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name)).map(|i| MODES[i]);
+}
+
+pub fn valid_freq(freq: u32) -> bool {
+    return freq <= MAX_FREQ;
+}
+
+pub fn filter_width(filter: FilterId) -> u32 {
+    return FILTERS[filter as usize].1;
+}
+
+// The nearest filter to a bandwidth
+pub fn filter_by_width(width: u32) -> FilterId {
+    let mut best = FILTERS[0];
+    for f in FILTERS.iter() {
+        if (f.1 as i64 - width as i64).abs() < (best.1 as i64 - width as i64).abs() {
+            best = *f;
+        }
+    }
+    return best.0;
+}
+
+//==================================================================================
+// Reads come from the globals, changes are also sent to the UI which owns the settings.
+// The globals are updated at once so a read straight after a set sees the new value.
+pub struct Radio {
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+}
+
+impl Radio {
+    pub fn new(cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> Radio {
+        Radio {
+            cat_sender: cat_sender,
+        }
+    }
+
+    // Receivers are 1 based
+    pub fn freq(&self, rx: u32) -> u32 {
+        return globals::get_rx_freq(rx as i32);
+    }
+
+    // Servers check the range so they can reject a bad frequency, this is the backstop
+    pub fn set_freq(&self, rx: u32, freq: u32) {
+        let freq = u32::min(freq, MAX_FREQ);
+        self.send(messages::CatMsg::SetFreq(rx, freq));
+        globals::set_rx_freq(rx as i32, freq);
+    }
+
+    pub fn mode(&self, rx: u32) -> ModeId {
+        return MODES[usize::min(globals::get_mode(rx as i32) as usize, MODES.len() - 1)];
+    }
+
+    pub fn set_mode(&self, rx: u32, mode: ModeId) {
+        self.send(messages::CatMsg::SetMode(rx, mode));
+        globals::set_mode(rx as i32, mode as u32);
+    }
+
+    // Filter bandwidth in Hz
+    pub fn width(&self, rx: u32) -> u32 {
+        return FILTERS[usize::min(globals::get_filter(rx as i32) as usize, FILTERS.len() - 1)].1;
+    }
+
+    // The nearest filter to the requested bandwidth
+    pub fn set_width(&self, rx: u32, width: u32) {
+        let filter = filter_by_width(width);
+        self.send(messages::CatMsg::SetFilter(rx, filter));
+        globals::set_filter(rx as i32, filter as u32);
+    }
+
+    pub fn sel_rx(&self) -> u32 {
+        return globals::get_sel_rx();
+    }
+
+    pub fn num_rx(&self) -> u32 {
+        return globals::get_num_rx();
+    }
+
+    pub fn set_sel_rx(&self, rx: u32) {
+        self.send(messages::CatMsg::SetRx(rx));
+        globals::set_sel_rx(rx);
+    }
+
+    // Transmitting from any source
+    pub fn ptt(&self) -> bool {
+        return udp::udp_writer::mox();
+    }
+
+    pub fn set_ptt(&self, state: bool) {
+        globals::set_cat_ptt(state);
+    }
+
+    pub fn split(&self) -> bool {
+        return globals::get_split();
+    }
+
+    pub fn set_split(&self, state: bool) {
+        self.send(messages::CatMsg::SetSplit(state));
+        globals::set_split(state);
+    }
+
+    // AF gain 0-100%
+    pub fn af_gain(&self) -> f32 {
+        return globals::get_af_gain();
+    }
+
+    pub fn set_af_gain(&self, gain: f32) {
+        self.send(messages::CatMsg::SetAfGain(gain));
+        globals::set_af_gain(gain);
+    }
+
+    // Hardware was found
+    pub fn discovered(&self) -> bool {
+        return globals::get_discover_state();
+    }
+
+    pub fn running(&self) -> bool {
+        return globals::get_run_state();
+    }
+
+    // The UI starts and stops the hardware
+    pub fn set_run(&self, state: bool) {
+        self.send(messages::CatMsg::SetRun(state));
+    }
+
+    pub fn smpl_rate(&self) -> u32 {
+        return globals::get_smpl_rate();
+    }
+
+    // Selected receiver signal in dBm including the meter calibration
+    pub fn dbm(&self) -> f32 {
+        let ch = globals::get_sel_rx() as i32 - 1;
+        let sig = dsp::dsp_interface::wdsp_get_rx_meter(ch, common_defs::MeterType::SAverage as i32) as f32;
+        return sig + globals::get_meter_cal();
+    }
+
+    // Every meter of the selected receiver without calibration, indexed by MeterType
+    pub fn meters(&self) -> Vec<f32> {
+        let ch = globals::get_sel_rx() as i32 - 1;
+        return METERS.iter().map(|mt| dsp::dsp_interface::wdsp_get_rx_meter(ch, *mt as i32) as f32).collect();
+    }
+
+    // Signal in dB relative to S9
+    pub fn strength(&self) -> f32 {
+        return self.dbm() - S9_DBM;
+    }
+
+    fn send(&self, msg: messages::CatMsg) {
+        match self.cat_sender.send(msg) {
+            Ok(()) => (),
+            Err(e) => println!("Failed to send CAT command to the UI {:?}", e),
+        }
+    }
+}
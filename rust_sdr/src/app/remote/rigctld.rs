@@ -0,0 +1,625 @@
+/*
+rigctld.rs
+
+Module - rigctld
+Hamlib rigctld compatible TCP server
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::Duration;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::remote::radio;
+use crate::app::remote::radio::Radio;
+use crate::app::common::common_defs::ModeId;
+
+// Socket poll interval
+const POLL_MS: u64 = 10;
+
+// Hamlib return codes
+const RIG_OK: i32 = 0;
+const RIG_EINVAL: i32 = -1;
+const RIG_ENIMPL: i32 = -4;
+
+// Hamlib mode names, the first match is used when reporting a mode
+const MODE_NAMES: [(&str, ModeId); 12] = [
+    ("LSB", ModeId::Lsb),
+    ("USB", ModeId::Usb),
+    ("DSB", ModeId::Dsb),
+    ("CWR", ModeId::CwL),
+    ("CW", ModeId::CwU),
+    ("FM", ModeId::Fm),
+    ("AM", ModeId::Am),
+    ("PKTUSB", ModeId::DigU),
+    ("PKTLSB", ModeId::DigL),
+    ("SAM", ModeId::Sam),
+    // No Hamlib equivalent so reported as the nearest
+    ("DSB", ModeId::Spec),
+    ("AM", ModeId::Drm),
+];
+
+// Capabilities for \dump_state, protocol version 1.
+// Modes are the Hamlib bits for the names above, the first filter for a mode is its normal width.
+const DUMP_STATE: &str = "1
+2
+0
+10000.000000 55000000.000000 0x90caf -1 -1 0x7 0x0
+0 0 0 0 0 0 0
+1800000.000000 55000000.000000 0x90caf -1 -1 0x7 0x0
+0 0 0 0 0 0 0
+0x90caf 1
+0 0
+0xc0c 2400
+0xc0c 2700
+0xc0c 2100
+0xc0c 1000
+0x82 500
+0x82 250
+0x82 100
+0x82 1000
+0x90001 6000
+0x90001 4000
+0x20 6000
+0 0
+0
+0
+0
+0
+0
+0
+0x0
+0x0
+0x40000000
+0x0
+0x0
+0x0
+vfo_ops=0x0
+ptt_type=0x1
+targetable_vfo=0x0
+has_set_vfo=1
+has_get_vfo=1
+has_set_freq=1
+has_get_freq=1
+has_set_conf=0
+has_get_conf=0
+has_power2mW=0
+has_mW2power=0
+timeout=0
+done";
+
+// The long form of each short command
+fn long_name(c: char) -> Option<&'static str> {
+    match c {
+        'F' => return Some("set_freq"),
+        'f' => return Some("get_freq"),
+        'M' => return Some("set_mode"),
+        'm' => return Some("get_mode"),
+        'V' => return Some("set_vfo"),
+        'v' => return Some("get_vfo"),
+        'T' => return Some("set_ptt"),
+        't' => return Some("get_ptt"),
+        'S' => return Some("set_split_vfo"),
+        's' => return Some("get_split_vfo"),
+        'I' => return Some("set_split_freq"),
+        'i' => return Some("get_split_freq"),
+        'X' => return Some("set_split_mode"),
+        'x' => return Some("get_split_mode"),
+        'l' => return Some("get_level"),
+        'L' => return Some("set_level"),
+        '_' => return Some("get_info"),
+        'q' | 'Q' => return Some("quit"),
+        _ => return None,
+    }
+}
+
+fn mode_name(mode: ModeId) -> &'static str {
+    for (name, m) in MODE_NAMES.iter() {
+        if *m == mode {
+            return name;
+        }
+    }
+    return "USB";
+}
+
+fn vfo_name(rx: u32) -> &'static str {
+    match rx {
+        2 => return "VFOB",
+        3 => return "VFOC",
+        _ => return "VFOA",
+    }
+}
+
+// VFOA, B and C are the receivers, Main and Sub are RX1 and RX2
+fn vfo_rx(name: &str, sel_rx: u32) -> Option<u32> {
+    match name {
+        "VFOA" | "Main" | "RX" => return Some(1),
+        "VFOB" | "Sub" | "TX" => return Some(2),
+        "VFOC" => return Some(3),
+        "currVFO" | "VFO" => return Some(sel_rx),
+        _ => return None,
+    }
+}
+
+// Frequencies may be sent with decimals, outside the tuning range is invalid
+fn parse_freq(arg: Option<&&str>) -> Option<u32> {
+    match arg.and_then(|a| a.parse::<f64>().ok()) {
+        Some(f) if f >= 0.0 && f <= radio::MAX_FREQ as f64 => return Some(f.round() as u32),
+        _ => return None,
+    }
+}
+
+//==================================================================================
+// Result of a command, labels are used in extended response mode
+struct Reply {
+    values: Vec<(&'static str, String)>,
+    code: i32,
+}
+
+impl Reply {
+    fn ok() -> Reply {
+        return Reply { values: Vec::new(), code: RIG_OK };
+    }
+
+    fn err(code: i32) -> Reply {
+        return Reply { values: Vec::new(), code: code };
+    }
+
+    fn value(label: &'static str, value: String) -> Reply {
+        return Reply { values: vec![(label, value)], code: RIG_OK };
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    addr: String,
+    buf: Vec<u8>,
+}
+
+//==================================================================================
+// Runtime object for thread
+pub struct RigctldData {
+    receiver : crossbeam_channel::Receiver<messages::RigctlMsg>,
+    radio : Radio,
+    listener : Option<TcpListener>,
+    clients : Vec<Client>,
+    // Client that set PTT on, and a PTT set by the command in progress
+    tx_client : Option<String>,
+    ptt_set : Option<bool>,
+}
+
+// Implementation methods on RigctldData
+impl RigctldData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::RigctlMsg>,
+        cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> RigctldData {
+
+        RigctldData {
+            receiver: receiver,
+            radio: Radio::new(cat_sender),
+            listener: None,
+            clients: Vec::new(),
+            tx_client: None,
+            ptt_set: None,
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn rigctld_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::RigctlMsg::Terminate => break,
+                        messages::RigctlMsg::Listen(addr) => self.listen(&addr),
+                        messages::RigctlMsg::Close => self.close(),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.accept();
+            self.service();
+        }
+        self.close();
+    }
+
+    fn listen(&mut self, addr: &str) {
+        self.close();
+        let r = TcpListener::bind(addr).and_then(|l| {
+            l.set_nonblocking(true)?;
+            Ok(l)
+        });
+        match r {
+            Ok(listener) => {
+                println!("Rigctl listening on {}", addr);
+                self.listener = Some(listener);
+            },
+            Err(e) => println!("Failed to listen for rigctl clients on {}: {:?}", addr, e),
+        }
+    }
+
+    fn close(&mut self) {
+        if self.listener.take().is_some() {
+            println!("Rigctl closed");
+            // Don't leave the radio transmitting
+            self.radio.set_ptt(false);
+        }
+        self.clients.clear();
+        self.tx_client = None;
+        globals::set_rigctl_clients(0);
+    }
+
+    fn accept(&mut self) {
+        let listener = match self.listener.as_ref() {
+            Some(l) => l,
+            None => return,
+        };
+        loop {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    let _ = stream.set_nonblocking(true);
+                    let _ = stream.set_nodelay(true);
+                    println!("Rigctl client connected from {}", addr);
+                    self.clients.push(Client { stream: stream, addr: addr.to_string(), buf: Vec::new() });
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Rigctl accept error {:?}", e);
+                    break;
+                },
+            }
+        }
+        globals::set_rigctl_clients(self.clients.len() as u32);
+    }
+
+    // Read and answer complete command lines from each client
+    fn service(&mut self) {
+        let mut i = 0;
+        while i < self.clients.len() {
+            let mut open = true;
+            let mut data = [0; 1024];
+            loop {
+                match self.clients[i].stream.read(&mut data) {
+                    Ok(0) => {
+                        open = false;
+                        break;
+                    },
+                    Ok(n) => self.clients[i].buf.extend_from_slice(&data[..n]),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(_e) => {
+                        open = false;
+                        break;
+                    },
+                }
+            }
+            while open {
+                let pos = match self.clients[i].buf.iter().position(|b| *b == b'\n') {
+                    Some(pos) => pos,
+                    None => break,
+                };
+                let line: Vec<u8> = self.clients[i].buf.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+                if line.len() == 0 {
+                    continue;
+                }
+                match self.command(&line) {
+                    Some(resp) => {
+                        if self.clients[i].stream.write_all(resp.as_bytes()).is_err() {
+                            open = false;
+                        }
+                    },
+                    None => open = false,
+                }
+                if let Some(state) = self.ptt_set.take() {
+                    self.tx_client = None;
+                    if state {
+                        self.tx_client = Some(self.clients[i].addr.clone());
+                    }
+                }
+            }
+            if open {
+                i += 1;
+            } else {
+                println!("Rigctl client {} disconnected", self.clients[i].addr);
+                // Don't leave the radio transmitting for a client that has gone
+                if self.tx_client.as_ref() == Some(&self.clients[i].addr) {
+                    self.tx_client = None;
+                    self.radio.set_ptt(false);
+                }
+                self.clients.remove(i);
+                globals::set_rigctl_clients(self.clients.len() as u32);
+            }
+        }
+    }
+
+    // Response text for a command line or None to disconnect.
+    // A leading + asks for the extended response with labels.
+    fn command(&mut self, line: &str) -> Option<String> {
+        let mut line = line;
+        let mut extended = false;
+        if line.starts_with('+') {
+            extended = true;
+            line = &line[1..];
+        }
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() == 0 {
+            return Some(String::new());
+        }
+        let first = tokens.remove(0);
+        let name;
+        if first.starts_with('\\') {
+            name = &first[1..];
+        } else {
+            let c = first.chars().next().unwrap();
+            // Short commands may run into their first argument
+            if first.len() > c.len_utf8() {
+                tokens.insert(0, &first[c.len_utf8()..]);
+            }
+            match long_name(c) {
+                Some(n) => name = n,
+                None => return Some(format!("RPRT {}\n", RIG_ENIMPL)),
+            }
+        }
+        if name == "quit" {
+            return None;
+        }
+        if name == "dump_state" {
+            return Some(format!("{}\n", DUMP_STATE));
+        }
+
+        let reply = self.execute(name, &tokens);
+        let mut resp = String::new();
+        if extended {
+            resp += name;
+            resp += ":";
+            for t in tokens.iter() {
+                resp += " ";
+                resp += t;
+            }
+            resp += "\n";
+            for (label, value) in reply.values.iter() {
+                resp += &format!("{}: {}\n", label, value);
+            }
+            resp += &format!("RPRT {}\n", reply.code);
+        } else if reply.values.len() > 0 && reply.code == RIG_OK {
+            for (_, value) in reply.values.iter() {
+                resp += value;
+                resp += "\n";
+            }
+        } else {
+            resp += &format!("RPRT {}\n", reply.code);
+        }
+        return Some(resp);
+    }
+
+    // Commands apply to the selected receiver
+    fn execute(&mut self, name: &str, args: &[&str]) -> Reply {
+        let rx = self.radio.sel_rx();
+        match name {
+            "get_freq" => return Reply::value("Frequency", self.radio.freq(rx).to_string()),
+            "set_freq" => {
+                match parse_freq(args.get(0)) {
+                    Some(f) => self.radio.set_freq(rx, f),
+                    None => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "get_mode" => {
+                return Reply {
+                    values: vec![
+                        ("Mode", String::from(mode_name(self.radio.mode(rx)))),
+                        ("Passband", self.radio.width(rx).to_string()),
+                    ],
+                    code: RIG_OK,
+                };
+            },
+            "set_mode" => {
+                let mode = MODE_NAMES.iter().find(|(n, _)| Some(n) == args.get(0));
+                match mode {
+                    Some((_, m)) => self.radio.set_mode(rx, *m),
+                    None => return Reply::err(RIG_EINVAL),
+                }
+                // 0 is the default width and -1 no change, both leave the filter alone
+                match args.get(1).and_then(|a| a.parse::<i32>().ok()) {
+                    Some(pb) if pb > 0 => self.radio.set_width(rx, pb as u32),
+                    _ => (),
+                }
+            },
+            "get_vfo" => return Reply::value("VFO", String::from(vfo_name(rx))),
+            "set_vfo" => {
+                match args.get(0).and_then(|a| vfo_rx(a, rx)) {
+                    Some(n) if n <= self.radio.num_rx() => self.radio.set_sel_rx(n),
+                    _ => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "get_ptt" => {
+                let mut ptt = "0";
+                if self.radio.ptt() {
+                    ptt = "1";
+                }
+                return Reply::value("PTT", String::from(ptt));
+            },
+            "set_ptt" => {
+                match args.get(0).and_then(|a| a.parse::<u32>().ok()) {
+                    Some(p) => {
+                        self.radio.set_ptt(p != 0);
+                        self.ptt_set = Some(p != 0);
+                    },
+                    None => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "get_split_vfo" => {
+                let mut split = ("0", "VFOA");
+                if self.radio.split() {
+                    split = ("1", "VFOB");
+                }
+                return Reply {
+                    values: vec![("Split", String::from(split.0)), ("TX VFO", String::from(split.1))],
+                    code: RIG_OK,
+                };
+            },
+            "set_split_vfo" => {
+                // TX is always on the VFOB frequency in split
+                match args.get(0).and_then(|a| a.parse::<u32>().ok()) {
+                    Some(s) => self.radio.set_split(s != 0),
+                    None => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "get_split_freq" => return Reply::value("TX Frequency", self.radio.freq(2).to_string()),
+            "set_split_freq" => {
+                match parse_freq(args.get(0)) {
+                    Some(f) => self.radio.set_freq(2, f),
+                    None => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "get_split_mode" => {
+                // TX follows the RX1 mode
+                return Reply {
+                    values: vec![
+                        ("TX Mode", String::from(mode_name(self.radio.mode(1)))),
+                        ("TX Passband", self.radio.width(1).to_string()),
+                    ],
+                    code: RIG_OK,
+                };
+            },
+            "set_split_mode" => (),
+            "get_level" => {
+                match args.get(0) {
+                    Some(&"STRENGTH") => return Reply::value("Level Value", format!("{}", self.radio.strength().round() as i32)),
+                    Some(&"AF") => return Reply::value("Level Value", format!("{:.6}", globals::get_af_gain() / 100.0)),
+                    _ => return Reply::err(RIG_EINVAL),
+                }
+            },
+            "chk_vfo" => return Reply::value("ChkVFO", String::from("0")),
+            "get_powerstat" => return Reply::value("Power Status", String::from("1")),
+            "get_info" => return Reply::value("Info", String::from("RustSDR")),
+            _ => return Reply::err(RIG_ENIMPL),
+        }
+        return Reply::ok();
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn rigctld_start(
+    receiver : crossbeam_channel::Receiver<messages::RigctlMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        rigctld_run(receiver, cat_sender);
+    });
+    return join_handle;
+}
+
+fn rigctld_run(
+    receiver : crossbeam_channel::Receiver<messages::RigctlMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) {
+    println!("Rigctl server running");
+
+    // Instantiate the runtime object
+    let mut i_rigctld = RigctldData::new(receiver, cat_sender);
+
+    // Exits when the server loop exits
+    i_rigctld.rigctld_run();
+
+    println!("Rigctl server exiting");
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::common::common_defs::FilterId;
+
+    #[test]
+    fn names_and_arguments() {
+        assert_eq!(long_name('F'), Some("set_freq"));
+        assert_eq!(long_name('m'), Some("get_mode"));
+        assert_eq!(long_name('Q'), Some("quit"));
+        assert_eq!(long_name('z'), None);
+        assert_eq!(vfo_rx("VFOB", 1), Some(2));
+        assert_eq!(vfo_rx("Main", 3), Some(1));
+        assert_eq!(vfo_rx("currVFO", 3), Some(3));
+        assert_eq!(vfo_rx("VFOD", 1), None);
+        assert_eq!(parse_freq(Some(&"7100000")), Some(7100000));
+        assert_eq!(parse_freq(Some(&"7100000.6")), Some(7100001));
+        assert_eq!(parse_freq(Some(&"55000000")), Some(55000000));
+        assert_eq!(parse_freq(Some(&"55000001")), None);
+        assert_eq!(parse_freq(Some(&"-1")), None);
+        assert_eq!(parse_freq(Some(&"abc")), None);
+        assert_eq!(parse_freq(None), None);
+    }
+
+    // One test as the radio state is in the globals
+    #[test]
+    fn command_replies() {
+        let (_rig_s, rig_r) = crossbeam_channel::unbounded();
+        let (cat_s, cat_r) = crossbeam_channel::unbounded();
+        let mut rig = RigctldData::new(rig_r, cat_s);
+        globals::set_num_rx(2);
+        globals::set_sel_rx(1);
+        let mut cmd = |line: &str| rig.command(line);
+
+        // Short and long forms, a short command may run into its argument
+        assert_eq!(cmd("F 7100000").as_deref(), Some("RPRT 0\n"));
+        assert!(matches!(cat_r.try_recv(), Ok(messages::CatMsg::SetFreq(1, 7100000))));
+        assert_eq!(cmd("f").as_deref(), Some("7100000\n"));
+        assert_eq!(cmd("F14074000").as_deref(), Some("RPRT 0\n"));
+        assert_eq!(cmd("\\get_freq").as_deref(), Some("14074000\n"));
+
+        // Extended replies echo the command and label the values
+        assert_eq!(cmd("+f").as_deref(), Some("get_freq:\nFrequency: 14074000\nRPRT 0\n"));
+        assert_eq!(cmd("+\\set_freq 7000000").as_deref(), Some("set_freq: 7000000\nRPRT 0\n"));
+
+        // The passband sets the nearest filter, 0 and -1 leave it alone
+        while cat_r.try_recv().is_ok() {}
+        assert_eq!(cmd("M USB 2400").as_deref(), Some("RPRT 0\n"));
+        assert!(matches!(cat_r.try_recv(), Ok(messages::CatMsg::SetMode(1, ModeId::Usb))));
+        assert!(matches!(cat_r.try_recv(), Ok(messages::CatMsg::SetFilter(1, FilterId::F2_4KHz))));
+        assert_eq!(cmd("m").as_deref(), Some("USB\n2400\n"));
+        assert_eq!(cmd("M LSB 0").as_deref(), Some("RPRT 0\n"));
+        assert_eq!(cmd("M CW -1").as_deref(), Some("RPRT 0\n"));
+        assert_eq!(cmd("+m").as_deref(), Some("get_mode:\nMode: CW\nPassband: 2400\nRPRT 0\n"));
+
+        // Invalid arguments and unknown commands
+        assert_eq!(cmd("F abc").as_deref(), Some("RPRT -1\n"));
+        assert_eq!(cmd("F 60000000").as_deref(), Some("RPRT -1\n"));
+        assert_eq!(cmd("M XYZ").as_deref(), Some("RPRT -1\n"));
+        assert_eq!(cmd("V VFOC").as_deref(), Some("RPRT -1\n"));
+        assert_eq!(cmd("T on").as_deref(), Some("RPRT -1\n"));
+        assert_eq!(cmd("+\\set_freq").as_deref(), Some("set_freq:\nRPRT -1\n"));
+        assert_eq!(cmd("z").as_deref(), Some("RPRT -4\n"));
+        assert_eq!(cmd("\\set_nothing").as_deref(), Some("RPRT -4\n"));
+        assert_eq!(cmd("f").as_deref(), Some("7000000\n"));
+
+        // Selecting a receiver that is running
+        assert_eq!(cmd("V VFOB").as_deref(), Some("RPRT 0\n"));
+        assert_eq!(cmd("v").as_deref(), Some("VFOB\n"));
+
+        assert_eq!(cmd("").as_deref(), Some(""));
+        assert_eq!(cmd("q"), None);
+    }
+}
@@ -0,0 +1,217 @@
+/*
+rtl_tcp.rs
+
+Module - rtl_tcp
+rtl_tcp protocol and 8 bit IQ conversion
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+// Sent once on connect, the magic then the tuner type and the number of gains it has
+pub const HEADER_SZ: usize = 12;
+const MAGIC: &[u8; 4] = b"RTL0";
+// Reported as an R820T so clients use its gain table
+const TUNER_R820T: u32 = 5;
+// R820T gains in tenths of a dB
+const GAINS: [u32; 29] = [
+    0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254,
+    280, 297, 328, 338, 364, 372, 386, 402, 421, 434, 439, 445, 480, 496,
+];
+
+// Commands are a byte then a u32 parameter in network order
+pub const CMD_SZ: usize = 5;
+const CMD_FREQ: u8 = 0x01;
+const CMD_RATE: u8 = 0x02;
+const CMD_GAIN_MODE: u8 = 0x03;
+const CMD_GAIN: u8 = 0x04;
+const CMD_GAIN_INDEX: u8 = 0x0d;
+
+// With manual gain the 8 bit full scale is this far below the hardware full scale
+// at the bottom of each 10dB attenuator step, the client gain within the step is added.
+const MANUAL_GAIN_DB: f64 = 50.0;
+
+// With auto gain the level follows the peaks as 8 bits cannot hold the range of the hardware.
+// Peaks are put here and the level comes back up 20dB in RELEASE_SECS, no further than MAX_GAIN.
+const LEVEL_TARGET: f64 = 0.5;
+const MAX_GAIN: f64 = 1000.0;
+const RELEASE_SECS: f64 = 2.0;
+
+#[derive(PartialEq, Debug)]
+pub enum Command {
+    Freq(u32),
+    Rate(u32),
+    // True for manual gain
+    GainMode(bool),
+    // Tenths of a dB
+    Gain(u32),
+    // Anything else is accepted and ignored
+    Other(u8, u32),
+}
+
+//==================================================================================
+pub fn header() -> [u8; HEADER_SZ] {
+    let mut h = [0u8; HEADER_SZ];
+    h[..4].copy_from_slice(MAGIC);
+    h[4..8].copy_from_slice(&TUNER_R820T.to_be_bytes());
+    h[8..].copy_from_slice(&(GAINS.len() as u32).to_be_bytes());
+    return h;
+}
+
+pub fn parse(data: &[u8; CMD_SZ]) -> Command {
+    let param = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+    match data[0] {
+        CMD_FREQ => return Command::Freq(param),
+        CMD_RATE => return Command::Rate(param),
+        CMD_GAIN_MODE => return Command::GainMode(param != 0),
+        CMD_GAIN => return Command::Gain(param),
+        CMD_GAIN_INDEX => return Command::Gain(GAINS[usize::min(param as usize, GAINS.len() - 1)]),
+        cmd => return Command::Other(cmd, param),
+    }
+}
+
+// Attenuator step 0-3 and preamp for a gain in tenths of a dB.
+// Each 10dB less than 30dB is another attenuator step, the preamp is the top 10dB.
+pub fn rf_gain(tenths: u32) -> (u32, bool) {
+    let db = tenths / 10;
+    if db >= 40 {
+        return (0, true);
+    }
+    return (3 - u32::min(db / 10, 3), false);
+}
+
+// Gain in dB applied to the IQ for a manual gain, the part of the client gain
+// the attenuator and preamp steps do not give
+pub fn level_gain(tenths: u32) -> f64 {
+    let (attn, preamp) = rf_gain(tenths);
+    let mut step_db = 30.0 - 10.0 * attn as f64;
+    if preamp {
+        step_db = 40.0;
+    }
+    let fine = f64::min(f64::max(tenths as f64 / 10.0 - step_db, 0.0), 10.0);
+    return MANUAL_GAIN_DB + fine;
+}
+
+//==================================================================================
+// Scales IQ in the range +-1.0 to the unsigned 8 bit samples rtl_tcp sends
+pub struct Level {
+    peak: f64,
+    decay: f64,
+    // Fixed gain, otherwise it follows the peaks
+    fixed: Option<f64>,
+}
+
+impl Level {
+    // Rate in IQ samples a second
+    pub fn new(rate: u32) -> Level {
+        Level {
+            peak: 0.0,
+            decay: 10f64.powf(-1.0 / (RELEASE_SECS * rate as f64 * 2.0)),
+            fixed: None,
+        }
+    }
+
+    // Gain in dB for manual gain, None for auto
+    pub fn set_gain(&mut self, gain_db: Option<f64>) {
+        self.fixed = gain_db.map(|db| 10f64.powf(db / 20.0));
+    }
+
+    pub fn to_u8(&mut self, iq: &[f32], out: &mut Vec<u8>) {
+        for x in iq.iter() {
+            let x = *x as f64;
+            self.peak = f64::max(x.abs(), self.peak * self.decay);
+            let gain = match self.fixed {
+                Some(g) => g,
+                None => f64::min(LEVEL_TARGET / f64::max(self.peak, 1e-12), MAX_GAIN),
+            };
+            let v = x * gain * 127.5 + 127.5;
+            out.push(f64::min(f64::max(v.round(), 0.0), 255.0) as u8);
+        }
+    }
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_and_commands() {
+        let h = header();
+        assert_eq!(&h[..4], b"RTL0");
+        assert_eq!(&h[4..], &[0, 0, 0, 5, 0, 0, 0, 29]);
+        assert_eq!(parse(&[0x01, 0x00, 0x6C, 0x5E, 0xB8]), Command::Freq(7102136));
+        assert_eq!(parse(&[0x02, 0x00, 0x03, 0xA9, 0x80]), Command::Rate(240000));
+        assert_eq!(parse(&[0x03, 0, 0, 0, 1]), Command::GainMode(true));
+        assert_eq!(parse(&[0x03, 0, 0, 0, 0]), Command::GainMode(false));
+        assert_eq!(parse(&[0x04, 0, 0, 1, 0x2C]), Command::Gain(300));
+        assert_eq!(parse(&[0x0d, 0, 0, 0, 2]), Command::Gain(14));
+        assert_eq!(parse(&[0x0d, 0, 0, 1, 0]), Command::Gain(496));
+        assert_eq!(parse(&[0x05, 0, 0, 0, 3]), Command::Other(0x05, 3));
+    }
+
+    #[test]
+    fn gain_steps() {
+        assert_eq!(rf_gain(0), (3, false));
+        assert_eq!(rf_gain(125), (2, false));
+        assert_eq!(rf_gain(254), (1, false));
+        assert_eq!(rf_gain(364), (0, false));
+        assert_eq!(rf_gain(496), (0, true));
+        // Within a step the rest of the gain is applied to the IQ
+        assert_eq!(level_gain(300), MANUAL_GAIN_DB);
+        assert!((level_gain(364) - MANUAL_GAIN_DB - 6.4).abs() < 1e-9);
+        assert!((level_gain(77) - MANUAL_GAIN_DB - 7.7).abs() < 1e-9);
+        assert!((level_gain(496) - MANUAL_GAIN_DB - 9.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn level_fills_8_bits() {
+        let mut l = Level::new(48000);
+        let mut out = Vec::new();
+        l.to_u8(&[0.0, 0.0], &mut out);
+        assert_eq!(out, vec![128, 128]);
+        // A weak signal is brought up to the target
+        out.clear();
+        let weak: Vec<f32> = [1e-3, -1e-3].repeat(480);
+        l.to_u8(&weak, &mut out);
+        let max = out[100..].iter().max().unwrap();
+        let min = out[100..].iter().min().unwrap();
+        assert!(*max >= 190 && *max <= 193);
+        assert!(*min >= 63 && *min <= 66);
+        // but noise is not brought up to full scale
+        out.clear();
+        let noise: Vec<f32> = [1e-6, -1e-6].repeat(480);
+        l.to_u8(&noise, &mut out);
+        assert!(out.iter().all(|x| *x >= 127 && *x <= 128));
+    }
+
+    #[test]
+    fn manual_level_is_fixed() {
+        let mut l = Level::new(48000);
+        l.set_gain(Some(20.0));
+        let mut out = Vec::new();
+        // 20dB up from 0.01 is 0.1 of full scale whatever went before
+        l.to_u8(&[0.9, -0.9], &mut out);
+        out.clear();
+        l.to_u8(&[0.01, -0.01], &mut out);
+        assert_eq!(out, vec![140, 115]);
+    }
+}
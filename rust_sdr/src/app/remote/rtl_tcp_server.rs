@@ -0,0 +1,385 @@
+/*
+rtl_tcp_server.rs
+
+Module - rtl_tcp_server
+rtl_tcp server sharing one receiver with another application
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::app::protocol;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::common::cc_out_defs;
+use crate::app::remote::rtl_tcp::{self, Command, Level};
+use crate::app::audio::resampler::Resampler;
+
+// Socket poll interval
+const POLL_MS: u64 = 5;
+
+// IQ is dropped when the client is not taking it fast enough, about 2 seconds at 2.4M
+const MAX_BACKLOG: usize = 10000000;
+
+//==================================================================================
+// Runtime object for thread.
+// Streams one receiver to a single client as 8 bit IQ at the rate it asks for.
+// The client tunes that receiver and while it has manual gain set it drives the
+// attenuator and preamp and the IQ is at a fixed level, in auto gain the level
+// follows the signal. The hardware sample rate stays with this application,
+// the IQ is resampled to the client rate.
+pub struct RtlTcpData {
+    receiver : crossbeam_channel::Receiver<messages::RtlTcpMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    listener : Option<TcpListener>,
+    client : Option<TcpStream>,
+    // Receiver to share, 1 based
+    rx : u32,
+    inbuf : Vec<u8>,
+    outbuf : Vec<u8>,
+    // Last frequency and rate the client asked for
+    freq : Option<u32>,
+    rate : Option<u32>,
+    // Hardware rate the resampler was made for
+    in_rate : u32,
+    resampler : Option<Resampler>,
+    level : Option<Level>,
+    // Gain the client set, only used in manual mode
+    manual : bool,
+    gain : Option<u32>,
+}
+
+// Implementation methods on RtlTcpData
+impl RtlTcpData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::RtlTcpMsg>,
+        cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+        i_cc : Arc<Mutex<protocol::cc_out::CCData>>) -> RtlTcpData {
+
+        RtlTcpData {
+            receiver: receiver,
+            cat_sender: cat_sender,
+            i_cc: i_cc,
+            listener: None,
+            client: None,
+            rx: 1,
+            inbuf: Vec::new(),
+            outbuf: Vec::new(),
+            freq: None,
+            rate: None,
+            in_rate: 0,
+            resampler: None,
+            level: None,
+            manual: false,
+            gain: None,
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn rtl_tcp_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::RtlTcpMsg::Terminate => break,
+                        messages::RtlTcpMsg::Listen(addr, rx) => self.listen(&addr, rx),
+                        messages::RtlTcpMsg::Close => self.close(),
+                        messages::RtlTcpMsg::Iq(iq) => self.iq(&iq),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.accept();
+            self.service();
+            self.flush();
+        }
+        self.close();
+    }
+
+    fn listen(&mut self, addr: &str, rx: u32) {
+        self.close();
+        self.rx = rx;
+        let r = TcpListener::bind(addr).and_then(|l| {
+            l.set_nonblocking(true)?;
+            Ok(l)
+        });
+        match r {
+            Ok(listener) => {
+                println!("rtl_tcp server listening on {} sharing RX{}", addr, rx);
+                self.listener = Some(listener);
+            },
+            Err(e) => println!("Failed to listen for rtl_tcp clients on {}: {:?}", addr, e),
+        }
+    }
+
+    fn close(&mut self) {
+        self.drop_client("server closed");
+        if self.listener.take().is_some() {
+            println!("rtl_tcp server closed");
+        }
+    }
+
+    fn accept(&mut self) {
+        let listener = match self.listener.as_ref() {
+            Some(l) => l,
+            None => return,
+        };
+        loop {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    if self.client.is_some() {
+                        println!("rtl_tcp client {} refused, one client at a time", addr);
+                        continue;
+                    }
+                    let _ = stream.set_nonblocking(true);
+                    let _ = stream.set_nodelay(true);
+                    println!("rtl_tcp client {} connected on RX{}", addr, self.rx);
+                    if self.rx > globals::get_num_rx() {
+                        println!("RX{} is not running, the rtl_tcp client will get no IQ", self.rx);
+                    }
+                    self.client = Some(stream);
+                    self.inbuf.clear();
+                    self.outbuf.clear();
+                    self.outbuf.extend_from_slice(&rtl_tcp::header());
+                    globals::set_rtl_tcp_rx(self.rx);
+                    globals::set_rtl_tcp_client(addr.to_string());
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("rtl_tcp server accept error {:?}", e);
+                    break;
+                },
+            }
+        }
+    }
+
+    // Read and act on complete commands
+    fn service(&mut self) {
+        let mut data = [0; 1024];
+        loop {
+            let r = match self.client.as_mut() {
+                Some(stream) => stream.read(&mut data),
+                None => return,
+            };
+            match r {
+                Ok(0) => {
+                    self.drop_client("disconnected");
+                    return;
+                },
+                Ok(sz) => self.inbuf.extend_from_slice(&data[..sz]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.drop_client(&format!("read error {:?}", e));
+                    return;
+                },
+            }
+        }
+        while self.inbuf.len() >= rtl_tcp::CMD_SZ {
+            let mut cmd = [0u8; rtl_tcp::CMD_SZ];
+            cmd.copy_from_slice(&self.inbuf[..rtl_tcp::CMD_SZ]);
+            self.inbuf.drain(..rtl_tcp::CMD_SZ);
+            self.command(rtl_tcp::parse(&cmd));
+        }
+    }
+
+    fn command(&mut self, cmd: Command) {
+        match cmd {
+            Command::Freq(freq) => {
+                if self.freq != Some(freq) {
+                    self.freq = Some(freq);
+                    self.cat(messages::CatMsg::SetFreq(self.rx, freq));
+                }
+            },
+            Command::Rate(rate) => {
+                if rate == 0 || self.rate == Some(rate) {
+                    return;
+                }
+                self.rate = Some(rate);
+                // Made again at the new rate with the next IQ
+                self.resampler = None;
+                if rate > globals::get_smpl_rate() {
+                    println!("rtl_tcp client asked for {} but the radio is at {}", rate, globals::get_smpl_rate());
+                }
+            },
+            Command::GainMode(manual) => {
+                self.manual = manual;
+                self.set_gain();
+            },
+            Command::Gain(gain) => {
+                self.gain = Some(gain);
+                self.set_gain();
+            },
+            Command::Other(_, _) => (),
+        }
+    }
+
+    // The client gain in manual mode with the IQ at a fixed level, otherwise back to
+    // the gain set in this application with the IQ level following the signal
+    fn set_gain(&mut self) {
+        let (attn, preamp) = match (self.manual, self.gain) {
+            (true, Some(gain)) => {
+                let (attn, preamp) = rtl_tcp::rf_gain(gain);
+                globals::set_rf_remote(attn, preamp);
+                (attn, preamp)
+            },
+            _ => {
+                globals::clear_rf_remote();
+                (globals::get_rf_attn(), globals::get_rf_preamp())
+            },
+        };
+        let level_gain = self.level_gain();
+        if let Some(l) = self.level.as_mut() {
+            l.set_gain(level_gain);
+        }
+        let attn = match attn {
+            1 => cc_out_defs::CCOAlexAttn::Attn10db,
+            2 => cc_out_defs::CCOAlexAttn::Attn20db,
+            3 => cc_out_defs::CCOAlexAttn::Attn30db,
+            _ => cc_out_defs::CCOAlexAttn::Attn0db,
+        };
+        let preamp = if preamp { cc_out_defs::CCOPreamp::PreAmpOn } else { cc_out_defs::CCOPreamp::PreAmpOff };
+        let mut cc = self.i_cc.lock().unwrap();
+        cc.cc_alex_attn(attn);
+        cc.cc_preamp(preamp);
+    }
+
+    fn level_gain(&self) -> Option<f64> {
+        match (self.manual, self.gain) {
+            (true, Some(gain)) => return Some(rtl_tcp::level_gain(gain)),
+            _ => return None,
+        }
+    }
+
+    fn drop_client(&mut self, reason: &str) {
+        if self.client.take().is_none() {
+            return;
+        }
+        println!("rtl_tcp client {} {}", globals::get_rtl_tcp_client(), reason);
+        let manual = self.manual;
+        self.manual = false;
+        if manual {
+            self.set_gain();
+        }
+        self.gain = None;
+        self.freq = None;
+        self.rate = None;
+        self.resampler = None;
+        self.inbuf.clear();
+        self.outbuf.clear();
+        globals::set_rtl_tcp_rx(0);
+        globals::set_rtl_tcp_client(String::new());
+    }
+
+    // IQ of the shared receiver from the pipeline, resampled and queued as 8 bit
+    fn iq(&mut self, iq: &[f32]) {
+        if self.client.is_none() {
+            return;
+        }
+        let in_rate = globals::get_smpl_rate();
+        if self.resampler.is_none() || in_rate != self.in_rate {
+            let out_rate = self.rate.unwrap_or(in_rate);
+            self.in_rate = in_rate;
+            self.resampler = Some(Resampler::new(in_rate, out_rate, 2));
+            let mut level = Level::new(out_rate);
+            level.set_gain(self.level_gain());
+            self.level = Some(level);
+        }
+        let mut out = Vec::with_capacity(iq.len());
+        if let Some(r) = self.resampler.as_mut() {
+            r.process(iq, &mut out);
+        }
+        if let Some(l) = self.level.as_mut() {
+            l.to_u8(&out, &mut self.outbuf);
+        }
+        if self.outbuf.len() > MAX_BACKLOG {
+            // Whole IQ pairs so I stays first
+            let excess = (self.outbuf.len() - MAX_BACKLOG + 1) & !1;
+            self.outbuf.drain(..excess);
+        }
+    }
+
+    // Write as much as the client will take
+    fn flush(&mut self) {
+        while self.outbuf.len() > 0 {
+            let r = match self.client.as_mut() {
+                Some(stream) => stream.write(&self.outbuf),
+                None => return,
+            };
+            match r {
+                Ok(0) => {
+                    self.drop_client("disconnected");
+                    return;
+                },
+                Ok(sz) => {
+                    self.outbuf.drain(..sz);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    self.drop_client(&format!("write error {:?}", e));
+                    return;
+                },
+            }
+        }
+    }
+
+    // Tuning goes through the UI which owns the settings
+    fn cat(&self, msg: messages::CatMsg) {
+        match self.cat_sender.send(msg) {
+            Ok(()) => (),
+            Err(e) => println!("Failed to send CAT command to the UI {:?}", e),
+        }
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn rtl_tcp_start(
+    receiver : crossbeam_channel::Receiver<messages::RtlTcpMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    i_cc : Arc<Mutex<protocol::cc_out::CCData>>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        rtl_tcp_run(receiver, cat_sender, i_cc);
+    });
+    return join_handle;
+}
+
+fn rtl_tcp_run(
+    receiver : crossbeam_channel::Receiver<messages::RtlTcpMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    i_cc : Arc<Mutex<protocol::cc_out::CCData>>) {
+    println!("rtl_tcp server running");
+
+    // Instantiate the runtime object
+    let mut i_rtl_tcp = RtlTcpData::new(receiver, cat_sender, i_cc);
+
+    // Exits when the server loop exits
+    i_rtl_tcp.rtl_tcp_run();
+
+    println!("rtl_tcp server exiting");
+}
@@ -0,0 +1,481 @@
+/*
+ts2000.rs
+
+Module - ts2000
+Kenwood TS-2000 CAT emulation on a pseudo-terminal
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::Duration;
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::path::Path;
+
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::remote::radio;
+use crate::app::remote::radio::Radio;
+use crate::app::common::common_defs::ModeId;
+
+// Port poll interval
+const POLL_MS: u64 = 10;
+
+// Answer to ID
+const TS2000_ID: &str = "019";
+
+// Linux EIO, reads fail with this while no client has the port open
+const EIO: i32 = 5;
+
+// TS-2000 mode numbers, the first match is used when reporting a mode
+const MODES: [(u32, ModeId); 12] = [
+    (1, ModeId::Lsb),
+    (2, ModeId::Usb),
+    (3, ModeId::CwU),
+    (4, ModeId::Fm),
+    (5, ModeId::Am),
+    (6, ModeId::DigU),
+    (7, ModeId::CwL),
+    (9, ModeId::DigL),
+    // No TS-2000 equivalent so reported as the nearest
+    (5, ModeId::Dsb),
+    (5, ModeId::Sam),
+    (5, ModeId::Drm),
+    (2, ModeId::Spec),
+];
+
+fn mode_number(mode: ModeId) -> u32 {
+    for (n, m) in MODES.iter() {
+        if *m == mode {
+            return *n;
+        }
+    }
+    return 2;
+}
+
+// S meter reading 0-30, S9 is 15 and 30 is S9+60dB
+fn meter_reading(strength: f32) -> u32 {
+    let reading;
+    if strength <= 0.0 {
+        reading = 15.0 + strength * 15.0 / 54.0;
+    } else {
+        reading = 15.0 + strength * 15.0 / 60.0;
+    }
+    return f32::min(f32::max(reading.round(), 0.0), 30.0) as u32;
+}
+
+// IF answer, 38 characters. After the frequency come the blank step size, RIT/XIT
+// offset, RIT, XIT, a zero and the memory channel, then TX, mode, VFO, scan, split,
+// tone, tone number and shift.
+fn if_answer(freq: u32, tx: u32, mode: u32, vfo: u32, split: u32) -> String {
+    return format!("IF{:011}00000+000000000{}{}{}0{}0000;", freq, tx, mode, vfo, split);
+}
+
+//==================================================================================
+// Runtime object for thread
+pub struct CatPtyData {
+    receiver : crossbeam_channel::Receiver<messages::CatPtyMsg>,
+    radio : Radio,
+    // Master side of the pty and the link made to the slave
+    master : Option<File>,
+    link : Option<String>,
+    buf : Vec<u8>,
+    // Auto information and the last IF sent
+    ai : bool,
+    last_info : String,
+    // PTT was set on from the port
+    keyed : bool,
+}
+
+// Implementation methods on CatPtyData
+impl CatPtyData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::CatPtyMsg>,
+        cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> CatPtyData {
+
+        CatPtyData {
+            receiver: receiver,
+            radio: Radio::new(cat_sender),
+            master: None,
+            link: None,
+            buf: Vec::new(),
+            ai: false,
+            keyed: false,
+            last_info: String::new(),
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn cat_pty_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::CatPtyMsg::Terminate => break,
+                        messages::CatPtyMsg::Open(link) => self.open(&link),
+                        messages::CatPtyMsg::Close => self.close(),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.service();
+        }
+        self.close();
+    }
+
+    fn open(&mut self, link: &str) {
+        self.close();
+        let (master, name) = match pty::open() {
+            Ok(pty) => pty,
+            Err(e) => {
+                println!("Failed to open CAT pty: {:?}", e);
+                return;
+            }
+        };
+        println!("TS-2000 CAT on {}", name);
+        // Replace a stale link but never a real file
+        if link.len() > 0 {
+            let path = Path::new(link);
+            if fs::symlink_metadata(path).map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+                let _ = fs::remove_file(path);
+            }
+            match pty::link(&name, path) {
+                Ok(()) => {
+                    println!("CAT pty linked from {}", link);
+                    self.link = Some(String::from(link));
+                },
+                Err(e) => println!("Failed to link CAT pty from {}: {:?}", link, e),
+            }
+        }
+        globals::set_cat_pty_name(name);
+        self.master = Some(master);
+    }
+
+    fn close(&mut self) {
+        if self.master.take().is_some() {
+            println!("CAT pty closed");
+            // Don't leave the radio transmitting
+            self.radio.set_ptt(false);
+        }
+        // Only remove the link if it is still ours
+        if let Some(link) = self.link.take() {
+            if fs::read_link(&link).map(|p| p.display().to_string() == globals::get_cat_pty_name()).unwrap_or(false) {
+                let _ = fs::remove_file(&link);
+            }
+        }
+        globals::set_cat_pty_name(String::new());
+        self.buf.clear();
+        self.ai = false;
+        self.keyed = false;
+    }
+
+    // Answer complete commands and send auto information
+    fn service(&mut self) {
+        let mut data = [0; 256];
+        loop {
+            let master = match self.master.as_mut() {
+                Some(m) => m,
+                None => return,
+            };
+            match master.read(&mut data) {
+                Ok(0) => break,
+                Ok(n) => self.buf.extend_from_slice(&data[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.raw_os_error() == Some(EIO) => {
+                    // Nothing has the port open, auto information and TX stop with the client
+                    self.buf.clear();
+                    self.ai = false;
+                    if self.keyed {
+                        self.keyed = false;
+                        self.radio.set_ptt(false);
+                    }
+                    return;
+                },
+                Err(e) => {
+                    println!("CAT pty read error {:?}", e);
+                    return;
+                },
+            }
+        }
+
+        let mut resp = String::new();
+        while let Some(pos) = self.buf.iter().position(|b| *b == b';') {
+            let cmd: Vec<u8> = self.buf.drain(..=pos).collect();
+            let cmd = String::from_utf8_lossy(&cmd[..cmd.len() - 1]).trim().to_uppercase();
+            if cmd.len() > 0 {
+                resp += &self.command(&cmd);
+            }
+        }
+        if self.ai {
+            let info = self.info();
+            if info != self.last_info {
+                resp += &info;
+                self.last_info = info;
+            }
+        }
+        if resp.len() > 0 {
+            if let Some(master) = self.master.as_mut() {
+                let _ = master.write_all(resp.as_bytes());
+            }
+        }
+    }
+
+    // Answer to a command without its terminator, set commands have no answer
+    fn command(&mut self, cmd: &str) -> String {
+        if cmd.len() < 2 || !cmd.is_char_boundary(2) {
+            return String::from("?;");
+        }
+        let (name, param) = cmd.split_at(2);
+        let rx = self.radio.sel_rx();
+        match name {
+            "FA" | "FB" => {
+                let vfo = if name == "FA" {1} else {2};
+                if param.len() == 0 {
+                    return format!("{}{:011};", name, self.radio.freq(vfo));
+                }
+                // Out of the tuning range is rejected like a bad number
+                match param.parse::<u32>() {
+                    Ok(f) if radio::valid_freq(f) => self.radio.set_freq(vfo, f),
+                    _ => return String::from("?;"),
+                }
+            },
+            "IF" => return self.info(),
+            "MD" => {
+                if param.len() == 0 {
+                    return format!("MD{};", mode_number(self.radio.mode(rx)));
+                }
+                let mode = param.parse::<u32>().ok().and_then(|n| MODES.iter().find(|m| m.0 == n));
+                match mode {
+                    Some((_, m)) => self.radio.set_mode(rx, *m),
+                    None => return String::from("?;"),
+                }
+            },
+            // TX0 is microphone and TX1 data, both key the transmitter
+            "TX" => {
+                self.radio.set_ptt(true);
+                self.keyed = true;
+            },
+            "RX" => {
+                self.radio.set_ptt(false);
+                self.keyed = false;
+            },
+            "SM" => return format!("SM0{:04};", meter_reading(self.radio.strength())),
+            // Receive VFO, A is RX1 and B is RX2
+            "FR" => {
+                if param.len() == 0 {
+                    return format!("FR{};", self.vfo());
+                }
+                match param {
+                    "0" => self.radio.set_sel_rx(1),
+                    "1" if self.radio.num_rx() >= 2 => self.radio.set_sel_rx(2),
+                    _ => return String::from("?;"),
+                }
+            },
+            // Transmit VFO, B is split
+            "FT" => {
+                if param.len() == 0 {
+                    let mut ft = self.vfo();
+                    if self.radio.split() {
+                        ft = 1;
+                    }
+                    return format!("FT{};", ft);
+                }
+                match param {
+                    "0" => self.radio.set_split(false),
+                    "1" => self.radio.set_split(true),
+                    _ => return String::from("?;"),
+                }
+            },
+            "AI" => {
+                if param.len() == 0 {
+                    let mut ai = 0;
+                    if self.ai {
+                        ai = 2;
+                    }
+                    return format!("AI{};", ai);
+                }
+                self.ai = param != "0";
+                self.last_info = self.info();
+            },
+            "ID" => return format!("ID{};", TS2000_ID),
+            "PS" => {
+                if param.len() == 0 {
+                    return String::from("PS1;");
+                }
+            },
+            _ => return String::from("?;"),
+        }
+        return String::new();
+    }
+
+    fn vfo(&self) -> u32 {
+        if self.radio.sel_rx() == 2 {
+            return 1;
+        }
+        return 0;
+    }
+
+    // Transceiver status, fields not modelled here are zero
+    fn info(&self) -> String {
+        let rx = self.radio.sel_rx();
+        let mut tx = 0;
+        if self.radio.ptt() {
+            tx = 1;
+        }
+        let mut split = 0;
+        if self.radio.split() {
+            split = 1;
+        }
+        return if_answer(self.radio.freq(rx), tx, mode_number(self.radio.mode(rx)), self.vfo(), split);
+    }
+}
+
+//==================================================================================
+// The pty is opened raw and nonblocking
+#[cfg(target_os = "linux")]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::io::FromRawFd;
+
+    const O_RDWR: c_int = 0o2;
+    const O_NOCTTY: c_int = 0o400;
+    const O_NONBLOCK: c_int = 0o4000;
+    const F_GETFL: c_int = 3;
+    const F_SETFL: c_int = 4;
+    const TCSANOW: c_int = 0;
+
+    extern "C" {
+        fn posix_openpt(flags: c_int) -> c_int;
+        fn grantpt(fd: c_int) -> c_int;
+        fn unlockpt(fd: c_int) -> c_int;
+        fn ptsname_r(fd: c_int, buf: *mut c_char, len: usize) -> c_int;
+        fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+        fn tcgetattr(fd: c_int, termios: *mut c_void) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const c_void) -> c_int;
+        fn cfmakeraw(termios: *mut c_void);
+    }
+
+    // The master side and the name of the slave device
+    pub fn open() -> io::Result<(File, String)> {
+        unsafe {
+            let fd = posix_openpt(O_RDWR | O_NOCTTY);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Owned from here so it is closed on error
+            let master = File::from_raw_fd(fd);
+            if grantpt(fd) != 0 || unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut name = [0 as c_char; 128];
+            let r = ptsname_r(fd, name.as_mut_ptr(), name.len());
+            if r != 0 {
+                return Err(io::Error::from_raw_os_error(r));
+            }
+            // Raw so answers are not echoed back as commands.
+            // Only libc touches the termios so it is just a large enough buffer.
+            let mut termios = [0u64; 32];
+            if tcgetattr(fd, termios.as_mut_ptr() as *mut c_void) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            cfmakeraw(termios.as_mut_ptr() as *mut c_void);
+            if tcsetattr(fd, TCSANOW, termios.as_ptr() as *const c_void) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let flags = fcntl(fd, F_GETFL);
+            if flags < 0 || fcntl(fd, F_SETFL, flags | O_NONBLOCK) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let name = CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned();
+            return Ok((master, name));
+        }
+    }
+
+    pub fn link(name: &str, path: &Path) -> io::Result<()> {
+        return std::os::unix::fs::symlink(name, path);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod pty {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn open() -> io::Result<(File, String)> {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "CAT pty is only available on Linux"));
+    }
+
+    pub fn link(_name: &str, _path: &Path) -> io::Result<()> {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "CAT pty is only available on Linux"));
+    }
+}
+
+//==================================================================================
+// Thread startup
+pub fn cat_pty_start(
+    receiver : crossbeam_channel::Receiver<messages::CatPtyMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        cat_pty_run(receiver, cat_sender);
+    });
+    return join_handle;
+}
+
+fn cat_pty_run(
+    receiver : crossbeam_channel::Receiver<messages::CatPtyMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>) {
+    println!("CAT pty server running");
+
+    // Instantiate the runtime object
+    let mut i_cat_pty = CatPtyData::new(receiver, cat_sender);
+
+    // Exits when the server loop exits
+    i_cat_pty.cat_pty_run();
+
+    println!("CAT pty server exiting");
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_layout() {
+        let a = if_answer(14074000, 1, 2, 0, 1);
+        assert_eq!(a.len(), 38);
+        assert_eq!(&a[2..13], "00014074000");
+        // Step size then the signed RIT/XIT offset
+        assert_eq!(&a[13..18], "00000");
+        assert_eq!(&a[18..23], "+0000");
+        assert_eq!(&a[28..33], "12001");
+        assert!(a.ends_with(";"));
+    }
+}
@@ -0,0 +1,835 @@
+/*
+web_server.rs
+
+Module - web_server
+HTTP and WebSocket server for remote control and spectrum
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::thread;
+use std::time::{Duration, Instant};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use crate::app::common::common_defs;
+use crate::app::common::messages;
+use crate::app::common::globals;
+use crate::app::common::ringb;
+use crate::app::audio::opus;
+use crate::app::audio::jitter;
+use crate::app::remote::radio::{self, Radio};
+use crate::app::remote::websocket;
+
+// Socket poll interval
+const POLL_MS: u64 = 10;
+
+// How often the state is checked for changes and sent anyway
+const STATE_MS: u64 = 200;
+const STATE_REFRESH_MS: u64 = 2000;
+const METER_MS: u64 = 200;
+
+// Limits on what a client can send
+const MAX_REQUEST: usize = 8192;
+const MAX_FRAME: usize = 4096;
+
+// Spectrum rows are dropped for a client with this much waiting to go out
+const MAX_BACKLOG: usize = 256 * 1024;
+
+// Binary message types, the first byte of each binary frame
+pub const BIN_SPECTRUM: u8 = 1;
+pub const BIN_AUDIO: u8 = 2;
+pub const BIN_MIC: u8 = 3;
+
+// Opus audio, bitrate starts here and adapts to what the connection takes
+const AUDIO_BITRATE: u32 = 24000;
+const AUDIO_STEP: u32 = 4000;
+const ADAPT_MS: u64 = 1000;
+// Over this backlog the bitrate comes down, audio is dropped at the limit
+const AUDIO_HIGH: usize = 8 * 1024;
+const AUDIO_BACKLOG: usize = 32 * 1024;
+
+// Mic jitter buffer in 20ms frames
+const MIC_FRAME_MS: u128 = 20;
+const MIC_DEPTH: usize = 3;
+const MIC_MAX: usize = 15;
+// Mic audio has stopped when nothing arrives for this long
+const MIC_IDLE_MS: u64 = 200;
+
+// The bundled browser client
+const CLIENT_HTML: &str = include_str!("web_client.html");
+
+enum Conn {
+    Http,
+    WebSocket,
+}
+
+// Compressed audio for one client
+struct ClientAudio {
+    rx: u32,
+    encoder: opus::Encoder,
+    pending: Vec<f32>,
+    seq: u16,
+    bitrate: u32,
+    max_bitrate: u32,
+    adapt_time: Instant,
+    // Client reported gaps since the last adapt
+    underruns: u32,
+    // Mic from the client while it has PTT
+    decoder: opus::Decoder,
+    mic: jitter::JitterBuffer,
+    mic_start: Option<Instant>,
+    mic_frames: u32,
+    mic_last: Option<Instant>,
+}
+
+struct WebClient {
+    stream: TcpStream,
+    addr: String,
+    conn: Conn,
+    inbuf: Vec<u8>,
+    outbuf: Vec<u8>,
+    // Close once everything is sent
+    closing: bool,
+    dead: bool,
+    audio: Option<ClientAudio>,
+}
+
+//==================================================================================
+// Runtime object for thread.
+// The page at / is the client, it connects to /ws with the token as ?token=.
+// Other clients may send it as Authorization: Bearer.
+// Server messages are JSON text with a type of state, meter or error, and
+// binary spectrum rows of little endian f32 dBm values across the sample rate.
+// Binary messages start with a type byte.
+//  Spectrum rows [1][f32...]
+//  RX audio to the client [2][rx][seq u16 BE][Opus 20ms mono 48K]
+//  Mic audio from the client [3][seq u16 BE][Opus]
+// Client messages are JSON commands, see command().
+pub struct WebData {
+    receiver : crossbeam_channel::Receiver<messages::WebMsg>,
+    radio : Radio,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    // Client that keyed PTT, only its mic is used
+    tx_client : Option<String>,
+    token : String,
+    listener : Option<TcpListener>,
+    clients : Vec<WebClient>,
+    last_state : String,
+    state_time : Instant,
+    refresh_time : Instant,
+    meter_time : Instant,
+}
+
+// Implementation methods on WebData
+impl WebData {
+	// Create a new instance
+    pub fn new(
+        receiver : crossbeam_channel::Receiver<messages::WebMsg>,
+        cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+        rb_mic : Arc<ringb::SyncByteRingBuf>) -> WebData {
+
+        WebData {
+            receiver: receiver,
+            radio: Radio::new(cat_sender),
+            rb_mic: rb_mic,
+            tx_client: None,
+            token: String::new(),
+            listener: None,
+            clients: Vec::new(),
+            last_state: String::new(),
+            state_time: Instant::now(),
+            refresh_time: Instant::now(),
+            meter_time: Instant::now(),
+        }
+    }
+
+    // This is the thread main loop. When this exits the thread exits.
+    pub fn web_run(&mut self) {
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(POLL_MS)) {
+                Ok(msg) => {
+                    match msg {
+                        messages::WebMsg::Terminate => break,
+                        messages::WebMsg::Listen(addr, token) => self.listen(&addr, token),
+                        messages::WebMsg::Close => self.close(),
+                        messages::WebMsg::Spectrum(row) => self.spectrum(&row),
+                        messages::WebMsg::RxAudio(rx, audio) => self.rx_audio(rx, &audio),
+                        messages::WebMsg::Decoded(text) => self.decoded(&text),
+                    }
+                },
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+            }
+            self.accept();
+            self.service();
+            self.mic();
+            self.publish();
+            self.flush();
+            self.adapt();
+        }
+        self.close();
+    }
+
+    fn listen(&mut self, addr: &str, token: String) {
+        self.close();
+        self.token = token;
+        let r = TcpListener::bind(addr).and_then(|l| {
+            l.set_nonblocking(true)?;
+            Ok(l)
+        });
+        match r {
+            Ok(listener) => {
+                println!("Web server listening on {}", addr);
+                self.listener = Some(listener);
+            },
+            Err(e) => println!("Failed to listen for web clients on {}: {:?}", addr, e),
+        }
+    }
+
+    fn close(&mut self) {
+        if self.listener.take().is_some() {
+            println!("Web server closed");
+        }
+        self.clients.clear();
+        self.tx_client = None;
+        globals::set_web_clients(0);
+        globals::set_web_audio(false);
+        globals::set_web_tx(false);
+    }
+
+    fn accept(&mut self) {
+        let listener = match self.listener.as_ref() {
+            Some(l) => l,
+            None => return,
+        };
+        loop {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    let _ = stream.set_nonblocking(true);
+                    let _ = stream.set_nodelay(true);
+                    self.clients.push(WebClient {
+                        stream: stream,
+                        addr: addr.to_string(),
+                        conn: Conn::Http,
+                        inbuf: Vec::new(),
+                        outbuf: Vec::new(),
+                        closing: false,
+                        dead: false,
+                        audio: None,
+                    });
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Web server accept error {:?}", e);
+                    break;
+                },
+            }
+        }
+    }
+
+    // Read from each client and act on complete requests and frames
+    fn service(&mut self) {
+        for i in 0..self.clients.len() {
+            let mut data = [0; 4096];
+            loop {
+                match self.clients[i].stream.read(&mut data) {
+                    Ok(0) => {
+                        self.clients[i].dead = true;
+                        break;
+                    },
+                    Ok(n) => self.clients[i].inbuf.extend_from_slice(&data[..n]),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(_e) => {
+                        self.clients[i].dead = true;
+                        break;
+                    },
+                }
+            }
+            if self.clients[i].dead || self.clients[i].closing {
+                continue;
+            }
+            match self.clients[i].conn {
+                Conn::Http => self.http(i),
+                Conn::WebSocket => self.frames(i),
+            }
+        }
+
+        let before = self.clients.len();
+        self.clients.retain(|c| !c.dead && !(c.closing && c.outbuf.len() == 0));
+        if self.clients.len() != before {
+            self.update_count();
+        }
+    }
+
+    fn update_count(&mut self) {
+        let n = self.clients.iter().filter(|c| matches!(c.conn, Conn::WebSocket)).count();
+        globals::set_web_clients(n as u32);
+        globals::set_web_audio(self.clients.iter().any(|c| c.audio.is_some()));
+        // Mic goes back to the radio when the client that had it goes
+        if let Some(addr) = self.tx_client.as_ref() {
+            if !self.clients.iter().any(|c| &c.addr == addr) {
+                self.tx_client = None;
+                self.radio.set_ptt(false);
+                globals::set_web_tx(false);
+            }
+        }
+    }
+
+    // A complete request is answered then the connection closed, unless it is a WebSocket upgrade
+    fn http(&mut self, i: usize) {
+        let end = match self.clients[i].inbuf.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) => end,
+            None => {
+                if self.clients[i].inbuf.len() > MAX_REQUEST {
+                    self.respond(i, "431 Request Header Fields Too Large", "text/plain", b"Request too large");
+                }
+                return;
+            }
+        };
+        let request = String::from_utf8_lossy(&self.clients[i].inbuf[..end]).to_string();
+        self.clients[i].inbuf.clear();
+
+        let mut lines = request.split("\r\n");
+        let parts: Vec<&str> = lines.next().unwrap_or("").split(' ').collect();
+        if parts.len() < 3 || parts[0] != "GET" {
+            self.respond(i, "405 Method Not Allowed", "text/plain", b"Only GET is supported");
+            return;
+        }
+        let (path, query) = parts[1].split_once('?').unwrap_or((parts[1], ""));
+        let mut upgrade = false;
+        let mut key = None;
+        let mut token = None;
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                match name.trim().to_lowercase().as_str() {
+                    "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                    "sec-websocket-key" => key = Some(String::from(value)),
+                    "authorization" => token = value.strip_prefix("Bearer ").map(|t| String::from(t.trim())),
+                    _ => (),
+                }
+            }
+        }
+        for param in query.split('&') {
+            if let Some(("token", value)) = param.split_once('=') {
+                token = Some(String::from(value));
+            }
+        }
+
+        match path {
+            "/" | "/index.html" => self.respond(i, "200 OK", "text/html; charset=utf-8", CLIENT_HTML.as_bytes()),
+            "/ws" => {
+                let key = match key {
+                    Some(k) if upgrade => k,
+                    _ => {
+                        self.respond(i, "400 Bad Request", "text/plain", b"WebSocket upgrade required");
+                        return;
+                    }
+                };
+                if !self.authorised(token.as_deref().unwrap_or("")) {
+                    println!("Web client {} refused, bad token", self.clients[i].addr);
+                    self.respond(i, "401 Unauthorized", "text/plain", b"Bad token");
+                    return;
+                }
+                let resp = format!(
+                    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                    websocket::accept_key(&key));
+                self.clients[i].outbuf.extend_from_slice(resp.as_bytes());
+                self.clients[i].conn = Conn::WebSocket;
+                println!("Web client connected from {}", self.clients[i].addr);
+                // Everything at once for the new client
+                let state = self.state();
+                self.send_text(i, &state);
+                self.update_count();
+            },
+            _ => self.respond(i, "404 Not Found", "text/plain", b"Not found"),
+        }
+    }
+
+    fn respond(&mut self, i: usize, status: &str, content_type: &str, body: &[u8]) {
+        let head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            status, content_type, body.len());
+        let c = &mut self.clients[i];
+        c.outbuf.extend_from_slice(head.as_bytes());
+        c.outbuf.extend_from_slice(body);
+        c.closing = true;
+    }
+
+    // No token configured means no access.
+    // Every character is compared so the time taken does not give the token away.
+    fn authorised(&self, token: &str) -> bool {
+        if self.token.len() == 0 || token.len() != self.token.len() {
+            return false;
+        }
+        let mut diff = 0;
+        for (a, b) in token.bytes().zip(self.token.bytes()) {
+            diff |= a ^ b;
+        }
+        return diff == 0;
+    }
+
+    fn frames(&mut self, i: usize) {
+        loop {
+            let frame = match websocket::decode(&self.clients[i].inbuf, MAX_FRAME) {
+                Ok(Some((frame, n))) => {
+                    self.clients[i].inbuf.drain(..n);
+                    frame
+                },
+                Ok(None) => return,
+                Err(e) => {
+                    println!("Web client {} protocol error: {}", self.clients[i].addr, e);
+                    self.close_client(i);
+                    return;
+                }
+            };
+            if !frame.fin || frame.opcode == websocket::OP_CONTINUATION {
+                // Commands are small so are never fragmented
+                self.close_client(i);
+                return;
+            }
+            match frame.opcode {
+                websocket::OP_TEXT => {
+                    let text = String::from_utf8_lossy(&frame.payload).to_string();
+                    match self.command(i, &text) {
+                        Some(err) => self.send_text(i, &err),
+                        None => (),
+                    }
+                },
+                websocket::OP_BINARY => {
+                    if frame.payload.len() > 3 && frame.payload[0] == BIN_MIC {
+                        self.mic_frame(i, &frame.payload);
+                    }
+                },
+                websocket::OP_PING => {
+                    let pong = websocket::encode(websocket::OP_PONG, &frame.payload);
+                    self.clients[i].outbuf.extend_from_slice(&pong);
+                },
+                websocket::OP_CLOSE => {
+                    self.close_client(i);
+                    return;
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn close_client(&mut self, i: usize) {
+        // Normal closure
+        let close = websocket::encode(websocket::OP_CLOSE, &1000u16.to_be_bytes());
+        self.clients[i].outbuf.extend_from_slice(&close);
+        self.clients[i].closing = true;
+        println!("Web client {} disconnected", self.clients[i].addr);
+    }
+
+    // Commands are JSON objects with a cmd and its arguments, rx is optional and 1 based
+    //  {"cmd":"tune","freq":7100000}
+    //  {"cmd":"mode","mode":"USB"}
+    //  {"cmd":"filter","width":2400}
+    //  {"cmd":"gain","value":50}
+    //  {"cmd":"rx","rx":2}
+    //  {"cmd":"start"} and {"cmd":"stop"}
+    //  {"cmd":"audio","enable":true,"bitrate":32000} Opus audio of rx, bitrate is the most to use
+    //  {"cmd":"audio_stats","underruns":n} gaps in the client playback
+    //  {"cmd":"ptt","state":true} TX with the mic from this client
+    // Returns an error message for the client.
+    fn command(&mut self, i: usize, text: &str) -> Option<String> {
+        let v: Value = match serde_json::from_str(text) {
+            Ok(v) => v,
+            Err(_) => return Some(error("Bad JSON")),
+        };
+        let rx = match v["rx"].as_u64() {
+            Some(rx) if rx >= 1 && rx <= self.radio.num_rx() as u64 => rx as u32,
+            Some(_) => return Some(error("No such receiver")),
+            None => self.radio.sel_rx(),
+        };
+        match v["cmd"].as_str().unwrap_or("") {
+            "tune" => {
+                match v["freq"].as_u64() {
+                    Some(f) if f <= radio::MAX_FREQ as u64 => self.radio.set_freq(rx, f as u32),
+                    _ => return Some(error("Bad frequency")),
+                }
+            },
+            "mode" => {
+                match v["mode"].as_str().and_then(radio::mode_by_name) {
+                    Some(m) => self.radio.set_mode(rx, m),
+                    None => return Some(error("Bad mode")),
+                }
+            },
+            "filter" => {
+                match v["width"].as_u64() {
+                    Some(w) => self.radio.set_width(rx, w as u32),
+                    None => return Some(error("Bad filter width")),
+                }
+            },
+            "gain" => {
+                match v["value"].as_f64() {
+                    Some(g) if g >= 0.0 && g <= 100.0 => self.radio.set_af_gain(g as f32),
+                    _ => return Some(error("Bad gain")),
+                }
+            },
+            "rx" => {
+                self.radio.set_sel_rx(rx);
+                // Audio follows the selected receiver
+                if let Some(a) = self.clients[i].audio.as_mut() {
+                    a.rx = rx;
+                    a.pending.clear();
+                }
+            },
+            "start" => self.radio.set_run(true),
+            "stop" => self.radio.set_run(false),
+            "audio" => {
+                if v["enable"].as_bool() == Some(false) {
+                    self.release_mic(i);
+                    self.clients[i].audio = None;
+                } else {
+                    let max = v["bitrate"].as_u64().unwrap_or(opus::MAX_BITRATE as u64) as u32;
+                    match self.client_audio(i, rx, u32::min(u32::max(max, opus::MIN_BITRATE), opus::MAX_BITRATE)) {
+                        Ok(()) => (),
+                        Err(e) => return Some(error(&e)),
+                    }
+                }
+                self.update_count();
+            },
+            "audio_stats" => {
+                if let Some(a) = self.clients[i].audio.as_mut() {
+                    a.underruns += v["underruns"].as_u64().unwrap_or(0) as u32;
+                }
+            },
+            "ptt" => {
+                if v["state"].as_bool() == Some(true) {
+                    self.tx_client = Some(self.clients[i].addr.clone());
+                    self.radio.set_ptt(true);
+                } else if self.tx_client.as_ref() == Some(&self.clients[i].addr) {
+                    self.release_mic(i);
+                    self.tx_client = None;
+                    self.radio.set_ptt(false);
+                }
+            },
+            _ => return Some(error("Unknown command")),
+        }
+        return None;
+    }
+
+    // The selected receiver at the top level and every receiver in receivers
+    fn state(&self) -> String {
+        let rx = self.radio.sel_rx();
+        let receivers: Vec<Value> = (1..=self.radio.num_rx()).map(|r| json!({
+            "freq": self.radio.freq(r),
+            "mode": radio::mode_name(self.radio.mode(r)),
+            "width": self.radio.width(r),
+        })).collect();
+        return json!({
+            "type": "state",
+            "hw": self.radio.discovered(),
+            "run": self.radio.running(),
+            "rx": rx,
+            "num_rx": self.radio.num_rx(),
+            "freq": self.radio.freq(rx),
+            "mode": radio::mode_name(self.radio.mode(rx)),
+            "width": self.radio.width(rx),
+            "gain": self.radio.af_gain(),
+            "ptt": self.radio.ptt(),
+            "rate": self.radio.smpl_rate(),
+            "receivers": receivers,
+        }).to_string();
+    }
+
+    // State when it changes or every few seconds, and the meter
+    fn publish(&mut self) {
+        if globals::get_web_clients() == 0 {
+            return;
+        }
+        if self.state_time.elapsed() >= Duration::from_millis(STATE_MS) {
+            self.state_time = Instant::now();
+            let state = self.state();
+            if state != self.last_state || self.refresh_time.elapsed() >= Duration::from_millis(STATE_REFRESH_MS) {
+                self.broadcast(websocket::encode(websocket::OP_TEXT, state.as_bytes()), false);
+                self.last_state = state;
+                self.refresh_time = Instant::now();
+            }
+        }
+        if self.meter_time.elapsed() >= Duration::from_millis(METER_MS) {
+            self.meter_time = Instant::now();
+            // raw is without the meter calibration, meters are every source without it
+            let dbm = self.radio.dbm();
+            let meters: Vec<f32> = self.radio.meters().iter().map(|m| (m * 10.0).round() / 10.0).collect();
+            let meter = json!({
+                "type": "meter",
+                "dbm": (dbm * 10.0).round() / 10.0,
+                "raw": ((dbm - globals::get_meter_cal()) * 10.0).round() / 10.0,
+                "meters": meters,
+            }).to_string();
+            self.broadcast(websocket::encode(websocket::OP_TEXT, meter.as_bytes()), true);
+        }
+    }
+
+    fn spectrum(&mut self, row: &[f32]) {
+        let mut data = Vec::with_capacity(row.len() * 4 + 1);
+        data.push(BIN_SPECTRUM);
+        for v in row.iter() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        self.broadcast(websocket::encode(websocket::OP_BINARY, &data), true);
+    }
+
+    fn decoded(&mut self, text: &str) {
+        let msg = json!({"type": "decode", "text": text}).to_string();
+        self.broadcast(websocket::encode(websocket::OP_TEXT, msg.as_bytes()), false);
+    }
+
+    // Droppable frames are not queued for a client that is not keeping up
+    fn broadcast(&mut self, frame: Vec<u8>, droppable: bool) {
+        for c in self.clients.iter_mut() {
+            if !matches!(c.conn, Conn::WebSocket) || c.closing {
+                continue;
+            }
+            if droppable && c.outbuf.len() > MAX_BACKLOG {
+                continue;
+            }
+            c.outbuf.extend_from_slice(&frame);
+        }
+    }
+
+    //==================================================================================
+    // Opus audio
+
+    fn client_audio(&mut self, i: usize, rx: u32, max_bitrate: u32) -> Result<(), String> {
+        let bitrate = u32::min(AUDIO_BITRATE, max_bitrate);
+        let audio = ClientAudio {
+            rx: rx,
+            encoder: opus::Encoder::new(bitrate)?,
+            pending: Vec::with_capacity(2 * opus::FRAME_SZ),
+            seq: 0,
+            bitrate: bitrate,
+            max_bitrate: max_bitrate,
+            adapt_time: Instant::now(),
+            underruns: 0,
+            decoder: opus::Decoder::new()?,
+            mic: jitter::JitterBuffer::new(MIC_DEPTH, MIC_MAX),
+            mic_start: None,
+            mic_frames: 0,
+            mic_last: None,
+        };
+        self.clients[i].audio = Some(audio);
+        let msg = audio_msg(rx, bitrate);
+        self.send_text(i, &msg);
+        return Ok(());
+    }
+
+    // Encode receiver audio for each client listening to it
+    fn rx_audio(&mut self, rx: u32, audio: &[f32]) {
+        for c in self.clients.iter_mut() {
+            if c.closing {
+                continue;
+            }
+            let a = match c.audio.as_mut() {
+                Some(a) if a.rx == rx => a,
+                _ => continue,
+            };
+            a.pending.extend_from_slice(audio);
+            while a.pending.len() >= opus::FRAME_SZ {
+                let frame: Vec<f32> = a.pending.drain(..opus::FRAME_SZ).collect();
+                let data = match a.encoder.encode(&frame) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                let seq = a.seq;
+                a.seq = a.seq.wrapping_add(1);
+                // A client this far behind gets a gap rather than more delay
+                if c.outbuf.len() > AUDIO_BACKLOG {
+                    continue;
+                }
+                let mut payload = Vec::with_capacity(data.len() + 4);
+                payload.push(BIN_AUDIO);
+                payload.push(rx as u8);
+                payload.extend_from_slice(&seq.to_be_bytes());
+                payload.extend_from_slice(&data);
+                c.outbuf.extend_from_slice(&websocket::encode(websocket::OP_BINARY, &payload));
+            }
+        }
+    }
+
+    // Mic frame from a client, only taken from the client with PTT
+    fn mic_frame(&mut self, i: usize, payload: &[u8]) {
+        if self.tx_client.as_ref() != Some(&self.clients[i].addr) {
+            return;
+        }
+        if let Some(a) = self.clients[i].audio.as_mut() {
+            let seq = u16::from_be_bytes([payload[1], payload[2]]);
+            a.mic.push(seq, payload[3..].to_vec());
+            a.mic_last = Some(Instant::now());
+            globals::set_web_tx(true);
+        }
+    }
+
+    // Back to the radio Mic if this client has it
+    fn release_mic(&mut self, i: usize) {
+        if self.tx_client.as_ref() != Some(&self.clients[i].addr) {
+            return;
+        }
+        if let Some(a) = self.clients[i].audio.as_mut() {
+            a.mic.reset();
+            a.mic_start = None;
+            a.mic_frames = 0;
+            a.mic_last = None;
+        }
+        globals::set_web_tx(false);
+    }
+
+    // Play the mic out of the jitter buffer into the Mic ring buffer at the frame rate
+    fn mic(&mut self) {
+        let addr = match self.tx_client.as_ref() {
+            Some(a) => a,
+            None => return,
+        };
+        let a = match self.clients.iter_mut().find(|c| &c.addr == addr).and_then(|c| c.audio.as_mut()) {
+            Some(a) => a,
+            None => return,
+        };
+        if let Some(last) = a.mic_last {
+            if last.elapsed() > Duration::from_millis(MIC_IDLE_MS) {
+                // Back to the radio Mic
+                a.mic_last = None;
+                a.mic_start = None;
+                a.mic_frames = 0;
+                a.mic.reset();
+                globals::set_web_tx(false);
+            }
+        }
+        if a.mic_last.is_none() {
+            return;
+        }
+        let start = *a.mic_start.get_or_insert_with(|| Instant::now());
+        let due = (start.elapsed().as_millis() / MIC_FRAME_MS) as u32 + 1;
+        while a.mic_frames < due {
+            let pcm = match a.mic.pop() {
+                Some(jitter::Slot::Frame(f)) => a.decoder.decode(Some(&f)),
+                Some(jitter::Slot::Lost) => a.decoder.decode(None),
+                None => {
+                    // Filling, the clock starts again when it is ready
+                    a.mic_start = None;
+                    a.mic_frames = 0;
+                    break;
+                }
+            };
+            a.mic_frames += 1;
+            // Big endian 16 bit mono at 48K as the Mic
+            let mut data = Vec::with_capacity(pcm.len() * common_defs::MIC_BYTES_PER_SAMPLE as usize);
+            for s in pcm.iter() {
+                let v = (f32::min(f32::max(*s, -1.0), 1.0) * i16::MAX as f32) as i16;
+                data.extend_from_slice(&v.to_be_bytes());
+            }
+            match self.rb_mic.write().write(&data) {
+                Ok(_) => (),
+                // Pipeline not keeping up, TX audio will have a gap
+                Err(_e) => (),
+            }
+        }
+    }
+
+    // Bring the bitrate down when a client falls behind and back up when it keeps up
+    fn adapt(&mut self) {
+        for c in self.clients.iter_mut() {
+            let a = match c.audio.as_mut() {
+                Some(a) => a,
+                None => continue,
+            };
+            if a.adapt_time.elapsed() < Duration::from_millis(ADAPT_MS) {
+                continue;
+            }
+            a.adapt_time = Instant::now();
+            let mut bitrate = a.bitrate;
+            if c.outbuf.len() > AUDIO_HIGH || a.underruns > 0 {
+                bitrate = u32::max(opus::MIN_BITRATE, bitrate * 3 / 4);
+            } else if c.outbuf.len() == 0 {
+                bitrate = u32::min(a.max_bitrate, bitrate + AUDIO_STEP);
+            }
+            a.underruns = 0;
+            if bitrate != a.bitrate {
+                a.bitrate = bitrate;
+                a.encoder.set_bitrate(bitrate);
+                let msg = audio_msg(a.rx, bitrate);
+                c.outbuf.extend_from_slice(&websocket::encode(websocket::OP_TEXT, msg.as_bytes()));
+            }
+        }
+    }
+
+    fn send_text(&mut self, i: usize, text: &str) {
+        let frame = websocket::encode(websocket::OP_TEXT, text.as_bytes());
+        self.clients[i].outbuf.extend_from_slice(&frame);
+    }
+
+    // Write as much as each client will take
+    fn flush(&mut self) {
+        for c in self.clients.iter_mut() {
+            while c.outbuf.len() > 0 && !c.dead {
+                match c.stream.write(&c.outbuf) {
+                    Ok(0) => c.dead = true,
+                    Ok(n) => {
+                        c.outbuf.drain(..n);
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(_e) => c.dead = true,
+                }
+            }
+        }
+    }
+}
+
+fn audio_msg(rx: u32, bitrate: u32) -> String {
+    return json!({"type": "audio", "rx": rx, "bitrate": bitrate}).to_string();
+}
+
+fn error(message: &str) -> String {
+    return json!({"type": "error", "message": message}).to_string();
+}
+
+//==================================================================================
+// Thread startup
+pub fn web_start(
+    receiver : crossbeam_channel::Receiver<messages::WebMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>) -> thread::JoinHandle<()> {
+    let join_handle = thread::spawn(  move || {
+        web_run(receiver, cat_sender, rb_mic);
+    });
+    return join_handle;
+}
+
+fn web_run(
+    receiver : crossbeam_channel::Receiver<messages::WebMsg>,
+    cat_sender : crossbeam_channel::Sender<messages::CatMsg>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>) {
+    println!("Web server running");
+
+    // Instantiate the runtime object
+    let mut i_web = WebData::new(receiver, cat_sender, rb_mic);
+
+    // Exits when the server loop exits
+    i_web.web_run();
+
+    println!("Web server exiting");
+}
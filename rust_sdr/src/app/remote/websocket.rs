@@ -0,0 +1,330 @@
+/*
+websocket.rs
+
+Module - websocket
+WebSocket handshake and framing (RFC 6455)
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Appended to the client key for the accept key
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// Opcodes
+pub const OP_CONTINUATION: u8 = 0x0;
+pub const OP_TEXT: u8 = 0x1;
+pub const OP_BINARY: u8 = 0x2;
+pub const OP_CLOSE: u8 = 0x8;
+pub const OP_PING: u8 = 0x9;
+pub const OP_PONG: u8 = 0xa;
+
+#[derive(PartialEq, Debug)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+//==================================================================================
+// Handshake
+
+// Sec-WebSocket-Accept for the client Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+    let mut s = String::from(key.trim());
+    s += WS_GUID;
+    return base64(&sha1(s.as_bytes()));
+}
+
+// Sec-WebSocket-Key for a client, 16 random bytes
+pub fn client_key() -> String {
+    return base64(&random_bytes(16));
+}
+
+// Client frames take a new mask each time
+pub fn new_mask() -> [u8; 4] {
+    let b = random_bytes(4);
+    return [b[0], b[1], b[2], b[3]];
+}
+
+// A random 128 bit access token as hex
+pub fn new_token() -> String {
+    return random_bytes(16).iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+// Good enough to be unguessable, not for keys
+fn random_bytes(n: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(n + 8);
+    while out.len() < n {
+        // Each RandomState has its own random keys
+        let mut h = RandomState::new().build_hasher();
+        h.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0));
+        out.extend_from_slice(&h.finish().to_be_bytes());
+    }
+    out.truncate(n);
+    return out;
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    // Pad to a multiple of 64 bytes with the length in bits at the end
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for i in 0..80 {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut out = [0u8; 20];
+    for i in 0..5 {
+        out[4 * i..4 * i + 4].copy_from_slice(&h[i].to_be_bytes());
+    }
+    return out;
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    return out;
+}
+
+//==================================================================================
+// Framing
+
+// A server frame, these are never masked
+pub fn encode(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    return encode_frame(opcode, payload, None);
+}
+
+// A client frame, these are always masked
+pub fn encode_masked(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    return encode_frame(opcode, payload, Some(mask));
+}
+
+fn encode_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut f = Vec::with_capacity(payload.len() + 14);
+    f.push(0x80 | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    if payload.len() < 126 {
+        f.push(mask_bit | payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        f.push(mask_bit | 126);
+        f.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        f.push(mask_bit | 127);
+        f.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    match mask {
+        Some(m) => {
+            f.extend_from_slice(&m);
+            f.extend(payload.iter().enumerate().map(|(i, b)| b ^ m[i % 4]));
+        },
+        None => f.extend_from_slice(payload),
+    }
+    return f;
+}
+
+// The client frame at the start of data and its size, None until it is complete.
+// Client frames must be masked and no larger than max_len.
+pub fn decode(data: &[u8], max_len: usize) -> Result<Option<(Frame, usize)>, &'static str> {
+    return decode_frame(data, max_len, true);
+}
+
+// The same for a server frame, these must not be masked
+pub fn decode_server(data: &[u8], max_len: usize) -> Result<Option<(Frame, usize)>, &'static str> {
+    return decode_frame(data, max_len, false);
+}
+
+fn decode_frame(data: &[u8], max_len: usize, masked: bool) -> Result<Option<(Frame, usize)>, &'static str> {
+    if data.len() < 2 {
+        return Ok(None);
+    }
+    let fin = data[0] & 0x80 != 0;
+    let opcode = data[0] & 0x0f;
+    if (data[1] & 0x80 != 0) != masked {
+        if masked {
+            return Err("client frame not masked");
+        }
+        return Err("server frame masked");
+    }
+    let mut pos = 2;
+    let mut len = (data[1] & 0x7f) as u64;
+    if len == 126 {
+        if data.len() < pos + 2 {
+            return Ok(None);
+        }
+        len = u16::from_be_bytes([data[2], data[3]]) as u64;
+        pos += 2;
+    } else if len == 127 {
+        if data.len() < pos + 8 {
+            return Ok(None);
+        }
+        let mut b = [0u8; 8];
+        b.copy_from_slice(&data[2..10]);
+        len = u64::from_be_bytes(b);
+        pos += 8;
+    }
+    if len > max_len as u64 {
+        return Err("frame too large");
+    }
+    let len = len as usize;
+    let mask_len = if masked { 4 } else { 0 };
+    if data.len() < pos + mask_len + len {
+        return Ok(None);
+    }
+    let payload;
+    if masked {
+        let mask = [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]];
+        pos += 4;
+        payload = data[pos..pos + len].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+    } else {
+        payload = data[pos..pos + len].to_vec();
+    }
+    return Ok(Some((Frame { fin: fin, opcode: opcode, payload: payload }, pos + len)));
+}
+
+//==================================================================================
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(b: &[u8]) -> String {
+        return b.iter().map(|x| format!("{:02x}", x)).collect();
+    }
+
+    fn masked(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        return encode_masked(opcode, payload, [0x37, 0xfa, 0x21, 0x3d]);
+    }
+
+    #[test]
+    fn sha1_vectors() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn rfc_accept_key() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn encode_lengths() {
+        assert_eq!(encode(OP_TEXT, b"Hello"), vec![0x81, 0x05, b'H', b'e', b'l', b'l', b'o']);
+        let f = encode(OP_BINARY, &vec![0; 300]);
+        assert_eq!(&f[..4], &[0x82, 126, 0x01, 0x2c]);
+        let f = encode(OP_BINARY, &vec![0; 70000]);
+        assert_eq!(&f[..10], &[0x82, 127, 0, 0, 0, 0, 0, 0x01, 0x11, 0x70]);
+        assert_eq!(f.len(), 70010);
+    }
+
+    #[test]
+    fn decode_masked() {
+        // RFC 6455 masked "Hello"
+        let data = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        let (frame, n) = decode(&data, 1024).unwrap().unwrap();
+        assert_eq!(n, data.len());
+        assert_eq!(frame, Frame { fin: true, opcode: OP_TEXT, payload: b"Hello".to_vec() });
+        // Incomplete until the last byte
+        assert_eq!(decode(&data[..10], 1024), Ok(None));
+        let long = masked(OP_BINARY, &vec![7; 1000]);
+        let (frame, n) = decode(&long, 1024).unwrap().unwrap();
+        assert_eq!((n, frame.payload.len(), frame.payload[999]), (long.len(), 1000, 7));
+    }
+
+    #[test]
+    fn decode_rejects() {
+        assert!(decode(&encode(OP_TEXT, b"Hello"), 1024).is_err());
+        assert!(decode(&masked(OP_BINARY, &vec![0; 2000]), 1024).is_err());
+        assert!(decode_server(&masked(OP_TEXT, b"Hello"), 1024).is_err());
+    }
+
+    #[test]
+    fn random_keys() {
+        assert_eq!(client_key().len(), 24);
+        assert_eq!(new_token().len(), 32);
+        assert_ne!(new_token(), new_token());
+    }
+
+    #[test]
+    fn client_round_trip() {
+        // RFC 6455 masked "Hello"
+        assert_eq!(masked(OP_TEXT, b"Hello"), vec![0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]);
+        let f = encode(OP_BINARY, &vec![5; 300]);
+        let (frame, n) = decode_server(&f, 1024).unwrap().unwrap();
+        assert_eq!((n, frame.opcode, frame.payload.len()), (f.len(), OP_BINARY, 300));
+        assert_eq!(decode_server(&f[..100], 1024), Ok(None));
+    }
+}
@@ -44,13 +44,16 @@ use crate::app::common::messages;
 pub struct UDPRData{
     receiver : crossbeam_channel::Receiver<messages::ReaderMsg>,
 	p_sock :  Arc<socket2::Socket>,
-    rb_iq : Arc<ringb::SyncByteRingBuf>,
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
     iq_cond : Arc<(Mutex<bool>, Condvar)>,
+    hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
     udp_frame : [MaybeUninit<u8>; common_defs::FRAME_SZ as usize],
     pub i_seq: protocol::seq_in::SeqData,
     listen: bool,
-    iq: [u8; common_defs::IQ_ARR_SZ_R1 as usize],
+    iq: [[u8; common_defs::IQ_ARR_SZ_R1 as usize]; common_defs::MAX_RADIOS as usize],
     mic: [u8; common_defs::MIC_ARR_SZ_R1 as usize],
+    mic_count: u32,
 }
 
 // Implementation methods on UDPRData
@@ -59,8 +62,10 @@ impl UDPRData {
     pub fn new(
         receiver : crossbeam_channel::Receiver<messages::ReaderMsg>, 
         p_sock : Arc<socket2::Socket>, 
-        rb_iq : Arc<ringb::SyncByteRingBuf>,
-        iq_cond : Arc<(Mutex<bool>, Condvar)>) -> UDPRData {
+        rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+        rb_mic : Arc<ringb::SyncByteRingBuf>,
+        iq_cond : Arc<(Mutex<bool>, Condvar)>,
+        hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>) -> UDPRData {
         // Create an instance of the sequence type
         let i_seq = protocol::seq_in::SeqData::new();
 
@@ -68,14 +73,18 @@ impl UDPRData {
             receiver: receiver,
 			p_sock: p_sock,
             rb_iq : rb_iq,
+            rb_mic : rb_mic,
             iq_cond : iq_cond,
+            hpsdr_sender : hpsdr_sender,
             // Received UDP data buffer
             udp_frame: [MaybeUninit::uninit(); common_defs::FRAME_SZ as usize],
             // UDP data contains a header + 2 protocol frames
             i_seq: i_seq,
             listen: false,
-            iq: [0; common_defs::IQ_ARR_SZ_R1 as usize],
+            iq: [[0; common_defs::IQ_ARR_SZ_R1 as usize]; common_defs::MAX_RADIOS as usize],
             mic: [0; common_defs::MIC_ARR_SZ_R1 as usize],
+            // Mic decimation carries over between frames
+            mic_count: 0,
 		}
 	}
 
@@ -124,7 +133,6 @@ impl UDPRData {
     fn split_frame(&mut self) { 
         
         let num_rx = globals::get_num_rx();
-        let sel_rx = globals::get_sel_rx();
         let mut j: usize = 0;
         let mut ep6_seq : [u8; 4] = [0,0,0,0];
         
@@ -151,6 +159,8 @@ impl UDPRData {
                     //Boolean return incase we need to do anything
                     // Sequence errors are reported in cc-in
                 }
+                // Hardware PTT and key inputs
+                protocol::cc_in::cc_in_decode(&self.udp_frame);
             } else if self.udp_frame[3].assume_init() == common_defs::EP4 {
                 // We have wideband data
                 // TBD
@@ -158,29 +168,55 @@ impl UDPRData {
             }
         }
         
-        // Decode into contiguous IQ and Mic frames
-        let num_smpls = protocol::decoder::frame_decode(
-            num_rx, sel_rx, globals::get_smpl_rate(), 
-            &self.udp_frame, &mut self.iq, &mut self.mic);
+        // Decode into contiguous IQ frames for each receiver and a Mic frame
+        let (num_smpls, mic_bytes) = protocol::decoder::frame_decode(
+            num_rx, globals::get_smpl_rate(), 
+            &self.udp_frame, &mut self.iq, &mut self.mic, &mut self.mic_count);
 
         //================================================================================
         // At this point we have separated the IQ and Mic data into separate buffers
         // Truncate vec if necessary for RX samples for current number of receivers
+        // When a file is playing the player feeds the receivers instead
         let mut success = false;
-        let mut vec_iq = self.iq.to_vec();
-        if num_rx > 1 {
-            vec_iq.resize((num_smpls*common_defs::BYTES_PER_SAMPLE) as usize, 0);
+        let mut active_rx = u32::min(num_rx, common_defs::MAX_RADIOS) as usize;
+        if globals::get_playback() {
+            active_rx = 0;
         }
-        // Copy the UDP frame into the rb_iq ring buffer
-        let r = self.rb_iq.write().write(&vec_iq);
-        match r {
-            Err(e) => {
-                println!("Write error on rb_iq, skipping block {:?}", e);
+        for rx in 0..active_rx {
+            let mut vec_iq = self.iq[rx].to_vec();
+            if num_rx > 1 {
+                vec_iq.resize((num_smpls*common_defs::BYTES_PER_SAMPLE) as usize, 0);
             }
-            Ok(_sz) => {
-                success = true;  
+            // Copy the receiver data into its rb_iq ring buffer
+            let r = self.rb_iq[rx].write().write(&vec_iq);
+            match r {
+                Err(e) => {
+                    println!("Write error on rb_iq for RX{}, skipping block {:?}", rx + 1, e);
+                }
+                Ok(_sz) => {
+                    success = true;
+                }
             }
         }
+        // The receiver shared with a Protocol 1 client goes to it as well
+        let share = globals::get_hpsdr_rx() as usize;
+        if share >= 1 && share <= active_rx {
+            let iq = self.iq[share - 1][..(num_smpls*common_defs::BYTES_PER_SAMPLE) as usize].to_vec();
+            // Never hold up or panic the reader, a closed server just misses the IQ
+            let _ = self.hpsdr_sender.send(messages::HpsdrMsg::Iq(iq));
+        }
+        // Mic data is always at 48K, the pipeline takes it when a block is available
+        // The audio bridge or a web client replaces it while TX audio is arriving
+        if !globals::get_bridge_tx() && !globals::get_web_tx() {
+            let r = self.rb_mic.write().write(&self.mic[..mic_bytes as usize]);
+            match r {
+                Err(_e) => {
+                    // Pipeline not keeping up, TX audio will have a gap
+                }
+                Ok(_sz) => (),
+            }
+        }
+
         // Signal the pipeline that data is available
         if success {
             let mut locked = self.iq_cond.0.lock().unwrap();
@@ -196,10 +232,12 @@ impl UDPRData {
 pub fn reader_start(
     receiver : crossbeam_channel::Receiver<messages::ReaderMsg>, 
     p_sock : Arc<socket2::Socket>, 
-    rb_iq : Arc<ringb::SyncByteRingBuf>, 
-    iq_cond : Arc<(Mutex<bool>, Condvar)>) -> thread::JoinHandle<()> {
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>, 
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    iq_cond : Arc<(Mutex<bool>, Condvar)>,
+    hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>) -> thread::JoinHandle<()> {
     let join_handle = thread::spawn(  move || {
-        reader_run(receiver, p_sock, rb_iq, iq_cond);
+        reader_run(receiver, p_sock, rb_iq, rb_mic, iq_cond, hpsdr_sender);
     });
     return join_handle;
 }
@@ -207,12 +245,14 @@ pub fn reader_start(
 fn reader_run(
     receiver : crossbeam_channel::Receiver<messages::ReaderMsg>, 
     p_sock : Arc<socket2::Socket>, 
-    rb_iq : Arc<ringb::SyncByteRingBuf>,
-    iq_cond : Arc<(Mutex<bool>, Condvar)>) {
+    rb_iq : Vec<Arc<ringb::SyncByteRingBuf>>,
+    rb_mic : Arc<ringb::SyncByteRingBuf>,
+    iq_cond : Arc<(Mutex<bool>, Condvar)>,
+    hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>) {
     println!("UDP Reader running");
 
     // Instantiate the runtime object
-    let mut i_reader = UDPRData::new(receiver,  p_sock, rb_iq, iq_cond);
+    let mut i_reader = UDPRData::new(receiver,  p_sock, rb_iq, rb_mic, iq_cond, hpsdr_sender);
 
     // Exits when the reader loop exits
     i_reader.reader_run();
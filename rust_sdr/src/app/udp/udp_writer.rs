@@ -32,6 +32,7 @@ use std::sync::{Arc, Mutex, Condvar};
 use std::io:: Read;
 
 use crate::app::common::common_defs;
+use crate::app::common::globals;
 use crate::app::common::messages;
 use crate::app::protocol;
 use crate::app::common::ringb;
@@ -133,7 +134,10 @@ impl UDPWData {
                     match prot_frame {
                         Ok(_sz) => {
                             // Encode the next frame
-                            protocol::encoder::encode(&mut self.i_seq, &mut self.i_cc.lock().unwrap(), &mut self.udp_frame, &mut self.prot_frame);
+                            let mut cc = self.i_cc.lock().unwrap();
+                            cc.cc_mox(mox());
+                            protocol::encoder::encode(&mut self.i_seq, &mut cc, &mut self.udp_frame, &mut self.prot_frame);
+                            drop(cc);
                             // Send to hardware
                             let r = self.p_sock.send_to(&self.udp_frame, &self.p_addr);
                             match r {
@@ -156,6 +160,11 @@ impl UDPWData {
     }
 }
 
+// Transmit when PTT is pressed on the hardware or a CAT client, VOX is keyed or CW is being sent
+pub fn mox() -> bool {
+    return globals::get_ptt() || globals::get_cat_ptt() || (globals::get_vox() && globals::get_vox_active()) || globals::get_cw_mox() || globals::get_test_mox();
+}
+
 //==================================================================================
 // Thread startup
 pub fn writer_start(
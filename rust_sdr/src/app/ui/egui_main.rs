@@ -27,57 +27,102 @@ bob@bobcowdery.plus.com
 
 pub mod components;
 
-use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
 use std::thread;
 use std::time::Duration;
 
 use crate::app::common::common_defs;
 use crate::app::common::prefs;
-use crate::app::protocol;
-use crate::app::dsp;
-use crate::app::udp::hw_control;
+use crate::app::common::messages;
+use crate::app::client::radio_client::RadioClient;
 
 use eframe::egui;
 
 //===========================================================================================
 // State for UIApp
 pub struct UIMain {
-    _i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    client : Rc<RefCell<dyn RadioClient>>,
     central : components::egui_central::UICentral,
     modes : components::egui_mode::UIMode,
     filters : components::egui_filter::UIFilter,
     vfo : Rc<RefCell<components::egui_vfo::UIVfo>>,
     spec : Rc<RefCell<components::egui_spec::UISpec>>,
     meter : Rc<RefCell<components::egui_meter::UIMeter>>,
+    mixer : components::egui_mixer::UIMixer,
+    decoder : components::egui_decoder::UIDecoder,
+    diversity : components::egui_diversity::UIDiversity,
+    sub_rx : components::egui_subrx::UISubRx,
+    tx : components::egui_tx::UITx,
+    vox : components::egui_vox::UIVox,
+    cw : components::egui_cw::UICw,
+    test : components::egui_test::UITest,
+    repeater : components::egui_repeater::UIRepeater,
+    record : components::egui_record::UIRecord,
+    // The servers are for this engine only
+    remote : Option<components::egui_remote::UIRemote>,
     out_real: [f32; (common_defs::DSP_BLK_SZ ) as usize],
     prefs: Rc<RefCell<prefs::Prefs>>,
-    _hw: Rc<RefCell<hw_control::HWData>>
 }
 
 //===========================================================================================
 // Implementation for UIApp
 impl UIMain {
-    pub fn new(cc: &eframe::CreationContext<'_>, i_cc : Arc<Mutex<protocol::cc_out::CCData>>, prefs: Rc<RefCell<prefs::Prefs>>, hw: Rc<RefCell<hw_control::HWData>>) -> Self{
-
-        let central = components::egui_central::UICentral::new(i_cc.clone(), prefs.clone(), hw.clone());
-        let vfo = Rc::new(RefCell::new(components::egui_vfo::UIVfo::new(cc, i_cc.clone(), prefs.clone())));
-        let spec = Rc::new(RefCell::new(components::egui_spec::UISpec::new(cc, i_cc.clone(), vfo.clone())));
-        let meter = Rc::new(RefCell::new(components::egui_meter::UIMeter::new(cc)));
-        let modes = components::egui_mode::UIMode::new(cc, i_cc.clone(), spec.clone(), prefs.clone());
-        let filters = components::egui_filter::UIFilter::new(cc, i_cc.clone(), spec.clone(), prefs.clone());
+    pub fn new(
+            cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>,
+            dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>,
+            rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+            play_sender : crossbeam_channel::Sender<messages::PlayerMsg>,
+            rig_sender : crossbeam_channel::Sender<messages::RigctlMsg>,
+            cat_pty_sender : crossbeam_channel::Sender<messages::CatPtyMsg>,
+            bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+            web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+            hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+            rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+            cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>) -> Self{
+
+        let central = components::egui_central::UICentral::new(prefs.clone(), client.clone());
+        let vfo = Rc::new(RefCell::new(components::egui_vfo::UIVfo::new(cc, client.clone(), prefs.clone())));
+        let spec = Rc::new(RefCell::new(components::egui_spec::UISpec::new(cc, client.clone(), vfo.clone(), prefs.clone())));
+        let meter = Rc::new(RefCell::new(components::egui_meter::UIMeter::new(cc, client.clone(), prefs.clone())));
+        let modes = components::egui_mode::UIMode::new(cc, client.clone(), spec.clone(), prefs.clone());
+        let filters = components::egui_filter::UIFilter::new(cc, client.clone(), spec.clone(), prefs.clone());
+        let mixer = components::egui_mixer::UIMixer::new(cc, client.clone(), prefs.clone());
+        let decoder = components::egui_decoder::UIDecoder::new(cc, prefs.clone(), dec_receiver);
+        let diversity = components::egui_diversity::UIDiversity::new(cc, client.clone(), prefs.clone());
+        let sub_rx = components::egui_subrx::UISubRx::new(cc, client.clone(), prefs.clone());
+        let tx = components::egui_tx::UITx::new(cc, client.clone(), prefs.clone());
+        let vox = components::egui_vox::UIVox::new(cc, client.clone(), prefs.clone());
+        let cw = components::egui_cw::UICw::new(cc, client.clone(), prefs.clone());
+        let test = components::egui_test::UITest::new(cc, client.clone(), prefs.clone());
+        let repeater = components::egui_repeater::UIRepeater::new(cc, client.clone(), prefs.clone());
+        let record = components::egui_record::UIRecord::new(cc, prefs.clone(), vfo.clone(), rec_sender, play_sender);
+        let mut remote = None;
+        if !client.borrow().is_remote() {
+            remote = Some(components::egui_remote::UIRemote::new(
+                cc, client.clone(), prefs.clone(), vfo.clone(), rig_sender, cat_pty_sender, bridge_sender, web_sender, hpsdr_sender, rtl_sender, cat_receiver));
+        }
         
         Self {
-            _i_cc : i_cc,
+            client : client,
             central: central,
             modes : modes,
             filters : filters,
             vfo : vfo,
             spec : spec,
             meter : meter,
+            mixer : mixer,
+            decoder : decoder,
+            diversity : diversity,
+            sub_rx : sub_rx,
+            tx : tx,
+            vox : vox,
+            cw : cw,
+            test : test,
+            repeater : repeater,
+            record : record,
+            remote : remote,
             out_real: [0.0; (common_defs::DSP_BLK_SZ ) as usize],
             prefs: prefs,
-            _hw: hw,
         }
     }
 }
@@ -86,8 +131,19 @@ impl UIMain {
 impl eframe::App for UIMain {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
 
+        // Apply any remote control or remote engine changes before the components restore their settings
+        self.client.borrow_mut().poll();
+        if let Some(remote) = self.remote.as_mut() {
+            remote.poll();
+        }
+
         // Get the latest data update
-        dsp::dsp_interface::wdsp_get_display_data(0, &mut self.out_real);
+        let new_data = self.client.borrow_mut().display(&mut self.out_real);
+        if let Some(remote) = self.remote.as_mut() {
+            remote.spectrum(new_data, &self.out_real);
+        }
+        // A remote engine has the rest of the settings where it runs
+        let local = !self.client.borrow().is_remote();
 
         // Central pane has all common controls and status
         egui::TopBottomPanel::top(String::from("TOP")).show(ctx, |ui| {
@@ -107,6 +163,53 @@ impl eframe::App for UIMain {
             self.filters.filters(ui);
         });
 
+        // Local audio mixer
+        egui::TopBottomPanel::bottom(String::from("MIXER")).show(ctx, |ui| {
+            self.mixer.mixer(ui);
+        });
+
+        if local {
+            // Decoded text
+            egui::TopBottomPanel::bottom(String::from("DECODER")).show(ctx, |ui| {
+                self.decoder.decoder(ui);
+            });
+
+            // Diversity combining
+            egui::TopBottomPanel::bottom(String::from("DIVERSITY")).show(ctx, |ui| {
+                self.diversity.diversity(ui);
+            });
+
+            // Recording
+            egui::TopBottomPanel::bottom(String::from("RECORD")).show(ctx, |ui| {
+                self.record.record(ui);
+            });
+
+            // Remote control servers
+            if let Some(remote) = self.remote.as_mut() {
+                egui::TopBottomPanel::bottom(String::from("REMOTE")).show(ctx, |ui| {
+                    remote.remote(ui);
+                });
+            }
+
+            // Sub-receivers in the passband of the selected receiver
+            egui::TopBottomPanel::bottom(String::from("SUBRX")).show(ctx, |ui| {
+                self.sub_rx.sub_rx(ui);
+            });
+
+            // TX audio processing and metering
+            egui::SidePanel::right(String::from("TX")).show(ctx, |ui| {
+                self.vox.vox(ui);
+                ui.separator();
+                self.cw.cw(ui);
+                ui.separator();
+                self.test.test(ui);
+                ui.separator();
+                self.repeater.repeater(ui);
+                ui.separator();
+                self.tx.tx(ui);
+            });
+        }
+
         //egui::TopBottomPanel::bottom(String::from("BOTTOM")).show(ctx, |ui| {
         //    egui::Grid::new("grid-1").show(ui, |ui| {
         //        self.vfo.borrow_mut().vfo(ui);
@@ -224,7 +327,18 @@ impl eframe::App for UIMain {
 }
 
 // Instantiate the one and only main window and run the event loop
-pub fn ui_run(i_cc: Arc<Mutex<protocol::cc_out::CCData>>, prefs: Rc<RefCell<prefs::Prefs>>, hw: Rc<RefCell<hw_control::HWData>>) {
+pub fn ui_run(
+        client: Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>,
+        dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>,
+        rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+        play_sender : crossbeam_channel::Sender<messages::PlayerMsg>,
+        rig_sender : crossbeam_channel::Sender<messages::RigctlMsg>,
+        cat_pty_sender : crossbeam_channel::Sender<messages::CatPtyMsg>,
+        bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+        web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+        hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+        rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+        cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>) {
     
     let x = prefs.borrow().frame.x;
     let y = prefs.borrow().frame.y;
@@ -253,13 +367,11 @@ pub fn ui_run(i_cc: Arc<Mutex<protocol::cc_out::CCData>>, prefs: Rc<RefCell<pref
         default_theme: eframe::Theme::Dark,
         run_and_return: true
     };
-    let i_cc = i_cc.clone();
     let prefs = prefs.clone();
-    let hw = hw.clone();
     eframe::run_native(
         "Rust SDR",
         options,
-        Box::new(|cc| Box::new(UIMain::new(cc, i_cc, prefs, hw))),
+        Box::new(|cc| Box::new(UIMain::new(cc, client, prefs, dec_receiver, rec_sender, play_sender, rig_sender, cat_pty_sender, bridge_sender, web_sender, hpsdr_sender, rtl_sender, cat_receiver))),
     );
 
 }
\ No newline at end of file
@@ -31,5 +31,16 @@ pub mod egui_filter;
 pub mod egui_vfo;
 pub mod egui_spec;
 pub mod egui_meter;
+pub mod egui_mixer;
+pub mod egui_decoder;
+pub mod egui_diversity;
+pub mod egui_subrx;
+pub mod egui_tx;
+pub mod egui_vox;
+pub mod egui_cw;
+pub mod egui_test;
+pub mod egui_repeater;
+pub mod egui_record;
+pub mod egui_remote;
 
 
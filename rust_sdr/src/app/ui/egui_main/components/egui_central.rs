@@ -25,16 +25,11 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
 
-use crate::app::protocol;
 use crate ::app::common::prefs;
 use crate::app::common::common_defs;
-use crate::app::common::cc_out_defs;
-use crate::app::common::globals;
-use crate::app::udp::hw_control;
-use crate::app::dsp::dsp_interface;
+use crate::app::client::radio_client::RadioClient;
 
 use egui::{RichText, TextStyle};
 use eframe::egui;
@@ -43,39 +38,40 @@ use eframe::egui;
 #[derive(Debug)]
 #[derive(PartialEq)]
 enum NumRadiosEnum {One, Two, Three}
-enum RestartState {None, Stop, Start}
+
+// What a remote engine has set where it runs
+const REMOTE_NOTE: &str = "Receivers, sample rate, RF gain, notches, sub-receivers, diversity, recording, decoders, TX, VOX, CW and test signals are set where the engine runs";
 
 //===========================================================================================
 // State for Control
 pub struct UICentral {
-    i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
-    hw: Rc<RefCell<hw_control::HWData>>,
+    client: Rc<RefCell<dyn RadioClient>>,
     prefs: Rc<RefCell<prefs::Prefs>>,
     selected_radio: u32,
     num_radios: NumRadiosEnum,
     smpl_rate: u32,
     running: bool,
     gain: f32,
-    restart_state: RestartState,
 }
 
 //===========================================================================================
 // Implementation for UIApp
 impl UICentral {
-    pub fn new(i_cc : Arc<Mutex<protocol::cc_out::CCData>>, prefs: Rc<RefCell<prefs::Prefs>>, hw: Rc<RefCell<hw_control::HWData>>) -> Self{
+    pub fn new(prefs: Rc<RefCell<prefs::Prefs>>, client: Rc<RefCell<dyn RadioClient>>) -> Self{
         
         let af_gain = prefs.borrow().radio.af_gain;
         let smpl_rate = prefs.borrow().radio.smpl_rate;
+        // The system starts at the default rate so bring it into line with the restored rate.
+        // A remote engine keeps its own rate.
+        client.borrow_mut().set_smpl_rate(smpl_rate);
         Self {
-            i_cc: i_cc,
-            hw: hw,
+            client: client,
             prefs: prefs,
             selected_radio: 1,
             num_radios: NumRadiosEnum::One,
             smpl_rate: smpl_rate,
             running: false,
             gain: af_gain,
-            restart_state: RestartState::None,
         }
     }
 
@@ -83,12 +79,18 @@ impl UICentral {
     // Populate control window
     pub fn central_panel(&mut self, ui: &mut egui::Ui) {
         
-        // Do we have a scheduled restart
-        // Note this is done outside of event procs else the rest of the system gets no chance to reset correctly
-        match self.restart_state {
-            RestartState::None => (),
-            RestartState::Stop => self.do_stop(),
-            RestartState::Start => self.do_start(),
+        // The receiver, gain and run state may have been set by remote control
+        self.selected_radio = self.client.borrow().sel_rx();
+        self.gain = self.prefs.borrow().radio.af_gain;
+        self.running = self.client.borrow().running();
+        let remote = self.client.borrow().is_remote();
+        if remote {
+            self.smpl_rate = self.client.borrow().smpl_rate();
+            match self.client.borrow().num_rx() {
+                2 => self.num_radios = NumRadiosEnum::Two,
+                3 => self.num_radios = NumRadiosEnum::Three,
+                _ => self.num_radios = NumRadiosEnum::One,
+            }
         }
 
         // Combination layouts at top of native window
@@ -120,7 +122,7 @@ impl UICentral {
                 // Remainter is controls on one line
                 // Set start button color
                 let mut bcolor = egui::Color32::RED;
-                if self.running && globals::get_discover_state() {
+                if self.running && self.client.borrow().discovered() {
                     bcolor = egui::Color32::GREEN;
                 }
 
@@ -131,11 +133,8 @@ impl UICentral {
                 .background_color(egui::Color32::TRANSPARENT)
                 .color(bcolor));
                 if b.clicked() {
-                    if globals::get_discover_state() {
-                        self.hw.borrow_mut().do_start(false);
-                        self.running = true;
-                        globals::set_run_state(true);
-                    }
+                    self.client.borrow_mut().set_run(true);
+                    self.running = self.client.borrow().running();
                 }
 
                 // Stop button
@@ -144,46 +143,36 @@ impl UICentral {
                 .size(16.0)
                 .background_color(egui::Color32::TRANSPARENT));
                 if b.clicked() {
-                    if self.running {
-                        self.hw.borrow_mut().do_stop();
-                        self.running = false;
-                        globals::set_run_state(false);
-                    }
+                    self.client.borrow_mut().set_run(false);
+                    self.running = self.client.borrow().running();
                 }
 
                 // Audio gain
                 ui.add(egui::Slider::new(&mut self.gain, 0.0..=100.0).suffix("%"));
                 self.prefs.borrow_mut().radio.af_gain = self.gain;
-                globals::set_af_gain(self.gain);
-
-                // Num RX
-                egui::ComboBox::from_label( "")
-                    .selected_text(format!("{:?}", self.num_radios))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.num_radios, NumRadiosEnum::One, "One");
-                        ui.selectable_value(&mut self.num_radios, NumRadiosEnum::Two, "Two");
-                        ui.selectable_value(&mut self.num_radios, NumRadiosEnum::Three, "Three");
-                    }
-                );
-                match self.num_radios {
-                    NumRadiosEnum::One => {
-                        self.query_restart(globals::get_num_rx(), 1);
-                        self.prefs.borrow_mut().radio.num_rx = 1;
-                        globals::set_num_rx(1);
-                        self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx1);
-                    },
-                    NumRadiosEnum::Two => {
-                        self.query_restart(globals::get_num_rx(), 2);
-                        self.prefs.borrow_mut().radio.num_rx = 2;
-                        globals::set_num_rx(2);
-                        self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx2);
-                    },
-                    NumRadiosEnum::Three => {
-                        self.query_restart(globals::get_num_rx(), 3);
-                        self.prefs.borrow_mut().radio.num_rx = 3;
-                        globals::set_num_rx(3);
-                        self.i_cc.lock().unwrap().cc_num_rx(cc_out_defs::CCONumRx::NumRx3);
+                self.client.borrow_mut().set_af_gain(self.gain);
+
+                // Num RX, a remote engine is configured where it runs
+                ui.add_enabled_ui(!remote, |ui| {
+                    egui::ComboBox::from_label( "")
+                        .selected_text(format!("{:?}", self.num_radios))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.num_radios, NumRadiosEnum::One, "One");
+                            ui.selectable_value(&mut self.num_radios, NumRadiosEnum::Two, "Two");
+                            ui.selectable_value(&mut self.num_radios, NumRadiosEnum::Three, "Three");
+                        }
+                    );
+                });
+                if !remote {
+                    // The client restarts the hardware when the number changes
+                    let num_rx;
+                    match self.num_radios {
+                        NumRadiosEnum::One => num_rx = 1,
+                        NumRadiosEnum::Two => num_rx = 2,
+                        NumRadiosEnum::Three => num_rx = 3,
                     }
+                    self.prefs.borrow_mut().radio.num_rx = num_rx;
+                    self.client.borrow_mut().set_num_rx(num_rx);
                 }
 
                 // Selected RX
@@ -191,79 +180,59 @@ impl UICentral {
                     if ui.add(egui::RadioButton::new(self.selected_radio == 1, "RX1")).clicked() {
                         self.selected_radio = 1;
                         self.prefs.borrow_mut().radio.sel_rx = 1;
-                        globals::set_sel_rx(1);
+                        self.client.borrow_mut().set_sel_rx(1);
                     }
                     if ui.add(egui::RadioButton::new(self.selected_radio == 2, "RX2")).clicked() {
                         self.selected_radio = 2;
                         self.prefs.borrow_mut().radio.sel_rx = 2;
-                        globals::set_sel_rx(2);
+                        self.client.borrow_mut().set_sel_rx(2);
                     }
                     if ui.add(egui::RadioButton::new(self.selected_radio == 3, "RX3")).clicked() {
                         self.selected_radio = 3;
                         self.prefs.borrow_mut().radio.sel_rx = 3;
-                        globals::set_sel_rx(3);
+                        self.client.borrow_mut().set_sel_rx(3);
                     }
                 });
 
                 // Sample rate
-                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
-                    if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_48K, "48K")).clicked() {
-                        self.smpl_rate = common_defs::SMPLS_48K;
-                        self.prefs.borrow_mut().radio.smpl_rate = common_defs::SMPLS_48K;
-                        globals::set_smpl_rate(common_defs::SMPLS_48K);
-                        self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S48kHz);
-                        dsp_interface::wdsp_set_input_rate(0, common_defs::SMPLS_48K as i32);
-                        dsp_interface::wdsp_set_dsp_rate(0, common_defs::SMPLS_48K as i32);
-                    }
-                    if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_96K, "96K")).clicked() {
-                        self.smpl_rate = common_defs::SMPLS_96K;
-                        self.prefs.borrow_mut().radio.smpl_rate = common_defs::SMPLS_96K;
-                        globals::set_smpl_rate(common_defs::SMPLS_96K);
-                        self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S96kHz);
-                        dsp_interface::wdsp_set_input_rate(0, common_defs::SMPLS_96K as i32);
-                        dsp_interface::wdsp_set_dsp_rate(0, common_defs::SMPLS_96K as i32);
-                    }
-                    if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_192K, "192K")).clicked() {
-                        self.smpl_rate = common_defs::SMPLS_192K;
-                        self.prefs.borrow_mut().radio.smpl_rate = common_defs::SMPLS_192K;
-                        globals::set_smpl_rate(common_defs::SMPLS_192K);
-                        self.i_cc.lock().unwrap().cc_speed(cc_out_defs::CCOSpeed::S192kHz);
-                        dsp_interface::wdsp_set_input_rate(0, common_defs::SMPLS_192K as i32);
-                        dsp_interface::wdsp_set_dsp_rate(0, common_defs::SMPLS_192K as i32);
-                    }
+                ui.add_enabled_ui(!remote, |ui| {
+                    ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                        if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_48K, "48K")).clicked() {
+                            self.query_rate_change(common_defs::SMPLS_48K);
+                        }
+                        if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_96K, "96K")).clicked() {
+                            self.query_rate_change(common_defs::SMPLS_96K);
+                        }
+                        if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_192K, "192K")).clicked() {
+                            self.query_rate_change(common_defs::SMPLS_192K);
+                        }
+                        if ui.add(egui::RadioButton::new(self.smpl_rate == common_defs::SMPLS_384K, "384K")).clicked() {
+                            self.query_rate_change(common_defs::SMPLS_384K);
+                        }
+                    });
                 });
                 ui.end_row();
             });
+            // Connection state of a remote engine
+            let status = self.client.borrow().status();
+            if status.len() > 0 {
+                ui.label(RichText::new(status).color(egui::Color32::YELLOW));
+            }
+            // The controls for these are disabled or not shown
+            if remote {
+                ui.label(RichText::new(REMOTE_NOTE).color(egui::Color32::GRAY));
+            }
             // Separator line under main controls
             ui.separator();
         });
     }
 
-    // Tiny state machine to perform Stop/Start when number of radios is changed
-    // Restart if the number of radios has changed
-    fn query_restart(&mut self, rx: u32, new_rx: u32) {
-        if rx != new_rx {
-            if self.running {
-                self.restart_state = RestartState::Stop;
-            }
+    // Sample rate change, the client stops the hardware while the pipeline reconfigures
+    fn query_rate_change(&mut self, rate: u32) {
+        if rate != self.smpl_rate {
+            self.smpl_rate = rate;
+            self.prefs.borrow_mut().radio.smpl_rate = rate;
+            self.client.borrow_mut().set_smpl_rate(rate);
         }
     }
-
-    // Stop if we have changed number of radios
-    fn do_stop(&mut self) {
-        if self.running {
-            self.hw.borrow_mut().do_stop();
-            self.running = false;
-            globals::set_run_state(false);
-            self.restart_state = RestartState::Start;
-        }
-    }
-
-    // Start if we have changed number of radios
-    fn do_start(&mut self) {
-        self.hw.borrow_mut().do_start(false);
-        self.running = true;
-        globals::set_run_state(true);
-        self.restart_state = RestartState::None;
-    } 
-}
\ No newline at end of file
+}
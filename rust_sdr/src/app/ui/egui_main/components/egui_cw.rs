@@ -0,0 +1,109 @@
+/*
+egui_cw.rs
+
+Module - egui_cw
+CW keyer sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::client::radio_client::RadioClient;
+
+use eframe::egui;
+
+// Keyboard paddles
+const KEY_DOT: egui::Key = egui::Key::Z;
+const KEY_DASH: egui::Key = egui::Key::X;
+
+//===========================================================================================
+// State for CW
+pub struct UICw {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+}
+
+//===========================================================================================
+// Implementation for UICw
+impl UICw {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        // The hardware keyer is set on the first update
+        Self {
+            client: client,
+            prefs: prefs,
+        }
+    }
+
+    //===========================================================================================
+    // Populate CW window
+    pub fn cw(&mut self, ui: &mut egui::Ui) {
+
+        let mut p = self.prefs.borrow().cw.clone();
+        egui::CollapsingHeader::new("CW keyer").show(ui, |ui| {
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                ui.checkbox(&mut p.keyer, "Keyer");
+                ui.checkbox(&mut p.hw_keyer, "Hardware keyer");
+                ui.checkbox(&mut p.keyboard, "Keyboard (Z/X)");
+            });
+            egui::Grid::new("cw-grid").show(ui, |ui| {
+                ui.label(String::from("Mode"));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    ui.selectable_value(&mut p.mode_b, false, "Iambic A");
+                    ui.selectable_value(&mut p.mode_b, true, "Iambic B");
+                });
+                ui.end_row();
+                ui.label(String::from("Speed"));
+                ui.add(egui::Slider::new(&mut p.wpm, 5..=60).suffix("wpm"));
+                ui.end_row();
+                ui.label(String::from("Weight"));
+                ui.add(egui::Slider::new(&mut p.weight, 10..=90));
+                ui.end_row();
+                ui.label(String::from("Sidetone"));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    ui.add(egui::DragValue::new(&mut p.sidetone_freq).speed(10).clamp_range(200..=1200).suffix("Hz"));
+                    ui.add(egui::Slider::new(&mut p.sidetone_vol, 0.0..=1.0));
+                });
+                ui.end_row();
+                ui.label(String::from("Hang"));
+                ui.add(egui::DragValue::new(&mut p.hang).speed(10).clamp_range(0..=1000).suffix("ms"));
+                ui.end_row();
+            });
+        });
+
+        // Paddles on the keyboard unless a text field has focus
+        let mut dot = false;
+        let mut dash = false;
+        if p.keyboard && !ui.ctx().wants_keyboard_input() {
+            dot = ui.input().key_down(KEY_DOT);
+            dash = ui.input().key_down(KEY_DASH);
+        }
+        let mut client = self.client.borrow_mut();
+        client.set_paddles(dot, dash);
+        client.set_cw(&p);
+
+        if self.prefs.borrow().cw != p {
+            self.prefs.borrow_mut().cw = p;
+        }
+    }
+}
@@ -0,0 +1,256 @@
+/*
+egui_decoder.rs
+
+Module - egui_decoder
+Decoded text sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::common::messages;
+use crate::app::common::prefs;
+use crate::app::decoders::audio_spec;
+use crate::app::decoders::rtty_decoder;
+use crate::app::common::common_defs::ModeId;
+
+use egui::{RichText, TextStyle, Color32, Stroke, pos2, vec2};
+use eframe::egui;
+
+// Keep this much text, oldest is discarded
+const MAX_TEXT: usize = 4000;
+const TEXT_HEIGHT: f32 = 60.0;
+
+// Audio waterfall, one pixel per bin and one row per spectrum line
+const WF_WIDTH: usize = audio_spec::BINS;
+const WF_HEIGHT: usize = 40;
+// dB above the average level for full colour
+const WF_RANGE: f32 = 30.0;
+const MARKER_COLOR: Color32 = Color32::RED;
+
+//===========================================================================================
+// State for Decoder
+pub struct UIDecoder {
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>,
+    text: String,
+    wpm: u32,
+    new_text: bool,
+    dec_type: u32,
+    // Waterfall pixels, newest row at the top
+    image_data: Vec<Color32>,
+    // Loaded once then updated in place
+    texture: Option<egui::TextureHandle>,
+}
+
+//===========================================================================================
+// Implementation for UIDecoder
+impl UIDecoder {
+    pub fn new(_cc: &eframe::CreationContext<'_>, prefs: Rc<RefCell<prefs::Prefs>>, dec_receiver : crossbeam_channel::Receiver<messages::DecoderMsg>) -> Self{
+
+        Self {
+            prefs: prefs,
+            dec_receiver: dec_receiver,
+            text: String::new(),
+            wpm: 0,
+            new_text: false,
+            // Restored to globals at startup
+            dec_type: globals::get_dec_type(),
+            image_data: vec![Color32::BLACK; WF_WIDTH * WF_HEIGHT],
+            texture: None,
+        }
+    }
+
+    //===========================================================================================
+    // Populate decoder window
+    pub fn decoder(&mut self, ui: &mut egui::Ui) {
+
+        self.update();
+
+        let mode = globals::get_mode(globals::get_sel_rx() as i32);
+        let cw = mode == ModeId::CwL as u32 || mode == ModeId::CwU as u32;
+        let digi = mode == ModeId::DigU as u32 || mode == ModeId::DigL as u32;
+        let offset = globals::get_dec_offset();
+        let mut status = String::from("Decoder off");
+        if cw {
+            status = format!("CW {} WPM", self.wpm);
+        } else if digi {
+            let name;
+            match self.dec_type {
+                common_defs::DEC_RTTY_50 => name = "RTTY 50",
+                common_defs::DEC_PSK31 => name = "PSK31",
+                _ => name = "RTTY 45",
+            }
+            status = format!("{} {} Hz", name, offset.round() as i32);
+        }
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            ui.label(RichText::new(status).text_style(TextStyle::Monospace));
+            // Decoder used in the digital modes
+            if ui.add(egui::RadioButton::new(self.dec_type == common_defs::DEC_RTTY_45, "RTTY 45")).clicked() {
+                self.dec_type = common_defs::DEC_RTTY_45;
+            }
+            if ui.add(egui::RadioButton::new(self.dec_type == common_defs::DEC_RTTY_50, "RTTY 50")).clicked() {
+                self.dec_type = common_defs::DEC_RTTY_50;
+            }
+            if ui.add(egui::RadioButton::new(self.dec_type == common_defs::DEC_PSK31, "PSK31")).clicked() {
+                self.dec_type = common_defs::DEC_PSK31;
+            }
+            if ui.button("Clear").clicked() {
+                self.text.clear();
+            }
+        });
+        globals::set_dec_type(self.dec_type);
+        self.prefs.borrow_mut().decoder.dec_type = self.dec_type;
+
+        if cw || digi {
+            self.waterfall(ui, digi);
+        }
+        // The offset follows the PSK AFC so keep prefs in step
+        self.prefs.borrow_mut().decoder.offset = globals::get_dec_offset();
+
+        egui::ScrollArea::vertical()
+            .max_height(TEXT_HEIGHT)
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.label(RichText::new(&self.text).text_style(TextStyle::Monospace));
+                if self.new_text {
+                    // Keep the latest text in view
+                    ui.scroll_to_cursor(Some(egui::Align::BOTTOM));
+                    self.new_text = false;
+                }
+            });
+    }
+
+    // Collect anything from the decoders
+    fn update(&mut self) {
+        loop {
+            match self.dec_receiver.try_recv() {
+                Ok(msg) => {
+                    match msg {
+                        messages::DecoderMsg::Text(text) => {
+                            self.text.push_str(&text);
+                            self.new_text = true;
+                        },
+                        messages::DecoderMsg::Wpm(wpm) => self.wpm = wpm,
+                        messages::DecoderMsg::Spectrum(line) => self.add_line(&line),
+                    }
+                },
+                // Nothing more waiting
+                _ => break,
+            }
+        }
+        if self.text.len() > MAX_TEXT {
+            let mut cut = self.text.len() - MAX_TEXT;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+        }
+    }
+
+    //===========================================================================================
+    // Audio waterfall, click to set the offset of a digital signal
+    fn waterfall(&mut self, ui: &mut egui::Ui, digi: bool) {
+        let mut img = egui::ColorImage::new([WF_WIDTH, WF_HEIGHT], Color32::BLACK);
+        for y in 0..WF_HEIGHT {
+            for x in 0..WF_WIDTH {
+                img[(x, y)] = self.image_data[y * WF_WIDTH + x];
+            }
+        }
+        let texture_id;
+        match self.texture.as_mut() {
+            Some(texture) => {
+                texture.set(img, egui::TextureFilter::Linear);
+                texture_id = texture.id();
+            },
+            None => {
+                let texture = egui::Context::load_texture(ui.ctx(), "dec-wf", img, egui::TextureFilter::Linear);
+                texture_id = texture.id();
+                self.texture = Some(texture);
+            },
+        }
+        let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), WF_HEIGHT as f32), egui::Sense::click());
+        let painter = ui.painter();
+        painter.image(texture_id, rect, egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)), Color32::WHITE);
+        if !digi {
+            return;
+        }
+
+        if response.clicked() {
+            match response.interact_pointer_pos() {
+                Some(pos) => {
+                    let offset = (pos.x - rect.left()) / rect.width() * audio_spec::SPAN;
+                    globals::set_dec_offset(offset.round());
+                },
+                None => (),
+            }
+        }
+
+        // Mark the frequencies being decoded
+        let offset = globals::get_dec_offset();
+        let mut marks = vec![offset];
+        if self.dec_type != common_defs::DEC_PSK31 {
+            marks = vec![offset - rtty_decoder::SHIFT / 2.0, offset + rtty_decoder::SHIFT / 2.0];
+        }
+        for f in marks.iter() {
+            let x = rect.left() + f / audio_spec::SPAN * rect.width();
+            painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], Stroke::new(1.0, MARKER_COLOR));
+        }
+    }
+
+    // Add a spectrum line to the top of the waterfall
+    fn add_line(&mut self, line: &[f32]) {
+        // Colour relative to the average so the display adapts to the audio level
+        let mut avg = 0.0;
+        for db in line.iter() {
+            avg += *db;
+        }
+        avg = avg / line.len() as f32;
+        self.image_data.truncate(self.image_data.len() - WF_WIDTH);
+        let mut new_data: Vec<Color32> = Vec::with_capacity(WF_WIDTH * WF_HEIGHT);
+        for x in 0..WF_WIDTH {
+            let mut level = 0.0;
+            if x < line.len() {
+                level = f32::max(0.0, f32::min(1.0, (line[x] - avg) / WF_RANGE));
+            }
+            new_data.push(self.level_to_color(level));
+        }
+        new_data.append(&mut self.image_data);
+        self.image_data = new_data;
+    }
+
+    // Black through blue and yellow to red
+    fn level_to_color(&self, level: f32) -> Color32 {
+        if level < 0.33 {
+            let l = level / 0.33;
+            return Color32::from_rgb(0, 0, (l * 255.0) as u8);
+        } else if level < 0.66 {
+            let l = (level - 0.33) / 0.33;
+            return Color32::from_rgb((l * 255.0) as u8, (l * 255.0) as u8, ((1.0 - l) * 255.0) as u8);
+        }
+        let l = (level - 0.66) / 0.34;
+        return Color32::from_rgb(255, ((1.0 - l) * 255.0) as u8, 0);
+    }
+}
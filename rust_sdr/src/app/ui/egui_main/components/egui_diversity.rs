@@ -0,0 +1,98 @@
+/*
+egui_diversity.rs
+
+Module - egui_diversity
+Diversity combining sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::client::radio_client::RadioClient;
+
+use egui::{RichText, TextStyle};
+use eframe::egui;
+
+// Gain of RX2 relative to RX1
+const MIN_GAIN_DB: f32 = -20.0;
+const MAX_GAIN_DB: f32 = 20.0;
+
+//===========================================================================================
+// State for Diversity
+pub struct UIDiversity {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    enabled: bool,
+    gain_db: f32,
+    phase: f32,
+}
+
+//===========================================================================================
+// Implementation for UIDiversity
+impl UIDiversity {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        // Settings were restored to globals at startup
+        Self {
+            client: client,
+            prefs: prefs,
+            enabled: globals::get_diversity(),
+            gain_db: 20.0 * f32::max(globals::get_div_gain(), 1e-3).log10(),
+            phase: globals::get_div_phase(),
+        }
+    }
+
+    //===========================================================================================
+    // Populate diversity window
+    pub fn diversity(&mut self, ui: &mut egui::Ui) {
+
+        let available = globals::get_num_rx() >= 2;
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            ui.checkbox(&mut self.enabled, "Diversity RX1+RX2");
+            if !available {
+                ui.label(String::from("Needs 2 receivers"));
+                return;
+            }
+            ui.label(String::from("Gain"));
+            ui.add(egui::Slider::new(&mut self.gain_db, MIN_GAIN_DB..=MAX_GAIN_DB).suffix("dB"));
+            ui.label(String::from("Phase"));
+            ui.add(egui::Slider::new(&mut self.phase, -180.0..=180.0).suffix("°"));
+            // Fine adjustment for the null
+            ui.add(egui::DragValue::new(&mut self.phase).speed(0.1).clamp_range(-180.0..=180.0));
+            if self.enabled {
+                // Adjust gain and phase for the lowest level
+                let sig = self.client.borrow().rx_meter(1, common_defs::MeterType::SAverage);
+                ui.label(RichText::new(format!("RX1 {:.1} dBm", sig)).text_style(TextStyle::Monospace));
+            }
+        });
+
+        let gain = f32::powf(10.0, self.gain_db / 20.0);
+        self.client.borrow_mut().set_diversity(self.enabled, gain, self.phase);
+        let mut p = self.prefs.borrow_mut();
+        p.diversity.enabled = self.enabled;
+        p.diversity.gain = gain;
+        p.diversity.phase = self.phase;
+    }
+}
@@ -25,32 +25,16 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
 
 use crate ::app::common::globals;
 use crate ::app::common::prefs;
-use crate::app::protocol;
-use crate::app::dsp;
+use crate::app::common::common_defs::FilterId;
+use crate::app::client::radio_client::RadioClient;
 use crate::app::ui::egui_main::components;
 
 use egui::{RichText, TextStyle};
 use eframe::egui;
-use serde:: {Serialize, Deserialize};
-
-// Filter enumerations
-#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
-pub enum FilterId {
-    F6_0KHz,
-    F4_0KHz,
-    F2_7KHz,
-    F2_4KHz,
-    F2_1KHz,
-    F1_0KHz,
-    F500Hz,
-    F250Hz,
-    F100Hz,
-}
 
 const FILT_NORMAL_COLOR: egui::Color32 = egui::Color32::TRANSPARENT;
 const FILT_HIGHLIGHT_COLOR: egui::Color32 = egui::Color32::DARK_RED;
@@ -59,7 +43,7 @@ const FILT_HIGHLIGHT_COLOR: egui::Color32 = egui::Color32::DARK_RED;
 // State for Filters
 pub struct UIFilter {
     rx : i32,
-    _i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    client : Rc<RefCell<dyn RadioClient>>,
     filter: FilterId,
     fi_array: [(String, egui::Color32); 9],
     spec : Rc<RefCell<components::egui_spec::UISpec>>,
@@ -70,7 +54,7 @@ pub struct UIFilter {
 // Implementation for UIApp
 impl UIFilter {
     pub fn new(_cc: &eframe::CreationContext<'_>, 
-        i_cc : Arc<Mutex<protocol::cc_out::CCData>>, 
+        client : Rc<RefCell<dyn RadioClient>>, 
         spec : Rc<RefCell<components::egui_spec::UISpec>>,
         prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
 
@@ -96,7 +80,7 @@ impl UIFilter {
             3 => filter = prefs.borrow().radio.rx3.filter,
             _ => (),
         }
-        dsp::dsp_interface::set_mode_filter(0, rx as i32);
+        client.borrow_mut().set_filter(rx, filter);
         let width;
         match filter {
             FilterId::F6_0KHz => width = 6000,
@@ -113,7 +97,7 @@ impl UIFilter {
 
         Self {
             rx: rx as i32,
-            _i_cc: i_cc,
+            client: client,
             fi_array: fi_array,
             filter: filter,
             prefs: prefs,
@@ -234,8 +218,7 @@ impl UIFilter {
             },
             _ => (),
         }
-        globals::set_filter(self.rx, self.filter as u32);
-        dsp::dsp_interface::set_mode_filter(0, rx as i32);
+        self.client.borrow_mut().set_filter(self.rx as u32, self.filter);
         self.filter = filter;
 
         let width;
@@ -279,8 +262,7 @@ impl UIFilter {
             },
             _ => (),
         }
-        globals::set_filter(self.rx, self.filter as u32);
-        dsp::dsp_interface::set_mode_filter(0, self.rx);
+        self.client.borrow_mut().set_filter(self.rx as u32, self.filter);
     }
 
 }
\ No newline at end of file
@@ -26,64 +26,154 @@ bob@bobcowdery.plus.com
 */
 
 use epaint::Color32;
+use std::{cell::RefCell, rc::Rc};
+use std::time::Instant;
 
 use crate::app::common::globals;
 use crate::app::common::common_defs;
-use crate::app::dsp;
+use crate::app::common::prefs;
+use crate::app::client::radio_client::RadioClient;
+
+use egui::{RichText, TextStyle};
 
 // Drawing colors
 const TEXT_COLOR: Color32 = Color32::from_rgba_premultiplied(150,0,0,70);
 const GRID_COLOR: Color32 = Color32::from_rgba_premultiplied(0,50,0,10);
 const SIG_COLOR: Color32 = Color32::from_rgba_premultiplied(150,150,0,70);
+const PEAK_COLOR: Color32 = Color32::from_rgba_premultiplied(150,0,0,70);
 
 const LEFT_MARGIN: f32 = 5.0;
 const RIGHT_MARGIN: f32 = 5.0;
+const TOP_MARGIN: f32 = 8.0;
 const TEXT_BOTTOM_MARGIN: f32 = 10.0;
 const GRID_BOTTOM_MARGIN: f32 = 20.0;
 const SIG_BOTTOM_MARGIN: f32 = 35.0;
-const INTER_GAP: f32 = 17.0;
+const INTER_GAP: f32 = 16.0;
 const FONT_SZ: f32 = 10.0;
 const GRID_STROKE: f32 = 0.5;
 const SIG_STROKE: f32 = 4.0;
+const PEAK_STROKE: f32 = 2.0;
+
+// Peak hold time then decay rate
+const HOLD_SECS: f32 = 1.0;
+const DECAY_DB_SEC: f32 = 10.0;
+// Calibration averages over this time against a signal of known level
+const CAL_SECS: f32 = 2.0;
+const CAL_REF: f32 = -73.0;
+
+// S meter levels for S1 to S9+60
+const S_LEVELS: [f32; 12] = [-121.0, -115.0, -109.0, -103.0, -97.0, -91.0, -85.0, -79.0, -73.0, -53.0, -33.0, -13.0];
+const S_LEGENDS: [&str; 12] = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "+20", "+40", "+60"];
+
+// Meter sources, type, name and whether it is the S meter which takes the calibration
+const SOURCES: [(common_defs::MeterType, &str, bool); 7] = [
+    (common_defs::MeterType::SPeak, "S Peak", true),
+    (common_defs::MeterType::SAverage, "S Avg", true),
+    (common_defs::MeterType::InputPeak, "ADC Peak", false),
+    (common_defs::MeterType::InputAverage, "ADC Avg", false),
+    (common_defs::MeterType::AGCGain, "AGC Gain", false),
+    (common_defs::MeterType::AGCPEak, "AGC Peak", false),
+    (common_defs::MeterType::AGCAVerage, "AGC Avg", false),
+];
+const ATTN: [&str; 4] = ["0dB", "10dB", "20dB", "30dB"];
+
+// Steps of the calibration wizard
+#[derive(PartialEq)]
+enum CalState {
+    Idle,
+    // Enter the level of a known signal
+    Setup,
+    Measuring,
+    // Offset calculated, waiting to save
+    Done,
+}
 
 //===========================================================================================
 // State for meter
 pub struct UIMeter {
     // Parameters
-    legends: [String; 11],
-    level: [i32; 11],
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    source: usize,
+    attn: u32,
+    preamp: bool,
+    // Peak hold
+    peak: f32,
+    peak_time: Instant,
+    last_time: Instant,
+    // Calibration
+    cal_state: CalState,
+    cal_ref: f32,
+    cal_sum: f64,
+    cal_count: u32,
+    cal_start: Instant,
+    cal_offset: f32,
 }
 
 //===========================================================================================
 // Implementation for UIMeter
 impl UIMeter {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self{
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
 
-        Self {
-            legends: [String::from("1"), String::from("2"), String::from("3"), String::from("4"), String::from("5"), String::from("6"), String::from("7"), String::from("8"), String::from("9"), String::from("+20"), String::from("+40")],
-            level: [-121, -115,-109,-103, -97,-91,-85,-79,-73,-53,-33],
+        let mut source = common_defs::MeterType::SAverage as usize;
+        let attn = prefs.borrow().meter.attn;
+        let preamp = prefs.borrow().meter.preamp;
+        if (prefs.borrow().meter.source as usize) < SOURCES.len() {
+            source = prefs.borrow().meter.source as usize;
         }
+        let mut meter = Self {
+            client: client,
+            prefs: prefs,
+            source: source,
+            attn: attn,
+            preamp: preamp,
+            peak: S_LEVELS[0],
+            peak_time: Instant::now(),
+            last_time: Instant::now(),
+            cal_state: CalState::Idle,
+            cal_ref: CAL_REF,
+            cal_sum: 0.0,
+            cal_count: 0,
+            cal_start: Instant::now(),
+            cal_offset: 0.0,
+        };
+        // Restore the RF gain
+        meter.set_rf_gain();
+        return meter;
     }
 
     pub fn meter(&mut self, ui: &mut egui::Ui) {
-        
+
+        ui.vertical(|ui| {
+            self.draw_meter(ui);
+            self.controls(ui);
+            if self.cal_state != CalState::Idle {
+                self.calibrate(ui);
+            }
+        });
+    }
+
+    // Meter scale, bar, peak and readout
+    fn draw_meter(&mut self, ui: &mut egui::Ui) {
         egui::Frame::canvas(ui.style()).show(ui, |ui| {
             // Ensure repaint
             ui.ctx().request_repaint();
 
             // Size appropriately for meter. We don't want it to stretch.
-            let desired_size = egui::vec2(200.0, 50.0);
+            let desired_size = egui::vec2(200.0, 62.0);
             let (_id, rect) = ui.allocate_space(desired_size);
 
             // Get the painter
             let painter = ui.painter();
 
             // Draw legends
-            for i in 0..self.legends.len() {
+            let (legends, _) = self.scale();
+            let gap = (S_LEGENDS.len() - 1) as f32 * INTER_GAP / (legends.len() - 1) as f32;
+            for i in 0..legends.len() {
                 painter.text(
-                    egui::pos2(rect.left() + LEFT_MARGIN + (i as f32 * INTER_GAP), rect.bottom() - TEXT_BOTTOM_MARGIN),
+                    egui::pos2(rect.left() + LEFT_MARGIN + (i as f32 * gap), rect.bottom() - TEXT_BOTTOM_MARGIN),
                     egui::Align2::LEFT_CENTER,
-                    &String::from(&self.legends[i]),
+                    &legends[i],
                     egui::FontId::new(FONT_SZ,egui::FontFamily::Proportional),
                     TEXT_COLOR,
                 );
@@ -99,37 +189,257 @@ impl UIMeter {
             );
 
             // Signal strength
-            if globals::get_run_state() {
-                let sig = dsp::dsp_interface::wdsp_get_rx_meter(0, common_defs::MeterType::SAverage as i32);
+            if self.client.borrow().running() {
+                let sig = self.reading();
+                self.update_peak(sig);
                 painter.line_segment(
                     [
                         egui::pos2(rect.left() + LEFT_MARGIN, rect.bottom() - SIG_BOTTOM_MARGIN),
-                        egui::pos2(rect.left() + self.sig_to_y(sig, (rect.width() - LEFT_MARGIN - RIGHT_MARGIN) as i32), rect.bottom() - SIG_BOTTOM_MARGIN),
+                        egui::pos2(rect.left() + LEFT_MARGIN + self.sig_to_x(sig), rect.bottom() - SIG_BOTTOM_MARGIN),
                     ],
                 egui::Stroke::new(SIG_STROKE, SIG_COLOR),
                 );
+                // Peak hold marker
+                let x = rect.left() + LEFT_MARGIN + self.sig_to_x(self.peak);
+                painter.line_segment(
+                    [
+                        egui::pos2(x, rect.bottom() - SIG_BOTTOM_MARGIN - SIG_STROKE),
+                        egui::pos2(x, rect.bottom() - SIG_BOTTOM_MARGIN + SIG_STROKE),
+                    ],
+                egui::Stroke::new(PEAK_STROKE, PEAK_COLOR),
+                );
+                // Numeric readout
+                painter.text(
+                    egui::pos2(rect.right() - RIGHT_MARGIN, rect.top() + TOP_MARGIN),
+                    egui::Align2::RIGHT_CENTER,
+                    &self.readout(sig),
+                    egui::FontId::new(FONT_SZ + 2.0,egui::FontFamily::Monospace),
+                    TEXT_COLOR,
+                );
+            }
+        });
+    }
+
+    // Source, attenuator, preamp and calibration
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            let last_source = self.source;
+            egui::ComboBox::from_id_source("meter-source")
+                .width(70.0)
+                .selected_text(SOURCES[self.source].1)
+                .show_ui(ui, |ui| {
+                    for i in 0..SOURCES.len() {
+                        ui.selectable_value(&mut self.source, i, SOURCES[i].1);
+                    }
+                }
+            );
+            if self.source != last_source {
+                // Scales differ so start the peak again
+                self.peak = f32::MIN;
+                self.prefs.borrow_mut().meter.source = self.source as u32;
+            }
+
+            // An engine has its gain set where it runs
+            if !self.client.borrow().is_remote() {
+                self.rf_controls(ui);
+            }
+
+            if ui.button("Cal").clicked() {
+                self.cal_state = CalState::Setup;
+            }
+        });
+    }
+
+    // Attenuator and preamp
+    fn rf_controls(&mut self, ui: &mut egui::Ui) {
+        if let Some((attn, preamp)) = globals::get_rf_remote() {
+            // An rtl_tcp client has the gain, ours comes back when it lets go
+            ui.add_enabled_ui(false, |ui| {
+                egui::ComboBox::from_id_source("meter-attn-remote")
+                    .width(50.0)
+                    .selected_text(ATTN[attn as usize])
+                    .show_ui(ui, |_ui| {});
+                let mut preamp = preamp;
+                ui.checkbox(&mut preamp, "Pre");
+            });
+            ui.label(String::from("rtl_tcp"));
+        } else {
+            let last_attn = self.attn;
+            let last_preamp = self.preamp;
+            egui::ComboBox::from_id_source("meter-attn")
+                .width(50.0)
+                .selected_text(ATTN[self.attn as usize])
+                .show_ui(ui, |ui| {
+                    for i in 0..ATTN.len() {
+                        ui.selectable_value(&mut self.attn, i as u32, ATTN[i]);
+                    }
+                }
+            );
+            ui.checkbox(&mut self.preamp, "Pre");
+            if self.attn != last_attn || self.preamp != last_preamp {
+                self.set_rf_gain();
+            }
+        }
+    }
+
+    // Calibrate against a signal of known level
+    fn calibrate(&mut self, ui: &mut egui::Ui) {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            match self.cal_state {
+                CalState::Setup => {
+                    ui.label(String::from("Known signal"));
+                    ui.add(egui::DragValue::new(&mut self.cal_ref).speed(0.5).clamp_range(-140.0..=0.0).suffix(" dBm"));
+                    if ui.button("Measure").clicked() {
+                        self.cal_sum = 0.0;
+                        self.cal_count = 0;
+                        self.cal_start = Instant::now();
+                        self.cal_state = CalState::Measuring;
+                    }
+                },
+                CalState::Measuring => {
+                    // Average the uncalibrated reading
+                    ui.label(String::from("Measuring..."));
+                    self.cal_sum += self.client.borrow().meter(common_defs::MeterType::SAverage) as f64;
+                    self.cal_count += 1;
+                    if self.cal_start.elapsed().as_secs_f32() >= CAL_SECS {
+                        self.cal_offset = self.cal_ref - (self.cal_sum / self.cal_count as f64) as f32;
+                        self.cal_state = CalState::Done;
+                    }
+                },
+                CalState::Done => {
+                    ui.label(RichText::new(format!("{} {:+.1} dB", self.cal_key(), self.cal_offset)).text_style(TextStyle::Monospace));
+                    if ui.button("Save").clicked() {
+                        let key = self.cal_key();
+                        self.prefs.borrow_mut().meter.cal.insert(key, self.cal_offset);
+                        self.cal_state = CalState::Idle;
+                    }
+                },
+                CalState::Idle => (),
+            }
+            if self.cal_state != CalState::Idle && ui.button("Cancel").clicked() {
+                self.cal_state = CalState::Idle;
             }
         });
     }
 
-    // Convert signal strength in dbM to a y offset for the meter
-    fn sig_to_y(&mut self, sig: f64, width: i32) -> f32{
-        let mut offset = 0.0;
-        let sig = sig as i32;
-        for dbm_idx in 0..self.level.len()-1{
-            if sig >= self.level[dbm_idx] && sig < self.level[dbm_idx+1] {
-                // We have a base value at dbx_idx and need the fractional value
-                let span = self.level[dbm_idx].abs() - self.level[dbm_idx+1].abs();
-                let diff = self.level[dbm_idx].abs() - sig.abs();
-                let sig_frac = diff as f32/span as f32;
-                let y_frac = width as f32/self.level.len() as f32;
-                let frac = sig_frac * y_frac;
-                // Offset is base + frac so we get a smooth display 
-                offset = ((dbm_idx as f32/self.level.len() as f32) * width as f32) + frac;
-                break;
+    //===========================================================================================
+    // Meter helpers
+    // Current reading for the selected source including any calibration
+    fn reading(&mut self) -> f32 {
+        let (mt, _, calibrate) = &SOURCES[self.source];
+        let mut sig = self.client.borrow().meter(*mt);
+        // Remote clients read the S meter
+        globals::set_meter_cal(self.cal_value());
+        if *calibrate {
+            sig += self.cal_value();
+        }
+        return sig;
+    }
+
+    // Hold the peak then let it decay
+    fn update_peak(&mut self, sig: f32) {
+        let now = Instant::now();
+        if sig >= self.peak {
+            self.peak = sig;
+            self.peak_time = now;
+        } else if self.peak_time.elapsed().as_secs_f32() > HOLD_SECS {
+            let dt = now.duration_since(self.last_time).as_secs_f32();
+            self.peak = f32::max(sig, self.peak - DECAY_DB_SEC * dt);
+        }
+        self.last_time = now;
+    }
+
+    // Legends and levels for the current source
+    fn scale(&self) -> (Vec<String>, Vec<f32>) {
+        let mut legends = Vec::new();
+        let mut levels = Vec::new();
+        let (low, high, step);
+        match SOURCES[self.source].0 {
+            common_defs::MeterType::SPeak | common_defs::MeterType::SAverage => {
+                for i in 0..S_LEVELS.len() {
+                    legends.push(String::from(S_LEGENDS[i]));
+                    levels.push(S_LEVELS[i]);
+                }
+                return (legends, levels);
+            },
+            common_defs::MeterType::AGCGain => {low = -20; high = 100; step = 20},
+            _ => {low = -120; high = 0; step = 20},
+        }
+        for l in (low..=high).step_by(step as usize) {
+            legends.push(l.to_string());
+            levels.push(l as f32);
+        }
+        return (legends, levels);
+    }
+
+    // Convert a reading to an x offset for the meter, aligned with the legends
+    fn sig_to_x(&self, sig: f32) -> f32 {
+        let (_, levels) = self.scale();
+        let gap = (S_LEGENDS.len() - 1) as f32 * INTER_GAP / (levels.len() - 1) as f32;
+        if sig <= levels[0] {
+            return 0.0;
+        }
+        for i in 0..levels.len() - 1 {
+            if sig < levels[i + 1] {
+                // Base value at i plus the fraction to the next level
+                let frac = (sig - levels[i]) / (levels[i + 1] - levels[i]);
+                return (i as f32 + frac) * gap;
             }
         }
-        return offset
+        return (levels.len() - 1) as f32 * gap;
+    }
+
+    // Reading as text, S units as well for the S meter
+    fn readout(&self, sig: f32) -> String {
+        match SOURCES[self.source].0 {
+            common_defs::MeterType::SPeak | common_defs::MeterType::SAverage => {
+                let s;
+                if sig > CAL_REF {
+                    s = format!("S9+{}", (sig - CAL_REF).round() as i32);
+                } else {
+                    // 6dB per S unit
+                    s = format!("S{}", f32::max(0.0, ((sig - S_LEVELS[0]) / 6.0).round() + 1.0) as i32);
+                }
+                return format!("{} {:.1}dBm", s, sig);
+            },
+            _ => return format!("{:.1}dB", sig),
+        }
     }
 
-}
\ No newline at end of file
+    //===========================================================================================
+    // Calibration and RF gain helpers
+    // Calibration is per band, attenuator and preamp
+    fn cal_key(&self) -> String {
+        let freq;
+        match globals::get_sel_rx() {
+            2 => freq = self.prefs.borrow().radio.rx2.frequency,
+            3 => freq = self.prefs.borrow().radio.rx3.frequency,
+            _ => freq = self.prefs.borrow().radio.rx1.frequency,
+        }
+        // What is set in the hardware, a remote client may have changed it
+        let (attn, preamp) = match globals::get_rf_remote() {
+            Some(remote) => remote,
+            None => (self.attn, self.preamp),
+        };
+        let mut pre = "";
+        if preamp {
+            pre = "/pre";
+        }
+        return format!("{}/{}{}", common_defs::band_for_freq(freq), ATTN[attn as usize], pre);
+    }
+
+    fn cal_value(&self) -> f32 {
+        match self.prefs.borrow().meter.cal.get(&self.cal_key()) {
+            Some(offset) => return *offset,
+            None => return 0.0,
+        }
+    }
+
+    // Set attenuator and preamp in the hardware
+    fn set_rf_gain(&mut self) {
+        self.client.borrow_mut().set_rf_gain(self.attn, self.preamp);
+        let mut p = self.prefs.borrow_mut();
+        p.meter.attn = self.attn;
+        p.meter.preamp = self.preamp;
+    }
+}
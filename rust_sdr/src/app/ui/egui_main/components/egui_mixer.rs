@@ -0,0 +1,159 @@
+/*
+egui_mixer.rs
+
+Module - egui_mixer
+Local audio mixer sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate ::app::common::prefs;
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::audio::audio_out;
+use crate::app::client::radio_client::RadioClient;
+
+use eframe::egui;
+
+//===========================================================================================
+// State for Mixer
+pub struct UIMixer {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    // Per receiver, volume is in percent
+    vol: [f32; 3],
+    pan: [f32; 3],
+    mute: [bool; 3],
+    route: [u32; 3],
+    aux_device: String,
+    devices: Vec<String>,
+}
+
+//===========================================================================================
+// Implementation for UIMixer
+impl UIMixer {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        // Settings were restored to globals at startup
+        let mut vol = [0.0; 3];
+        let mut pan = [0.0; 3];
+        let mut mute = [false; 3];
+        let mut route = [common_defs::AUDIO_MAIN; 3];
+        for i in 0..3 {
+            let rx = i as i32 + 1;
+            vol[i] = globals::get_rx_vol(rx) * 100.0;
+            pan[i] = globals::get_rx_pan(rx);
+            mute[i] = globals::get_rx_mute(rx);
+            route[i] = globals::get_rx_route(rx);
+        }
+        let aux_device = prefs.borrow().audio.aux_device.clone();
+
+        Self {
+            client: client,
+            prefs: prefs,
+            vol: vol,
+            pan: pan,
+            mute: mute,
+            route: route,
+            aux_device: aux_device,
+            // Enumerated once as this is slow on some hosts
+            devices: audio_out::output_device_names(),
+        }
+    }
+
+    //===========================================================================================
+    // Populate mixer window
+    pub fn mixer(&mut self, ui: &mut egui::Ui) {
+
+        egui::Grid::new("mixer-grid").show(ui, |ui| {
+            ui.label(String::from("Audio"));
+            ui.label(String::from("Mute"));
+            ui.label(String::from("Volume"));
+            ui.label(String::from("Pan L/R"));
+            ui.label(String::from("Device"));
+            ui.end_row();
+
+            // A row for each active receiver
+            for i in 0..globals::get_num_rx() as usize {
+                let rx = i as i32 + 1;
+                ui.label(format!("RX-{}", rx));
+                ui.checkbox(&mut self.mute[i], "");
+                ui.add(egui::Slider::new(&mut self.vol[i], 0.0..=100.0).suffix("%"));
+                ui.add(egui::Slider::new(&mut self.pan[i], -1.0..=1.0));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    if ui.add(egui::RadioButton::new(self.route[i] == common_defs::AUDIO_MAIN, "Main")).clicked() {
+                        self.route[i] = common_defs::AUDIO_MAIN;
+                    }
+                    if ui.add(egui::RadioButton::new(self.route[i] == common_defs::AUDIO_AUX, "Aux")).clicked() {
+                        self.route[i] = common_defs::AUDIO_AUX;
+                    }
+                });
+                ui.end_row();
+                self.set_rx(i);
+            }
+
+            // Auxiliary device, opened at startup
+            ui.label(String::from("Aux device"));
+            ui.label(String::from(""));
+            let mut selected = self.aux_device.clone();
+            if selected.len() == 0 {
+                selected = String::from("None");
+            }
+            egui::ComboBox::from_id_source("aux-device")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.aux_device, String::from(""), "None");
+                    for name in self.devices.iter() {
+                        ui.selectable_value(&mut self.aux_device, name.clone(), name);
+                    }
+                }
+            );
+            if self.aux_device != self.prefs.borrow().audio.aux_device {
+                self.prefs.borrow_mut().audio.aux_device = self.aux_device.clone();
+            }
+            if !globals::get_aux_audio_state() && self.aux_device.len() > 0 {
+                ui.label(String::from("Restart to open"));
+            }
+            ui.end_row();
+        });
+    }
+
+    // Set the receiver audio and update prefs
+    fn set_rx(&mut self, i: usize) {
+        let rx = i as u32 + 1;
+        let channel = prefs::MixerChannel {
+            vol: self.vol[i] / 100.0,
+            pan: self.pan[i],
+            mute: self.mute[i],
+            route: self.route[i],
+        };
+        self.client.borrow_mut().set_rx_audio(rx, &channel);
+
+        let mut p = self.prefs.borrow_mut();
+        match rx {
+            2 => p.audio.rx2 = channel,
+            3 => p.audio.rx3 = channel,
+            _ => p.audio.rx1 = channel,
+        }
+    }
+}
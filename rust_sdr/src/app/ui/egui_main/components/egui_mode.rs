@@ -25,36 +25,17 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
 
 use crate ::app::common::prefs;
 use crate::app::common::globals;
 use crate::app::common::common_defs;
-use crate::app::protocol;
-use crate::app::dsp;
+use crate::app::common::common_defs::ModeId;
+use crate::app::client::radio_client::RadioClient;
 use crate::app::ui::egui_main::components;
 
 use egui::{RichText, TextStyle};
 use eframe::egui;
-use serde:: {Serialize, Deserialize};
-
-// Mode enumerations
-#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
-pub enum ModeId {
-    Lsb, 
-    Usb,
-    Dsb,
-    CwL,
-    CwU,
-    Fm,
-    Am,
-    DigU,
-    Spec,
-    DigL,
-    Sam,
-    Drm,
-}
 
 const MODE_NORMAL_COLOR: egui::Color32 = egui::Color32::TRANSPARENT;
 const MODE_HIGHLIGHT_COLOR: egui::Color32 = egui::Color32::DARK_BLUE;
@@ -63,7 +44,7 @@ const MODE_HIGHLIGHT_COLOR: egui::Color32 = egui::Color32::DARK_BLUE;
 // State for Modes
 pub struct UIMode {
     rx : i32,
-    _i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    client : Rc<RefCell<dyn RadioClient>>,
     mode: ModeId,
     m_array: [(String, egui::Color32); 12],
     spec : Rc<RefCell<components::egui_spec::UISpec>>,
@@ -74,7 +55,7 @@ pub struct UIMode {
 // Implementation for UIApp
 impl UIMode {
     pub fn new(_cc: &eframe::CreationContext<'_>, 
-        i_cc : Arc<Mutex<protocol::cc_out::CCData>>, 
+        client : Rc<RefCell<dyn RadioClient>>, 
         spec : Rc<RefCell<components::egui_spec::UISpec>>,
         prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
 
@@ -103,7 +84,7 @@ impl UIMode {
             3 => mode = prefs.borrow().radio.rx3.mode,
             _ => (),
         }
-        dsp::dsp_interface::set_mode_filter(0, rx as i32);
+        client.borrow_mut().set_mode(rx, mode);
         let pos: common_defs::EnumModePos;
         if mode == ModeId::Lsb || mode == ModeId::CwL || mode == ModeId::DigL {
             pos = common_defs::EnumModePos::Lower; 
@@ -116,7 +97,7 @@ impl UIMode {
 
         Self {
             rx: rx as i32,
-            _i_cc: i_cc,
+            client: client,
             m_array: m_array,
             mode: mode,
             spec: spec,
@@ -261,8 +242,7 @@ impl UIMode {
             },
             _ => (),
         }
-        globals::set_mode(self.rx, self.mode as u32);
-        dsp::dsp_interface::set_mode_filter(0, self.rx);
+        self.client.borrow_mut().set_mode(self.rx as u32, self.mode);
     }
 
 }
\ No newline at end of file
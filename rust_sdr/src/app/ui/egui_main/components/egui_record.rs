@@ -0,0 +1,360 @@
+/*
+egui_record.rs
+
+Module - egui_record
+Recording sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::record::recorder::{IqFormat, IqSpec, AudioFormat, AudioSpec, AudioRx, Schedule};
+use crate::app::ui::egui_main::components;
+
+use egui::{RichText, TextStyle};
+use eframe::egui;
+
+const IQ_FORMATS: [(&str, IqFormat); 3] = [
+    ("WAV float", IqFormat::WavFloat),
+    ("WAV 24 bit", IqFormat::Wav24),
+    ("SigMF", IqFormat::Sigmf),
+];
+
+const AUDIO_FORMATS: [(&str, AudioFormat); 2] = [
+    ("WAV", AudioFormat::Wav),
+    ("FLAC", AudioFormat::Flac),
+];
+
+const AUDIO_SOURCES: [&str; 4] = ["Mixed", "RX1", "RX2", "RX3"];
+
+const REC_COLOR: egui::Color32 = egui::Color32::RED;
+
+//===========================================================================================
+// State for recording
+pub struct UIRecord {
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+    play_sender : crossbeam_channel::Sender<messages::PlayerMsg>,
+    vfo : Rc<RefCell<components::egui_vfo::UIVfo>>,
+    play_file : String,
+    looping : bool,
+    // Recorded centre frequency the VFO is showing, 0 when it shows the receiver
+    play_freq : u32,
+    // Schedule last sent to the recorder, which starts the recording
+    schedule : Option<Schedule>,
+}
+
+//===========================================================================================
+// Implementation for UIRecord
+impl UIRecord {
+    pub fn new(
+            _cc: &eframe::CreationContext<'_>, prefs: Rc<RefCell<prefs::Prefs>>, vfo: Rc<RefCell<components::egui_vfo::UIVfo>>,
+            rec_sender : crossbeam_channel::Sender<messages::RecorderMsg>,
+            play_sender : crossbeam_channel::Sender<messages::PlayerMsg>) -> Self{
+
+        let mut record = Self {
+            prefs: prefs,
+            rec_sender: rec_sender,
+            play_sender: play_sender,
+            vfo: vfo,
+            play_file: String::from(""),
+            looping: false,
+            play_freq: 0,
+            schedule: None,
+        };
+        // The schedule runs from startup whether or not the panel is drawn
+        record.update_schedule();
+        record
+    }
+
+    //===========================================================================================
+    // Populate record window
+    pub fn record(&mut self, ui: &mut egui::Ui) {
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            self.settings(ui);
+            ui.separator();
+            self.iq(ui);
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            self.audio(ui);
+            ui.separator();
+            self.schedule(ui);
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            self.playback(ui);
+        });
+    }
+
+    // Where and how to record
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        let mut p = self.prefs.borrow_mut();
+        ui.label(String::from("Folder"));
+        ui.add(egui::TextEdit::singleline(&mut p.record.dir).desired_width(200.0));
+        if ui.button(String::from("...")).clicked() {
+            match rfd::FileDialog::new().set_directory(&p.record.dir).pick_folder() {
+                Some(path) => p.record.dir = path.display().to_string(),
+                None => (),
+            }
+        }
+        ui.label(String::from("Limit"));
+        ui.add(egui::DragValue::new(&mut p.record.max_mb).speed(10).clamp_range(0..=100000).suffix("MB"));
+        ui.add(egui::DragValue::new(&mut p.record.max_mins).speed(1).clamp_range(0..=1440).suffix("min"));
+    }
+
+    // Raw IQ of the selected receiver
+    fn iq(&mut self, ui: &mut egui::Ui) {
+        let recording = globals::get_iq_recording();
+        let mut format = IqFormat::from_u32(self.prefs.borrow().record.iq_format);
+        ui.add_enabled_ui(!recording, |ui| {
+            egui::ComboBox::from_id_source("iq-format")
+                .selected_text(IQ_FORMATS[format as usize].0)
+                .show_ui(ui, |ui| {
+                    for (name, f) in IQ_FORMATS.iter() {
+                        ui.selectable_value(&mut format, *f, *name);
+                    }
+                }
+            );
+        });
+        self.prefs.borrow_mut().record.iq_format = format as u32;
+
+        if recording {
+            if ui.button(String::from("Stop IQ")).clicked() {
+                self.rec_sender.send(messages::RecorderMsg::StopIq).unwrap();
+            }
+            let secs = globals::get_iq_rec_secs();
+            ui.label(RichText::new(format!("REC {:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60))
+                .text_style(TextStyle::Monospace)
+                .color(REC_COLOR));
+        } else if ui.button(String::from("Record IQ")).clicked() {
+            let p = self.prefs.borrow();
+            let frequency = match globals::get_sel_rx() {
+                2 => p.radio.rx2.frequency,
+                3 => p.radio.rx3.frequency,
+                _ => p.radio.rx1.frequency,
+            };
+            let spec = IqSpec {
+                dir: p.record.dir.clone(),
+                format: format,
+                frequency: frequency,
+                rate: globals::get_smpl_rate(),
+                max_bytes: p.record.max_mb as u64 * 1024 * 1024,
+                max_secs: p.record.max_mins * 60,
+            };
+            self.rec_sender.send(messages::RecorderMsg::StartIq(spec)).unwrap();
+        }
+    }
+
+    // Demodulated audio of one receiver or all mixed
+    fn audio(&mut self, ui: &mut egui::Ui) {
+        let recording = globals::get_audio_recording();
+        let mut format = AudioFormat::from_u32(self.prefs.borrow().record.audio_format);
+        let mut src = self.prefs.borrow().record.audio_src as usize;
+        ui.add_enabled_ui(!recording, |ui| {
+            egui::ComboBox::from_id_source("audio-format")
+                .width(60.0)
+                .selected_text(AUDIO_FORMATS[format as usize].0)
+                .show_ui(ui, |ui| {
+                    for (name, f) in AUDIO_FORMATS.iter() {
+                        ui.selectable_value(&mut format, *f, *name);
+                    }
+                }
+            );
+            egui::ComboBox::from_id_source("audio-src")
+                .width(60.0)
+                .selected_text(AUDIO_SOURCES[usize::min(src, 3)])
+                .show_ui(ui, |ui| {
+                    for (i, name) in AUDIO_SOURCES.iter().enumerate() {
+                        ui.selectable_value(&mut src, i, *name);
+                    }
+                }
+            );
+        });
+        self.prefs.borrow_mut().record.audio_format = format as u32;
+        self.prefs.borrow_mut().record.audio_src = src as u32;
+
+        if recording {
+            if ui.button(String::from("Stop AF")).clicked() {
+                self.stop_audio();
+            }
+            let secs = globals::get_audio_rec_secs();
+            let mut state = "REC";
+            if globals::get_audio_rec_sched() {
+                state = "SCHED";
+            }
+            ui.label(RichText::new(format!("{} {:02}:{:02}:{:02}", state, secs / 3600, (secs / 60) % 60, secs % 60))
+                .text_style(TextStyle::Monospace)
+                .color(REC_COLOR));
+        } else if ui.button(String::from("Record AF")).clicked() {
+            let (max_mb, max_mins) = {
+                let p = self.prefs.borrow();
+                (p.record.max_mb, p.record.max_mins)
+            };
+            match self.audio_spec(max_mb as u64 * 1024 * 1024, max_mins * 60) {
+                Some(spec) => self.rec_sender.send(messages::RecorderMsg::StartAudio(spec)).unwrap(),
+                None => println!("RX{} is not active, audio recording not started", src),
+            }
+        }
+    }
+
+    // Daily recording at a UTC time, the duration is the time limit
+    fn schedule(&mut self, ui: &mut egui::Ui) {
+        let mut p = self.prefs.borrow_mut();
+        ui.checkbox(&mut p.record.sched_enabled, String::from("Daily at"));
+        ui.add(egui::DragValue::new(&mut p.record.sched_hour).clamp_range(0..=23).custom_formatter(|n, _| format!("{:02}", n)));
+        ui.label(String::from(":"));
+        ui.add(egui::DragValue::new(&mut p.record.sched_min).clamp_range(0..=59).custom_formatter(|n, _| format!("{:02}", n)));
+        ui.label(String::from("UTC for"));
+        ui.add(egui::DragValue::new(&mut p.record.sched_mins).clamp_range(1..=1440).suffix("min"));
+        drop(p);
+        self.update_schedule();
+    }
+
+    // The recorder keeps the time, it is sent the schedule with the tuning to record when it changes
+    fn update_schedule(&mut self) {
+        let mut schedule = None;
+        let (enabled, hour, minute, mins) = {
+            let p = self.prefs.borrow();
+            (p.record.sched_enabled, p.record.sched_hour, p.record.sched_min, p.record.sched_mins)
+        };
+        if enabled {
+            schedule = self.audio_spec(0, mins * 60).map(|spec| Schedule {
+                hour: hour,
+                minute: minute,
+                spec: spec,
+            });
+        }
+        if schedule != self.schedule {
+            self.rec_sender.send(messages::RecorderMsg::Schedule(schedule.clone())).unwrap();
+            self.schedule = schedule;
+        }
+    }
+
+    // What to record from the audio settings, None when the source is not running
+    fn audio_spec(&self, max_bytes: u64, max_secs: u32) -> Option<AudioSpec> {
+        let p = self.prefs.borrow();
+        let src = p.record.audio_src;
+        let tuning = [&p.radio.rx1, &p.radio.rx2, &p.radio.rx3];
+        let mut receivers = Vec::new();
+        for rx in 1..=u32::min(globals::get_num_rx(), 3) {
+            if src == 0 || src == rx {
+                let t = tuning[rx as usize - 1];
+                receivers.push(AudioRx {
+                    rx: rx,
+                    frequency: t.frequency,
+                    mode: format!("{:?}", t.mode).to_uppercase(),
+                });
+            }
+        }
+        if receivers.len() == 0 {
+            return None;
+        }
+        return Some(AudioSpec {
+            dir: p.record.dir.clone(),
+            format: AudioFormat::from_u32(p.record.audio_format),
+            source: src,
+            receivers: receivers,
+            max_bytes: max_bytes,
+            max_secs: max_secs,
+        });
+    }
+
+    fn stop_audio(&mut self) {
+        self.rec_sender.send(messages::RecorderMsg::StopAudio).unwrap();
+    }
+
+    // Play an IQ file in place of the hardware
+    fn playback(&mut self, ui: &mut egui::Ui) {
+        let playback = globals::get_playback();
+        let running = globals::get_play_running();
+        if ui.button(String::from("Open IQ")).clicked() {
+            let dir = self.prefs.borrow().record.dir.clone();
+            match rfd::FileDialog::new()
+                .set_directory(&dir)
+                .add_filter("IQ files", &["wav", "sigmf-meta", "sigmf-data"])
+                .pick_file() {
+                Some(path) => {
+                    self.play_file = path.display().to_string();
+                    self.play_sender.send(messages::PlayerMsg::Open(self.play_file.clone())).unwrap();
+                    self.play_sender.send(messages::PlayerMsg::Loop(self.looping)).unwrap();
+                },
+                None => (),
+            }
+        }
+        ui.add_enabled_ui(playback, |ui| {
+            if running {
+                if ui.button(String::from("Pause")).clicked() {
+                    self.play_sender.send(messages::PlayerMsg::Pause).unwrap();
+                }
+            } else if ui.button(String::from("Play")).clicked() {
+                self.play_sender.send(messages::PlayerMsg::Play).unwrap();
+            }
+            if ui.button(String::from("Stop")).clicked() {
+                self.play_sender.send(messages::PlayerMsg::Stop).unwrap();
+            }
+        });
+        if ui.checkbox(&mut self.looping, String::from("Loop")).changed() {
+            self.play_sender.send(messages::PlayerMsg::Loop(self.looping)).unwrap();
+        }
+
+        if !playback {
+            self.show_play_freq(0);
+            return;
+        }
+        // Position, dragging the slider seeks
+        let len = globals::get_play_len();
+        let mut pos = globals::get_play_pos();
+        let r = ui.add(egui::Slider::new(&mut pos, 0..=u32::max(len, 1)).show_value(false));
+        if r.drag_released() || (r.changed() && !r.dragged()) {
+            self.play_sender.send(messages::PlayerMsg::Seek(pos as f32 / u32::max(len, 1) as f32)).unwrap();
+        }
+        ui.label(RichText::new(format!("{:02}:{:02}/{:02}:{:02}", pos / 60, pos % 60, len / 60, len % 60))
+            .text_style(TextStyle::Monospace));
+
+        let freq = globals::get_play_freq();
+        if freq != 0 {
+            ui.label(RichText::new(format!("{:.6} MHz", freq as f64 / 1.0e6))
+                .text_style(TextStyle::Monospace));
+        }
+        self.show_play_freq(freq);
+    }
+
+    // The display shows the recorded centre frequency without tuning the receiver
+    // or saving it, the receiver frequency is shown again when playback stops.
+    // Recordings above what the VFO can show, such as 1090MHz ADS-B, are only in the label.
+    fn show_play_freq(&mut self, freq: u32) {
+        if freq == self.play_freq {
+            return;
+        }
+        let mut vfo = self.vfo.borrow_mut();
+        if freq == 0 || freq > components::egui_vfo::MAX_DISPLAY_FREQ {
+            vfo.restore_freq();
+        } else {
+            vfo.display_freq(freq);
+        }
+        vfo.set_freq();
+        self.play_freq = freq;
+    }
+}
@@ -0,0 +1,445 @@
+/*
+egui_remote.rs
+
+Module - egui_remote
+Remote control sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+use std::time::{Duration, Instant};
+
+use crate::app::common::prefs;
+use crate::app::common::common_defs;
+use crate::app::common::globals;
+use crate::app::common::messages;
+use crate::app::client::radio_client::RadioClient;
+use crate::app::audio::audio_bridge::BridgeSpec;
+use crate::app::remote::websocket::new_token;
+use crate::app::ui::egui_main::components;
+
+use egui::RichText;
+use eframe::egui;
+
+const PTT_COLOR: egui::Color32 = egui::Color32::RED;
+const TX_AUDIO_COLOR: egui::Color32 = egui::Color32::YELLOW;
+
+// Spectrum rows to web clients at most this often
+const WEB_SPECTRUM_MS: u64 = 100;
+
+//===========================================================================================
+// State for remote control
+pub struct UIRemote {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    vfo : Rc<RefCell<components::egui_vfo::UIVfo>>,
+    rig_sender : crossbeam_channel::Sender<messages::RigctlMsg>,
+    cat_pty_sender : crossbeam_channel::Sender<messages::CatPtyMsg>,
+    bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+    web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+    hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+    rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+    cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>,
+    // Address the rigctl server was last asked to listen on
+    rigctld_addr : Option<String>,
+    // Link the CAT pty was last opened with
+    cat_pty_link : Option<String>,
+    // Host and ports the audio bridge was last started with
+    bridge : Option<(String, u32, u32)>,
+    // Address and token the web server was last asked to listen with
+    web : Option<(String, String)>,
+    // Address and receiver the Protocol 1 server was last opened with
+    hpsdr : Option<(String, u32)>,
+    // Address and receiver the rtl_tcp server was last asked to listen with
+    rtl_tcp : Option<(String, u32)>,
+    // Last spectrum row sent to web clients
+    web_spectrum : Instant,
+    // Port or link is being edited so don't reopen yet
+    editing : bool,
+    // RX2 frequency last published, TX follows it in split
+    rx2_freq : u32,
+}
+
+//===========================================================================================
+// Implementation for UIRemote
+impl UIRemote {
+    pub fn new(
+            _cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>,
+            prefs: Rc<RefCell<prefs::Prefs>>,
+            vfo: Rc<RefCell<components::egui_vfo::UIVfo>>,
+            rig_sender : crossbeam_channel::Sender<messages::RigctlMsg>,
+            cat_pty_sender : crossbeam_channel::Sender<messages::CatPtyMsg>,
+            bridge_sender : crossbeam_channel::Sender<messages::BridgeMsg>,
+            web_sender : crossbeam_channel::Sender<messages::WebMsg>,
+            hpsdr_sender : crossbeam_channel::Sender<messages::HpsdrMsg>,
+            rtl_sender : crossbeam_channel::Sender<messages::RtlTcpMsg>,
+            cat_receiver : crossbeam_channel::Receiver<messages::CatMsg>) -> Self{
+
+        let rx2_freq = prefs.borrow().radio.rx2.frequency;
+        let mut remote = Self {
+            client: client,
+            prefs: prefs,
+            vfo: vfo,
+            rig_sender: rig_sender,
+            cat_pty_sender: cat_pty_sender,
+            bridge_sender: bridge_sender,
+            web_sender: web_sender,
+            hpsdr_sender: hpsdr_sender,
+            rtl_sender: rtl_sender,
+            cat_receiver: cat_receiver,
+            rigctld_addr: None,
+            cat_pty_link: None,
+            bridge: None,
+            web: None,
+            hpsdr: None,
+            rtl_tcp: None,
+            web_spectrum: Instant::now(),
+            editing: false,
+            rx2_freq: rx2_freq,
+        };
+        remote.publish();
+        remote.listen();
+        return remote;
+    }
+
+    //===========================================================================================
+    // Apply changes from the remote servers then publish the settings they read.
+    // Called at the start of each frame.
+    pub fn poll(&mut self) {
+        loop {
+            match self.cat_receiver.try_recv() {
+                Ok(msg) => self.apply(msg),
+                Err(_) => break,
+            }
+        }
+        self.publish();
+    }
+
+    fn apply(&mut self, msg: messages::CatMsg) {
+        match msg {
+            messages::CatMsg::SetFreq(rx, freq) => {
+                if rx == globals::get_sel_rx() {
+                    let mut vfo = self.vfo.borrow_mut();
+                    vfo.update_freq(freq);
+                    vfo.set_freq();
+                } else {
+                    match rx {
+                        1 => self.prefs.borrow_mut().radio.rx1.frequency = freq,
+                        2 => self.prefs.borrow_mut().radio.rx2.frequency = freq,
+                        3 => self.prefs.borrow_mut().radio.rx3.frequency = freq,
+                        _ => return,
+                    }
+                    self.client.borrow_mut().set_freq(rx, freq);
+                }
+            },
+            messages::CatMsg::SetMode(rx, mode) => {
+                match rx {
+                    1 => self.prefs.borrow_mut().radio.rx1.mode = mode,
+                    2 => self.prefs.borrow_mut().radio.rx2.mode = mode,
+                    3 => self.prefs.borrow_mut().radio.rx3.mode = mode,
+                    _ => return,
+                }
+                self.client.borrow_mut().set_mode(rx, mode);
+            },
+            messages::CatMsg::SetFilter(rx, filter) => {
+                match rx {
+                    1 => self.prefs.borrow_mut().radio.rx1.filter = filter,
+                    2 => self.prefs.borrow_mut().radio.rx2.filter = filter,
+                    3 => self.prefs.borrow_mut().radio.rx3.filter = filter,
+                    _ => return,
+                }
+                self.client.borrow_mut().set_filter(rx, filter);
+            },
+            messages::CatMsg::SetRx(rx) => {
+                self.prefs.borrow_mut().radio.sel_rx = rx;
+                self.client.borrow_mut().set_sel_rx(rx);
+                let mut vfo = self.vfo.borrow_mut();
+                vfo.restore_freq();
+                vfo.set_freq();
+            },
+            messages::CatMsg::SetSplit(split) => {
+                globals::set_split(split);
+                let freq = self.prefs.borrow().radio.rx1.frequency;
+                self.client.borrow_mut().set_freq(1, freq);
+            },
+            messages::CatMsg::SetAfGain(gain) => {
+                self.prefs.borrow_mut().radio.af_gain = gain;
+                self.client.borrow_mut().set_af_gain(gain);
+            },
+            messages::CatMsg::SetRun(run) => {
+                self.client.borrow_mut().set_run(run);
+            },
+        }
+    }
+
+    // Pass a new display row on to any web clients
+    pub fn spectrum(&mut self, new_data: bool, out_real: &[f32]) {
+        if !new_data || globals::get_web_clients() == 0 {
+            return;
+        }
+        if self.web_spectrum.elapsed() < Duration::from_millis(WEB_SPECTRUM_MS) {
+            return;
+        }
+        self.web_spectrum = Instant::now();
+        // Only the pixels the display is using
+        let width = usize::min(globals::get_disp_width() as usize, out_real.len());
+        self.web_sender.send(messages::WebMsg::Spectrum(out_real[..width].to_vec())).unwrap();
+    }
+
+    // The servers read the receiver settings from the globals
+    fn publish(&mut self) {
+        let p = self.prefs.borrow();
+        let rx = [&p.radio.rx1, &p.radio.rx2, &p.radio.rx3];
+        for (i, r) in rx.iter().enumerate() {
+            let id = i as i32 + 1;
+            globals::set_rx_freq(id, r.frequency);
+            globals::set_mode(id, r.mode as u32);
+            globals::set_filter(id, r.filter as u32);
+        }
+        // Retune TX when RX2 moves in split
+        if p.radio.rx2.frequency != self.rx2_freq {
+            self.rx2_freq = p.radio.rx2.frequency;
+            if globals::get_split() {
+                self.client.borrow_mut().set_freq(1, p.radio.rx1.frequency);
+            }
+        }
+    }
+
+    // Start, move or stop the servers to match the settings
+    fn listen(&mut self) {
+        if self.editing {
+            return;
+        }
+        let p = self.prefs.borrow();
+        let mut addr = None;
+        if p.remote.rigctld {
+            let mut host = "127.0.0.1";
+            if p.remote.rigctld_lan {
+                host = "0.0.0.0";
+            }
+            addr = Some(format!("{}:{}", host, p.remote.rigctld_port));
+        }
+        if addr != self.rigctld_addr {
+            let msg = match addr.as_ref() {
+                Some(a) => messages::RigctlMsg::Listen(a.clone()),
+                None => messages::RigctlMsg::Close,
+            };
+            self.rig_sender.send(msg).unwrap();
+            self.rigctld_addr = addr;
+        }
+
+        let mut link = None;
+        if p.remote.cat_pty {
+            link = Some(p.remote.cat_pty_link.clone());
+        }
+        if link != self.cat_pty_link {
+            let msg = match link.as_ref() {
+                Some(l) => messages::CatPtyMsg::Open(l.clone()),
+                None => messages::CatPtyMsg::Close,
+            };
+            self.cat_pty_sender.send(msg).unwrap();
+            self.cat_pty_link = link;
+        }
+
+        let mut bridge = None;
+        if p.remote.bridge {
+            bridge = Some((p.remote.bridge_host.clone(), p.remote.bridge_rx_port, p.remote.bridge_tx_port));
+        }
+        if bridge != self.bridge {
+            let msg = match bridge.as_ref() {
+                Some((host, rx_port, tx_port)) => messages::BridgeMsg::Start(BridgeSpec {
+                    host: host.clone(),
+                    rx_port: *rx_port,
+                    tx_port: *tx_port,
+                }),
+                None => messages::BridgeMsg::Stop,
+            };
+            self.bridge_sender.send(msg).unwrap();
+            self.bridge = bridge;
+        }
+
+        let mut web = None;
+        if p.remote.web {
+            let mut host = "127.0.0.1";
+            if p.remote.web_lan {
+                host = "0.0.0.0";
+            }
+            web = Some((format!("{}:{}", host, p.remote.web_port), p.remote.web_token.clone()));
+        }
+        if web != self.web {
+            let msg = match web.as_ref() {
+                Some((addr, token)) => messages::WebMsg::Listen(addr.clone(), token.clone()),
+                None => messages::WebMsg::Close,
+            };
+            self.web_sender.send(msg).unwrap();
+            self.web = web;
+        }
+
+        // Discovery is broadcast so this is always open to the LAN
+        let mut hpsdr = None;
+        if p.remote.hpsdr {
+            hpsdr = Some((format!("0.0.0.0:{}", p.remote.hpsdr_port), p.remote.hpsdr_rx));
+        }
+        if hpsdr != self.hpsdr {
+            let msg = match hpsdr.as_ref() {
+                Some((addr, rx)) => messages::HpsdrMsg::Listen(addr.clone(), *rx),
+                None => messages::HpsdrMsg::Close,
+            };
+            self.hpsdr_sender.send(msg).unwrap();
+            self.hpsdr = hpsdr;
+        }
+
+        let mut rtl_tcp = None;
+        if p.remote.rtl_tcp {
+            let mut host = "127.0.0.1";
+            if p.remote.rtl_tcp_lan {
+                host = "0.0.0.0";
+            }
+            rtl_tcp = Some((format!("{}:{}", host, p.remote.rtl_tcp_port), p.remote.rtl_tcp_rx));
+        }
+        if rtl_tcp != self.rtl_tcp {
+            let msg = match rtl_tcp.as_ref() {
+                Some((addr, rx)) => messages::RtlTcpMsg::Listen(addr.clone(), *rx),
+                None => messages::RtlTcpMsg::Close,
+            };
+            self.rtl_sender.send(msg).unwrap();
+            self.rtl_tcp = rtl_tcp;
+        }
+    }
+
+    //===========================================================================================
+    // Populate remote window
+    pub fn remote(&mut self, ui: &mut egui::Ui) {
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            {
+                let mut p = self.prefs.borrow_mut();
+                ui.checkbox(&mut p.remote.rigctld, "rigctld");
+                ui.label(String::from("Port"));
+                let r = ui.add(egui::DragValue::new(&mut p.remote.rigctld_port).speed(1).clamp_range(1024..=65535));
+                self.editing = r.has_focus() || r.dragged();
+                ui.checkbox(&mut p.remote.rigctld_lan, "Allow LAN");
+                ui.label(format!("Clients {}", globals::get_rigctl_clients()));
+                ui.separator();
+
+                // Serial CAT for tools without network control
+                ui.checkbox(&mut p.remote.cat_pty, "TS-2000 CAT");
+                ui.label(String::from("Link"));
+                let r = ui.add(egui::TextEdit::singleline(&mut p.remote.cat_pty_link).desired_width(140.0));
+                self.editing = self.editing || r.has_focus();
+                let name = globals::get_cat_pty_name();
+                if name.len() > 0 {
+                    ui.label(name);
+                }
+            }
+            ui.separator();
+
+            // TX on the RX2 frequency
+            let mut split = globals::get_split();
+            if ui.checkbox(&mut split, "Split").changed() {
+                self.apply(messages::CatMsg::SetSplit(split));
+            }
+            if globals::get_cat_ptt() {
+                ui.label(RichText::new("CAT PTT").color(PTT_COLOR));
+            }
+        });
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            // 48K RTP/L16 receiver audio out and TX audio in for digital mode applications
+            let mut p = self.prefs.borrow_mut();
+            ui.checkbox(&mut p.remote.bridge, "Audio bridge");
+            ui.label(String::from("Host"));
+            let r = ui.add(egui::TextEdit::singleline(&mut p.remote.bridge_host).desired_width(100.0));
+            self.editing = self.editing || r.has_focus();
+            ui.label(String::from("RX port"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.bridge_rx_port).speed(1).clamp_range(1024..=65530));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            ui.label(String::from("TX port"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.bridge_tx_port).speed(1).clamp_range(1024..=65535));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            if globals::get_bridge_tx() {
+                ui.label(RichText::new("TX audio").color(TX_AUDIO_COLOR));
+            }
+        });
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            // Browser control and spectrum, open http://host:port/ and give the token
+            let mut p = self.prefs.borrow_mut();
+            if ui.checkbox(&mut p.remote.web, "Web server").changed() {
+                if p.remote.web && p.remote.web_token.len() == 0 {
+                    p.remote.web_token = new_token();
+                }
+            }
+            ui.label(String::from("Port"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.web_port).speed(1).clamp_range(1024..=65535));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            // Plain http, browsers on other hosts only allow audio over https or a tunnel to localhost
+            ui.checkbox(&mut p.remote.web_lan, "Allow LAN")
+                .on_hover_text("Audio from another host needs https in front of this port or an ssh tunnel to localhost");
+            ui.label(String::from("Token"));
+            let r = ui.add(egui::TextEdit::singleline(&mut p.remote.web_token).desired_width(140.0));
+            self.editing = self.editing || r.has_focus();
+            if ui.button("New").clicked() {
+                p.remote.web_token = new_token();
+            }
+            ui.label(format!("Clients {}", globals::get_web_clients()));
+            if globals::get_web_tx() {
+                ui.label(RichText::new("Web TX audio").color(TX_AUDIO_COLOR));
+            }
+        });
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            // Another SDR application sees a Hermes with one receiver, it tunes that receiver
+            let mut p = self.prefs.borrow_mut();
+            ui.checkbox(&mut p.remote.hpsdr, "HPSDR server");
+            ui.label(String::from("Port"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.hpsdr_port).speed(1).clamp_range(1024..=65535));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            // RX1 carries our VFO and TX so only another receiver is shared
+            ui.label(String::from("RX"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.hpsdr_rx).speed(0.1).clamp_range(2..=common_defs::MAX_RADIOS));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            let client = globals::get_hpsdr_client();
+            if client.len() > 0 {
+                ui.label(format!("Client {}", client));
+            }
+        });
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            // rtl_tcp clients get one receiver at their own rate and can set the attenuator
+            let mut p = self.prefs.borrow_mut();
+            ui.checkbox(&mut p.remote.rtl_tcp, "rtl_tcp server");
+            ui.label(String::from("Port"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.rtl_tcp_port).speed(1).clamp_range(1024..=65535));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            ui.checkbox(&mut p.remote.rtl_tcp_lan, "Allow LAN");
+            ui.label(String::from("RX"));
+            let r = ui.add(egui::DragValue::new(&mut p.remote.rtl_tcp_rx).speed(0.1).clamp_range(1..=common_defs::MAX_RADIOS));
+            self.editing = self.editing || r.has_focus() || r.dragged();
+            let client = globals::get_rtl_tcp_client();
+            if client.len() > 0 {
+                ui.label(format!("Client {}", client));
+            }
+        });
+        self.listen();
+    }
+}
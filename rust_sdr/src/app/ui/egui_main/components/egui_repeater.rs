@@ -0,0 +1,119 @@
+/*
+egui_repeater.rs
+
+Module - egui_repeater
+FM repeater sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::common::common_defs;
+use crate::app::common::common_defs::RptShift;
+use crate::app::pipeline::tone_squelch::SqlType;
+use crate::app::client::radio_client::RadioClient;
+
+use eframe::egui;
+
+//===========================================================================================
+// State for the repeater settings
+pub struct UIRepeater {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+}
+
+//===========================================================================================
+// Implementation for UIRepeater
+impl UIRepeater {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        Self {
+            client: client,
+            prefs: prefs,
+        }
+    }
+
+    //===========================================================================================
+    // Populate repeater window
+    pub fn repeater(&mut self, ui: &mut egui::Ui) {
+
+        let mut p = self.prefs.borrow_mut();
+        egui::CollapsingHeader::new("FM repeater").show(ui, |ui| {
+            egui::Grid::new("repeater-grid").show(ui, |ui| {
+                // Offset of the repeater input from the output
+                ui.label(String::from("Shift"));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    ui.selectable_value(&mut p.repeater.shift_dir, RptShift::Simplex as u32, "Simplex");
+                    ui.selectable_value(&mut p.repeater.shift_dir, RptShift::Plus as u32, "+");
+                    ui.selectable_value(&mut p.repeater.shift_dir, RptShift::Minus as u32, "-");
+                    let mut khz = p.repeater.shift as f32 / 1000.0;
+                    if ui.add(egui::DragValue::new(&mut khz).speed(1.0).clamp_range(0.0..=10000.0).suffix("kHz")).changed() {
+                        p.repeater.shift = (khz * 1000.0) as u32;
+                    }
+                    ui.toggle_value(&mut p.repeater.reverse, "Reverse");
+                });
+                ui.end_row();
+
+                // Tone sent with the transmission
+                ui.checkbox(&mut p.repeater.ctcss_tx, "CTCSS TX");
+                tone_combo(ui, "ctcss-tx", &mut p.repeater.ctcss_tx_freq);
+                ui.end_row();
+
+                // Squelch opens on the tone or code
+                ui.label(String::from("Tone SQL"));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    ui.selectable_value(&mut p.repeater.tone_sql, SqlType::Off as u32, "Off");
+                    ui.selectable_value(&mut p.repeater.tone_sql, SqlType::Ctcss as u32, "CTCSS");
+                    ui.selectable_value(&mut p.repeater.tone_sql, SqlType::Dcs as u32, "DCS");
+                    if p.repeater.tone_sql == SqlType::Ctcss as u32 {
+                        tone_combo(ui, "ctcss-sql", &mut p.repeater.sql_ctcss);
+                    } else if p.repeater.tone_sql == SqlType::Dcs as u32 {
+                        egui::ComboBox::from_id_source("dcs-sql")
+                            .selected_text(format!("D{:03o}", p.repeater.sql_dcs))
+                            .show_ui(ui, |ui| {
+                                for code in common_defs::DCS_CODES.iter() {
+                                    ui.selectable_value(&mut p.repeater.sql_dcs, *code, format!("D{:03o}", code));
+                                }
+                            }
+                        );
+                    }
+                });
+                ui.end_row();
+            });
+        });
+
+        self.client.borrow_mut().set_repeater(&p.repeater);
+    }
+}
+
+// Select from the standard CTCSS tones
+fn tone_combo(ui: &mut egui::Ui, id: &str, freq: &mut f32) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(format!("{:.1} Hz", freq))
+        .show_ui(ui, |ui| {
+            for tone in common_defs::CTCSS_TONES.iter() {
+                ui.selectable_value(freq, *tone, format!("{:.1}", tone));
+            }
+        }
+    );
+}
@@ -25,14 +25,13 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-use std::sync::{Arc, Mutex};
 use std::{cell::RefCell, rc::Rc};
 
-use crate::app::protocol;
+use crate::app::client::radio_client::RadioClient;
 use crate::app::common::globals;
 use crate::app::common::common_defs;
+use crate::app::common::prefs;
 use crate::app::ui::egui_main::components;
-use crate::app::dsp;
 
 use egui::{Color32, Pos2, pos2, emath};
 
@@ -53,6 +52,10 @@ const GRID_COLOR: Color32 = Color32::from_rgba_premultiplied(0,50,0,10);
 const SPEC_COLOR: Color32 = Color32::from_rgba_premultiplied(150,150,0,70);
 const OVERLAY_COLOR: Color32 = Color32::from_rgba_premultiplied(0,30,0,10);
 const CENTRE_COLOR: Color32 = Color32::RED;
+const NOTCH_COLOR: Color32 = Color32::from_rgba_premultiplied(0,0,60,40);
+const SUB_RX_COLOR: Color32 = Color32::from_rgba_premultiplied(0,120,120,120);
+// Pixels either side of a notch that still select it
+const NOTCH_GRAB: f32 = 3.0;
 const DIVS: i32 = 6;
 const F_X_MARGIN: f32 = 15.0;
 const F_X_LABEL_ADJ: f32 = 20.0;
@@ -61,12 +64,13 @@ const F_X_LABEL_ADJ: f32 = 20.0;
 // State for spectrum
 pub struct UISpec {
     // Parameters
-    i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    client : Rc<RefCell<dyn RadioClient>>,
     vfo : Rc<RefCell<components::egui_vfo::UIVfo>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
     out_real: [f32; (common_defs::DSP_BLK_SZ ) as usize],
 
     // Spec
-    _span_freq: u32,
+    span_freq: u32,
     frequency: u32,
     filter_width: i32,
     mode_pos: common_defs::EnumModePos, 
@@ -75,6 +79,14 @@ pub struct UISpec {
     freq_at_ptr: f32,
     draw_at_ptr: bool,
 
+    // Notches
+    drag_notch: Option<usize>,
+    notch_dragged: bool,
+    notch_dirty: bool,
+    // Band and tune frequency last applied to each DSP channel
+    notch_band: Vec<String>,
+    notch_tune: Vec<u32>,
+
     // Waterfall
     last_disp_width: i32,
     image_data: Vec<Color32>,
@@ -93,14 +105,16 @@ pub struct UISpec {
 //===========================================================================================
 // Implementation for UIApp
 impl UISpec {
-    pub fn new(_cc: &eframe::CreationContext<'_>, i_cc : Arc<Mutex<protocol::cc_out::CCData>>, vfo : Rc<RefCell<components::egui_vfo::UIVfo>>) -> Self{
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, vfo : Rc<RefCell<components::egui_vfo::UIVfo>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
 
+        let span_freq = client.borrow().smpl_rate();
         Self {
-            i_cc: i_cc,
+            client: client,
             vfo: vfo,
+            prefs: prefs,
             out_real: [0.0; (common_defs::DSP_BLK_SZ ) as usize],
 
-            _span_freq: globals::get_smpl_rate(),
+            span_freq: span_freq,
             frequency: 7100000,
             disp_width: 300,
             mode_pos: common_defs::EnumModePos::Lower,
@@ -109,6 +123,12 @@ impl UISpec {
             freq_at_ptr: 7.1,
             draw_at_ptr: false,
 
+            drag_notch: None,
+            notch_dragged: false,
+            notch_dirty: true,
+            notch_band: vec![String::new(); common_defs::MAX_RADIOS as usize],
+            notch_tune: vec![0; common_defs::MAX_RADIOS as usize],
+
             last_disp_width: 300,
             image_height: 100,
             image_data: vec![Color32::TRANSPARENT; 30000],
@@ -174,9 +194,11 @@ impl UISpec {
             // Draw verticle lines and legends
             // Get the current frequency
             self.frequency = self.vfo.borrow_mut().get_freq();
+            // Track the sample rate as this sets the span
+            self.span_freq = self.client.borrow().smpl_rate();
             // Set up the parameters
-            let start_freq: i32 = self.frequency as i32 - (globals::get_smpl_rate() as i32 / 2);
-            let freq_inc = globals::get_smpl_rate() as i32 / DIVS;
+            let start_freq: i32 = self.frequency as i32 - (self.span_freq as i32 / 2);
+            let freq_inc = self.span_freq as i32 / DIVS;
             let pixels_per_div: f32 = (rect.width() - L_MARGIN - R_MARGIN - F_X_LABEL_ADJ) as f32 / DIVS as f32;
             let mut j = start_freq;
             for i in 0..=DIVS {
@@ -210,15 +232,11 @@ impl UISpec {
             // Update the display width if necessary
             if self.disp_width != (rect.width() - L_MARGIN + R_MARGIN) as i32 {
                 self.disp_width = (rect.width() - L_MARGIN + R_MARGIN) as i32;
-                dsp::dsp_interface::wdsp_update_disp(
-                    0, common_defs::FFT_SZ, common_defs::WindowTypes::Rectangular as i32, 
-                    common_defs::SUB_SPANS, common_defs::IN_SZ, self.disp_width, 
-                    common_defs::AvMode::PanTimeAvLin as i32, common_defs::OVER_FRAMES, 
-                    globals::get_smpl_rate() as i32, common_defs::FRAME_RATE);
+                self.client.borrow_mut().set_disp_width(self.disp_width as u32);
             }
             // The array out_real contains a set of db values, one per pixel of the horizontal display area.
             // Must be painted every iteration even when not changed otherwise it will flicker
-            if globals::get_run_state() {
+            if self.client.borrow().running() {
                 let mut shapes = vec![];
                 let end = (rect.width() - L_MARGIN + R_MARGIN) as i32; 
                 let points: Vec<egui::Pos2> = (0..end)
@@ -234,7 +252,7 @@ impl UISpec {
             let pos_top_left: Pos2;
             let pos_bottom_right: Pos2;
             // Width of filter in pixels
-            let filt_pix = (self.filter_width as f32/globals::get_smpl_rate() as f32) * self.disp_width as f32;
+            let filt_pix = (self.filter_width as f32/self.span_freq as f32) * self.disp_width as f32;
             if self.mode_pos == common_defs::EnumModePos::Lower {
                 pos_top_left = emath::pos2(rect.left() + L_MARGIN + (self.disp_width as f32/2.0) - filt_pix, rect.top() + T_MARGIN);
                 pos_bottom_right = emath::pos2(rect.left() + L_MARGIN + (self.disp_width as f32/2.0), rect.top() + rect.height() - B_MARGIN);
@@ -253,6 +271,59 @@ impl UISpec {
                 OVERLAY_COLOR,
             );
 
+            // Draw notches in the current band, an engine has its own
+            let remote = self.client.borrow().is_remote();
+            let notches;
+            if remote {
+                notches = vec![];
+            } else {
+                notches = self.band_notches();
+            }
+            for notch in notches.iter() {
+                let x = rect.left() + L_MARGIN + self.freq_to_x(notch.freq);
+                let half_pix = f32::max(1.0, (notch.width as f32 / self.span_freq as f32) * self.disp_width as f32 / 2.0);
+                if x + half_pix < rect.left() + L_MARGIN || x - half_pix > rect.right() + R_MARGIN {
+                    continue;
+                }
+                painter.rect_filled(
+                    emath::Rect::from_two_pos(
+                        emath::pos2(x - half_pix, rect.top() + T_MARGIN),
+                        emath::pos2(x + half_pix, rect.top() + rect.height() - B_MARGIN)),
+                    0.0,
+                    NOTCH_COLOR,
+                );
+            }
+
+            // Mark each enabled sub-receiver
+            for (i, sub) in self.prefs.borrow().sub_rx.iter().enumerate() {
+                if !sub.enabled {
+                    continue;
+                }
+                let x = rect.left() + L_MARGIN + self.freq_to_x(sub.frequency);
+                if x < rect.left() + L_MARGIN || x > rect.right() + R_MARGIN {
+                    continue;
+                }
+                painter.line_segment(
+                    [
+                        emath::pos2(x, rect.top() + T_MARGIN),
+                        emath::pos2(x, rect.top() + rect.height() - B_MARGIN),
+                    ],
+                    egui::Stroke::new(1.0, SUB_RX_COLOR),
+                );
+                painter.text(
+                    egui::pos2(x + 2.0, rect.top() + T_MARGIN + 8.0),
+                    egui::Align2::LEFT_CENTER,
+                    &format!("S{}", i + 1),
+                    egui::FontId::new(12.0,egui::FontFamily::Proportional),
+                    SUB_RX_COLOR,
+                );
+            }
+
+            // Follow any retune or notch change
+            if !remote {
+                self.notches_update();
+            }
+
             // Draw frequency at cursor
             if ui.rect_contains_pointer(rect) {
                 // Within the area
@@ -265,19 +336,27 @@ impl UISpec {
                     self.draw_at_ptr = false;
                 }
                 let e = &ui.ctx().input().events;
-                if e.len() > 0 {
-                    match &e[0] {
+                // Take all events so a button release is not missed
+                for ev in e.iter() {
+                    match ev {
                         egui::Event::PointerMoved(v) => {
                             self.mouse_pos = *v;
                             self.freq_at_ptr();
+                            if !remote && self.drag_notch.is_some() {
+                                self.notch_drag(*v);
+                            }
                         },
-                        egui::Event::PointerButton { pos, button: _, pressed, modifiers: _ } => {
-                            if *pressed {
+                        egui::Event::PointerButton { pos, button, pressed, modifiers: _ } => {
+                            if *button == egui::PointerButton::Secondary {
+                                // Right button adds, sizes and removes notches
+                                if !remote {
+                                    self.notch_button(*pos, *pressed);
+                                }
+                            } else if *pressed {
                                 let f = self.freq_at_click(*pos);
                                 self.frequency = f;
                                 self.vfo.borrow_mut().update_freq(f);
                                 self.vfo.borrow_mut().set_freq();
-                                self.i_cc.lock().unwrap().cc_set_rx_tx_freq(self.frequency);
                             }
                         }
                         _ => ()
@@ -301,7 +380,7 @@ impl UISpec {
             }
 
             // Add the waterfall display.
-            if globals::get_run_state() {
+            if self.client.borrow().running() {
                 if self.disp_width != self.last_disp_width {
                     // Resize the image data vector
                     // This vector is a linear representation of all pixel colors in the 2D waterfall display
@@ -339,7 +418,7 @@ impl UISpec {
     fn freq_at_ptr(&mut self) {
         let x = self.mouse_pos.x - L_MARGIN;
         let x_frac = x/self.disp_width as f32;
-        self.freq_at_ptr = (globals::get_smpl_rate() as f32 * x_frac + (self.frequency - globals::get_smpl_rate() /2 ) as f32)/1000000.0;
+        self.freq_at_ptr = (self.span_freq as f32 * x_frac + (self.frequency - self.span_freq /2 ) as f32)/1000000.0;
         self.freq_at_ptr = (self.freq_at_ptr * 1000.0).round() / 1000.0;
     }
 
@@ -347,10 +426,121 @@ impl UISpec {
     fn freq_at_click(&mut self, pos: Pos2) -> u32{
         let x = pos.x - L_MARGIN;
         let x_frac = x/self.disp_width as f32;
-        let f = (globals::get_smpl_rate() as f32 * x_frac + (self.frequency - globals::get_smpl_rate() /2 ) as f32) as u32;
+        let f = (self.span_freq as f32 * x_frac + (self.frequency - self.span_freq /2 ) as f32) as u32;
         return f;
     }
 
+    // X offset into the display for a frequency
+    fn freq_to_x(&self, freq: u32) -> f32 {
+        let start_freq = self.frequency as i64 - (self.span_freq as i64 / 2);
+        return ((freq as i64 - start_freq) as f32 / self.span_freq as f32) * self.disp_width as f32;
+    }
+
+    //==================================================================================
+    // Notch helpers
+    // Notches for the band the selected receiver is in
+    fn band_notches(&self) -> Vec<prefs::Notch> {
+        let band = common_defs::band_for_freq(self.frequency);
+        match self.prefs.borrow().notches.get(band) {
+            Some(notches) => return notches.clone(),
+            None => return Vec::new(),
+        }
+    }
+
+    // Right button pressed or released
+    fn notch_button(&mut self, pos: Pos2, pressed: bool) {
+        let f = self.freq_at_click(pos);
+        let band = common_defs::band_for_freq(self.frequency);
+        if pressed {
+            // Grab an existing notch or add a new one
+            let grab = ((NOTCH_GRAB / self.disp_width as f32) * self.span_freq as f32) as u32;
+            let notches = self.band_notches();
+            self.drag_notch = None;
+            for (i, notch) in notches.iter().enumerate() {
+                if (f as i64 - notch.freq as i64).abs() as u32 <= notch.width / 2 + grab {
+                    self.drag_notch = Some(i);
+                    self.notch_dragged = false;
+                }
+            }
+            if self.drag_notch.is_none() {
+                let mut p = self.prefs.borrow_mut();
+                let notches = p.notches.entry(band.to_string()).or_insert(Vec::new());
+                notches.push(prefs::Notch {freq: f, width: common_defs::NOTCH_WIDTH});
+                self.drag_notch = Some(notches.len() - 1);
+                // Don't remove it again on release
+                self.notch_dragged = true;
+                self.notch_dirty = true;
+            }
+        } else {
+            // A click on a notch without dragging removes it
+            match self.drag_notch {
+                Some(i) => {
+                    if !self.notch_dragged {
+                        let mut p = self.prefs.borrow_mut();
+                        let notches = p.notches.entry(band.to_string()).or_insert(Vec::new());
+                        if i < notches.len() {
+                            notches.remove(i);
+                        }
+                        self.notch_dirty = true;
+                    }
+                },
+                None => (),
+            }
+            self.drag_notch = None;
+        }
+    }
+
+    // Dragging sets the width of the grabbed notch
+    fn notch_drag(&mut self, pos: Pos2) {
+        let f = self.freq_at_click(pos);
+        let band = common_defs::band_for_freq(self.frequency);
+        let mut p = self.prefs.borrow_mut();
+        let notches = p.notches.entry(band.to_string()).or_insert(Vec::new());
+        match self.drag_notch {
+            Some(i) => {
+                if i < notches.len() {
+                    let width = 2 * (f as i64 - notches[i].freq as i64).abs() as u32;
+                    notches[i].width = u32::max(common_defs::NOTCH_MIN_WIDTH, u32::min(common_defs::NOTCH_MAX_WIDTH, width));
+                    self.notch_dragged = true;
+                    self.notch_dirty = true;
+                }
+            },
+            None => (),
+        }
+    }
+
+    // Keep the DSP notches in step with the receiver frequencies
+    fn notches_update(&mut self) {
+        for rx in 1..=globals::get_num_rx() as usize {
+            let freq;
+            match rx {
+                2 => freq = self.prefs.borrow().radio.rx2.frequency,
+                3 => freq = self.prefs.borrow().radio.rx3.frequency,
+                _ => freq = self.prefs.borrow().radio.rx1.frequency,
+            }
+            let band = common_defs::band_for_freq(freq);
+            if self.notch_dirty || band != self.notch_band[rx - 1] {
+                let mut list = vec![];
+                match self.prefs.borrow().notches.get(band) {
+                    Some(notches) => {
+                        for notch in notches.iter() {
+                            list.push((notch.freq, notch.width));
+                        }
+                    },
+                    None => (),
+                }
+                self.client.borrow_mut().set_notches(rx as u32, &list);
+                self.notch_band[rx - 1] = band.to_string();
+            }
+            if freq != self.notch_tune[rx - 1] {
+                // Notches are at RF so follow the tuning
+                self.client.borrow_mut().set_notch_tune(rx as u32, freq);
+                self.notch_tune[rx - 1] = freq;
+            }
+        }
+        self.notch_dirty = false;
+    }
+
     //==================================================================================
     // Waterfall helpers
     // Create a new image data
@@ -0,0 +1,176 @@
+/*
+egui_subrx.rs
+
+Module - egui_subrx
+Software sub-receivers sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::common::common_defs;
+use crate::app::common::common_defs::ModeId;
+use crate::app::common::common_defs::FilterId;
+use crate::app::client::radio_client::RadioClient;
+
+use eframe::egui;
+
+// Modes and filters offered for a sub-receiver
+const MODES: [(ModeId, &str); 8] = [
+    (ModeId::Lsb, "LSB"),
+    (ModeId::Usb, "USB"),
+    (ModeId::CwL, "CW-L"),
+    (ModeId::CwU, "CW-U"),
+    (ModeId::Am, "AM"),
+    (ModeId::Sam, "SAM"),
+    (ModeId::Fm, "FM"),
+    (ModeId::DigU, "DG-U"),
+];
+const FILTERS: [(FilterId, &str); 9] = [
+    (FilterId::F6_0KHz, "6K0"),
+    (FilterId::F4_0KHz, "4K0"),
+    (FilterId::F2_7KHz, "2K7"),
+    (FilterId::F2_4KHz, "2K4"),
+    (FilterId::F2_1KHz, "2K1"),
+    (FilterId::F1_0KHz, "1K0"),
+    (FilterId::F500Hz, "500H"),
+    (FilterId::F250Hz, "250H"),
+    (FilterId::F100Hz, "100H"),
+];
+
+//===========================================================================================
+// State for sub-receivers
+pub struct UISubRx {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+}
+
+//===========================================================================================
+// Implementation for UISubRx
+impl UISubRx {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        Self {
+            client: client,
+            prefs: prefs,
+        }
+    }
+
+    //===========================================================================================
+    // Populate sub-receiver window
+    pub fn sub_rx(&mut self, ui: &mut egui::Ui) {
+
+        let centre = self.centre_freq();
+        egui::Grid::new("subrx-grid").show(ui, |ui| {
+            ui.label(String::from("Sub RX"));
+            ui.label(String::from("Freq KHz"));
+            ui.label(String::from(""));
+            ui.label(String::from("Mode"));
+            ui.label(String::from("Filter"));
+            ui.label(String::from("Mute"));
+            ui.label(String::from("Volume"));
+            ui.label(String::from("Pan L/R"));
+            ui.end_row();
+
+            for i in 0..common_defs::MAX_SUB_RX as usize {
+                let mut sub = self.prefs.borrow().sub_rx[i].clone();
+                ui.checkbox(&mut sub.enabled, format!("S{}", i + 1));
+                let mut khz = sub.frequency as f64 / 1000.0;
+                ui.add(egui::DragValue::new(&mut khz).speed(0.1).fixed_decimals(3).clamp_range(0.0..=60000.0));
+                sub.frequency = (khz * 1000.0).round() as u32;
+                // Take the frequency of the selected receiver
+                if ui.button(String::from("VFO")).clicked() {
+                    sub.frequency = centre;
+                }
+                egui::ComboBox::from_id_source(format!("subrx-mode-{}", i))
+                    .width(60.0)
+                    .selected_text(mode_name(sub.mode))
+                    .show_ui(ui, |ui| {
+                        for (mode, name) in MODES.iter() {
+                            ui.selectable_value(&mut sub.mode, *mode, *name);
+                        }
+                    }
+                );
+                egui::ComboBox::from_id_source(format!("subrx-filter-{}", i))
+                    .width(60.0)
+                    .selected_text(filter_name(sub.filter))
+                    .show_ui(ui, |ui| {
+                        for (filter, name) in FILTERS.iter() {
+                            ui.selectable_value(&mut sub.filter, *filter, *name);
+                        }
+                    }
+                );
+                ui.checkbox(&mut sub.mute, "");
+                let mut vol = sub.vol * 100.0;
+                ui.add(egui::Slider::new(&mut vol, 0.0..=100.0).suffix("%"));
+                sub.vol = vol / 100.0;
+                ui.add(egui::Slider::new(&mut sub.pan, -1.0..=1.0));
+                // Outside the passband of the selected receiver
+                let in_band = self.in_passband(sub.frequency, centre);
+                if sub.enabled && !in_band {
+                    ui.label(String::from("Out of band"));
+                }
+                ui.end_row();
+
+                // The channel runs at an offset from the selected receiver
+                let offset = sub.frequency as i64 - centre as i64;
+                self.client.borrow_mut().set_sub_rx(i as u32, &sub, offset, sub.enabled && in_band);
+                self.prefs.borrow_mut().sub_rx[i] = sub;
+            }
+        });
+    }
+
+    // Frequency of the selected receiver which is the centre of the passband
+    fn centre_freq(&self) -> u32 {
+        let p = self.prefs.borrow();
+        match self.client.borrow().sel_rx() {
+            2 => return p.radio.rx2.frequency,
+            3 => return p.radio.rx3.frequency,
+            _ => return p.radio.rx1.frequency,
+        }
+    }
+
+    // The passband is the sample rate centred on the receiver frequency
+    fn in_passband(&self, freq: u32, centre: u32) -> bool {
+        let half = self.client.borrow().smpl_rate() as i64 / 2;
+        return (freq as i64 - centre as i64).abs() < half;
+    }
+}
+
+fn mode_name(mode: ModeId) -> &'static str {
+    for (m, name) in MODES.iter() {
+        if *m == mode {
+            return name;
+        }
+    }
+    return "";
+}
+
+fn filter_name(filter: FilterId) -> &'static str {
+    for (f, name) in FILTERS.iter() {
+        if *f == filter {
+            return name;
+        }
+    }
+    return "";
+}
@@ -0,0 +1,112 @@
+/*
+egui_test.rs
+
+Module - egui_test
+Tune and test signal sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::client::radio_client::RadioClient;
+use crate::app::pipeline::test_signal::TestMode;
+
+use eframe::egui;
+
+const MODES: [(&str, TestMode); 5] = [
+    ("Off", TestMode::Off),
+    ("Tune", TestMode::Tune),
+    ("Two tone", TestMode::TwoTone),
+    ("Sweep", TestMode::Sweep),
+    ("Noise", TestMode::Noise),
+];
+
+const TEST_ON_COLOR: egui::Color32 = egui::Color32::DARK_RED;
+
+//===========================================================================================
+// State for test signals
+pub struct UITest {
+    client: Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+}
+
+//===========================================================================================
+// Implementation for UITest
+impl UITest {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client: Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        Self {
+            client: client,
+            prefs: prefs,
+        }
+    }
+
+    //===========================================================================================
+    // Populate test signal window
+    pub fn test(&mut self, ui: &mut egui::Ui) {
+
+        // The pipeline turns the test off when it times out
+        let current = self.client.borrow().test_mode();
+        let mut mode = current;
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            for (name, m) in MODES.iter() {
+                let mut text = egui::RichText::new(*name);
+                if mode == *m && *m != TestMode::Off {
+                    text = text.background_color(TEST_ON_COLOR);
+                }
+                ui.selectable_value(&mut mode, *m, text);
+            }
+        });
+
+        let mut p = self.prefs.borrow_mut();
+        egui::CollapsingHeader::new("Test settings").show(ui, |ui| {
+            egui::Grid::new("test-grid").show(ui, |ui| {
+                ui.label(String::from("Tune level"));
+                ui.add(egui::Slider::new(&mut p.test.tune_level, 0.0..=1.0));
+                ui.end_row();
+                ui.label(String::from("Test level"));
+                ui.add(egui::Slider::new(&mut p.test.tone_level, 0.0..=1.0));
+                ui.end_row();
+                ui.label(String::from("Tones"));
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+                    ui.add(egui::DragValue::new(&mut p.test.tone1).speed(10).clamp_range(100..=3000).suffix("Hz"));
+                    ui.add(egui::DragValue::new(&mut p.test.tone2).speed(10).clamp_range(100..=3000).suffix("Hz"));
+                });
+                ui.end_row();
+                // Limits apply to every test
+                ui.label(String::from("Max level"));
+                ui.add(egui::Slider::new(&mut p.test.max_level, 0.0..=1.0));
+                ui.end_row();
+                ui.label(String::from("Timeout"));
+                ui.add(egui::DragValue::new(&mut p.test.timeout).speed(1).clamp_range(1..=600).suffix("s"));
+                ui.end_row();
+            });
+        });
+
+        self.client.borrow_mut().set_test(mode, &p.test);
+        // Only write a new selection so a timeout is not overwritten
+        if mode != current {
+            self.client.borrow_mut().set_test_mode(mode);
+        }
+    }
+}
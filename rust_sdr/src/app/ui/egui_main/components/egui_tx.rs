@@ -0,0 +1,197 @@
+/*
+egui_tx.rs
+
+Module - egui_tx
+TX audio processing sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::common::globals;
+use crate::app::common::common_defs;
+use crate::app::common::common_defs::TxMeterType;
+use crate::app::client::radio_client::RadioClient;
+
+use egui::{RichText, TextStyle};
+use eframe::egui;
+
+// Stages shown on the TX meter, peak level and any gain applied by the stage
+const STAGES: [(&str, TxMeterType, Option<TxMeterType>); 6] = [
+    ("Mic", TxMeterType::MicPk, None),
+    ("EQ", TxMeterType::EqPk, None),
+    ("Leveler", TxMeterType::LvlrPk, Some(TxMeterType::LvlrGain)),
+    ("Comp", TxMeterType::CompPk, None),
+    ("ALC", TxMeterType::AlcPk, Some(TxMeterType::AlcGain)),
+    ("Out", TxMeterType::OutPk, None),
+];
+// Meter range in dB
+const METER_MIN: f32 = -40.0;
+const METER_MAX: f32 = 3.0;
+// Equaliser range in dB
+const EQ_MIN: i32 = -12;
+const EQ_MAX: i32 = 12;
+
+//===========================================================================================
+// State for TX processing
+pub struct UITx {
+    client : Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+    // Settings being edited and those last saved to the profile
+    profile: prefs::TxProfile,
+    applied: prefs::TxProfile,
+    // Name for Save as
+    new_name: String,
+}
+
+//===========================================================================================
+// Implementation for UITx
+impl UITx {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        let mut profile = prefs.borrow().tx.current();
+        profile.eq.resize(common_defs::TX_EQ_BANDS as usize, 0);
+        Self {
+            client: client,
+            prefs: prefs,
+            profile: profile.clone(),
+            applied: profile,
+            new_name: String::new(),
+        }
+    }
+
+    //===========================================================================================
+    // Populate TX window
+    pub fn tx(&mut self, ui: &mut egui::Ui) {
+
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            self.profiles(ui);
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            ui.label(String::from("Mic"));
+            ui.add(egui::Slider::new(&mut self.profile.mic_gain, -20.0..=40.0).suffix("dB"));
+            ui.label(String::from("TX filter"));
+            ui.add(egui::DragValue::new(&mut self.profile.low).speed(10).clamp_range(0..=1000).suffix("Hz"));
+            ui.add(egui::DragValue::new(&mut self.profile.high).speed(10).clamp_range(1000..=4000).suffix("Hz"));
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            ui.checkbox(&mut self.profile.eq_enabled, "EQ");
+            ui.add(egui::Slider::new(&mut self.profile.eq_preamp, EQ_MIN..=EQ_MAX).vertical().text("Pre"));
+            for i in 0..common_defs::TX_EQ_BANDS as usize {
+                ui.add(egui::Slider::new(&mut self.profile.eq[i], EQ_MIN..=EQ_MAX).vertical().text(common_defs::TX_EQ_FREQS[i]));
+            }
+        });
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            ui.checkbox(&mut self.profile.leveler_enabled, "Leveler");
+            ui.add(egui::Slider::new(&mut self.profile.leveler_gain, 0.0..=20.0).suffix("dB"));
+            ui.checkbox(&mut self.profile.comp_enabled, "Comp");
+            ui.add(egui::Slider::new(&mut self.profile.comp_gain, 0.0..=20.0).suffix("dB"));
+            ui.checkbox(&mut self.profile.cfc_enabled, "CFC");
+            ui.checkbox(&mut self.profile.cessb_enabled, "CESSB");
+            ui.label(String::from("ALC"));
+            ui.add(egui::DragValue::new(&mut self.profile.alc_gain).speed(0.1).clamp_range(0.0..=20.0).suffix("dB"));
+        });
+        self.meter(ui);
+
+        self.apply();
+    }
+
+    // Select, save and delete profiles
+    fn profiles(&mut self, ui: &mut egui::Ui) {
+        ui.label(String::from("Profile"));
+        let mut selected = self.prefs.borrow().tx.profile.clone();
+        let mut names: Vec<String> = self.prefs.borrow().tx.profiles.keys().cloned().collect();
+        names.sort();
+        egui::ComboBox::from_id_source("tx-profile")
+            .selected_text(selected.clone())
+            .show_ui(ui, |ui| {
+                for name in names.iter() {
+                    ui.selectable_value(&mut selected, name.clone(), name);
+                }
+            }
+        );
+        if selected != self.prefs.borrow().tx.profile {
+            // Switch to the settings of the new profile
+            let mut p = self.prefs.borrow_mut();
+            p.tx.profile = selected;
+            self.profile = p.tx.current();
+            self.profile.eq.resize(common_defs::TX_EQ_BANDS as usize, 0);
+        }
+        ui.add(egui::TextEdit::singleline(&mut self.new_name).desired_width(80.0));
+        if ui.button(String::from("Save as")).clicked() && self.new_name.len() > 0 {
+            let mut p = self.prefs.borrow_mut();
+            p.tx.profiles.insert(self.new_name.clone(), self.profile.clone());
+            p.tx.profile = self.new_name.clone();
+            self.new_name.clear();
+        }
+        // Always keep one profile
+        if ui.button(String::from("Delete")).clicked() && names.len() > 1 {
+            let mut p = self.prefs.borrow_mut();
+            let current = p.tx.profile.clone();
+            p.tx.profiles.remove(&current);
+            let mut remaining: Vec<String> = p.tx.profiles.keys().cloned().collect();
+            remaining.sort();
+            p.tx.profile = remaining[0].clone();
+            self.profile = p.tx.current();
+            self.profile.eq.resize(common_defs::TX_EQ_BANDS as usize, 0);
+        }
+    }
+
+    // Level at each stage of the chain
+    fn meter(&mut self, ui: &mut egui::Ui) {
+        let client = self.client.borrow();
+        egui::Grid::new("tx-meter-grid").show(ui, |ui| {
+            for (name, level, gain) in STAGES.iter() {
+                // Stages that are off read very low
+                let db = f32::max(METER_MIN, client.tx_meter(*level));
+                let frac = (f32::min(db, METER_MAX) - METER_MIN) / (METER_MAX - METER_MIN);
+                ui.label(String::from(*name));
+                ui.add(egui::ProgressBar::new(frac).desired_width(200.0));
+                ui.label(RichText::new(format!("{:6.1} dB", db)).text_style(TextStyle::Monospace));
+                match gain {
+                    Some(gain) => {
+                        let g = client.tx_meter(*gain);
+                        ui.label(RichText::new(format!("gain {:5.1} dB", g)).text_style(TextStyle::Monospace));
+                    },
+                    None => {
+                        ui.label(String::from(""));
+                    },
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    // Set the TX channel and save any change to the profile
+    fn apply(&mut self) {
+        // TX follows the mode of the selected receiver
+        let mode = globals::get_mode(globals::get_sel_rx() as i32);
+        self.client.borrow_mut().set_tx(mode, &self.profile);
+        if self.profile != self.applied {
+            self.applied = self.profile.clone();
+            let mut prefs = self.prefs.borrow_mut();
+            let name = prefs.tx.profile.clone();
+            prefs.tx.profiles.insert(name, self.profile.clone());
+        }
+    }
+}
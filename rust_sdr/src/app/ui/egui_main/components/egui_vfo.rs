@@ -25,13 +25,12 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
-use std::sync::{Arc, Mutex};
 use std::ops::Neg;
 use std::{cell::RefCell, rc::Rc};
 
 use crate ::app::common::globals;
 use crate ::app::common::prefs;
-use crate::app::protocol;
+use crate::app::client::radio_client::RadioClient;
 
 use egui::{RichText, TextStyle};
 use eframe::egui;
@@ -54,11 +53,13 @@ const KHZ_SZ: f32 = 55.0;
 const HZ_SZ: f32 = 35.0;
 const VFO_NORMAL_COLOR: egui::Color32 = egui::Color32::TRANSPARENT;
 const VFO_HIGHLIGHT_COLOR: egui::Color32 = egui::Color32::DARK_GREEN;
+// Highest frequency the 9 digits can show
+pub const MAX_DISPLAY_FREQ: u32 = 999999999;
 
 //===========================================================================================
 // State for VFO
 pub struct UIVfo {
-    i_cc : Arc<Mutex<protocol::cc_out::CCData>>,
+    client : Rc<RefCell<dyn RadioClient>>,
     f_array: [(String, f32, egui::Color32); 9],
     frequency: u32,
     prefs: Rc<RefCell<prefs::Prefs>>,
@@ -67,7 +68,7 @@ pub struct UIVfo {
 //===========================================================================================
 // Implementation for UIApp
 impl UIVfo {
-    pub fn new(_cc: &eframe::CreationContext<'_>, i_cc : Arc<Mutex<protocol::cc_out::CCData>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+    pub fn new(_cc: &eframe::CreationContext<'_>, client : Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
 
         let f_array = [
            (String::from("0"), MHZ_SZ, egui::Color32::TRANSPARENT),
@@ -88,22 +89,22 @@ impl UIVfo {
         match rx {
             1 => {
                 freq = prefs.borrow().radio.rx1.frequency;
-                i_cc.lock().unwrap().cc_set_rx_tx_freq(freq);
+                client.borrow_mut().set_freq(1, freq);
             },
             2 => {
                 freq = prefs.borrow().radio.rx2.frequency;
-                i_cc.lock().unwrap().cc_set_rx2_freq(freq);
+                client.borrow_mut().set_freq(2, freq);
             },
             3 => {
                 freq = prefs.borrow().radio.rx3.frequency;
-                i_cc.lock().unwrap().cc_set_rx3_freq(freq);
+                client.borrow_mut().set_freq(3, freq);
             },
             _ => (),
 
         }
 
         Self {
-            i_cc: i_cc,
+            client: client,
             f_array: f_array,
             frequency: freq,
             prefs: prefs,
@@ -216,15 +217,15 @@ impl UIVfo {
         match rx {
             1 => {
                 freq = self.prefs.borrow().radio.rx1.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx_tx_freq(freq);
+                self.client.borrow_mut().set_freq(1, freq);
             },
             2 => {
                 freq = self.prefs.borrow().radio.rx2.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx2_freq(freq);
+                self.client.borrow_mut().set_freq(2, freq);
             },
             3 => {
                 freq = self.prefs.borrow().radio.rx3.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx3_freq(freq);
+                self.client.borrow_mut().set_freq(3, freq);
             },
             _ => (),
         }
@@ -244,21 +245,26 @@ impl UIVfo {
         match rx {
             1 => {
                 self.prefs.borrow_mut().radio.rx1.frequency = self.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx_tx_freq(self.frequency);
+                self.client.borrow_mut().set_freq(1, self.frequency);
             },
             2 => {
                 self.prefs.borrow_mut().radio.rx2.frequency = self.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx2_freq(self.frequency);
+                self.client.borrow_mut().set_freq(2, self.frequency);
             },
             3 => {
                 self.prefs.borrow_mut().radio.rx3.frequency = self.frequency;
-                self.i_cc.lock().unwrap().cc_set_rx3_freq(self.frequency);
+                self.client.borrow_mut().set_freq(3, self.frequency);
             },
             _ => (),
         }
         
     }
 
+    // Change the display frequency only, the receiver stays where it is tuned
+    pub fn display_freq(&mut self, freq: u32) {
+        self.frequency = freq;
+    }
+
     // Get the display frequency
     pub fn get_freq(&mut self) -> u32{
         return self.frequency;
@@ -267,15 +273,8 @@ impl UIVfo {
     // Set the display frequency
     pub fn set_freq(&mut self) {
         // Set the digits to the new frequency
-        let new_freq : String = self.frequency.to_string();
-        // Need to make this a 9 digit string with leading zeros
-        let num_zeros = 9 - new_freq.len();
-        let mut zeros_str = String::from("");
-
-        for _i in 0..num_zeros {
-            zeros_str += "0";
-        }
-        let freq_str = String::from(zeros_str + &new_freq);
+        // Need to make this a 9 digit string with leading zeros, there are no more digits
+        let freq_str = format!("{:09}", u32::min(self.frequency, MAX_DISPLAY_FREQ));
         // We now have a 9 digit string
         // Set each digit from the string
         self.f_array[VfoId::F100M as usize].0 = freq_str.chars().nth(0).unwrap().to_string();
@@ -288,4 +287,4 @@ impl UIVfo {
         self.f_array[VfoId::F10H as usize].0 = freq_str.chars().nth(7).unwrap().to_string();
         self.f_array[VfoId::F1H as usize].0 = freq_str.chars().nth(8).unwrap().to_string();
     }
-}
\ No newline at end of file
+}
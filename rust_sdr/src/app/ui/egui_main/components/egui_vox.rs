@@ -0,0 +1,125 @@
+/*
+egui_vox.rs
+
+Module - egui_vox
+VOX indicator and settings sub-window
+
+Copyright (C) 2023 by G3UKB Bob Cowdery
+
+This program is free software; you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation; either version 2 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+
+The authors can be reached by email at:
+
+bob@bobcowdery.plus.com
+*/
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::app::common::prefs;
+use crate::app::client::radio_client::RadioClient;
+
+use egui::{RichText, TextStyle};
+use eframe::egui;
+
+// Level display range in dB
+const LEVEL_MIN: f32 = -80.0;
+const LEVEL_MAX: f32 = 0.0;
+
+const IND_OFF_COLOR: egui::Color32 = egui::Color32::DARK_GRAY;
+
+//===========================================================================================
+// State for VOX
+pub struct UIVox {
+    client: Rc<RefCell<dyn RadioClient>>,
+    prefs: Rc<RefCell<prefs::Prefs>>,
+}
+
+//===========================================================================================
+// Implementation for UIVox
+impl UIVox {
+    pub fn new(_cc: &eframe::CreationContext<'_>, client: Rc<RefCell<dyn RadioClient>>, prefs: Rc<RefCell<prefs::Prefs>>) -> Self{
+
+        Self {
+            client: client,
+            prefs: prefs,
+        }
+    }
+
+    //===========================================================================================
+    // Populate VOX window
+    pub fn vox(&mut self, ui: &mut egui::Ui) {
+
+        let ptt = self.client.borrow().ptt();
+        let vox = self.client.borrow().vox_keyed();
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui|  {
+            indicator(ui, "TX", ptt || vox, egui::Color32::RED);
+            indicator(ui, "PTT", ptt, egui::Color32::YELLOW);
+            indicator(ui, "VOX", vox, egui::Color32::GREEN);
+        });
+
+        let mut p = self.prefs.borrow_mut();
+        egui::CollapsingHeader::new("VOX settings").show(ui, |ui| {
+            egui::Grid::new("vox-grid").show(ui, |ui| {
+                ui.checkbox(&mut p.vox.enabled, "VOX");
+                ui.end_row();
+
+                // Show the Mic against the threshold to set it
+                ui.label(String::from("Mic"));
+                let mic = self.client.borrow().mic_level();
+                ui.add(egui::ProgressBar::new(level_frac(mic)).desired_width(150.0));
+                ui.label(RichText::new(format!("{:6.1} dB", mic)).text_style(TextStyle::Monospace));
+                ui.end_row();
+                ui.label(String::from("Threshold"));
+                ui.add(egui::Slider::new(&mut p.vox.threshold, LEVEL_MIN..=LEVEL_MAX).suffix("dB"));
+                ui.end_row();
+                ui.label(String::from("Attack"));
+                ui.add(egui::DragValue::new(&mut p.vox.attack).speed(1).clamp_range(0..=500).suffix("ms"));
+                ui.end_row();
+                ui.label(String::from("Hang"));
+                ui.add(egui::DragValue::new(&mut p.vox.hang).speed(10).clamp_range(0..=5000).suffix("ms"));
+                ui.end_row();
+
+                // Receiver audio picked up by the Mic must not key the transmitter
+                ui.checkbox(&mut p.vox.anti_vox, "Anti-VOX");
+                let rx = self.client.borrow().rx_level();
+                ui.add(egui::ProgressBar::new(level_frac(rx)).desired_width(150.0));
+                ui.label(RichText::new(format!("{:6.1} dB", rx)).text_style(TextStyle::Monospace));
+                ui.end_row();
+                ui.label(String::from("Anti-VOX gain"));
+                ui.add(egui::Slider::new(&mut p.vox.anti_vox_gain, 0.0..=40.0).suffix("dB"));
+                ui.end_row();
+            });
+        });
+
+        self.client.borrow_mut().set_vox(&p.vox);
+    }
+}
+
+// Label lit in the given color when on
+fn indicator(ui: &mut egui::Ui, text: &str, on: bool, color: egui::Color32) {
+    let mut c = IND_OFF_COLOR;
+    if on {
+        c = color;
+    }
+    ui.label(RichText::new(text)
+        .text_style(TextStyle::Monospace)
+        .size(16.0)
+        .color(c));
+}
+
+// Position of a level on the display range
+fn level_frac(db: f32) -> f32 {
+    return (f32::max(LEVEL_MIN, f32::min(LEVEL_MAX, db)) - LEVEL_MIN) / (LEVEL_MAX - LEVEL_MIN);
+}
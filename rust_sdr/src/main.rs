@@ -24,6 +24,7 @@ The authors can be reached by email at:
 bob@bobcowdery.plus.com
 */
 
+use std::env;
 use std::thread;
 use std::time::Duration;
 use std::{cell::RefCell, rc::Rc};
@@ -33,9 +34,17 @@ use crate::app::common::prefs;
 pub mod app;
 
 /// Entry point for RustConsole SDR application
+///   --headless            run the radio engine without a UI, web clients control it
+///   --connect host:port   run the UI for a headless engine on another machine
+///   --token token         access token of that engine
 fn main() {
     println!("Starting Rust Console...");
 
+    let args: Vec<String> = env::args().collect();
+    let headless = args.iter().any(|a| a == "--headless");
+    let connect = arg_value(&args, "--connect");
+    let token = arg_value(&args, "--token").unwrap_or(String::new());
+
     // Create a Prefs instance
     // This is passed to anything that requires persistent data
     let prefs = prefs::Prefs::new();
@@ -43,14 +52,20 @@ fn main() {
     wprefs.borrow_mut().restore();
 
     // Create an instance of the Application manager type
-    let mut i_app = app::Appdata::new(wprefs.clone());
+    // Hardware, DSP and the servers are only set up when the radio is ours
+    let mut i_app = app::Appdata::new(wprefs.clone(), connect.is_none());
 
     // This will initialise all modules and run the back-end and DSP system
     i_app.app_init();
 
-    // Initialise the UI
-    // This runs the UI event loop and will return only when the UI is closed
-    i_app.ui_run(wprefs.clone());
+    if headless {
+        // Runs until told to quit
+        i_app.engine_run(wprefs.clone());
+    } else {
+        // Initialise the UI
+        // This runs the UI event loop and will return only when the UI is closed
+        i_app.ui_run(wprefs.clone(), connect.map(|addr| (addr, token)));
+    }
 
     // Tidy up
     // Close application
@@ -64,4 +79,8 @@ fn main() {
     thread::sleep(Duration::from_millis(1000));
 }
 
-
+// The argument following name
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    return args.get(i + 1).cloned();
+}